@@ -0,0 +1,133 @@
+use clap::Args;
+use prop_amm_executor::{AfterSwapFn, SwapFn};
+use prop_amm_shared::normalizer::{after_swap as normalizer_after_swap, compute_swap};
+use prop_amm_sim::attribution::{AttributionBucket, EdgeAttribution};
+use prop_amm_sim::engine::Simulation;
+use prop_amm_sim::objective::Objective;
+use prop_amm_sim::runner;
+
+use super::{compile, loader};
+use crate::output::{fmt_f64, fmt_signed_f64};
+
+#[derive(Args)]
+pub struct AttributeArgs {
+    /// Path to the .rs source file
+    pub file: String,
+    /// Seed of the simulation to attribute
+    #[arg(long, default_value = "0")]
+    pub seed: u64,
+    /// Number of steps to simulate
+    #[arg(long, default_value = "10000")]
+    pub steps: u32,
+}
+
+pub fn run(args: AttributeArgs) -> anyhow::Result<()> {
+    println!("Compiling {} (native)...", args.file);
+    let native_path = compile::compile_native(&args.file)?;
+    let lib = loader::open_library(&native_path)?;
+    let (swap_fn, after_swap_fn) = loader::install(loader::resolve_symbols(lib)?)?;
+    let strategy = loader::read_metadata(lib).title(&args.file);
+
+    println!(
+        "Attributing {}'s edge on seed {} ({} steps) against the normalizer on the same flow...",
+        strategy, args.seed, args.steps
+    );
+    let attribution = attribute(swap_fn, after_swap_fn, args.seed, args.steps)?;
+    print!("{}", render(&attribution));
+    Ok(())
+}
+
+/// Run one seed against the normalizer, replaying the submission's fills on a shadow one.
+fn attribute(
+    swap_fn: SwapFn,
+    after_swap_fn: Option<AfterSwapFn>,
+    seed: u64,
+    steps: u32,
+) -> anyhow::Result<EdgeAttribution> {
+    let config = &runner::seeded_configs(1, steps, seed, 1)[0];
+    let mut sim = Simulation::native(
+        swap_fn,
+        after_swap_fn,
+        compute_swap,
+        Some(normalizer_after_swap),
+        config,
+    );
+    // Routing quotes the pool, then fills the same order at the same reserves.
+    sim.submission_mut().cache_quotes();
+    let mut attribution = EdgeAttribution::new(config);
+    sim.run_with(&mut attribution)?;
+    Ok(attribution)
+}
+
+fn render(attribution: &EdgeAttribution) -> String {
+    let mut out = String::new();
+    out.push_str("\n========================================\n");
+    out.push_str(&format!(
+        "  {:<26} {:>7} {:>16} {:>16} {:>16}\n",
+        "Bucket", "Trades", "Edge", "Normalizer", "Advantage"
+    ));
+    let buckets = attribution.buckets();
+    let mut dimension = "";
+    for bucket in &buckets {
+        if bucket.dimension != dimension {
+            dimension = bucket.dimension;
+            out.push_str(&format!("  by {}\n", dimension));
+        }
+        out.push_str(&row(bucket));
+    }
+    out.push_str(&format!(
+        "  Total advantage over the normalizer: {}\n",
+        fmt_signed_f64(attribution.finalize())
+    ));
+    out.push_str("========================================\n");
+    out
+}
+
+fn row(bucket: &AttributionBucket) -> String {
+    format!(
+        "    {:<24} {:>7} {:>16} {:>16} {:>16}\n",
+        bucket.label,
+        bucket.trades,
+        fmt_f64(bucket.edge),
+        fmt_f64(bucket.normalizer_edge),
+        fmt_signed_f64(bucket.advantage())
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use prop_amm_sim::objective::Objective;
+    use prop_amm_sim::opponents;
+
+    use super::{attribute, render};
+
+    #[test]
+    fn table_has_every_bucket_and_matches_the_total() {
+        let starter = opponents::find("starter").unwrap();
+        let attribution = attribute(starter.swap, starter.after_swap, 5, 1_000).unwrap();
+        let table = render(&attribution);
+        for heading in [
+            "by size",
+            "by side",
+            "by volatility",
+            "large (>=",
+            "AMM sells X",
+        ] {
+            assert!(table.contains(heading), "{heading} missing from:\n{table}");
+        }
+
+        // Every dimension partitions the same fills.
+        let buckets = attribution.buckets();
+        for dimension in ["size", "side", "volatility"] {
+            let advantage: f64 = buckets
+                .iter()
+                .filter(|b| b.dimension == dimension)
+                .map(|b| b.advantage())
+                .sum();
+            assert!(
+                (advantage - attribution.finalize()).abs() < 1e-6,
+                "{dimension}"
+            );
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use std::path::Path;
+
+use clap::Args;
+use prop_amm_shared::normalizer::{after_swap as normalizer_after_swap, compute_swap};
+use prop_amm_sim::{opponents, runner};
+use serde::{Deserialize, Serialize};
+
+use crate::output::fmt_f64;
+
+/// Largest per-seed edge difference `--check` still treats as unchanged.
+const EDGE_EPSILON: f64 = 1e-9;
+
+const BATTERY: &str = "starter vs normalizer";
+const BATTERY_SIMULATIONS: u32 = 100;
+const BATTERY_STEPS: u32 = 1000;
+
+#[derive(Args)]
+pub struct BaselineArgs {
+    /// Write the baseline JSON here instead of stdout
+    #[arg(long, conflicts_with = "check")]
+    pub output: Option<String>,
+    /// Rerun the battery and fail if any edge differs from this baseline JSON
+    #[arg(long)]
+    pub check: Option<String>,
+    /// Number of parallel workers, respected exactly (0 = one per core, at most 8)
+    #[arg(long, default_value = "0")]
+    pub workers: usize,
+}
+
+/// Per-seed edges of the fixed battery: the starter strategy against the normalizer.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Baseline {
+    pub battery: String,
+    pub steps: u32,
+    pub seeds: Vec<u64>,
+    pub edges: Vec<f64>,
+}
+
+/// Run the battery: seeds `0..BATTERY_SIMULATIONS`, `BATTERY_STEPS` steps each.
+pub fn run_battery(n_workers: Option<usize>) -> anyhow::Result<Baseline> {
+    let starter = opponents::find("starter").expect("starter is a built-in opponent");
+    let configs = runner::seeded_configs(BATTERY_SIMULATIONS, BATTERY_STEPS, 0, 1);
+    let result = runner::run_batch_native(
+        starter.swap,
+        starter.after_swap,
+        compute_swap,
+        Some(normalizer_after_swap),
+        configs,
+        n_workers,
+    )?;
+    Ok(Baseline {
+        battery: BATTERY.to_string(),
+        steps: BATTERY_STEPS,
+        seeds: result.results.iter().map(|r| r.seed).collect(),
+        edges: result.results.iter().map(|r| r.submission_edge).collect(),
+    })
+}
+
+/// Every difference between `expected` and `actual`, one line each; empty when they agree.
+fn compare(expected: &Baseline, actual: &Baseline) -> Vec<String> {
+    if expected.battery != actual.battery
+        || expected.steps != actual.steps
+        || expected.seeds != actual.seeds
+    {
+        return vec![format!(
+            "battery mismatch: baseline is `{}` ({} steps, {} seeds), current is `{}` ({} steps, {} seeds)",
+            expected.battery,
+            expected.steps,
+            expected.seeds.len(),
+            actual.battery,
+            actual.steps,
+            actual.seeds.len()
+        )];
+    }
+    expected
+        .seeds
+        .iter()
+        .zip(expected.edges.iter().zip(&actual.edges))
+        .filter(|(_, (want, got))| {
+            let diff = (*want - *got).abs();
+            diff.is_nan() || diff > EDGE_EPSILON
+        })
+        .map(|(seed, (want, got))| {
+            format!(
+                "seed {}: baseline edge {}, current edge {}",
+                seed,
+                fmt_f64(*want),
+                fmt_f64(*got)
+            )
+        })
+        .collect()
+}
+
+/// Rerun the battery and compare it against the baseline at `path`.
+pub fn check(path: &Path, n_workers: Option<usize>) -> anyhow::Result<()> {
+    let expected: Baseline = serde_json::from_str(&std::fs::read_to_string(path)?)
+        .map_err(|e| anyhow::anyhow!("{}: invalid baseline: {}", path.display(), e))?;
+    let actual = run_battery(n_workers)?;
+    let diffs = compare(&expected, &actual);
+    if !diffs.is_empty() {
+        for diff in &diffs {
+            eprintln!("  {}", diff);
+        }
+        anyhow::bail!(
+            "{} of {} edges drifted from {}",
+            diffs.len(),
+            expected.edges.len(),
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+pub fn run(args: BaselineArgs) -> anyhow::Result<()> {
+    let workers = (args.workers != 0).then_some(args.workers);
+    if let Some(path) = args.check {
+        println!(
+            "Checking {} ({} seeds, {} steps) against {}...",
+            BATTERY, BATTERY_SIMULATIONS, BATTERY_STEPS, path
+        );
+        check(Path::new(&path), workers)?;
+        println!("Baseline matches: every edge within {:e}.", EDGE_EPSILON);
+        return Ok(());
+    }
+
+    let json = serde_json::to_string_pretty(&run_battery(workers)?)?;
+    match args.output {
+        Some(path) => {
+            std::fs::write(&path, json + "\n")?;
+            println!("Wrote baseline to {}", path);
+        }
+        None => println!("{}", json),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{check, compare, Baseline};
+
+    #[test]
+    fn committed_baseline_matches_current_engine() {
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/baseline.json");
+        check(&fixture, None).unwrap();
+    }
+
+    #[test]
+    fn drifted_edge_is_reported_by_seed() {
+        let expected = Baseline {
+            battery: "starter vs normalizer".to_string(),
+            steps: 1000,
+            seeds: vec![0, 1, 2],
+            edges: vec![12.5, -3.0, 40.25],
+        };
+        let mut actual = expected.clone();
+        actual.edges[0] += 1e-12;
+        assert!(compare(&expected, &actual).is_empty());
+
+        actual.edges[1] += 1e-6;
+        let diffs = compare(&expected, &actual);
+        assert_eq!(
+            diffs,
+            vec!["seed 1: baseline edge -3.000000, current edge -2.999999"]
+        );
+
+        actual.steps = 2000;
+        assert!(compare(&expected, &actual)[0].starts_with("battery mismatch"));
+    }
+}
@@ -0,0 +1,41 @@
+use super::compile::{self, Build};
+use super::run::{self, RunArgs};
+
+/// Build what the run needs, then run it. Each artifact is rebuilt only when the source
+/// changed since its last successful build.
+pub fn run(mut args: RunArgs) -> anyhow::Result<()> {
+    if args.bpf {
+        if args.bpf_so.is_none() {
+            let build = built("BPF", &args.file, compile::compile_bpf_cached(&args.file))?;
+            args.bpf_so = Some(build.path.to_string_lossy().into_owned());
+        }
+    } else {
+        let build = built(
+            "native",
+            &args.file,
+            compile::compile_native_cached(&args.file),
+        )?;
+        args.native_lib = Some(build.path);
+    }
+    run::run(args)
+}
+
+fn built(kind: &str, file: &str, build: anyhow::Result<Build>) -> anyhow::Result<Build> {
+    let build = build.map_err(|e| {
+        e.context(format!(
+            "Building {} ({}) failed; nothing was run",
+            file, kind
+        ))
+    })?;
+    if build.rebuilt {
+        println!("Built {} ({}): {}", file, kind, build.path.display());
+    } else {
+        println!(
+            "{} ({}) unchanged since its last build; reusing {}",
+            file,
+            kind,
+            build.path.display()
+        );
+    }
+    Ok(build)
+}
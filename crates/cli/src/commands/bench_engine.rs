@@ -0,0 +1,57 @@
+use clap::Args;
+use prop_amm_sim::{bench, runner};
+
+use crate::stamp::RunStamp;
+
+const WORKLOAD: &str = "starter vs normalizer";
+const WORKLOAD_SIMULATIONS: u32 = 200;
+const WORKLOAD_STEPS: u32 = 2000;
+
+#[derive(Args)]
+pub struct BenchEngineArgs {
+    /// Number of parallel workers, respected exactly (0 = one per core, at most 8); use 1 for stable
+    /// single-thread numbers
+    #[arg(long, default_value = "0")]
+    pub workers: usize,
+    /// Pin each worker thread to its own CPU (Linux)
+    #[arg(long)]
+    pub pin_workers: bool,
+}
+
+pub fn run(args: BenchEngineArgs) -> anyhow::Result<()> {
+    let workers = (args.workers != 0).then_some(args.workers);
+    runner::set_pin_workers(args.pin_workers);
+    eprintln!(
+        "Timing {} ({} seeds, {} steps)...",
+        WORKLOAD, WORKLOAD_SIMULATIONS, WORKLOAD_STEPS
+    );
+    let throughput = bench::measure_throughput(WORKLOAD_SIMULATIONS, WORKLOAD_STEPS, workers)?;
+    // Both strategies are built into this binary, so the version identifies them.
+    let configs = runner::seeded_configs(WORKLOAD_SIMULATIONS, WORKLOAD_STEPS, 0, 1);
+    let stamp = RunStamp::new(&configs, &[], "normalizer");
+    let mut report = serde_json::to_value(&throughput)?;
+    report["stamp"] = stamp.to_json();
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use prop_amm_sim::bench;
+
+    #[test]
+    fn small_workload_reports_positive_throughput() {
+        let throughput = bench::measure_throughput(4, 100, Some(2)).unwrap();
+        assert!(throughput.swaps > 0);
+        assert!(throughput.swaps_per_sec > 0.0);
+        assert!(throughput.simulations_per_sec > 0.0);
+
+        let json: serde_json::Value = serde_json::to_value(&throughput).unwrap();
+        assert_eq!(json["simulations"], 4);
+        assert_eq!(json["workers"], 2);
+        assert!(json["swaps_per_sec"].as_f64().unwrap() > 0.0);
+        if cfg!(target_os = "linux") {
+            assert!(json["peak_rss_kib"].as_u64().unwrap() > 0);
+        }
+    }
+}
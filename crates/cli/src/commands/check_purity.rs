@@ -0,0 +1,232 @@
+use std::fmt;
+
+use clap::Args;
+use prop_amm_executor::SwapFn;
+use prop_amm_shared::instruction::{encode_swap_instruction, STORAGE_SIZE};
+
+use super::validate::mix;
+use super::{compile, loader};
+
+#[derive(Args)]
+pub struct CheckPurityArgs {
+    /// Path to the .rs source file
+    pub file: String,
+    /// Number of distinct swap inputs to probe
+    #[arg(long, default_value = "256")]
+    pub inputs: usize,
+    /// How many times each phase replays every input
+    #[arg(long, default_value = "8")]
+    pub repeats: u32,
+    /// Threads calling the strategy concurrently in the last phase
+    #[arg(long, default_value = "4")]
+    pub threads: usize,
+}
+
+/// How an input was being replayed when its output changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// The same input called back to back.
+    Repeated,
+    /// Inputs called in a shuffled order.
+    Interleaved,
+    /// Inputs called from several threads at once.
+    Threaded,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Phase::Repeated => "repeated",
+            Phase::Interleaved => "interleaved",
+            Phase::Threaded => "threaded",
+        })
+    }
+}
+
+/// One swap input fed to the strategy.
+#[derive(Debug, Clone)]
+pub struct Probe {
+    pub side: u8,
+    pub amount: u64,
+    pub reserve_x: u64,
+    pub reserve_y: u64,
+    data: Vec<u8>,
+}
+
+/// The first input whose output differed from its first evaluation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Impurity {
+    pub input: usize,
+    pub side: u8,
+    pub amount: u64,
+    pub reserve_x: u64,
+    pub reserve_y: u64,
+    pub expected: u64,
+    pub got: u64,
+    pub phase: Phase,
+}
+
+impl fmt::Display for Impurity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "input #{} (side={} amount={} reserve_x={} reserve_y={}) returned {} then {} ({} calls)",
+            self.input,
+            self.side,
+            self.amount,
+            self.reserve_x,
+            self.reserve_y,
+            self.expected,
+            self.got,
+            self.phase
+        )
+    }
+}
+
+pub fn run(args: CheckPurityArgs) -> anyhow::Result<()> {
+    println!("Compiling {} (native)...", args.file);
+    let native_path = compile::compile_native(&args.file)?;
+    let lib = loader::open_library(&native_path)?;
+    let (swap_fn, _) = loader::install(loader::resolve_symbols(lib)?)?;
+
+    println!(
+        "Checking purity of compute_swap ({} inputs, {} repeats, {} threads)...",
+        args.inputs, args.repeats, args.threads
+    );
+    if let Some(impurity) = check_purity(swap_fn, args.inputs, args.repeats, args.threads) {
+        anyhow::bail!("FAIL: compute_swap has hidden state: {}", impurity);
+    }
+    println!("  [PASS] Every input returned the same output on every call");
+    Ok(())
+}
+
+/// Deterministic spread of sides, sizes and reserves. Storage stays zeroed, as at the start of
+/// a simulation: arbitrary bytes there could be states the strategy never writes.
+pub fn probes(n: usize) -> Vec<Probe> {
+    let storage = [0u8; STORAGE_SIZE];
+    (0..n as u64)
+        .map(|seed| {
+            let side = (seed & 1) as u8;
+            let amount = 1_000_000 + (mix(seed ^ 0xDEAD_BEEF) % 10_000_000_000);
+            let reserve_x = 1_000_000_000 + (mix(seed ^ 0x0123_4567_89AB_CDEF) % 2_000_000_000_000);
+            let reserve_y =
+                1_000_000_000 + (mix(seed ^ 0x0F0F_0F0F_F0F0_F0F0) % 200_000_000_000_000);
+            Probe {
+                side,
+                amount,
+                reserve_x,
+                reserve_y,
+                data: encode_swap_instruction(side, amount, reserve_x, reserve_y, &storage),
+            }
+        })
+        .collect()
+}
+
+/// Call `swap_fn` on every probe repeatedly, in shuffled order and from `threads` threads,
+/// and return the first input whose output ever differs from its first evaluation.
+pub fn check_purity(
+    swap_fn: SwapFn,
+    n_inputs: usize,
+    repeats: u32,
+    threads: usize,
+) -> Option<Impurity> {
+    let probes = probes(n_inputs);
+    let expected: Vec<u64> = probes.iter().map(|p| swap_fn(&p.data)).collect();
+    let impurity = |input: usize, got: u64, phase: Phase| {
+        let probe = &probes[input];
+        Impurity {
+            input,
+            side: probe.side,
+            amount: probe.amount,
+            reserve_x: probe.reserve_x,
+            reserve_y: probe.reserve_y,
+            expected: expected[input],
+            got,
+            phase,
+        }
+    };
+
+    for (i, probe) in probes.iter().enumerate() {
+        for _ in 0..repeats {
+            let got = swap_fn(&probe.data);
+            if got != expected[i] {
+                return Some(impurity(i, got, Phase::Repeated));
+            }
+        }
+    }
+
+    for round in 0..repeats as u64 {
+        for i in shuffled(probes.len(), round) {
+            let got = swap_fn(&probes[i].data);
+            if got != expected[i] {
+                return Some(impurity(i, got, Phase::Interleaved));
+            }
+        }
+    }
+
+    // Each thread walks the inputs from its own offset so different inputs overlap in time.
+    let found = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads.max(1))
+            .map(|t| {
+                let (probes, expected) = (&probes, &expected);
+                scope.spawn(move || {
+                    let n = probes.len();
+                    for _ in 0..repeats {
+                        for k in 0..n {
+                            let i = (k + t * n / threads.max(1)) % n;
+                            let got = swap_fn(&probes[i].data);
+                            if got != expected[i] {
+                                return Some((i, got));
+                            }
+                        }
+                    }
+                    None
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .filter_map(|w| w.join().expect("purity worker panicked"))
+            .min_by_key(|(i, _)| *i)
+    });
+    found.map(|(i, got)| impurity(i, got, Phase::Threaded))
+}
+
+/// A permutation of `0..n` that differs per `round`.
+fn shuffled(n: usize, round: u64) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by_key(|&i| mix((round << 32) ^ i as u64));
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use prop_amm_shared::normalizer::compute_swap;
+
+    use super::{check_purity, Phase};
+
+    static CALLS: AtomicU64 = AtomicU64::new(0);
+
+    /// Adds one nano to every third quote: a call counter leaking into the output.
+    fn counting_swap(data: &[u8]) -> u64 {
+        let calls = CALLS.fetch_add(1, Ordering::Relaxed);
+        compute_swap(data) + u64::from(calls % 3 == 2)
+    }
+
+    #[test]
+    fn pure_strategy_passes() {
+        assert_eq!(check_purity(compute_swap, 64, 4, 4), None);
+    }
+
+    #[test]
+    fn call_counter_is_flagged() {
+        let impurity = check_purity(counting_swap, 64, 4, 4).expect("counter must be flagged");
+        assert_eq!(impurity.phase, Phase::Repeated);
+        assert_eq!(impurity.got.abs_diff(impurity.expected), 1);
+        assert!(impurity
+            .to_string()
+            .starts_with(&format!("input #{} ", impurity.input)));
+    }
+}
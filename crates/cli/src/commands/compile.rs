@@ -6,8 +6,16 @@ use std::{
 };
 
 const BUILD_RUNS_DIR: &str = ".build/runs";
+/// Written into a build dir once its native / BPF artifact built; see `cached_build`.
+const NATIVE_STAMP: &str = ".native-built";
+const BPF_STAMP: &str = ".bpf-built";
 pub const NATIVE_SWAP_SYMBOL: &[u8] = b"__prop_amm_compute_swap_export";
 pub const NATIVE_AFTER_SWAP_SYMBOL: &[u8] = b"__prop_amm_after_swap_export";
+/// Exported when the submission defines `const NAME: &str` / `const VERSION: &str`.
+pub const NATIVE_NAME_SYMBOL: &[u8] = b"__prop_amm_strategy_name_export";
+pub const NATIVE_VERSION_SYMBOL: &[u8] = b"__prop_amm_strategy_version_export";
+/// Exported when the submission defines `const STORAGE_SCHEMA: &str`.
+pub const NATIVE_STORAGE_SCHEMA_SYMBOL: &[u8] = b"__prop_amm_storage_schema_export";
 
 const CARGO_TOML: &str = r#"[package]
 name = "user_program"
@@ -33,13 +41,13 @@ fn cargo_toml_with_sdk_path() -> String {
     )
 }
 
-pub fn ensure_build_dir(safe_source: &str) -> anyhow::Result<PathBuf> {
+pub fn ensure_build_dir(runs_dir: &Path, safe_source: &str) -> anyhow::Result<PathBuf> {
     let mut hasher = DefaultHasher::new();
     safe_source.hash(&mut hasher);
     CARGO_TOML.hash(&mut hasher);
     let build_key = format!("{:016x}", hasher.finish());
 
-    let build_dir = PathBuf::from(BUILD_RUNS_DIR).join(build_key);
+    let build_dir = runs_dir.join(build_key);
     std::fs::create_dir_all(build_dir.join("src"))?;
 
     let cargo_toml = cargo_toml_with_sdk_path();
@@ -66,14 +74,80 @@ pub fn ensure_build_dir(safe_source: &str) -> anyhow::Result<PathBuf> {
 }
 
 pub fn compile_native(rs_file: &str) -> anyhow::Result<PathBuf> {
+    cargo_build_native(&prepare_build_dir(Path::new(BUILD_RUNS_DIR), rs_file)?)
+}
+
+pub fn compile_bpf(rs_file: &str) -> anyhow::Result<PathBuf> {
+    cargo_build_bpf(&prepare_build_dir(Path::new(BUILD_RUNS_DIR), rs_file)?)
+}
+
+/// An artifact and whether this call had to build it.
+pub struct Build {
+    pub path: PathBuf,
+    pub rebuilt: bool,
+}
+
+/// Like [`compile_native`], but skips cargo when this exact source already built.
+pub fn compile_native_cached(rs_file: &str) -> anyhow::Result<Build> {
+    cached_build(
+        Path::new(BUILD_RUNS_DIR),
+        rs_file,
+        NATIVE_STAMP,
+        cargo_build_native,
+    )
+}
+
+/// Like [`compile_bpf`], but skips `cargo build-sbf` when this exact source already built.
+pub fn compile_bpf_cached(rs_file: &str) -> anyhow::Result<Build> {
+    cached_build(
+        Path::new(BUILD_RUNS_DIR),
+        rs_file,
+        BPF_STAMP,
+        cargo_build_bpf,
+    )
+}
+
+/// Build dirs are keyed by a hash of the safe source, so an edited source lands in a fresh
+/// dir. `stamp` is written there, holding the artifact path, only after `build` succeeds;
+/// the build is skipped while the stamp and the artifact it names both exist.
+fn cached_build(
+    runs_dir: &Path,
+    rs_file: &str,
+    stamp: &str,
+    build: impl FnOnce(&Path) -> anyhow::Result<PathBuf>,
+) -> anyhow::Result<Build> {
+    let build_dir = prepare_build_dir(runs_dir, rs_file)?;
+    let stamp_path = build_dir.join(stamp);
+    if let Ok(artifact) = std::fs::read_to_string(&stamp_path) {
+        let path = PathBuf::from(artifact);
+        if path.exists() {
+            return Ok(Build {
+                path,
+                rebuilt: false,
+            });
+        }
+    }
+
+    let _ = std::fs::remove_file(&stamp_path);
+    let path = build(&build_dir)?;
+    std::fs::write(&stamp_path, path.to_string_lossy().as_bytes())?;
+    Ok(Build {
+        path,
+        rebuilt: true,
+    })
+}
+
+fn prepare_build_dir(runs_dir: &Path, rs_file: &str) -> anyhow::Result<PathBuf> {
     let rs_path = Path::new(rs_file);
     if !rs_path.exists() {
         anyhow::bail!("File not found: {}", rs_file);
     }
 
     let safe_source = make_safe_submission_source(rs_path)?;
-    let build_dir = ensure_build_dir(&safe_source)?;
+    ensure_build_dir(runs_dir, &safe_source)
+}
 
+fn cargo_build_native(build_dir: &Path) -> anyhow::Result<PathBuf> {
     let status = Command::new("cargo")
         .arg("build")
         .arg("--release")
@@ -87,18 +161,10 @@ pub fn compile_native(rs_file: &str) -> anyhow::Result<PathBuf> {
         anyhow::bail!("Native build failed");
     }
 
-    find_native_lib(&build_dir)
+    find_native_lib(build_dir)
 }
 
-pub fn compile_bpf(rs_file: &str) -> anyhow::Result<PathBuf> {
-    let rs_path = Path::new(rs_file);
-    if !rs_path.exists() {
-        anyhow::bail!("File not found: {}", rs_file);
-    }
-
-    let safe_source = make_safe_submission_source(rs_path)?;
-    let build_dir = ensure_build_dir(&safe_source)?;
-
+fn cargo_build_bpf(build_dir: &Path) -> anyhow::Result<PathBuf> {
     let status = Command::new("cargo")
         .arg("build-sbf")
         .arg("--manifest-path")
@@ -109,7 +175,7 @@ pub fn compile_bpf(rs_file: &str) -> anyhow::Result<PathBuf> {
         anyhow::bail!("BPF build failed");
     }
 
-    find_bpf_so(&build_dir)
+    find_bpf_so(build_dir)
 }
 
 fn find_native_lib(build_dir: &Path) -> anyhow::Result<PathBuf> {
@@ -152,7 +218,7 @@ fn make_safe_submission_source(rs_path: &Path) -> anyhow::Result<String> {
     let mut safe_source = source;
     safe_source.push('\n');
     safe_source.push('\n');
-    safe_source.push_str(&native_shim_source(analysis.has_after_swap));
+    safe_source.push_str(&native_shim_source(analysis));
 
     Ok(safe_source)
 }
@@ -161,6 +227,9 @@ fn make_safe_submission_source(rs_path: &Path) -> anyhow::Result<String> {
 struct SourceAnalysis {
     has_compute_swap: bool,
     has_after_swap: bool,
+    has_name: bool,
+    has_version: bool,
+    has_storage_schema: bool,
 }
 
 fn analyze_source(source: &str) -> anyhow::Result<SourceAnalysis> {
@@ -169,25 +238,89 @@ fn analyze_source(source: &str) -> anyhow::Result<SourceAnalysis> {
 
     let mut has_compute_swap = false;
     let mut has_after_swap = false;
+    let mut has_name = false;
+    let mut has_version = false;
+    let mut has_storage_schema = false;
 
     for item in parsed.items {
-        if let syn::Item::Fn(item_fn) = item {
-            let name = item_fn.sig.ident.to_string();
-            if name == "compute_swap" {
-                has_compute_swap = true;
-            } else if name == "after_swap" {
-                has_after_swap = true;
+        match item {
+            syn::Item::Fn(item_fn) => {
+                let name = item_fn.sig.ident.to_string();
+                if name == "compute_swap" {
+                    has_compute_swap = true;
+                } else if name == "after_swap" {
+                    has_after_swap = true;
+                }
             }
+            syn::Item::Const(item_const) if is_str_ref(&item_const.ty) => {
+                if item_const.ident == "NAME" {
+                    has_name = true;
+                } else if item_const.ident == "VERSION" {
+                    has_version = true;
+                } else if item_const.ident == "STORAGE_SCHEMA" {
+                    has_storage_schema = true;
+                }
+            }
+            _ => {}
         }
     }
 
     Ok(SourceAnalysis {
         has_compute_swap,
         has_after_swap,
+        has_name,
+        has_version,
+        has_storage_schema,
     })
 }
 
-fn native_shim_source(has_after_swap: bool) -> String {
+fn is_str_ref(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Reference(reference) => {
+            matches!(&*reference.elem, syn::Type::Path(path) if path.path.is_ident("str"))
+        }
+        _ => false,
+    }
+}
+
+/// `#[no_mangle]` accessor handing a `&'static str` constant to the CLI.
+fn metadata_export_source(export: &str, constant: &str) -> String {
+    format!(
+        r#"
+#[cfg(not(target_os = "solana"))]
+#[no_mangle]
+pub extern "C" fn {}(out_len: *mut usize) -> *const u8 {{
+    prop_amm_submission_sdk::ffi_str({}, out_len)
+}}
+"#,
+        export, constant
+    )
+}
+
+fn native_shim_source(analysis: SourceAnalysis) -> String {
+    let mut shim = swap_shim_source(analysis.has_after_swap);
+    if analysis.has_name {
+        shim.push_str(&metadata_export_source(
+            "__prop_amm_strategy_name_export",
+            "NAME",
+        ));
+    }
+    if analysis.has_version {
+        shim.push_str(&metadata_export_source(
+            "__prop_amm_strategy_version_export",
+            "VERSION",
+        ));
+    }
+    if analysis.has_storage_schema {
+        shim.push_str(&metadata_export_source(
+            "__prop_amm_storage_schema_export",
+            "STORAGE_SCHEMA",
+        ));
+    }
+    shim
+}
+
+fn swap_shim_source(has_after_swap: bool) -> String {
     let after_swap_target = if has_after_swap {
         "after_swap"
     } else {
@@ -260,3 +393,58 @@ fn find_bpf_so(build_dir: &Path) -> anyhow::Result<PathBuf> {
 
     anyhow::bail!("No BPF .so found in {}/target/deploy/", build_dir.display())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::path::{Path, PathBuf};
+
+    use super::cached_build;
+
+    #[test]
+    fn cached_build_reruns_only_when_the_source_changes() {
+        let root =
+            std::env::temp_dir().join(format!("prop-amm-cached-build-{}", std::process::id()));
+        let runs_dir = root.join("runs");
+        std::fs::create_dir_all(&root).unwrap();
+        let source = root.join("my_amm.rs");
+        let source_file = source.to_str().unwrap();
+
+        let builds = Cell::new(0);
+        let build = |build_dir: &Path| -> anyhow::Result<PathBuf> {
+            builds.set(builds.get() + 1);
+            let artifact = build_dir.join("libuser_program.so");
+            std::fs::write(&artifact, b"")?;
+            Ok(artifact)
+        };
+        let failing_build =
+            |_: &Path| -> anyhow::Result<PathBuf> { anyhow::bail!("Native build failed") };
+
+        std::fs::write(&source, "pub fn compute_swap(_data: &[u8]) -> u64 { 1 }\n").unwrap();
+        let first = cached_build(&runs_dir, source_file, ".native-built", build).unwrap();
+        assert!(first.rebuilt);
+        let unchanged = cached_build(&runs_dir, source_file, ".native-built", build).unwrap();
+        assert!(!unchanged.rebuilt);
+        assert_eq!(unchanged.path, first.path);
+        assert_eq!(builds.get(), 1);
+
+        std::fs::write(&source, "pub fn compute_swap(_data: &[u8]) -> u64 { 2 }\n").unwrap();
+        // A failed build must not leave a stamp behind that a later call would trust.
+        assert!(cached_build(&runs_dir, source_file, ".native-built", failing_build).is_err());
+        let edited = cached_build(&runs_dir, source_file, ".native-built", build).unwrap();
+        assert!(edited.rebuilt);
+        assert_ne!(edited.path, first.path);
+        assert_eq!(builds.get(), 2);
+
+        // A deleted artifact is rebuilt even though its stamp survives.
+        std::fs::remove_file(&edited.path).unwrap();
+        assert!(
+            cached_build(&runs_dir, source_file, ".native-built", build)
+                .unwrap()
+                .rebuilt
+        );
+        assert_eq!(builds.get(), 3);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
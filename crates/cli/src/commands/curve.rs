@@ -0,0 +1,176 @@
+use std::fmt::Write as _;
+
+use clap::{Args, ValueEnum};
+use prop_amm_executor::{AfterSwapFn, SwapFn};
+use prop_amm_shared::instruction::Side;
+use prop_amm_sim::amm::BpfAmm;
+use serde_json::json;
+
+use super::{compile, loader};
+use crate::output::fmt_f64;
+use crate::stamp::RunStamp;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum CurveSide {
+    /// Pay Y, receive X
+    Buy,
+    /// Pay X, receive Y
+    Sell,
+    /// Both directions
+    Both,
+}
+
+impl CurveSide {
+    fn quote_sides(self) -> &'static [Side] {
+        match self {
+            CurveSide::Buy => &[Side::BuyX],
+            CurveSide::Sell => &[Side::SellX],
+            CurveSide::Both => &[Side::BuyX, Side::SellX],
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct CurveArgs {
+    /// Path to the .rs source file
+    pub file: String,
+    /// Pool reserves as X,Y
+    #[arg(long, value_parser = parse_reserves, default_value = "100,10000")]
+    pub reserves: (f64, f64),
+    /// Trade direction to sample
+    #[arg(long, value_enum, default_value = "both")]
+    pub side: CurveSide,
+    /// Largest input size, in the input token (Y for buy, X for sell).
+    /// Defaults to half of the input-side reserve.
+    #[arg(long)]
+    pub max_size: Option<f64>,
+    /// Number of evenly spaced input sizes
+    #[arg(long, default_value = "100")]
+    pub points: usize,
+}
+
+fn parse_reserves(raw: &str) -> Result<(f64, f64), String> {
+    let (x, y) = raw
+        .split_once(',')
+        .ok_or_else(|| format!("expected X,Y, got `{}`", raw))?;
+    let parse = |v: &str| {
+        v.trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|v| v.is_finite() && *v > 0.0)
+            .ok_or_else(|| format!("invalid reserve `{}`", v.trim()))
+    };
+    Ok((parse(x)?, parse(y)?))
+}
+
+pub fn run(args: CurveArgs) -> anyhow::Result<()> {
+    if args.points == 0 {
+        anyhow::bail!("--points must be >= 1");
+    }
+    if let Some(max_size) = args.max_size {
+        if !max_size.is_finite() || max_size <= 0.0 {
+            anyhow::bail!("--max-size must be a positive number");
+        }
+    }
+
+    eprintln!("Compiling {} (native)...", args.file);
+    let native_path = compile::compile_native(&args.file)?;
+    let lib = loader::open_library(&native_path)?;
+    let (swap, after_swap) = loader::install(loader::resolve_symbols(lib)?)?;
+    let inputs = json!({
+        "reserves": args.reserves,
+        "side": args.side.to_possible_value().map(|side| side.get_name().to_string()),
+        "max_size": args.max_size,
+        "points": args.points,
+    });
+    let stamp = RunStamp::for_artifact(&inputs, &native_path, "none")?;
+    print!(
+        "{}{}",
+        stamp.csv_comment(),
+        curve_csv(swap, after_swap, &args)
+    );
+    Ok(())
+}
+
+/// CSV of `side,input_size,output,effective_price` from non-mutating quotes.
+fn curve_csv(swap: SwapFn, after_swap: Option<AfterSwapFn>, args: &CurveArgs) -> String {
+    let (reserve_x, reserve_y) = args.reserves;
+    let mut amm = BpfAmm::new_native(
+        swap,
+        after_swap,
+        reserve_x,
+        reserve_y,
+        "submission".to_string(),
+    );
+    // Fine grids round neighbouring sizes to the same nano amount.
+    amm.cache_quotes();
+
+    let mut out = String::from("side,input_size,output,effective_price\n");
+    for &side in args.side.quote_sides() {
+        let (label, input_reserve) = if side == Side::BuyX {
+            ("buy", reserve_y)
+        } else {
+            ("sell", reserve_x)
+        };
+        let max_size = args.max_size.unwrap_or(input_reserve * 0.5);
+        for point in amm.sample_curve(side, max_size, args.points) {
+            let _ = writeln!(
+                out,
+                "{},{},{},{}",
+                label,
+                fmt_f64(point.input),
+                fmt_f64(point.output),
+                fmt_f64(point.effective_price)
+            );
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use prop_amm_shared::normalizer::compute_swap as normalizer_swap;
+
+    use super::{curve_csv, parse_reserves, CurveArgs, CurveSide};
+
+    fn args(side: CurveSide) -> CurveArgs {
+        CurveArgs {
+            file: "normalizer.rs".to_string(),
+            reserves: (100.0, 10_000.0),
+            side,
+            max_size: None,
+            points: 50,
+        }
+    }
+
+    #[test]
+    fn normalizer_curve_is_monotone_in_output() {
+        let csv = curve_csv(normalizer_swap, None, &args(CurveSide::Both));
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("side,input_size,output,effective_price"));
+
+        let rows: Vec<Vec<&str>> = lines.map(|l| l.split(',').collect()).collect();
+        assert_eq!(rows.len(), 100);
+        for side in ["buy", "sell"] {
+            let outputs: Vec<f64> = rows
+                .iter()
+                .filter(|r| r[0] == side)
+                .map(|r| r[2].parse().unwrap())
+                .collect();
+            assert_eq!(outputs.len(), 50);
+            assert!(
+                outputs.windows(2).all(|w| w[1] > w[0]),
+                "{side} outputs not increasing"
+            );
+        }
+    }
+
+    #[test]
+    fn single_side_and_reserve_parsing() {
+        let csv = curve_csv(normalizer_swap, None, &args(CurveSide::Sell));
+        assert!(csv.lines().skip(1).all(|l| l.starts_with("sell,")));
+        assert_eq!(parse_reserves("100, 2500").unwrap(), (100.0, 2500.0));
+        assert!(parse_reserves("100").is_err());
+        assert!(parse_reserves("-1,5").is_err());
+    }
+}
@@ -0,0 +1,291 @@
+use std::fmt;
+
+use clap::Args;
+use prop_amm_executor::{AfterSwapFn, SwapFn};
+use prop_amm_shared::config::SimulationConfig;
+use prop_amm_shared::normalizer::{after_swap as normalizer_after_swap, compute_swap};
+use prop_amm_sim::audit::FillLog;
+use prop_amm_sim::engine::Simulation;
+use prop_amm_sim::objective::{SubmissionTrade, TradeSource};
+use prop_amm_sim::runner;
+
+use super::{compile, loader};
+
+#[derive(Args)]
+pub struct DiffVersionsArgs {
+    /// Path to the first version's .rs source file
+    pub artifact_a: String,
+    /// Path to the second version's .rs source file
+    pub artifact_b: String,
+    /// Number of simulations to replay
+    #[arg(long, default_value = "10")]
+    pub simulations: u32,
+    /// Number of steps per simulation
+    #[arg(long, default_value = "10000")]
+    pub steps: u32,
+    /// Starting seed for simulation config generation
+    #[arg(long, default_value = "0")]
+    pub seed_start: u64,
+    /// Seed step between simulations
+    #[arg(long, default_value = "1")]
+    pub seed_stride: u64,
+}
+
+/// One build of a strategy, as engine callbacks.
+type Version = (SwapFn, Option<AfterSwapFn>);
+
+/// First step where the two versions stopped producing bit-identical results.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VersionDivergence {
+    pub seed: u64,
+    pub step: u32,
+    /// Market the step started from, identical for both versions up to here.
+    pub inputs: String,
+    /// What came out differently.
+    pub outputs: String,
+}
+
+impl fmt::Display for VersionDivergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "seed {} step {} diverged: {}\n  inputs: {}",
+            self.seed, self.step, self.outputs, self.inputs
+        )
+    }
+}
+
+/// Outcome of replaying every config with both versions.
+#[derive(Debug, Clone)]
+pub struct VersionDiff {
+    /// Steps compared, including a diverging one.
+    pub steps: u64,
+    pub divergence: Option<VersionDivergence>,
+}
+
+fn describe(trade: Option<&SubmissionTrade>) -> String {
+    match trade {
+        Some(t) => format!(
+            "{} {} x={:e} y={:e}",
+            match t.source {
+                TradeSource::Arbitrage => "arb",
+                TradeSource::Retail => "retail",
+            },
+            if t.amm_buys_x { "sell-x" } else { "buy-x" },
+            t.amount_x,
+            t.amount_y
+        ),
+        None => "no fill".to_string(),
+    }
+}
+
+fn same_fill(a: &SubmissionTrade, b: &SubmissionTrade) -> bool {
+    a.source == b.source
+        && a.amm_buys_x == b.amm_buys_x
+        && a.amount_x.to_bits() == b.amount_x.to_bits()
+        && a.amount_y.to_bits() == b.amount_y.to_bits()
+}
+
+/// The first fill, then the first pool or edge field, that differs at all between `a` and `b`
+/// after the step both just ran.
+fn step_difference(
+    a: (&Simulation, &[SubmissionTrade]),
+    b: (&Simulation, &[SubmissionTrade]),
+) -> Option<String> {
+    let (fills_a, fills_b) = (a.1, b.1);
+    for i in 0..fills_a.len().max(fills_b.len()) {
+        let (fa, fb) = (fills_a.get(i), fills_b.get(i));
+        if !matches!((fa, fb), (Some(x), Some(y)) if same_fill(x, y)) {
+            return Some(format!("fill #{}: {} vs {}", i, describe(fa), describe(fb)));
+        }
+    }
+    let (a, b) = (a.0, b.0);
+    let fields = [
+        (
+            "submission_x",
+            a.submission().reserve_x,
+            b.submission().reserve_x,
+        ),
+        (
+            "submission_y",
+            a.submission().reserve_y,
+            b.submission().reserve_y,
+        ),
+        (
+            "normalizer_x",
+            a.normalizer().reserve_x,
+            b.normalizer().reserve_x,
+        ),
+        (
+            "normalizer_y",
+            a.normalizer().reserve_y,
+            b.normalizer().reserve_y,
+        ),
+        ("submission_edge", a.submission_edge(), b.submission_edge()),
+    ];
+    let diffs: Vec<String> = fields
+        .iter()
+        .filter(|(_, a, b)| a.to_bits() != b.to_bits())
+        .map(|(name, a, b)| format!("{} {} vs {} ({:e})", name, a, b, b - a))
+        .collect();
+    (!diffs.is_empty()).then(|| diffs.join(", "))
+}
+
+/// Replay `config` with both versions in lockstep; returns the steps compared and the first
+/// step whose fills, reserves or edge differ in any bit.
+fn diff_seed(
+    a: Version,
+    b: Version,
+    config: &SimulationConfig,
+) -> (u64, Option<VersionDivergence>) {
+    let sim = |(swap, after_swap): Version| {
+        Simulation::native(
+            swap,
+            after_swap,
+            compute_swap,
+            Some(normalizer_after_swap),
+            config,
+        )
+    };
+    let (mut sim_a, mut sim_b) = (sim(a), sim(b));
+    let (mut log_a, mut log_b) = (FillLog::default(), FillLog::default());
+    let mut steps = 0;
+    loop {
+        let pool = sim_a.submission();
+        let inputs = format!(
+            "reserves x={} y={}, fair price {} before the move",
+            pool.reserve_x,
+            pool.reserve_y,
+            sim_a.fair_price()
+        );
+        let step = sim_a.current_step();
+        let ran = (sim_a.step_with(&mut log_a), sim_b.step_with(&mut log_b));
+        let outputs = match ran {
+            (false, false) => return (steps, None),
+            (true, true) => step_difference(
+                (&sim_a, log_a.steps.last().map_or(&[], Vec::as_slice)),
+                (&sim_b, log_b.steps.last().map_or(&[], Vec::as_slice)),
+            ),
+            (true, false) => Some("only the first version ran this step".to_string()),
+            (false, true) => Some("only the second version ran this step".to_string()),
+        };
+        steps += 1;
+        if let Some(outputs) = outputs {
+            let divergence = VersionDivergence {
+                seed: config.seed,
+                step,
+                inputs,
+                outputs,
+            };
+            return (steps, Some(divergence));
+        }
+    }
+}
+
+/// Replay every config with both versions, stopping at the first divergence.
+pub fn diff(a: Version, b: Version, configs: &[SimulationConfig]) -> VersionDiff {
+    let mut steps = 0;
+    for config in configs {
+        let (compared, divergence) = diff_seed(a, b, config);
+        steps += compared;
+        if divergence.is_some() {
+            return VersionDiff { steps, divergence };
+        }
+    }
+    VersionDiff {
+        steps,
+        divergence: None,
+    }
+}
+
+pub fn run(args: DiffVersionsArgs) -> anyhow::Result<()> {
+    if args.seed_stride == 0 {
+        anyhow::bail!("--seed-stride must be >= 1");
+    }
+    let mut versions = Vec::with_capacity(2);
+    for (slot, file) in [&args.artifact_a, &args.artifact_b].into_iter().enumerate() {
+        println!("Compiling {} (native)...", file);
+        let native_path = compile::compile_native(file)?;
+        let lib = loader::open_library(&native_path)?;
+        versions.push(loader::install_at(slot, loader::resolve_symbols(lib)?)?);
+    }
+
+    println!(
+        "Replaying {} and {} through {} simulations ({} steps each), seeds {} + i*{}...",
+        args.artifact_a,
+        args.artifact_b,
+        args.simulations,
+        args.steps,
+        args.seed_start,
+        args.seed_stride,
+    );
+    let configs = runner::seeded_configs(
+        args.simulations,
+        args.steps,
+        args.seed_start,
+        args.seed_stride,
+    );
+    let result = diff(versions[0], versions[1], &configs);
+    match result.divergence {
+        None => {
+            println!(
+                "Bit-identical: every fill, reserve and edge matched over {} steps.",
+                result.steps
+            );
+            Ok(())
+        }
+        Some(divergence) => anyhow::bail!(
+            "the versions behave differently after {} matching steps: {}",
+            result.steps - 1,
+            divergence
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prop_amm_shared::normalizer::{after_swap, compute_swap};
+    use prop_amm_sim::runner;
+
+    use super::diff;
+
+    /// The normalizer's curve, but 1 bps cheaper on inputs above 10 Y or 0.1 X.
+    fn cheaper_on_size(data: &[u8]) -> u64 {
+        let input = u64::from_le_bytes(data[1..9].try_into().unwrap());
+        let threshold = if data[0] == 0 {
+            10_000_000_000
+        } else {
+            100_000_000
+        };
+        let quote = compute_swap(data);
+        if input > threshold {
+            quote + quote / 10_000
+        } else {
+            quote
+        }
+    }
+
+    #[test]
+    fn identical_versions_match_and_a_change_is_caught_at_its_first_step() {
+        let configs = runner::seeded_configs(3, 300, 5, 1);
+        let same = diff(
+            (compute_swap, Some(after_swap)),
+            (compute_swap, Some(after_swap)),
+            &configs,
+        );
+        assert_eq!(same.steps, 900);
+        assert!(same.divergence.is_none(), "{:?}", same.divergence);
+
+        let changed = diff(
+            (compute_swap, Some(after_swap)),
+            (cheaper_on_size, Some(after_swap)),
+            &configs,
+        );
+        let divergence = changed.divergence.expect("the larger quotes must show");
+        assert_eq!(divergence.seed, 5);
+        assert_eq!(u64::from(divergence.step) + 1, changed.steps);
+        assert!(divergence.step > 0, "{divergence}");
+        assert!(divergence.outputs.starts_with("fill #"), "{divergence}");
+        assert!(divergence.inputs.starts_with("reserves x="), "{divergence}");
+    }
+}
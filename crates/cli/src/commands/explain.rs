@@ -0,0 +1,176 @@
+use clap::Args;
+use prop_amm_executor::{AfterSwapFn, SwapFn};
+use prop_amm_shared::normalizer::{after_swap as normalizer_after_swap, compute_swap};
+use prop_amm_sim::engine::Simulation;
+use prop_amm_sim::objective::{NotableStep, NotableSteps, Objective, TradeSource};
+use prop_amm_sim::runner;
+
+use super::{compile, loader};
+use crate::output::{fmt_f64, fmt_signed_f64};
+
+#[derive(Args)]
+pub struct ExplainArgs {
+    /// Path to the .rs source file
+    pub file: String,
+    /// Seed of the simulation to narrate
+    #[arg(long, default_value = "0")]
+    pub seed: u64,
+    /// Number of steps to simulate
+    #[arg(long, default_value = "10000")]
+    pub steps: u32,
+    /// Number of most impactful steps to describe
+    #[arg(long, default_value = "5")]
+    pub top: usize,
+}
+
+pub fn run(args: ExplainArgs) -> anyhow::Result<()> {
+    println!("Compiling {} (native)...", args.file);
+    let native_path = compile::compile_native(&args.file)?;
+    let lib = loader::open_library(&native_path)?;
+    let (swap_fn, after_swap_fn) = loader::install(loader::resolve_symbols(lib)?)?;
+    let strategy = loader::read_metadata(lib).title(&args.file);
+
+    println!(
+        "Explaining {} on seed {} ({} steps)...\n",
+        strategy, args.seed, args.steps
+    );
+    print!(
+        "{}",
+        explain(swap_fn, after_swap_fn, args.seed, args.steps, args.top)?
+    );
+    Ok(())
+}
+
+/// Run one seed against the normalizer and narrate its `top` most impactful steps.
+fn explain(
+    swap_fn: SwapFn,
+    after_swap_fn: Option<AfterSwapFn>,
+    seed: u64,
+    steps: u32,
+    top: usize,
+) -> anyhow::Result<String> {
+    let config = &runner::seeded_configs(1, steps, seed, 1)[0];
+    let mut sim = Simulation::native(
+        swap_fn,
+        after_swap_fn,
+        compute_swap,
+        Some(normalizer_after_swap),
+        config,
+    );
+    // Routing quotes the pool, then fills the same order at the same reserves.
+    sim.submission_mut().cache_quotes();
+    let mut notable = NotableSteps::new(top, sim.fair_price());
+    sim.run_with(&mut notable)?;
+    Ok(narrate(&notable))
+}
+
+fn narrate(notable: &NotableSteps) -> String {
+    let mut out = String::new();
+    if notable.top().is_empty() {
+        out.push_str("Nothing traded against the strategy.\n");
+        return out;
+    }
+    for step in notable.top() {
+        out.push_str(&describe(step));
+        out.push('\n');
+    }
+    if let Some(worst) = notable.worst() {
+        out.push_str(&format!(
+            "\nWorst step {}: {} edge.\n",
+            worst.step,
+            fmt_signed_f64(worst.edge())
+        ));
+    }
+    out.push_str(&format!("Total edge: {}\n", fmt_f64(notable.finalize())));
+    out
+}
+
+/// "step 312: price rose 2.100000%, arb sold 45.000000 X, pool lost 0.800000 edge"
+fn describe(step: &NotableStep) -> String {
+    let direction = if step.price_move() < 0.0 {
+        "fell"
+    } else {
+        "rose"
+    };
+    let mut parts = vec![format!(
+        "price {} {}%",
+        direction,
+        fmt_f64(step.price_move().abs() * 100.0)
+    )];
+    for trade in &step.trades {
+        let who = match trade.source {
+            TradeSource::Arbitrage => "arb",
+            TradeSource::Retail => "retail",
+        };
+        // The pool buying X means the trader sold it.
+        let action = if trade.amm_buys_x { "sold" } else { "bought" };
+        parts.push(format!("{} {} {} X", who, action, fmt_f64(trade.amount_x)));
+    }
+    let edge = step.edge();
+    let outcome = if edge < 0.0 { "lost" } else { "captured" };
+    parts.push(format!("pool {} {} edge", outcome, fmt_f64(edge.abs())));
+    format!("step {}: {}", step.step, parts.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use prop_amm_shared::normalizer::{after_swap, compute_swap};
+    use prop_amm_sim::engine::Simulation;
+    use prop_amm_sim::objective::{MarketCtx, Objective, StepRecord};
+    use prop_amm_sim::runner;
+
+    use super::explain;
+
+    /// Tracks the lowest-edge step on its own, without `NotableSteps`.
+    #[derive(Default)]
+    struct WorstStep {
+        worst: Option<(u32, f64)>,
+    }
+
+    impl Objective for WorstStep {
+        fn accumulate(&mut self, step: &StepRecord, _ctx: &MarketCtx) {
+            if step.trades.is_empty() {
+                return;
+            }
+            let edge: f64 = step.trades.iter().map(|t| t.edge).sum();
+            if self.worst.is_none_or(|(_, worst)| edge < worst) {
+                self.worst = Some((step.step, edge));
+            }
+        }
+
+        fn finalize(&self) -> f64 {
+            self.worst.map_or(0.0, |(_, edge)| edge)
+        }
+    }
+
+    #[test]
+    fn narrative_references_worst_step() {
+        let (seed, steps) = (3, 2_000);
+        let config = &runner::seeded_configs(1, steps, seed, 1)[0];
+        let mut tracker = WorstStep::default();
+        Simulation::native(
+            compute_swap,
+            Some(after_swap),
+            compute_swap,
+            Some(after_swap),
+            config,
+        )
+        .run_with(&mut tracker)
+        .unwrap();
+        let (worst, _) = tracker.worst.expect("something traded");
+
+        let narrative = explain(compute_swap, Some(after_swap), seed, steps, 5).unwrap();
+        let described: Vec<_> = narrative
+            .lines()
+            .filter(|line| line.starts_with("step "))
+            .collect();
+        assert_eq!(described.len(), 5);
+        assert!(
+            described
+                .iter()
+                .any(|line| line.starts_with(&format!("step {}: ", worst))),
+            "worst step {worst} missing from:\n{narrative}"
+        );
+        assert!(narrative.contains(&format!("Worst step {}:", worst)));
+    }
+}
@@ -0,0 +1,158 @@
+use clap::Args;
+
+use super::loader::{self, StrategyMetadata, SymbolSource};
+use super::storage_trace::StorageSchema;
+use super::{compile, run};
+
+#[derive(Args)]
+pub struct InspectArgs {
+    /// Path to the .rs source file
+    pub file: String,
+    /// Also build and load the BPF artifact (JIT and compute-unit metering support)
+    #[arg(long)]
+    pub bpf: bool,
+}
+
+/// Entry points a native strategy library exports, by the symbol that provides each.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    pub compute_swap: Option<String>,
+    pub after_swap: Option<String>,
+    pub metadata: StrategyMetadata,
+    /// Declared storage layout (`name:type@offset, ..`) or why it can't be used; `None`
+    /// when the strategy declares none and its storage is shown as raw bytes.
+    pub storage: Option<String>,
+}
+
+impl Capabilities {
+    /// Probe `source` the way `prop-amm run` resolves it, without calling any hook.
+    pub fn detect(source: &dyn SymbolSource) -> Self {
+        let export = |name, legacy| {
+            loader::find_symbol(source, name, legacy)
+                .map(|(symbol, _)| String::from_utf8_lossy(symbol).into_owned())
+        };
+        Self {
+            compute_swap: export(compile::NATIVE_SWAP_SYMBOL, loader::LEGACY_SWAP_SYMBOL),
+            after_swap: export(
+                compile::NATIVE_AFTER_SWAP_SYMBOL,
+                loader::LEGACY_AFTER_SWAP_SYMBOL,
+            ),
+            metadata: loader::read_metadata(source),
+            storage: loader::read_storage_schema(source).map(|text| {
+                match StorageSchema::parse(&text) {
+                    Ok(schema) => schema.describe(),
+                    Err(e) => format!("invalid STORAGE_SCHEMA: {}", e),
+                }
+            }),
+        }
+    }
+
+    /// One line per capability, as printed by `prop-amm inspect`.
+    pub fn report(&self) -> Vec<String> {
+        let hook = |symbol: &Option<String>| match symbol {
+            Some(symbol) => format!("yes ({})", symbol),
+            None => "no".to_string(),
+        };
+        let text = |value: &Option<String>| value.as_deref().unwrap_or("-").to_string();
+        vec![
+            format!("compute_swap: {}", hook(&self.compute_swap)),
+            format!("after_swap:   {}", hook(&self.after_swap)),
+            format!("name:         {}", text(&self.metadata.name)),
+            format!("version:      {}", text(&self.metadata.version)),
+            format!(
+                "storage:      {}",
+                self.storage
+                    .as_deref()
+                    .unwrap_or("raw bytes (no STORAGE_SCHEMA)")
+            ),
+        ]
+    }
+}
+
+pub fn run(args: InspectArgs) -> anyhow::Result<()> {
+    println!("Compiling {} (native)...", args.file);
+    let native_path = compile::compile_native(&args.file)?;
+    let lib = loader::open_library(&native_path)?;
+    let capabilities = Capabilities::detect(lib);
+
+    println!("Capabilities of {}:", args.file);
+    println!("  backend:      native ({})", native_path.display());
+    for line in capabilities.report() {
+        println!("  {}", line);
+    }
+
+    if args.bpf {
+        println!("Compiling {} (BPF)...", args.file);
+        let program = run::load_bpf_program(&compile::compile_bpf(&args.file)?)?;
+        let engine = if program.jit_available() {
+            "JIT"
+        } else {
+            "interpreter"
+        };
+        println!("  backend:      bpf ({})", engine);
+        println!("  CU metering:  yes");
+    } else {
+        println!("  CU metering:  BPF only (inspect with --bpf)");
+    }
+
+    if capabilities.compute_swap.is_none() {
+        anyhow::bail!("FAIL: no compute_swap export; `prop-amm run` cannot load this strategy");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Capabilities;
+    use crate::commands::compile;
+    use crate::commands::loader::fixtures::{
+        name_ffi, noop_after_swap_ffi, storage_schema_ffi, FixtureLibrary,
+    };
+
+    #[test]
+    fn reports_after_swap_only_when_exported() {
+        let bare = Capabilities::detect(&FixtureLibrary::starter());
+        assert_eq!(
+            bare.compute_swap.as_deref(),
+            Some("__prop_amm_compute_swap_export")
+        );
+        assert_eq!(bare.after_swap, None);
+        assert_eq!(bare.report()[1], "after_swap:   no");
+
+        let hooked = Capabilities::detect(
+            &FixtureLibrary::starter()
+                .with(b"after_swap_ffi", noop_after_swap_ffi as *mut ())
+                .with(compile::NATIVE_NAME_SYMBOL, name_ffi as *mut ()),
+        );
+        assert_eq!(hooked.after_swap.as_deref(), Some("after_swap_ffi"));
+        assert_eq!(
+            hooked.report(),
+            [
+                "compute_swap: yes (__prop_amm_compute_swap_export)",
+                "after_swap:   yes (after_swap_ffi)",
+                "name:         Fixture Strategy",
+                "version:      -",
+                "storage:      raw bytes (no STORAGE_SCHEMA)",
+            ]
+        );
+    }
+
+    #[test]
+    fn declared_storage_schema_is_listed_by_field() {
+        let described = Capabilities::detect(&FixtureLibrary::starter().with(
+            compile::NATIVE_STORAGE_SCHEMA_SYMBOL,
+            storage_schema_ffi as *mut (),
+        ));
+        assert_eq!(
+            described.report()[4],
+            "storage:      trades:u64@0, last_step:i64@8"
+        );
+    }
+
+    #[test]
+    fn missing_compute_swap_is_reported() {
+        let empty = Capabilities::detect(&FixtureLibrary::default());
+        assert_eq!(empty.compute_swap, None);
+        assert_eq!(empty.report()[0], "compute_swap: no");
+    }
+}
@@ -0,0 +1,197 @@
+use std::fmt::Write as _;
+use std::io::Write;
+
+use prop_amm_executor::{AfterSwapFn, SwapFn};
+use prop_amm_shared::config::SimulationConfig;
+use prop_amm_shared::instruction::{decode_oracle, decode_price_oracle, STORAGE_SIZE};
+use prop_amm_shared::normalizer::{after_swap as normalizer_after_swap, compute_swap};
+use prop_amm_shared::result::Phase;
+use prop_amm_sim::amm::InstructionRecord;
+use prop_amm_sim::engine::Simulation;
+use serde_json::json;
+
+/// Bytes of a compute_swap payload before the storage: side, input and both reserves.
+const HEADER_SIZE: usize = 25;
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut out, b| {
+        let _ = write!(out, "{:02x}", b);
+        out
+    })
+}
+
+/// One dump line: the step's `phase`, the header and storage as hex (storage with trailing
+/// zero bytes dropped, as `storage-trace` prints it), the oracles if the payload carried
+/// them, and the output.
+pub fn to_json_line(record: &InstructionRecord, phase: Phase) -> String {
+    let storage = &record.data[HEADER_SIZE..HEADER_SIZE + STORAGE_SIZE];
+    let used = storage.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+    json!({
+        "step": record.step,
+        "phase": phase,
+        "instruction": to_hex(&record.data[..HEADER_SIZE]),
+        "storage": to_hex(&storage[..used]),
+        "oracle": decode_oracle(&record.data),
+        "price": decode_price_oracle(&record.data),
+        "output": record.output,
+    })
+    .to_string()
+}
+
+/// Simulate `config` against the normalizer and write every compute_swap call the strategy
+/// receives to `out`, one JSON line each, step by step, then a `{"phases": ..}` line with
+/// the warmup and measured steps and edge. Returns the number of calls.
+pub fn dump(
+    swap_fn: SwapFn,
+    after_swap_fn: Option<AfterSwapFn>,
+    config: &SimulationConfig,
+    out: &mut impl Write,
+) -> anyhow::Result<usize> {
+    let mut sim = Simulation::native(
+        swap_fn,
+        after_swap_fn,
+        compute_swap,
+        Some(normalizer_after_swap),
+        config,
+    );
+    sim.submission_mut().record_instructions();
+    let mut n = 0;
+    loop {
+        let more = sim.step();
+        for record in sim.submission_mut().take_instructions() {
+            let phase = config.phase(record.step as u32);
+            writeln!(out, "{}", to_json_line(&record, phase))?;
+            n += 1;
+        }
+        if !more {
+            writeln!(out, "{}", json!({ "phases": sim.phases() }))?;
+            return Ok(n);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prop_amm_shared::config::SimulationConfig;
+    use prop_amm_shared::instruction::STORAGE_SIZE;
+    use serde_json::Value;
+
+    use super::{dump, HEADER_SIZE};
+
+    fn from_hex(text: &str) -> anyhow::Result<Vec<u8>> {
+        if !text.len().is_multiple_of(2) {
+            anyhow::bail!("odd-length hex `{}`", text);
+        }
+        (0..text.len())
+            .step_by(2)
+            .map(|i| {
+                u8::from_str_radix(&text[i..i + 2], 16)
+                    .map_err(|_| anyhow::anyhow!("bad hex `{}`", text))
+            })
+            .collect()
+    }
+
+    /// The exact compute_swap payload and output of a dump line, as a standalone test of the
+    /// strategy would rebuild them.
+    fn parse_line(line: &str) -> anyhow::Result<(Vec<u8>, u64)> {
+        let value: Value = serde_json::from_str(line)?;
+        let field = |name: &str| {
+            value
+                .get(name)
+                .ok_or_else(|| anyhow::anyhow!("dump line has no `{}`", name))
+        };
+        let hex = |name: &str| -> anyhow::Result<Vec<u8>> {
+            from_hex(
+                field(name)?
+                    .as_str()
+                    .ok_or_else(|| anyhow::anyhow!("`{}` is not a string", name))?,
+            )
+        };
+        let mut data = hex("instruction")?;
+        let storage = hex("storage")?;
+        if data.len() != HEADER_SIZE || storage.len() > STORAGE_SIZE {
+            anyhow::bail!("malformed dump line `{}`", line);
+        }
+        data.extend_from_slice(&storage);
+        data.resize(HEADER_SIZE + STORAGE_SIZE, 0);
+        let price = field("price")?.as_u64();
+        if let Some(oracle) = field("oracle")?.as_u64().or(price.map(|_| 0)) {
+            data.extend_from_slice(&oracle.to_le_bytes());
+        }
+        if let Some(price) = price {
+            data.extend_from_slice(&price.to_le_bytes());
+        }
+        let output = field("output")?
+            .as_u64()
+            .ok_or_else(|| anyhow::anyhow!("`output` is not a u64"))?;
+        Ok((data, output))
+    }
+
+    /// Constant product at a fee read from storage byte 0 (bps), bumped on every fill so the
+    /// dumped storage changes over the run.
+    fn storage_fee_swap(data: &[u8]) -> u64 {
+        let read = |at: usize| u64::from_le_bytes(data[at..at + 8].try_into().unwrap()) as u128;
+        let (input, rx, ry) = (read(1), read(9), read(17));
+        let fee = 30 + data[25] as u128 % 50;
+        let net = input * (10_000 - fee) / 10_000;
+        let out = if data[0] == 0 {
+            rx - rx * ry / (ry + net)
+        } else {
+            ry - rx * ry / (rx + net)
+        };
+        out as u64
+    }
+
+    fn bump_after_swap(_data: &[u8], storage: &mut [u8]) {
+        storage[0] = storage[0].wrapping_add(7);
+    }
+
+    #[test]
+    fn dumped_bytes_replay_to_the_recorded_outputs() {
+        let config = SimulationConfig {
+            n_steps: 40,
+            seed: 9,
+            twap_window: Some(8),
+            ..SimulationConfig::default()
+        };
+        let mut out = Vec::new();
+        let n = dump(storage_fee_swap, Some(bump_after_swap), &config, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(text.lines().count(), n + 1);
+        assert!(n > 40, "{n} calls");
+
+        let mut storages = std::collections::HashSet::new();
+        for line in text.lines().take(n) {
+            let (data, output) = parse_line(line).unwrap();
+            assert_eq!(storage_fee_swap(&data), output, "{line}");
+            assert_eq!(data.len(), 25 + 1024 + 8, "oracle should be appended");
+            storages.insert(data[25]);
+        }
+        assert!(storages.len() > 1, "storage should change between calls");
+    }
+
+    #[test]
+    fn calls_of_warmup_steps_are_tagged_and_totalled_apart() {
+        let config = SimulationConfig {
+            n_steps: 30,
+            warmup_steps: 12,
+            seed: 9,
+            ..SimulationConfig::default()
+        };
+        let mut out = Vec::new();
+        let n = dump(storage_fee_swap, Some(bump_after_swap), &config, &mut out).unwrap();
+        let lines: Vec<Value> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        for call in &lines[..n] {
+            let warmup = call["step"].as_u64().unwrap() < 12;
+            let phase = if warmup { "warmup" } else { "measured" };
+            assert_eq!(call["phase"], phase, "{call}");
+        }
+        let phases = &lines[n]["phases"];
+        assert_eq!(phases["warmup"]["steps"], 12);
+        assert_eq!(phases["measured"]["steps"], 18);
+    }
+}
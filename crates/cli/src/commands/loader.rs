@@ -0,0 +1,462 @@
+use std::path::Path;
+use std::sync::OnceLock;
+
+use prop_amm_executor::{AfterSwapFn, SwapFn};
+
+use super::compile;
+
+type FfiSwapFn = unsafe extern "C" fn(*const u8, usize) -> u64;
+type FfiAfterSwapFn = unsafe extern "C" fn(*const u8, usize, *mut u8, usize);
+type FfiStrFn = unsafe extern "C" fn(*mut usize) -> *const u8;
+
+pub const LEGACY_SWAP_SYMBOL: &[u8] = b"compute_swap_ffi";
+pub const LEGACY_AFTER_SWAP_SYMBOL: &[u8] = b"after_swap_ffi";
+pub const LEGACY_NAME_SYMBOL: &[u8] = b"strategy_name_ffi";
+pub const LEGACY_VERSION_SYMBOL: &[u8] = b"strategy_version_ffi";
+pub const LEGACY_STORAGE_SCHEMA_SYMBOL: &[u8] = b"storage_schema_ffi";
+
+/// Strategies one process can hold loaded side by side, one trampoline slot each.
+pub const MAX_LOADED: usize = 16;
+
+/// Addresses of the entry points installed in one trampoline slot.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Installed {
+    swap: usize,
+    after_swap: Option<usize>,
+}
+
+static LOADED: [OnceLock<Installed>; MAX_LOADED] = [const { OnceLock::new() }; MAX_LOADED];
+
+const SWAP_TRAMPOLINES: [SwapFn; MAX_LOADED] = [
+    dynamic_swap::<0>,
+    dynamic_swap::<1>,
+    dynamic_swap::<2>,
+    dynamic_swap::<3>,
+    dynamic_swap::<4>,
+    dynamic_swap::<5>,
+    dynamic_swap::<6>,
+    dynamic_swap::<7>,
+    dynamic_swap::<8>,
+    dynamic_swap::<9>,
+    dynamic_swap::<10>,
+    dynamic_swap::<11>,
+    dynamic_swap::<12>,
+    dynamic_swap::<13>,
+    dynamic_swap::<14>,
+    dynamic_swap::<15>,
+];
+
+const AFTER_SWAP_TRAMPOLINES: [AfterSwapFn; MAX_LOADED] = [
+    dynamic_after_swap::<0>,
+    dynamic_after_swap::<1>,
+    dynamic_after_swap::<2>,
+    dynamic_after_swap::<3>,
+    dynamic_after_swap::<4>,
+    dynamic_after_swap::<5>,
+    dynamic_after_swap::<6>,
+    dynamic_after_swap::<7>,
+    dynamic_after_swap::<8>,
+    dynamic_after_swap::<9>,
+    dynamic_after_swap::<10>,
+    dynamic_after_swap::<11>,
+    dynamic_after_swap::<12>,
+    dynamic_after_swap::<13>,
+    dynamic_after_swap::<14>,
+    dynamic_after_swap::<15>,
+];
+
+/// Anything that can resolve exported symbols by name (a loaded library, or a test fixture).
+pub trait SymbolSource {
+    fn symbol(&self, name: &[u8]) -> Option<*mut ()>;
+}
+
+impl SymbolSource for libloading::Library {
+    fn symbol(&self, name: &[u8]) -> Option<*mut ()> {
+        unsafe { self.get::<*mut ()>(name) }.ok().map(|sym| *sym)
+    }
+}
+
+/// Raw entry points resolved from a native strategy library.
+#[derive(Clone, Copy)]
+pub struct ResolvedSymbols {
+    pub swap: *mut (),
+    pub after_swap: Option<*mut ()>,
+}
+
+/// The first of `name` / `legacy` that `source` exports as a non-null symbol, with its address.
+pub fn find_symbol(
+    source: &dyn SymbolSource,
+    name: &'static [u8],
+    legacy: &'static [u8],
+) -> Option<(&'static [u8], *mut ())> {
+    [name, legacy].into_iter().find_map(|candidate| {
+        source
+            .symbol(candidate)
+            .filter(|ptr| !ptr.is_null())
+            .map(|ptr| (candidate, ptr))
+    })
+}
+
+/// Look up the swap (required) and after_swap (optional) exports, including legacy names.
+pub fn resolve_symbols(source: &dyn SymbolSource) -> anyhow::Result<ResolvedSymbols> {
+    let (_, swap) = find_symbol(source, compile::NATIVE_SWAP_SYMBOL, LEGACY_SWAP_SYMBOL)
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Missing native swap symbol: expected `{}` or `{}`",
+                String::from_utf8_lossy(compile::NATIVE_SWAP_SYMBOL),
+                String::from_utf8_lossy(LEGACY_SWAP_SYMBOL)
+            )
+        })?;
+    let after_swap = find_symbol(
+        source,
+        compile::NATIVE_AFTER_SWAP_SYMBOL,
+        LEGACY_AFTER_SWAP_SYMBOL,
+    )
+    .map(|(_, ptr)| ptr);
+    Ok(ResolvedSymbols { swap, after_swap })
+}
+
+/// Optional display metadata exported by a strategy library.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StrategyMetadata {
+    pub name: Option<String>,
+    pub version: Option<String>,
+}
+
+impl StrategyMetadata {
+    /// `name vVERSION` for report headers, or `fallback` (the source path) without a name.
+    pub fn title(&self, fallback: &str) -> String {
+        match (&self.name, &self.version) {
+            (Some(name), Some(version)) => format!("{} v{}", name, version),
+            (Some(name), None) => name.clone(),
+            (None, _) => fallback.to_string(),
+        }
+    }
+}
+
+/// Read the name/version exports, if any. Missing or empty strings count as absent.
+pub fn read_metadata(source: &dyn SymbolSource) -> StrategyMetadata {
+    StrategyMetadata {
+        name: read_str(source, compile::NATIVE_NAME_SYMBOL, LEGACY_NAME_SYMBOL),
+        version: read_str(
+            source,
+            compile::NATIVE_VERSION_SYMBOL,
+            LEGACY_VERSION_SYMBOL,
+        ),
+    }
+}
+
+/// Read the `STORAGE_SCHEMA` export, if any (see `storage_trace::StorageSchema`).
+pub fn read_storage_schema(source: &dyn SymbolSource) -> Option<String> {
+    read_str(
+        source,
+        compile::NATIVE_STORAGE_SCHEMA_SYMBOL,
+        LEGACY_STORAGE_SCHEMA_SYMBOL,
+    )
+}
+
+fn read_str(
+    source: &dyn SymbolSource,
+    name: &'static [u8],
+    legacy: &'static [u8],
+) -> Option<String> {
+    let (_, ptr) = find_symbol(source, name, legacy)?;
+    let f: FfiStrFn = unsafe { std::mem::transmute(ptr) };
+    let mut len = 0usize;
+    let data = unsafe { f(&mut len) };
+    if data.is_null() || len == 0 {
+        return None;
+    }
+    let bytes = unsafe { std::slice::from_raw_parts(data, len) };
+    let text = String::from_utf8_lossy(bytes).trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+/// Open a native library and keep it loaded for the rest of the process.
+pub fn open_library(native_path: &Path) -> anyhow::Result<&'static libloading::Library> {
+    let lib = Box::new(
+        unsafe { libloading::Library::new(native_path) }.map_err(|e| {
+            anyhow::anyhow!(
+                "Failed to load native library {}: {}",
+                native_path.display(),
+                e
+            )
+        })?,
+    );
+    Ok(Box::leak(lib))
+}
+
+/// Point the process-wide trampolines at `symbols` and return them as engine callbacks.
+///
+/// The trampolines are shared by every batch worker, so each slot is set at most once per
+/// process: re-installing the same strategy is a no-op, while installing a different one is
+/// an error rather than a silent swap under workers that may still be reading it.
+pub fn install(symbols: ResolvedSymbols) -> anyhow::Result<(SwapFn, Option<AfterSwapFn>)> {
+    install_at(0, symbols)
+}
+
+/// Like `install`, into trampoline slot `slot` (below `MAX_LOADED`), so several strategies
+/// can be loaded at once, e.g. one slot per round-robin entrant.
+pub fn install_at(
+    slot: usize,
+    symbols: ResolvedSymbols,
+) -> anyhow::Result<(SwapFn, Option<AfterSwapFn>)> {
+    if slot >= MAX_LOADED {
+        anyhow::bail!(
+            "at most {} native strategies can be loaded at once",
+            MAX_LOADED
+        );
+    }
+    let wanted = Installed {
+        swap: symbols.swap as usize,
+        after_swap: symbols.after_swap.map(|ptr| ptr as usize),
+    };
+    // Both entry points are published together: no trampoline ever sees half a strategy.
+    if *LOADED[slot].get_or_init(|| wanted) != wanted {
+        anyhow::bail!("A different native strategy is already loaded in this process");
+    }
+    let after_swap = wanted.after_swap.map(|_| AFTER_SWAP_TRAMPOLINES[slot]);
+    Ok((SWAP_TRAMPOLINES[slot], after_swap))
+}
+
+fn installed<const SLOT: usize>() -> Installed {
+    *LOADED[SLOT]
+        .get()
+        .expect("trampolines are only handed out once their slot is installed")
+}
+
+fn dynamic_swap<const SLOT: usize>(data: &[u8]) -> u64 {
+    let ptr = installed::<SLOT>().swap as *const ();
+    let f: FfiSwapFn = unsafe { std::mem::transmute(ptr) };
+    unsafe { f(data.as_ptr(), data.len()) }
+}
+
+fn dynamic_after_swap<const SLOT: usize>(data: &[u8], storage: &mut [u8]) {
+    let ptr = installed::<SLOT>()
+        .after_swap
+        .expect("only strategies with an after_swap get its trampoline") as *const ();
+    let f: FfiAfterSwapFn = unsafe { std::mem::transmute(ptr) };
+    unsafe {
+        f(
+            data.as_ptr(),
+            data.len(),
+            storage.as_mut_ptr(),
+            storage.len(),
+        )
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use std::collections::HashMap;
+
+    use super::SymbolSource;
+    use crate::commands::compile;
+
+    /// In-process stand-in for a compiled strategy library.
+    #[derive(Default)]
+    pub struct FixtureLibrary {
+        symbols: HashMap<Vec<u8>, *mut ()>,
+    }
+
+    impl FixtureLibrary {
+        pub fn with(mut self, name: &[u8], ptr: *mut ()) -> Self {
+            self.symbols.insert(name.to_vec(), ptr);
+            self
+        }
+
+        /// Constant-product strategy with a 5% fee, exported under the native shim names.
+        pub fn starter() -> Self {
+            Self::default().with(compile::NATIVE_SWAP_SYMBOL, starter_swap_ffi as *mut ())
+        }
+    }
+
+    impl SymbolSource for FixtureLibrary {
+        fn symbol(&self, name: &[u8]) -> Option<*mut ()> {
+            self.symbols.get(name).copied()
+        }
+    }
+
+    pub extern "C" fn starter_swap_ffi(data: *const u8, len: usize) -> u64 {
+        let data = unsafe { std::slice::from_raw_parts(data, len) };
+        if data.len() < 25 {
+            return 0;
+        }
+        let side = data[0];
+        let input = u64::from_le_bytes(data[1..9].try_into().unwrap()) as u128;
+        let rx = u64::from_le_bytes(data[9..17].try_into().unwrap()) as u128;
+        let ry = u64::from_le_bytes(data[17..25].try_into().unwrap()) as u128;
+        if rx == 0 || ry == 0 {
+            return 0;
+        }
+        let k = rx * ry;
+        let net = input * 950 / 1000;
+        match side {
+            0 => rx.saturating_sub(k.div_ceil(ry + net)) as u64,
+            1 => ry.saturating_sub(k.div_ceil(rx + net)) as u64,
+            _ => 0,
+        }
+    }
+
+    pub extern "C" fn name_ffi(out_len: *mut usize) -> *const u8 {
+        const NAME: &str = "Fixture Strategy";
+        unsafe { *out_len = NAME.len() };
+        NAME.as_ptr()
+    }
+
+    pub extern "C" fn version_ffi(out_len: *mut usize) -> *const u8 {
+        const VERSION: &str = "1.2.0";
+        unsafe { *out_len = VERSION.len() };
+        VERSION.as_ptr()
+    }
+
+    pub extern "C" fn storage_schema_ffi(out_len: *mut usize) -> *const u8 {
+        const STORAGE_SCHEMA: &str = "trades:u64@0, last_step:i64@8";
+        unsafe { *out_len = STORAGE_SCHEMA.len() };
+        STORAGE_SCHEMA.as_ptr()
+    }
+
+    pub extern "C" fn noop_after_swap_ffi(
+        _data: *const u8,
+        _data_len: usize,
+        _storage: *mut u8,
+        _storage_len: usize,
+    ) {
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixtures::{name_ffi, noop_after_swap_ffi, version_ffi, FixtureLibrary};
+    use super::{
+        install, install_at, read_metadata, resolve_symbols, StrategyMetadata, MAX_LOADED,
+    };
+    use crate::commands::compile;
+
+    #[test]
+    fn resolves_swap_and_optional_after_swap() {
+        let lib = FixtureLibrary::starter();
+        let symbols = resolve_symbols(&lib).expect("swap symbol present");
+        assert!(symbols.after_swap.is_none());
+
+        let lib = FixtureLibrary::starter().with(
+            compile::NATIVE_AFTER_SWAP_SYMBOL,
+            noop_after_swap_ffi as *mut (),
+        );
+        let symbols = resolve_symbols(&lib).expect("swap symbol present");
+        assert!(symbols.after_swap.is_some());
+    }
+
+    #[test]
+    fn metadata_symbols_title_the_strategy() {
+        let lib = FixtureLibrary::starter().with(compile::NATIVE_NAME_SYMBOL, name_ffi as *mut ());
+        let meta = read_metadata(&lib);
+        assert_eq!(meta.name.as_deref(), Some("Fixture Strategy"));
+        assert_eq!(meta.version, None);
+        assert_eq!(meta.title("my_amm.rs"), "Fixture Strategy");
+
+        let lib = FixtureLibrary::starter()
+            .with(b"strategy_name_ffi", name_ffi as *mut ())
+            .with(b"strategy_version_ffi", version_ffi as *mut ());
+        assert_eq!(
+            read_metadata(&lib).title("my_amm.rs"),
+            "Fixture Strategy v1.2.0"
+        );
+
+        let bare = read_metadata(&FixtureLibrary::starter());
+        assert_eq!(bare, StrategyMetadata::default());
+        assert_eq!(bare.title("my_amm.rs"), "my_amm.rs");
+    }
+
+    #[test]
+    fn missing_swap_symbol_is_reported() {
+        let err = resolve_symbols(&FixtureLibrary::default())
+            .err()
+            .expect("missing swap symbol must fail");
+        assert!(err.to_string().contains("Missing native swap symbol"));
+    }
+
+    #[test]
+    fn global_pointer_batch_is_deterministic_across_workers() {
+        use prop_amm_shared::normalizer::{after_swap, compute_swap};
+        use prop_amm_sim::runner;
+
+        // The exact path `prop-amm run` takes: resolve, install into the globals, then batch.
+        let lib = FixtureLibrary::starter().with(
+            compile::NATIVE_AFTER_SWAP_SYMBOL,
+            noop_after_swap_ffi as *mut (),
+        );
+        let (swap, after) = install(resolve_symbols(&lib).unwrap()).unwrap();
+        let batch = |workers| {
+            runner::run_batch_native(
+                swap,
+                after,
+                compute_swap,
+                Some(after_swap),
+                runner::seeded_configs(16, 400, 100, 1),
+                Some(workers),
+            )
+            .unwrap()
+        };
+
+        let single = batch(1);
+        let parallel = batch(8);
+        assert_eq!(single.n_sims(), 16);
+        for (a, b) in single.results.iter().zip(&parallel.results) {
+            assert_eq!(a.seed, b.seed);
+            assert_eq!(a.submission_edge.to_bits(), b.submission_edge.to_bits());
+        }
+
+        // Re-installing the same strategy is fine; swapping in another one mid-process is not.
+        assert!(install(resolve_symbols(&lib).unwrap()).is_ok());
+        let other =
+            FixtureLibrary::default().with(compile::NATIVE_SWAP_SYMBOL, noop_swap_ffi as *mut ());
+        assert!(install(resolve_symbols(&other).unwrap()).is_err());
+    }
+
+    #[test]
+    fn strategies_in_separate_slots_quote_independently() {
+        use prop_amm_shared::instruction::encode_instruction;
+
+        // The top slots, clear of the slot-0 strategy the other tests install.
+        let starter = resolve_symbols(&FixtureLibrary::starter()).unwrap();
+        let noop = resolve_symbols(
+            &FixtureLibrary::default().with(compile::NATIVE_SWAP_SYMBOL, noop_swap_ffi as *mut ()),
+        )
+        .unwrap();
+        let (starter_swap, _) = install_at(MAX_LOADED - 1, starter).unwrap();
+        let (noop_swap, _) = install_at(MAX_LOADED - 2, noop).unwrap();
+        let data = encode_instruction(0, 1_000_000_000, 100_000_000_000, 10_000_000_000_000);
+        assert!(starter_swap(&data) > 0);
+        assert_eq!(noop_swap(&data), 0);
+
+        assert!(install_at(MAX_LOADED - 1, noop).is_err());
+        assert!(install_at(MAX_LOADED, starter).is_err());
+    }
+
+    #[test]
+    fn a_refused_install_leaves_the_slot_as_it_was() {
+        use prop_amm_shared::instruction::encode_instruction;
+
+        let slot = MAX_LOADED - 3;
+        let (swap, after_swap) =
+            install_at(slot, resolve_symbols(&FixtureLibrary::starter()).unwrap()).unwrap();
+        assert!(after_swap.is_none());
+
+        // Same swap, plus an after_swap the installed strategy never had: refused whole.
+        let with_after_swap = FixtureLibrary::starter().with(
+            compile::NATIVE_AFTER_SWAP_SYMBOL,
+            noop_after_swap_ffi as *mut (),
+        );
+        assert!(install_at(slot, resolve_symbols(&with_after_swap).unwrap()).is_err());
+        let again = install_at(slot, resolve_symbols(&FixtureLibrary::starter()).unwrap());
+        assert!(again.unwrap().1.is_none());
+
+        let data = encode_instruction(0, 1_000_000_000, 100_000_000_000, 10_000_000_000_000);
+        assert!(swap(&data) > 0);
+    }
+
+    extern "C" fn noop_swap_ffi(_data: *const u8, _len: usize) -> u64 {
+        0
+    }
+}
@@ -1,4 +1,23 @@
+pub mod attribute;
+pub mod baseline;
+pub mod bench;
+pub mod bench_engine;
 pub mod build;
+pub mod check_purity;
 pub mod compile;
+pub mod curve;
+pub mod diff_versions;
+pub mod explain;
+pub mod inspect;
+pub mod instruction_dump;
+pub mod loader;
+pub mod profile_inputs;
+pub mod roundrobin;
 pub mod run;
+pub mod storage_trace;
+pub mod stress;
+pub mod sweep;
+pub mod tournament;
 pub mod validate;
+pub mod verify_golden;
+pub mod watch;
@@ -0,0 +1,274 @@
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use clap::Args;
+use prop_amm_executor::{BpfExecutor, SwapFn};
+use prop_amm_shared::config::{INITIAL_X, INITIAL_Y};
+use prop_amm_shared::instruction::{STORAGE_SIZE, SWAP_INSTRUCTION_SIZE};
+use prop_amm_shared::nano::f64_to_nano;
+
+use super::{compile, loader, run};
+use crate::output::fmt_f64;
+
+/// Trade size as a fraction of the reserve the input is paid into.
+const AMOUNT_FRACTIONS: [f64; 5] = [0.0001, 0.001, 0.01, 0.1, 0.5];
+/// Pool depth relative to the baseline reserves (thin .. deep).
+const RESERVE_MULTS: [f64; 4] = [0.1, 1.0, 10.0, 100.0];
+
+#[derive(Args)]
+pub struct ProfileInputsArgs {
+    /// Path to the .rs source file
+    pub file: String,
+    /// Measure BPF compute units instead of native nanoseconds
+    #[arg(long)]
+    pub bpf: bool,
+    /// Path to a prebuilt BPF .so to use with --bpf (skips compilation)
+    #[arg(long)]
+    pub bpf_so: Option<String>,
+    /// Native timing samples per bucket (the median is reported)
+    #[arg(long, default_value = "64")]
+    pub samples: u32,
+    /// Print the heatmap as CSV instead of a table
+    #[arg(long)]
+    pub csv: bool,
+}
+
+/// One cell of the input grid.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bucket {
+    /// 0 = buy X (pay Y), 1 = sell X (receive Y).
+    pub side: u8,
+    pub amount_frac: f64,
+    pub reserve_mult: f64,
+}
+
+impl Bucket {
+    pub fn reserves(&self) -> (u64, u64) {
+        (
+            f64_to_nano(INITIAL_X * self.reserve_mult),
+            f64_to_nano(INITIAL_Y * self.reserve_mult),
+        )
+    }
+
+    pub fn amount(&self) -> u64 {
+        let input_reserve = if self.side == 0 { INITIAL_Y } else { INITIAL_X };
+        f64_to_nano(input_reserve * self.reserve_mult * self.amount_frac).max(1)
+    }
+
+    fn side_label(&self) -> &'static str {
+        if self.side == 0 {
+            "buy"
+        } else {
+            "sell"
+        }
+    }
+}
+
+/// Cost of every bucket in grid order; `unit` is "ns" or "CU".
+pub struct InputProfile {
+    pub unit: &'static str,
+    pub cells: Vec<(Bucket, f64)>,
+}
+
+impl InputProfile {
+    pub fn worst(&self) -> Option<&(Bucket, f64)> {
+        self.cells.iter().max_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut out = format!("side,amount_frac,reserve_mult,cost_{}\n", self.unit);
+        for (bucket, cost) in &self.cells {
+            let _ = writeln!(
+                out,
+                "{},{},{},{}",
+                bucket.side_label(),
+                fmt_f64(bucket.amount_frac),
+                fmt_f64(bucket.reserve_mult),
+                fmt_f64(*cost)
+            );
+        }
+        out
+    }
+
+    /// One table per side: rows are trade sizes, columns are pool depths.
+    pub fn to_table(&self) -> String {
+        let mut out = String::new();
+        for side in [0u8, 1] {
+            let label = if side == 0 { "buy X" } else { "sell X" };
+            let _ = write!(out, "\n{:<7} {:>10}", label, "size\\depth");
+            for mult in RESERVE_MULTS {
+                let _ = write!(out, " {:>10}", format!("{}x", mult));
+            }
+            out.push('\n');
+            for frac in AMOUNT_FRACTIONS {
+                let _ = write!(out, "{:<7} {:>10}", "", format!("{}%", frac * 100.0));
+                for mult in RESERVE_MULTS {
+                    let cost = self
+                        .cells
+                        .iter()
+                        .find(|(b, _)| {
+                            b.side == side && b.amount_frac == frac && b.reserve_mult == mult
+                        })
+                        .map(|(_, c)| *c)
+                        .unwrap_or(f64::NAN);
+                    let _ = write!(out, " {:>10.1}", cost);
+                }
+                out.push('\n');
+            }
+        }
+        out
+    }
+}
+
+pub fn grid() -> Vec<Bucket> {
+    let mut buckets = Vec::with_capacity(2 * AMOUNT_FRACTIONS.len() * RESERVE_MULTS.len());
+    for side in [0u8, 1] {
+        for amount_frac in AMOUNT_FRACTIONS {
+            for reserve_mult in RESERVE_MULTS {
+                buckets.push(Bucket {
+                    side,
+                    amount_frac,
+                    reserve_mult,
+                });
+            }
+        }
+    }
+    buckets
+}
+
+/// Run `cost` over every bucket of the grid.
+pub fn profile<F>(unit: &'static str, mut cost: F) -> anyhow::Result<InputProfile>
+where
+    F: FnMut(&Bucket) -> anyhow::Result<f64>,
+{
+    let cells = grid()
+        .into_iter()
+        .map(|bucket| cost(&bucket).map(|c| (bucket, c)))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok(InputProfile { unit, cells })
+}
+
+/// Median wall-clock nanoseconds of `samples` warm calls per bucket.
+pub fn profile_native(swap: SwapFn, samples: u32) -> anyhow::Result<InputProfile> {
+    let samples = samples.max(1) as usize;
+    let mut data = [0u8; SWAP_INSTRUCTION_SIZE];
+    let mut timings = Vec::with_capacity(samples);
+    profile("ns", |bucket| {
+        let (rx, ry) = bucket.reserves();
+        data[0] = bucket.side;
+        data[1..9].copy_from_slice(&bucket.amount().to_le_bytes());
+        data[9..17].copy_from_slice(&rx.to_le_bytes());
+        data[17..25].copy_from_slice(&ry.to_le_bytes());
+
+        std::hint::black_box(swap(&data));
+        timings.clear();
+        for _ in 0..samples {
+            let start = Instant::now();
+            std::hint::black_box(swap(std::hint::black_box(&data)));
+            timings.push(start.elapsed().as_nanos() as f64);
+        }
+        timings.sort_by(f64::total_cmp);
+        Ok(timings[timings.len() / 2])
+    })
+}
+
+/// Compute units per bucket; deterministic, so one call each.
+pub fn profile_bpf(executor: &mut BpfExecutor) -> anyhow::Result<InputProfile> {
+    let storage = [0u8; STORAGE_SIZE];
+    profile("CU", |bucket| {
+        let (rx, ry) = bucket.reserves();
+        executor
+            .execute(bucket.side, bucket.amount(), rx, ry, &storage)
+            .map_err(|e| anyhow::anyhow!("Execution failed for {:?}: {}", bucket, e))?;
+        Ok(executor.last_instruction_count() as f64)
+    })
+}
+
+pub fn run(args: ProfileInputsArgs) -> anyhow::Result<()> {
+    let profile = if args.bpf {
+        if std::env::var_os("PROP_AMM_BPF_DISABLE_METER").is_some() {
+            anyhow::bail!("PROP_AMM_BPF_DISABLE_METER is set; compute units cannot be measured");
+        }
+        let bpf_path = if let Some(path) = args.bpf_so.as_deref() {
+            println!("Using prebuilt BPF .so: {}", path);
+            PathBuf::from(path)
+        } else {
+            println!("Compiling {} (BPF)...", args.file);
+            compile::compile_bpf(&args.file)?
+        };
+        let program = run::load_bpf_program(&bpf_path)?;
+        profile_bpf(&mut BpfExecutor::new(program))?
+    } else {
+        println!("Compiling {} (native)...", args.file);
+        let native_path = compile::compile_native(&args.file)?;
+        let lib = loader::open_library(&native_path)?;
+        let (swap, _) = loader::install(loader::resolve_symbols(lib)?)?;
+        profile_native(swap, args.samples)?
+    };
+
+    if args.csv {
+        print!("{}", profile.to_csv());
+    } else {
+        println!(
+            "\nCost per swap ({}), reserves scaled from {}/{}:",
+            profile.unit, INITIAL_X, INITIAL_Y
+        );
+        print!("{}", profile.to_table());
+    }
+    if let Some((bucket, cost)) = profile.worst() {
+        println!(
+            "\nMost expensive input class: {} {}% of reserve at {}x depth ({:.1} {})",
+            bucket.side_label(),
+            bucket.amount_frac * 100.0,
+            bucket.reserve_mult,
+            cost,
+            profile.unit
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{grid, profile, profile_native};
+
+    /// Constant-product quote that takes a deliberately slow branch for large buys in thin pools.
+    fn slow_thin_buy_swap(data: &[u8]) -> u64 {
+        let side = data[0];
+        let input = u64::from_le_bytes(data[1..9].try_into().unwrap()) as u128;
+        let rx = u64::from_le_bytes(data[9..17].try_into().unwrap()) as u128;
+        let ry = u64::from_le_bytes(data[17..25].try_into().unwrap()) as u128;
+        if side == 0 && ry < 5_000_000_000_000 && input * 4 > ry {
+            let mut acc = 0u64;
+            for i in 0..200_000u64 {
+                acc = std::hint::black_box(acc.wrapping_mul(31).wrapping_add(i));
+            }
+            std::hint::black_box(acc);
+        }
+        let k = rx * ry;
+        match side {
+            0 => rx.saturating_sub(k.div_ceil(ry + input)) as u64,
+            _ => ry.saturating_sub(k.div_ceil(rx + input)) as u64,
+        }
+    }
+
+    #[test]
+    fn slow_branch_is_reported_as_worst_bucket() {
+        let result = profile_native(slow_thin_buy_swap, 9).unwrap();
+        assert_eq!(result.cells.len(), grid().len());
+        let (worst, _) = result.worst().unwrap();
+        assert_eq!(worst.side, 0);
+        assert_eq!(worst.amount_frac, 0.5);
+        assert_eq!(worst.reserve_mult, 0.1);
+    }
+
+    #[test]
+    fn csv_has_one_row_per_bucket() {
+        let result = profile("CU", |b| Ok(b.amount_frac * b.reserve_mult)).unwrap();
+        let csv = result.to_csv();
+        assert!(csv.starts_with("side,amount_frac,reserve_mult,cost_CU\n"));
+        assert_eq!(csv.lines().count(), grid().len() + 1);
+        assert!(result.to_table().contains("sell X"));
+    }
+}
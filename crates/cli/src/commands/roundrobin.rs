@@ -0,0 +1,229 @@
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use prop_amm_sim::opponents::{self, Opponent, RoundRobin};
+use prop_amm_sim::runner;
+
+use super::{compile, loader};
+use crate::output::fmt_f64;
+use crate::stamp::RunStamp;
+
+#[derive(Args)]
+pub struct RoundRobinArgs {
+    /// Directory of strategy .rs source files, one entrant each
+    pub dir: String,
+    /// Number of simulations per pairing
+    #[arg(long, default_value = "100")]
+    pub simulations: u32,
+    /// Number of steps per simulation
+    #[arg(long, default_value = "10000")]
+    pub steps: u32,
+    /// Number of parallel workers, respected exactly (0 = one per core, at most 8)
+    #[arg(long, default_value = "0")]
+    pub workers: usize,
+    /// Starting seed for simulation config generation
+    #[arg(long, default_value = "0")]
+    pub seed_start: u64,
+    /// Seed step between simulations
+    #[arg(long, default_value = "1")]
+    pub seed_stride: u64,
+    /// Write the head-to-head matrix as CSV to this file instead of printing it
+    #[arg(long)]
+    pub csv: Option<PathBuf>,
+}
+
+/// The `.rs` files directly in `dir`, in path order.
+fn entrant_sources(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let entries = std::fs::read_dir(dir)
+        .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", dir.display(), e))?;
+    let mut sources = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "rs") {
+            sources.push(path);
+        }
+    }
+    sources.sort();
+    Ok(sources)
+}
+
+pub fn run(args: RoundRobinArgs) -> anyhow::Result<()> {
+    if args.seed_stride == 0 {
+        anyhow::bail!("--seed-stride must be >= 1");
+    }
+    if args.simulations == 0 {
+        anyhow::bail!("--simulations must be >= 1: an empty batch has no edge to report");
+    }
+    let sources = entrant_sources(Path::new(&args.dir))?;
+    if sources.len() < 2 {
+        anyhow::bail!(
+            "a round robin needs at least two .rs strategies in {}, found {}",
+            args.dir,
+            sources.len()
+        );
+    }
+    if sources.len() > loader::MAX_LOADED {
+        anyhow::bail!(
+            "a round robin holds at most {} strategies, found {} in {}",
+            loader::MAX_LOADED,
+            sources.len(),
+            args.dir
+        );
+    }
+
+    let mut entrants = Vec::with_capacity(sources.len());
+    // Every entrant's artifact, in slot order, as the stamp's binary.
+    let mut binaries = Vec::new();
+    for (slot, source) in sources.iter().enumerate() {
+        let file = source.display().to_string();
+        println!("Compiling {} (native)...", file);
+        let native_path = compile::compile_native(&file)?;
+        binaries.extend(
+            std::fs::read(&native_path)
+                .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", native_path.display(), e))?,
+        );
+        let lib = loader::open_library(&native_path)?;
+        let (swap, after_swap) = loader::install_at(slot, loader::resolve_symbols(lib)?)?;
+        // Loaded for the rest of the process like the library itself.
+        let name: &'static str = Box::leak(loader::read_metadata(lib).title(&file).into());
+        entrants.push(Opponent {
+            name,
+            description: "round-robin entrant",
+            swap,
+            after_swap,
+        });
+    }
+
+    println!(
+        "Playing {} strategies against each other and the normalizer, {} simulations ({} steps each) per pairing, seeds {} + i*{}...",
+        entrants.len(),
+        args.simulations,
+        args.steps,
+        args.seed_start,
+        args.seed_stride,
+    );
+    let configs = runner::seeded_configs(
+        args.simulations,
+        args.steps,
+        args.seed_start,
+        args.seed_stride,
+    );
+    let workers = (args.workers != 0).then_some(args.workers);
+    let round_robin = opponents::run_round_robin(&entrants, &configs, workers)?;
+    let stamp = RunStamp::new(&configs, &binaries, "round robin");
+    let csv = stamp.csv_comment() + &render_csv(&round_robin);
+    match &args.csv {
+        Some(path) => {
+            std::fs::write(path, csv)?;
+            println!("Wrote the head-to-head matrix to {}", path.display());
+        }
+        None => print!("\n{}", csv),
+    }
+    print!("{}", render(&round_robin));
+    Ok(())
+}
+
+/// One row per entrant: its average edge against each entrant (columns in the same order)
+/// and then the normalizer.
+fn render_csv(round_robin: &RoundRobin) -> String {
+    let mut out = String::from("strategy");
+    for name in round_robin.names.iter().chain(&["normalizer"]) {
+        let _ = write!(out, ",{}", csv_field(name));
+    }
+    out.push('\n');
+    for (name, row) in round_robin.names.iter().zip(&round_robin.edges) {
+        out.push_str(&csv_field(name));
+        for edge in row {
+            let _ = write!(out, ",{}", fmt_f64(*edge));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn csv_field(name: &str) -> String {
+    if name.contains([',', '"']) {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    } else {
+        name.to_string()
+    }
+}
+
+fn render(round_robin: &RoundRobin) -> String {
+    let normalizer = round_robin.names.len();
+    let mut out = String::new();
+    out.push_str("\n========================================\n");
+    out.push_str(&format!(
+        "  {:<4} {:<28} {:>16} {:>16}\n",
+        "Rank", "Strategy", "Score", "vs normalizer"
+    ));
+    for (rank, &i) in round_robin.ranking().iter().enumerate() {
+        out.push_str(&format!(
+            "  {:<4} {:<28} {:>16} {:>16}\n",
+            rank + 1,
+            round_robin.names[i],
+            fmt_f64(round_robin.score(i)),
+            fmt_f64(round_robin.edges[i][normalizer])
+        ));
+    }
+    out.push_str("  Score: average edge against every other strategy and the normalizer\n");
+    out.push_str("========================================\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use prop_amm_sim::opponents::RoundRobin;
+
+    use super::{entrant_sources, render, render_csv};
+
+    fn fixture() -> RoundRobin {
+        RoundRobin {
+            names: vec!["alpha", "beta, v2", "gamma"],
+            edges: vec![
+                vec![1.0, 10.0, 20.0, 30.0],
+                vec![-5.0, 0.0, -1.0, 3.0],
+                vec![4.0, 8.0, 2.0, 0.0],
+            ],
+        }
+    }
+
+    #[test]
+    fn csv_has_a_complete_matrix_with_a_normalizer_column() {
+        let csv = render_csv(&fixture());
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines[0], "strategy,alpha,\"beta, v2\",gamma,normalizer");
+        assert_eq!(lines.len(), 4);
+        assert!(lines[2].starts_with("\"beta, v2\",-5.000000,"));
+        assert!(lines[1..].iter().all(|line| line.matches(',').count() >= 4));
+    }
+
+    #[test]
+    fn summary_ranks_by_score_excluding_self_play() {
+        let table = render(&fixture());
+        // Scores: alpha (10 + 20 + 30) / 3, gamma (4 + 8 + 0) / 3, beta (-5 - 1 + 3) / 3.
+        let alpha = table.find("alpha").unwrap();
+        let gamma = table.find("gamma").unwrap();
+        let beta = table.find("beta").unwrap();
+        assert!(alpha < gamma && gamma < beta);
+        assert!(table.contains("20.000000"));
+        assert!(table.contains("-1.000000"));
+    }
+
+    #[test]
+    fn entrants_are_the_rs_files_in_path_order() {
+        let dir = std::env::temp_dir().join(format!("prop-amm-roundrobin-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("nested.rs")).unwrap();
+        for name in ["b.rs", "a.rs", "notes.md"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        let sources = entrant_sources(&dir).unwrap();
+        let names: Vec<_> = sources
+            .iter()
+            .map(|p| p.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(names, ["a.rs", "b.rs"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
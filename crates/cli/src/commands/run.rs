@@ -1,206 +1,1459 @@
-use std::sync::atomic::{AtomicPtr, Ordering};
+use std::fmt;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
-use prop_amm_executor::{AfterSwapFn, BpfProgram};
+use clap::{Args, ValueEnum};
+use prop_amm_executor::{AfterSwapFn, BpfProgram, SwapFn};
+use prop_amm_shared::config::{ReserveRange, SimulationConfig};
 use prop_amm_shared::normalizer::{
     after_swap as normalizer_after_swap_fn, compute_swap as normalizer_swap,
 };
-use prop_amm_sim::runner;
+use prop_amm_shared::result::{BatchResult, Disqualification, SimResult};
+use prop_amm_sim::runner::SimulationError;
+use prop_amm_sim::{audit, bench, runner};
 
-use super::compile;
-use crate::output;
+use super::loader::{self, SymbolSource};
+use super::{compile, instruction_dump};
+use crate::output::{self, fmt_f64, fmt_stat, EdgeUnit};
+use crate::stamp::RunStamp;
 
-type FfiSwapFn = unsafe extern "C" fn(*const u8, usize) -> u64;
-type FfiAfterSwapFn = unsafe extern "C" fn(*const u8, usize, *mut u8, usize);
+#[derive(Args)]
+pub struct RunArgs {
+    /// Path to the .rs source file
+    pub file: String,
+    /// Number of simulations
+    #[arg(long, default_value = "1000")]
+    pub simulations: u32,
+    /// Number of steps per simulation
+    #[arg(long, default_value = "10000")]
+    pub steps: u32,
+    /// Leading steps of each simulation that trade but are not scored, so the strategy's
+    /// state can settle; counted in --steps
+    #[arg(long, default_value = "0")]
+    pub warmup_steps: u32,
+    /// Number of parallel workers, respected exactly (0 = one per core, at most 8)
+    #[arg(long, default_value = "0")]
+    pub workers: usize,
+    /// Pin each worker thread to its own CPU (Linux) for steadier timings
+    #[arg(long)]
+    pub pin_workers: bool,
+    /// Starting seed for simulation config generation
+    #[arg(long, default_value = "0")]
+    pub seed_start: u64,
+    /// Seed step between simulations
+    #[arg(long, default_value = "1")]
+    pub seed_stride: u64,
+    /// Use BPF runtime instead of native (slower, for validation)
+    #[arg(long)]
+    pub bpf: bool,
+    /// Path to a prebuilt BPF .so to use when running with --bpf (skips compilation).
+    /// Useful on machines without the Solana SBF toolchain installed.
+    #[arg(long)]
+    pub bpf_so: Option<String>,
+    /// Load the strategy, resolve the config and print the run plan without simulating
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Replay every seed twice (on separate threads) and report the first step where the
+    /// fills against the strategy differ by more than one nano unit, instead of scoring
+    #[arg(long, conflicts_with_all = ["bpf", "dry_run"])]
+    pub audit_determinism: bool,
+    /// Write every disqualifying event (over-quote, CU exceeded, failed call, panic,
+    /// non-finite edge) to this file as one JSON line with its seed and step
+    #[arg(long)]
+    pub disqualification_log: Option<String>,
+    /// Rerun only the seeds named in a disqualification log from an earlier run (see
+    /// `--disqualification-log`) instead of `--simulations` seeds from `--seed-start`
+    #[arg(long, conflicts_with_all = ["simulations", "seed_start", "seed_stride"])]
+    pub only_failed: Option<String>,
+    /// Fail the run when the 5th-percentile edge across seeds is below this, however high
+    /// the average edge: flags strategies that win on average but blow up on some seeds
+    #[arg(long, allow_hyphen_values = true)]
+    pub min_p5_edge: Option<f64>,
+    /// Abort the batch at the first disqualifying event (over-quote, CU exceeded, failed
+    /// call, panic, non-finite edge) and report its seed and step, instead of running every
+    /// seed and tallying them
+    #[arg(long, conflicts_with = "audit_determinism")]
+    pub fail_fast: bool,
+    /// Derive each seed's initial reserves (pool size 0.1x to 10x, mispricing up to
+    /// ±50 bps) from the seed instead of starting every simulation from the same pools
+    #[arg(long)]
+    pub randomize_reserves: bool,
+    /// Unit the report states edge in: nano (1e-9 Y), token (Y), or bps of the
+    /// submission's initial pool value
+    #[arg(long, value_enum, default_value = "token")]
+    pub units: EdgeUnit,
+    /// Reference pool to compete with for retail flow: the normalizer at its sampled fee, or
+    /// `adaptive`, which re-prices every step to undercut the strategy's quoted fee
+    #[arg(long, value_enum, default_value = "normalizer")]
+    pub opponent: OpponentMode,
+    /// How many bps under the strategy's quoted fee the adaptive opponent charges
+    #[arg(long, default_value = "5")]
+    pub undercut_bps: u16,
+    /// Simulate only the first seed and write every compute_swap call the strategy receives
+    /// (instruction and storage as hex, oracle, output) to this file as JSON lines, instead
+    /// of scoring. About 2 KB per call, so keep --steps small
+    #[arg(long, conflicts_with_all = ["bpf", "audit_determinism", "dry_run", "fail_fast"])]
+    pub dump_instructions: Option<String>,
+    /// Never call the strategy's after_swap, so it runs stateless; compare with a normal run
+    /// to see what its state updates are worth
+    #[arg(long)]
+    pub ignore_after_swap: bool,
+    /// Time this many of the batch's simulations (default 8) on one worker, then print how
+    /// long and how much memory the full batch would take and exit without running it
+    #[arg(
+        long,
+        value_name = "CALIBRATION_SIMS",
+        num_args = 0..=1,
+        default_missing_value = "8",
+        conflicts_with_all = ["bpf", "audit_determinism", "dry_run", "dump_instructions"]
+    )]
+    pub estimate: Option<u32>,
+    /// Native library already built by `prop-amm bench`; skips compilation
+    #[arg(skip)]
+    pub native_lib: Option<PathBuf>,
+}
 
-static LOADED_SWAP: AtomicPtr<()> = AtomicPtr::new(std::ptr::null_mut());
-static LOADED_AFTER_SWAP: AtomicPtr<()> = AtomicPtr::new(std::ptr::null_mut());
+/// Pricing of the reference pool a run competes with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OpponentMode {
+    /// Fee and liquidity sampled per seed, fixed for the run.
+    Normalizer,
+    /// Fee reset every step to undercut the strategy (`opponent_undercut_bps`).
+    Adaptive,
+}
 
-fn dynamic_swap(data: &[u8]) -> u64 {
-    let ptr = LOADED_SWAP.load(Ordering::Relaxed);
-    let f: FfiSwapFn = unsafe { std::mem::transmute(ptr) };
-    unsafe { f(data.as_ptr(), data.len()) }
+/// Fully resolved description of what a run will execute.
+pub struct RunPlan {
+    /// Strategy name/version from its metadata exports, else the source path.
+    pub strategy: String,
+    pub artifact: PathBuf,
+    pub backend: String,
+    pub simulations: u32,
+    pub steps: u32,
+    /// Unscored leading steps of each simulation (`--warmup-steps`).
+    pub warmup_steps: u32,
+    pub workers: Option<usize>,
+    pub pin_workers: bool,
+    pub opponent: &'static str,
+    /// Bps the opponent undercuts the strategy's quoted fee by, re-priced every step.
+    pub undercut_bps: Option<u16>,
+    pub seed_start: u64,
+    pub seed_stride: u64,
+    /// Explicit seeds (`--only-failed`), replacing `seed_start + i * seed_stride`.
+    pub seeds: Option<Vec<u64>>,
+    /// Range each seed's initial reserves are drawn from (`--randomize-reserves`).
+    pub seeded_reserves: Option<ReserveRange>,
+    /// Skip the strategy's `after_swap` (`--ignore-after-swap`).
+    pub ignore_after_swap: bool,
 }
 
-fn dynamic_after_swap(data: &[u8], storage: &mut [u8]) {
-    let ptr = LOADED_AFTER_SWAP.load(Ordering::Relaxed);
-    let f: FfiAfterSwapFn = unsafe { std::mem::transmute(ptr) };
-    unsafe {
-        f(
-            data.as_ptr(),
-            data.len(),
-            storage.as_mut_ptr(),
-            storage.len(),
+impl RunPlan {
+    /// One config per seed, each watching for an `after_swap` that never writes storage.
+    fn configs(&self) -> Vec<SimulationConfig> {
+        let mut configs = match &self.seeds {
+            Some(seeds) => runner::configs_for_seeds(seeds.iter().copied(), self.steps),
+            None => runner::seeded_configs(
+                self.simulations,
+                self.steps,
+                self.seed_start,
+                self.seed_stride,
+            ),
+        };
+        for config in &mut configs {
+            config.check_storage_writes = true;
+            config.warmup_steps = self.warmup_steps;
+            config.opponent_undercut_bps = self.undercut_bps;
+            config.ignore_after_swap = self.ignore_after_swap;
+            config.seeded_reserves = self.seeded_reserves;
+            *config = config.with_seeded_reserves();
+        }
+        configs
+    }
+
+    /// Print the starting market (and the spread of initial reserves the seeds drew, when
+    /// they were randomized), and warn about one that looks mistyped.
+    fn report_start(&self, configs: &[SimulationConfig]) {
+        if let Some(summary) = output::start_summary(configs, self.opponent) {
+            println!("  {}", summary);
+        }
+        if self.seeded_reserves.is_some() {
+            if let Some(summary) = reserve_distribution(configs) {
+                println!("  Initial reserves: {}", summary);
+            }
+        }
+        if let Some(warning) = output::start_warning(configs) {
+            eprintln!("{}", warning);
+        }
+    }
+
+    /// Provenance for the report: these configs, the artifact's bytes and the opponent.
+    fn stamp(&self) -> anyhow::Result<RunStamp> {
+        RunStamp::for_artifact(&self.configs(), &self.artifact, self.opponent)
+    }
+
+    /// `seeds 10 + i*3`, or the explicit list.
+    fn seeds_label(&self) -> String {
+        match &self.seeds {
+            Some(seeds) => format!("seeds {}", join_seeds(seeds)),
+            None => format!("seeds {} + i*{}", self.seed_start, self.seed_stride),
+        }
+    }
+
+    fn seed_end(&self) -> Option<u64> {
+        let last = (self.simulations as u64).checked_sub(1)?;
+        Some(
+            self.seed_start
+                .wrapping_add(last.wrapping_mul(self.seed_stride)),
         )
     }
 }
 
-pub fn run(
-    file: &str,
-    simulations: u32,
-    steps: u32,
-    workers: usize,
-    seed_start: u64,
-    seed_stride: u64,
-    bpf: bool,
-    bpf_so: Option<&str>,
-) -> anyhow::Result<()> {
-    if seed_stride == 0 {
-        anyhow::bail!("--seed-stride must be >= 1");
+impl fmt::Display for RunPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Run plan:")?;
+        writeln!(f, "  Strategy:    {}", self.strategy)?;
+        writeln!(f, "  Artifact:    {}", self.artifact.display())?;
+        writeln!(f, "  Backend:     {}", self.backend)?;
+        writeln!(f, "  Opponent:    {}", self.opponent)?;
+        if let Some(bps) = self.undercut_bps {
+            writeln!(
+                f,
+                "  Undercut:    {} bps below the strategy's quoted fee",
+                bps
+            )?;
+        }
+        if self.ignore_after_swap {
+            writeln!(f, "  After swap:  ignored (strategy runs stateless)")?;
+        }
+        writeln!(f, "  Simulations: {}", self.simulations)?;
+        writeln!(f, "  Steps:       {}", self.steps)?;
+        if self.warmup_steps > 0 {
+            writeln!(f, "  Warmup:      {} steps, not scored", self.warmup_steps)?;
+        }
+        let workers = runner::worker_count(self.workers);
+        match self.workers {
+            Some(_) => write!(f, "  Workers:     {}", workers)?,
+            None => write!(f, "  Workers:     {} (auto)", workers)?,
+        }
+        match runner::allowed_cpus() {
+            Some(cpus) if self.pin_workers => writeln!(
+                f,
+                ", pinned to {} of {} allowed CPUs",
+                workers.min(cpus.len()),
+                cpus.len()
+            )?,
+            Some(cpus) => writeln!(f, ", {} CPUs allowed", cpus.len())?,
+            None if self.pin_workers => writeln!(f, ", unpinned (no affinity support)")?,
+            None => writeln!(f)?,
+        }
+        if let Some(range) = &self.seeded_reserves {
+            writeln!(
+                f,
+                "  Reserves:    per seed, {}x..{}x, mispricing ±{} bps",
+                range.scale_min, range.scale_max, range.max_mispricing_bps
+            )?;
+        }
+        if let Some(seeds) = &self.seeds {
+            return write!(f, "  Seeds:       {}", join_seeds(seeds));
+        }
+        match self.seed_end() {
+            Some(end) => write!(
+                f,
+                "  Seeds:       {} + i*{} ({}..={})",
+                self.seed_start, self.seed_stride, self.seed_start, end
+            ),
+            None => write!(f, "  Seeds:       none"),
+        }
     }
-    let n_workers = if workers == 0 { None } else { Some(workers) };
+}
 
-    if bpf {
-        run_bpf(
-            file,
-            simulations,
-            steps,
-            n_workers,
-            bpf_so,
-            seed_start,
-            seed_stride,
+/// Range and median of the configs' initial X reserve, and the range of their mispricing.
+fn reserve_distribution(configs: &[SimulationConfig]) -> Option<String> {
+    let mut xs: Vec<f64> = configs.iter().map(|c| c.initial_x).collect();
+    xs.sort_by(f64::total_cmp);
+    let median = *xs.get(xs.len() / 2)?;
+    let (min_bps, max_bps) = configs
+        .iter()
+        .map(|c| c.initial_mispricing_bps)
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), bps| {
+            (lo.min(bps), hi.max(bps))
+        });
+    Some(format!(
+        "X {}..{} (median {}), mispricing {}..{} bps",
+        fmt_f64(xs[0]),
+        fmt_f64(xs[xs.len() - 1]),
+        fmt_f64(median),
+        fmt_f64(min_bps),
+        fmt_f64(max_bps)
+    ))
+}
+
+fn join_seeds(seeds: &[u64]) -> String {
+    seeds
+        .iter()
+        .map(u64::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Distinct seeds of the events in a disqualification log, ascending. The stamp record
+/// heading the log is skipped.
+fn failed_seeds(log: &str) -> anyhow::Result<Vec<u64>> {
+    let mut seeds = Vec::new();
+    for (i, line) in log.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let not_an_event =
+            |e| anyhow::anyhow!("line {}: not a disqualification event: {}", i + 1, e);
+        let record: serde_json::Value = serde_json::from_str(line).map_err(not_an_event)?;
+        if record.get("stamp").is_some() {
+            continue;
+        }
+        let event: Disqualification = serde_json::from_value(record).map_err(not_an_event)?;
+        seeds.push(event.seed);
+    }
+    seeds.sort_unstable();
+    seeds.dedup();
+    Ok(seeds)
+}
+
+fn read_failed_seeds(path: &str) -> anyhow::Result<Vec<u64>> {
+    let log = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path, e))?;
+    let seeds = failed_seeds(&log).map_err(|e| anyhow::anyhow!("{}: {}", path, e))?;
+    if seeds.is_empty() {
+        anyhow::bail!("{} lists no failed seeds; nothing to rerun", path);
+    }
+    Ok(seeds)
+}
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// On the first Ctrl-C, stop starting new simulations so the finished ones can be reported;
+/// a second Ctrl-C exits immediately.
+fn install_interrupt_handler() -> &'static AtomicBool {
+    let installed = ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            eprintln!("\nForce quit.");
+            std::process::exit(130);
+        }
+        eprintln!(
+            "\nInterrupted: finishing in-flight simulations (press Ctrl-C again to force quit)..."
+        );
+    });
+    if let Err(e) = installed {
+        eprintln!("Warning: could not install Ctrl-C handler: {}", e);
+    }
+    &INTERRUPTED
+}
+
+/// Notice for a batch Ctrl-C cut short; `None` unless it was interrupted, even if it
+/// stopped early for another reason (a fail-fast abort).
+fn interruption_notice(interrupted: bool, completed: usize, requested: u32) -> Option<String> {
+    (interrupted && completed < requested as usize).then(|| {
+        format!(
+            "interrupted after {}/{} simulations; partial results below.",
+            completed, requested
         )
+    })
+}
+
+fn report_interruption(result: &BatchResult, requested: u32) {
+    let interrupted = INTERRUPTED.load(Ordering::SeqCst);
+    if let Some(notice) = interruption_notice(interrupted, result.n_sims(), requested) {
+        println!("\n{}", notice);
+    }
+}
+
+const OPPONENT_NORMALIZER: &str = "normalizer (fee/liquidity sampled per seed)";
+const OPPONENT_ADAPTIVE: &str = "adaptive normalizer (re-priced every step to undercut)";
+
+pub fn run(args: RunArgs) -> anyhow::Result<()> {
+    check_batch_args(&args)?;
+    runner::set_pin_workers(args.pin_workers);
+
+    let outcome = if let Some(path) = &args.dump_instructions {
+        run_dump(&args, path)
+    } else if let Some(calibration_sims) = args.estimate {
+        run_estimate(&args, calibration_sims)
+    } else if args.audit_determinism {
+        run_audit(&args)
+    } else if args.bpf {
+        run_bpf(&args)
     } else {
-        run_native(file, simulations, steps, n_workers, seed_start, seed_stride)
+        run_native(&args)
+    };
+    if let Some(failure) = outcome
+        .as_ref()
+        .err()
+        .and_then(|err| err.downcast_ref::<SimulationError>())
+    {
+        if let Some(step) = failure.step {
+            eprintln!(
+                "\n--fail-fast: seed {} was disqualified at step {}.",
+                failure.config.seed, step
+            );
+        }
+        eprintln!("\nReproduce this simulation alone with:");
+        eprintln!("  {}", reproduction_hint(&args, &failure.config));
     }
+    outcome
 }
 
-fn run_native(
-    file: &str,
-    simulations: u32,
-    steps: u32,
-    n_workers: Option<usize>,
-    seed_start: u64,
-    seed_stride: u64,
+/// Under `--fail-fast`, log the disqualification that stopped the batch, then pass its error
+/// on.
+fn log_batch_failure(args: &RunArgs, plan: &RunPlan, err: anyhow::Error) -> anyhow::Error {
+    let (Some(path), Some(failure)) = (
+        &args.disqualification_log,
+        err.downcast_ref::<SimulationError>(),
+    ) else {
+        return err;
+    };
+    let logged = plan
+        .stamp()
+        .and_then(|stamp| write_disqualification_log(path, &stamp, [&failure.disqualification()]));
+    match logged {
+        Ok(()) => err,
+        Err(log_err) => log_err,
+    }
+}
+
+/// Replace the file at `path` with `stamp`'s record followed by `events`, as JSON lines.
+fn write_disqualification_log<'a>(
+    path: &str,
+    stamp: &RunStamp,
+    events: impl IntoIterator<Item = &'a Disqualification>,
 ) -> anyhow::Result<()> {
+    let mut out = BufWriter::new(
+        std::fs::File::create(path)
+            .map_err(|e| anyhow::anyhow!("Failed to create {}: {}", path, e))?,
+    );
+    out.write_all(stamp.json_line().as_bytes())?;
+    let n = write_disqualifications(&mut out, events)?;
+    out.flush()?;
+    println!("Logged {} disqualifying events to {}", n, path);
+    Ok(())
+}
+
+fn write_disqualifications<'a>(
+    out: &mut impl Write,
+    events: impl IntoIterator<Item = &'a Disqualification>,
+) -> anyhow::Result<usize> {
+    let mut n = 0;
+    for event in events {
+        serde_json::to_writer(&mut *out, event)?;
+        out.write_all(b"\n")?;
+        n += 1;
+    }
+    Ok(n)
+}
+
+/// Reject batches that would run nothing, with the reason, before anything is compiled.
+fn check_batch_args(args: &RunArgs) -> anyhow::Result<()> {
+    if args.seed_stride == 0 {
+        anyhow::bail!("--seed-stride must be >= 1");
+    }
+    if args.simulations == 0 {
+        anyhow::bail!("--simulations must be >= 1: an empty batch has no edge to report");
+    }
+    if args.steps == 0 {
+        anyhow::bail!(
+            "--steps must be >= 1: with no steps nothing trades and every seed scores exactly 0 edge"
+        );
+    }
+    if args.warmup_steps >= args.steps {
+        anyhow::bail!(
+            "--warmup-steps {} leaves none of the {} steps scored",
+            args.warmup_steps,
+            args.steps
+        );
+    }
+    Ok(())
+}
+
+fn batch_disqualifications(result: &BatchResult) -> impl Iterator<Item = &Disqualification> {
+    result.results.iter().flat_map(|r| &r.disqualifications)
+}
+
+/// Command line that reruns just the simulation `config` describes, with the same backend.
+fn reproduction_hint(args: &RunArgs, config: &SimulationConfig) -> String {
+    let mut hint = format!(
+        "prop-amm run {} --simulations 1 --seed-start {} --steps {}",
+        args.file, config.seed, config.n_steps
+    );
+    if config.warmup_steps > 0 {
+        hint.push_str(&format!(" --warmup-steps {}", config.warmup_steps));
+    }
+    if args.randomize_reserves {
+        hint.push_str(" --randomize-reserves");
+    }
+    if args.bpf {
+        hint.push_str(" --bpf");
+    }
+    if let Some(so) = &args.bpf_so {
+        hint.push_str(&format!(" --bpf-so {}", so));
+    }
+    hint
+}
+
+fn plan_for(
+    args: &RunArgs,
+    strategy: String,
+    artifact: PathBuf,
+    backend: String,
+) -> anyhow::Result<RunPlan> {
+    let seeds = args
+        .only_failed
+        .as_deref()
+        .map(read_failed_seeds)
+        .transpose()?;
+    Ok(RunPlan {
+        strategy,
+        artifact,
+        backend,
+        simulations: seeds
+            .as_ref()
+            .map_or(args.simulations, |seeds| seeds.len() as u32),
+        steps: args.steps,
+        warmup_steps: args.warmup_steps,
+        workers: if args.workers == 0 {
+            None
+        } else {
+            Some(args.workers)
+        },
+        pin_workers: args.pin_workers,
+        opponent: match args.opponent {
+            OpponentMode::Normalizer => OPPONENT_NORMALIZER,
+            OpponentMode::Adaptive => OPPONENT_ADAPTIVE,
+        },
+        undercut_bps: (args.opponent == OpponentMode::Adaptive).then_some(args.undercut_bps),
+        seed_start: args.seed_start,
+        seed_stride: args.seed_stride,
+        seeds,
+        seeded_reserves: args.randomize_reserves.then(ReserveRange::default),
+        ignore_after_swap: args.ignore_after_swap,
+    })
+}
+
+/// Resolve the strategy's symbols, then either stop at the plan (`dry_run`) or hand the
+/// installed callbacks to `run_batch`. Symbol errors surface before any simulation starts.
+fn execute_native<F>(
+    symbols: &dyn SymbolSource,
+    plan: &RunPlan,
+    dry_run: bool,
+    run_batch: F,
+) -> anyhow::Result<Option<BatchResult>>
+where
+    F: FnOnce(SwapFn, Option<AfterSwapFn>) -> anyhow::Result<BatchResult>,
+{
+    let resolved = loader::resolve_symbols(symbols)?;
+    if dry_run {
+        println!("{}", plan);
+        plan.report_start(&plan.configs());
+        println!("\nDry run: strategy symbols verified, no simulations executed.");
+        return Ok(None);
+    }
+    let (swap_fn, after_swap_fn) = loader::install(resolved)?;
+    run_batch(swap_fn, after_swap_fn).map(Some)
+}
+
+fn run_native(args: &RunArgs) -> anyhow::Result<()> {
     let total_start = std::time::Instant::now();
-    println!("Compiling {} (native)...", file);
     let build_start = std::time::Instant::now();
-    let native_path = compile::compile_native(file)?;
+    let native_path = native_library(args)?;
     let build_elapsed = build_start.elapsed();
 
     // Load the native library — leak it so symbols remain valid for the process lifetime.
     let load_start = std::time::Instant::now();
-    let lib = Box::new(
-        unsafe { libloading::Library::new(&native_path) }
-            .map_err(|e| anyhow::anyhow!("Failed to load {}: {}", native_path.display(), e))?,
-    );
-    let lib = Box::leak(lib);
+    let lib = loader::open_library(&native_path)?;
+    let compile_or_load_elapsed = build_elapsed + load_start.elapsed();
+
+    let strategy = loader::read_metadata(lib).title(&args.file);
+    let plan = plan_for(args, strategy, native_path, "native".to_string())?;
+    let mut sim_elapsed = std::time::Duration::ZERO;
+    let result = execute_native(lib, &plan, args.dry_run, |swap_fn, after_swap_fn| {
+        println!(
+            "Running {} simulations ({} steps each) natively with {}...",
+            plan.simulations,
+            plan.steps,
+            plan.seeds_label(),
+        );
+        let configs = plan.configs();
+        plan.report_start(&configs);
+        let sim_start = std::time::Instant::now();
+        let run_batch = if args.fail_fast {
+            runner::run_batch_native_fail_fast
+        } else {
+            runner::run_batch_native_cancellable
+        };
+        let result = run_batch(
+            swap_fn,
+            after_swap_fn,
+            normalizer_swap,
+            Some(normalizer_after_swap_fn),
+            configs,
+            plan.workers,
+            install_interrupt_handler(),
+        );
+        sim_elapsed = sim_start.elapsed();
+        result.map_err(|err| log_batch_failure(args, &plan, err))
+    })?;
 
-    let swap_fn: libloading::Symbol<FfiSwapFn> = unsafe {
-        lib.get(compile::NATIVE_SWAP_SYMBOL)
-            .or_else(|_| lib.get(b"compute_swap_ffi"))
+    if let Some(result) = result {
+        report_interruption(&result, plan.simulations);
+        let stamp = plan.stamp()?;
+        if let Some(path) = &args.disqualification_log {
+            write_disqualification_log(path, &stamp, batch_disqualifications(&result))?;
+        }
+        output::print_results(
+            &result,
+            &plan.strategy,
+            &stamp,
+            output::RunTimings {
+                compile_or_load: compile_or_load_elapsed,
+                simulation: sim_elapsed,
+                total: total_start.elapsed(),
+            },
+            args.units,
+        );
+        check_p5_edge(&result, args.min_p5_edge)?;
     }
-    .map_err(|e| anyhow::anyhow!("Missing native swap symbol: {}", e))?;
-    LOADED_SWAP.store(*swap_fn as *mut (), Ordering::Relaxed);
+    Ok(())
+}
 
-    let has_after_swap = if let Ok(after_fn) = unsafe {
-        lib.get::<FfiAfterSwapFn>(compile::NATIVE_AFTER_SWAP_SYMBOL)
-            .or_else(|_| lib.get::<FfiAfterSwapFn>(b"after_swap_ffi"))
-    } {
-        LOADED_AFTER_SWAP.store(*after_fn as *mut (), Ordering::Relaxed);
-        true
-    } else {
-        false
-    };
+fn native_library(args: &RunArgs) -> anyhow::Result<PathBuf> {
+    if let Some(path) = &args.native_lib {
+        println!("Using prebuilt native library: {}", path.display());
+        return Ok(path.clone());
+    }
+    println!("Compiling {} (native)...", args.file);
+    compile::compile_native(&args.file)
+}
 
-    let submission_after_swap: Option<AfterSwapFn> = if has_after_swap {
-        Some(dynamic_after_swap)
-    } else {
-        None
-    };
-    let compile_or_load_elapsed = build_elapsed + load_start.elapsed();
+fn run_audit(args: &RunArgs) -> anyhow::Result<()> {
+    let native_path = native_library(args)?;
+    let lib = loader::open_library(&native_path)?;
+    let (swap_fn, after_swap_fn) = loader::install(loader::resolve_symbols(lib)?)?;
 
+    let strategy = loader::read_metadata(lib).title(&args.file);
+    let plan = plan_for(args, strategy, native_path, "native".to_string())?;
     println!(
-        "Running {} simulations ({} steps each) natively with seeds {} + i*{}...",
-        simulations, steps, seed_start, seed_stride,
+        "Auditing determinism of {} simulations ({} steps each) with {}...",
+        plan.simulations,
+        plan.steps,
+        plan.seeds_label(),
     );
-
-    let sim_start = std::time::Instant::now();
-    let result = runner::run_default_batch_native_seeded(
-        dynamic_swap,
-        submission_after_swap,
+    let configs = plan.configs();
+    plan.report_start(&configs);
+    let audits = audit::audit_batch_native(
+        swap_fn,
+        after_swap_fn,
         normalizer_swap,
         Some(normalizer_after_swap_fn),
-        simulations,
-        steps,
-        n_workers,
-        seed_start,
-        seed_stride,
+        &configs,
+        plan.workers,
     )?;
-    let sim_elapsed = sim_start.elapsed();
 
-    output::print_results(
-        &result,
-        output::RunTimings {
-            compile_or_load: compile_or_load_elapsed,
-            simulation: sim_elapsed,
-            total: total_start.elapsed(),
-        },
+    let mut diverged = 0;
+    for (seed, divergence) in &audits {
+        if let Some(divergence) = divergence {
+            diverged += 1;
+            println!(
+                "  [FAIL] seed {}: first divergence at step {}: {}",
+                seed, divergence.step, divergence.description
+            );
+        }
+    }
+    if diverged > 0 {
+        anyhow::bail!(
+            "Determinism audit failed for {}/{} seeds",
+            diverged,
+            audits.len()
+        );
+    }
+    println!(
+        "  [PASS] All {} seeds replayed identically (tolerance: 1 nano)",
+        audits.len()
     );
     Ok(())
 }
 
-fn run_bpf(
-    file: &str,
-    simulations: u32,
-    steps: u32,
-    n_workers: Option<usize>,
-    bpf_so: Option<&str>,
-    seed_start: u64,
-    seed_stride: u64,
-) -> anyhow::Result<()> {
+fn run_dump(args: &RunArgs, path: &str) -> anyhow::Result<()> {
+    let native_path = native_library(args)?;
+    let lib = loader::open_library(&native_path)?;
+    let (swap_fn, after_swap_fn) = loader::install(loader::resolve_symbols(lib)?)?;
+
+    let strategy = loader::read_metadata(lib).title(&args.file);
+    let plan = plan_for(args, strategy, native_path, "native".to_string())?;
+    let config = &plan.configs()[0];
+    println!(
+        "Dumping compute_swap calls of seed {} ({} steps) to {}...",
+        config.seed, config.n_steps, path
+    );
+    let mut out = BufWriter::new(
+        std::fs::File::create(path)
+            .map_err(|e| anyhow::anyhow!("Failed to create {}: {}", path, e))?,
+    );
+    let n = instruction_dump::dump(swap_fn, after_swap_fn, config, &mut out)?;
+    out.flush()?;
+    println!("Wrote {} compute_swap calls to {}", n, path);
+    Ok(())
+}
+
+/// `--estimate`: what a full batch costs, extrapolated from timing a few of its simulations.
+struct BatchEstimate {
+    calibration_sims: usize,
+    /// Wall time of one simulation on one worker.
+    per_sim: Duration,
+    /// Bytes each finished simulation's result holds until the report.
+    retained_bytes_per_sim: f64,
+    /// Peak bytes one running simulation adds; 0 where `/proc` is unavailable.
+    working_set_bytes: u64,
+}
+
+impl BatchEstimate {
+    /// Time `simulations` take spread over `workers`, one simulation per worker at a time.
+    fn duration(&self, simulations: u32, workers: usize) -> Duration {
+        let rounds = simulations.div_ceil(workers.max(1) as u32);
+        self.per_sim * rounds
+    }
+
+    /// Peak memory of the batch: every result, plus one working set per busy worker.
+    fn memory_bytes(&self, simulations: u32, workers: usize) -> f64 {
+        let busy = workers.min(simulations as usize);
+        simulations as f64 * self.retained_bytes_per_sim
+            + busy as f64 * self.working_set_bytes as f64
+    }
+
+    fn summary(&self, simulations: u32, workers: usize) -> String {
+        format!(
+            "estimated {}, ~{} for {} sims on {} worker{} ({} calibration sims at {:.3}s each)",
+            fmt_duration(self.duration(simulations, workers)),
+            fmt_bytes(self.memory_bytes(simulations, workers)),
+            simulations,
+            workers,
+            if workers == 1 { "" } else { "s" },
+            self.calibration_sims,
+            self.per_sim.as_secs_f64()
+        )
+    }
+}
+
+/// Run the first `calibration_sims` of `plan`'s configs through `run_batch` and time them.
+fn calibrate<F>(
+    plan: &RunPlan,
+    calibration_sims: u32,
+    run_batch: F,
+) -> anyhow::Result<BatchEstimate>
+where
+    F: FnOnce(Vec<SimulationConfig>) -> anyhow::Result<BatchResult>,
+{
+    let mut configs = plan.configs();
+    configs.truncate(calibration_sims.max(1) as usize);
+    let peak_before = bench::peak_rss_kib();
+    let start = Instant::now();
+    let result = run_batch(configs)?;
+    let elapsed = start.elapsed();
+    let n = result.n_sims();
+    anyhow::ensure!(n > 0, "no calibration simulation finished");
+    let working_set_bytes = match (peak_before, bench::peak_rss_kib()) {
+        (Some(before), Some(after)) => after.saturating_sub(before) * 1024,
+        _ => 0,
+    };
+    let retained: usize = result.results.iter().map(retained_bytes).sum();
+    Ok(BatchEstimate {
+        calibration_sims: n,
+        per_sim: elapsed / n as u32,
+        retained_bytes_per_sim: retained as f64 / n as f64,
+        working_set_bytes,
+    })
+}
+
+/// Heap and inline bytes a finished simulation's result holds.
+fn retained_bytes(result: &SimResult) -> usize {
+    std::mem::size_of::<SimResult>()
+        + result.recording_bytes
+        + result
+            .disqualifications
+            .iter()
+            .map(|d| std::mem::size_of::<Disqualification>() + d.detail.len())
+            .sum::<usize>()
+}
+
+/// `12m30s`, `2h05m`, or `45.2s`.
+fn fmt_duration(duration: Duration) -> String {
+    let secs = duration.as_secs_f64();
+    if secs < 60.0 {
+        return format!("{:.1}s", secs);
+    }
+    let whole = secs.round() as u64;
+    if whole < 3_600 {
+        format!("{}m{:02}s", whole / 60, whole % 60)
+    } else {
+        format!("{}h{:02}m", whole / 3_600, whole % 3_600 / 60)
+    }
+}
+
+/// `1.2 GB`, `340.0 MB`, or `12.0 KB` (decimal units).
+fn fmt_bytes(bytes: f64) -> String {
+    match bytes {
+        b if b >= 1e9 => format!("{:.1} GB", b / 1e9),
+        b if b >= 1e6 => format!("{:.1} MB", b / 1e6),
+        b => format!("{:.1} KB", b / 1e3),
+    }
+}
+
+fn run_estimate(args: &RunArgs, calibration_sims: u32) -> anyhow::Result<()> {
+    let native_path = native_library(args)?;
+    let lib = loader::open_library(&native_path)?;
+    let (swap_fn, after_swap_fn) = loader::install(loader::resolve_symbols(lib)?)?;
+
+    let strategy = loader::read_metadata(lib).title(&args.file);
+    let plan = plan_for(args, strategy, native_path, "native".to_string())?;
+    let calibration_sims = calibration_sims.min(plan.simulations);
+    println!(
+        "Calibrating on {} of {} simulations ({} steps each, one worker)...",
+        calibration_sims, plan.simulations, plan.steps
+    );
+    let estimate = calibrate(&plan, calibration_sims, |configs| {
+        runner::run_batch_native(
+            swap_fn,
+            after_swap_fn,
+            normalizer_swap,
+            Some(normalizer_after_swap_fn),
+            configs,
+            Some(1),
+        )
+    })?;
+    println!(
+        "  {}",
+        estimate.summary(plan.simulations, runner::worker_count(plan.workers))
+    );
+    println!("\nEstimate only: the full batch was not run.");
+    Ok(())
+}
+
+fn run_bpf(args: &RunArgs) -> anyhow::Result<()> {
     let total_start = std::time::Instant::now();
     let build_or_load_start = std::time::Instant::now();
-    let bpf_path = if let Some(path) = bpf_so {
+    let bpf_path = if let Some(path) = args.bpf_so.as_deref() {
         println!("Using prebuilt BPF .so: {}", path);
-        std::path::PathBuf::from(path)
+        PathBuf::from(path)
     } else {
-        println!("Compiling {} (BPF)...", file);
-        compile::compile_bpf(file)?
+        println!("Compiling {} (BPF)...", args.file);
+        compile::compile_bpf(&args.file)?
     };
 
-    let bytes = std::fs::read(&bpf_path)
-        .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", bpf_path.display(), e))?;
-    let submission_program = BpfProgram::load(&bytes)
-        .map_err(|e| anyhow::anyhow!("Failed to load BPF program: {}", e))?;
+    let submission_program = load_bpf_program(&bpf_path)?;
     let compile_or_load_elapsed = build_or_load_start.elapsed();
 
     let meter_disabled = std::env::var_os("PROP_AMM_BPF_DISABLE_METER").is_some();
-
-    println!(
-        "Running {} simulations ({} steps each) via BPF{}{} with seeds {} + i*{}...",
-        simulations,
-        steps,
+    let backend = format!(
+        "bpf{}{}",
         if submission_program.jit_available() {
             " (JIT)"
         } else {
             " (interpreter)"
         },
         if meter_disabled { " (no meter)" } else { "" },
-        seed_start,
-        seed_stride,
     );
+    let plan = plan_for(args, args.file.clone(), bpf_path, backend)?;
+    if args.dry_run {
+        println!("{}", plan);
+        plan.report_start(&plan.configs());
+        println!("\nDry run: BPF program loaded and verified, no simulations executed.");
+        return Ok(());
+    }
+
+    println!(
+        "Running {} simulations ({} steps each) via {} with {}...",
+        plan.simulations,
+        plan.steps,
+        plan.backend.replacen("bpf", "BPF", 1),
+        plan.seeds_label(),
+    );
+    let configs = plan.configs();
+    plan.report_start(&configs);
 
     let sim_start = std::time::Instant::now();
-    let result = runner::run_default_batch_mixed_seeded(
+    let run_batch = if args.fail_fast {
+        runner::run_batch_mixed_fail_fast
+    } else {
+        runner::run_batch_mixed_cancellable
+    };
+    let result = run_batch(
         submission_program,
         normalizer_swap,
         Some(normalizer_after_swap_fn),
-        simulations,
-        steps,
-        n_workers,
-        seed_start,
-        seed_stride,
-    )?;
+        configs,
+        plan.workers,
+        install_interrupt_handler(),
+    )
+    .map_err(|err| log_batch_failure(args, &plan, err))?;
     let sim_elapsed = sim_start.elapsed();
 
+    report_interruption(&result, plan.simulations);
+    let stamp = plan.stamp()?;
+    if let Some(path) = &args.disqualification_log {
+        write_disqualification_log(path, &stamp, batch_disqualifications(&result))?;
+    }
+
     output::print_results(
         &result,
+        &plan.strategy,
+        &stamp,
         output::RunTimings {
             compile_or_load: compile_or_load_elapsed,
             simulation: sim_elapsed,
             total: total_start.elapsed(),
         },
+        args.units,
+    );
+    check_p5_edge(&result, args.min_p5_edge)
+}
+
+/// `--min-p5-edge`: the batch passes only if its worst 5% of seeds stay above the floor.
+fn check_p5_edge(result: &BatchResult, min_p5_edge: Option<f64>) -> anyhow::Result<()> {
+    let Some(min_p5_edge) = min_p5_edge else {
+        return Ok(());
+    };
+    let Some(p5_edge) = result.edge_quantile(0.05) else {
+        anyhow::bail!("--min-p5-edge needs at least one finished simulation");
+    };
+    if p5_edge < min_p5_edge {
+        anyhow::bail!(
+            "5th-percentile edge {} is below --min-p5-edge {} (avg edge {})",
+            fmt_f64(p5_edge),
+            fmt_f64(min_p5_edge),
+            fmt_stat(result.avg_edge())
+        );
+    }
+    println!(
+        "  [PASS] 5th-percentile edge {} >= --min-p5-edge {}",
+        fmt_f64(p5_edge),
+        fmt_f64(min_p5_edge)
     );
     Ok(())
 }
+
+pub(crate) fn load_bpf_program(bpf_path: &Path) -> anyhow::Result<BpfProgram> {
+    let bytes = std::fs::read(bpf_path)
+        .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", bpf_path.display(), e))?;
+    BpfProgram::load(&bytes).map_err(|e| anyhow::anyhow!("Failed to load BPF program: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::path::PathBuf;
+
+    use clap::Parser;
+    use prop_amm_shared::config::{ReserveRange, SimulationConfig};
+    use prop_amm_shared::nano::f64_to_nano;
+    use prop_amm_shared::result::{BatchResult, Disqualification, DisqualificationKind, SimResult};
+    use prop_amm_sim::runner::{self, SimulationError};
+
+    use super::{
+        batch_disqualifications, calibrate, check_batch_args, check_p5_edge, execute_native,
+        failed_seeds, fmt_bytes, fmt_duration, interruption_notice, normalizer_swap, plan_for,
+        reproduction_hint, reserve_distribution, write_disqualifications, RunArgs, RunPlan,
+        OPPONENT_NORMALIZER,
+    };
+    use crate::commands::loader::fixtures::FixtureLibrary;
+    use crate::output;
+    use crate::stamp::RunStamp;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        run: RunArgs,
+    }
+
+    fn plan() -> RunPlan {
+        RunPlan {
+            strategy: "Fixture Strategy v1.2.0".to_string(),
+            artifact: PathBuf::from("fixture.so"),
+            backend: "native".to_string(),
+            simulations: 5,
+            steps: 100,
+            warmup_steps: 0,
+            workers: Some(1),
+            pin_workers: false,
+            opponent: OPPONENT_NORMALIZER,
+            undercut_bps: None,
+            ignore_after_swap: false,
+            seed_start: 10,
+            seed_stride: 3,
+            seeds: None,
+            seeded_reserves: None,
+        }
+    }
+
+    #[test]
+    fn dry_run_with_missing_symbol_fails_before_simulating() {
+        let batches = Cell::new(0);
+        let err = execute_native(&FixtureLibrary::default(), &plan(), true, |_, _| {
+            batches.set(batches.get() + 1);
+            Ok(BatchResult::from_results(Vec::new()))
+        })
+        .expect_err("missing symbol must fail");
+        assert!(err.to_string().contains("Missing native swap symbol"));
+        assert_eq!(batches.get(), 0);
+    }
+
+    #[test]
+    fn randomized_reserves_vary_per_seed_and_are_reported() {
+        let randomized = RunPlan {
+            seeded_reserves: Some(ReserveRange::default()),
+            ..plan()
+        };
+        let configs = randomized.configs();
+        assert!(configs.iter().all(|c| c.seeded_reserves.is_none()));
+        assert!(configs.windows(2).all(|w| w[0].initial_x != w[1].initial_x));
+        assert_eq!(randomized.configs()[0].initial_x, configs[0].initial_x);
+        assert!(randomized
+            .to_string()
+            .contains("Reserves:    per seed, 0.1x..10x"));
+        let summary = reserve_distribution(&configs).unwrap();
+        assert!(
+            summary.starts_with("X ") && summary.contains("mispricing"),
+            "{summary}"
+        );
+
+        let default = SimulationConfig::default();
+        assert!(plan()
+            .configs()
+            .iter()
+            .all(|c| c.initial_x == default.initial_x && c.initial_mispricing_bps == 0.0));
+    }
+
+    #[test]
+    fn start_summary_reflects_the_configs_the_flags_resolve_to() {
+        let summary = |argv: &[&str]| {
+            let args = Cli::parse_from(argv).run;
+            let plan = plan_for(
+                &args,
+                "fixture".to_string(),
+                PathBuf::from("fixture.so"),
+                "native".to_string(),
+            )
+            .unwrap();
+            let configs = plan.configs();
+            (
+                output::start_summary(&configs, plan.opponent).unwrap(),
+                configs,
+            )
+        };
+
+        let (fixed, configs) = summary(&["run", "my_amm.rs", "--simulations", "4"]);
+        assert!(
+            fixed.starts_with("Start: X 100.00 / Y 10000.00 (price 100.0000), opponent fee "),
+            "{fixed}"
+        );
+        assert!(
+            fixed.ends_with(&format!("seeds 0..=3; opponent {}", OPPONENT_NORMALIZER)),
+            "{fixed}"
+        );
+        let sigma_lo = configs
+            .iter()
+            .map(|c| c.gbm_sigma)
+            .fold(f64::INFINITY, f64::min);
+        assert!(
+            fixed.contains(&format!("volatility {:.6}..", sigma_lo)),
+            "{fixed}"
+        );
+
+        let (overridden, configs) = summary(&[
+            "run",
+            "my_amm.rs",
+            "--simulations",
+            "3",
+            "--seed-start",
+            "40",
+            "--seed-stride",
+            "5",
+            "--randomize-reserves",
+        ]);
+        assert!(overridden.contains("seeds 40..=50;"), "{overridden}");
+        let (x_lo, x_hi) = configs.iter().fold((f64::INFINITY, 0.0f64), |(lo, hi), c| {
+            (lo.min(c.initial_x), hi.max(c.initial_x))
+        });
+        assert!(
+            overridden.starts_with(&format!("Start: X {:.2}..{:.2} / Y ", x_lo, x_hi)),
+            "{overridden}"
+        );
+    }
+
+    #[test]
+    fn estimate_times_the_calibration_sims_and_scales_with_the_batch() {
+        use std::time::Duration;
+
+        use super::normalizer_after_swap_fn;
+
+        let calibrated = Cell::new(0);
+        let estimate = calibrate(&plan(), 3, |configs| {
+            calibrated.set(configs.len());
+            runner::run_batch_native(
+                normalizer_swap,
+                Some(normalizer_after_swap_fn),
+                normalizer_swap,
+                Some(normalizer_after_swap_fn),
+                configs,
+                Some(1),
+            )
+        })
+        .unwrap();
+        assert_eq!(calibrated.get(), 3);
+        assert_eq!(estimate.calibration_sims, 3);
+        assert!(estimate.per_sim > Duration::ZERO);
+        assert!(estimate.retained_bytes_per_sim > 0.0);
+
+        let small = estimate.duration(1_000, 4);
+        assert!(small > Duration::ZERO);
+        assert_eq!(estimate.duration(10_000, 4), small * 10);
+        assert_eq!(estimate.duration(1_000, 1), small * 4);
+        assert!(estimate.memory_bytes(10_000, 4) > estimate.memory_bytes(1_000, 4));
+        assert!(estimate
+            .summary(50_000, 8)
+            .contains("for 50000 sims on 8 workers (3 calibration sims"));
+
+        assert_eq!(fmt_duration(Duration::from_secs(750)), "12m30s");
+        assert_eq!(fmt_duration(Duration::from_secs(7_500)), "2h05m");
+        assert_eq!(fmt_duration(Duration::from_millis(45_200)), "45.2s");
+        assert_eq!(fmt_bytes(1.2e9), "1.2 GB");
+        assert_eq!(fmt_bytes(3.4e5), "340.0 KB");
+    }
+
+    #[test]
+    fn estimate_flag_defaults_its_calibration_count() {
+        let parse = |argv: &[&str]| Cli::parse_from(argv).run.estimate;
+        assert_eq!(parse(&["run", "my_amm.rs"]), None);
+        assert_eq!(parse(&["run", "my_amm.rs", "--estimate"]), Some(8));
+        assert_eq!(parse(&["run", "my_amm.rs", "--estimate", "20"]), Some(20));
+        assert!(Cli::try_parse_from(["run", "my_amm.rs", "--estimate", "--bpf"]).is_err());
+    }
+
+    #[test]
+    fn dry_run_with_valid_strategy_runs_zero_simulations() {
+        let batches = Cell::new(0);
+        let result = execute_native(&FixtureLibrary::starter(), &plan(), true, |_, _| {
+            batches.set(batches.get() + 1);
+            Ok(BatchResult::from_results(Vec::new()))
+        })
+        .expect("dry run succeeds");
+        assert!(result.is_none());
+        assert_eq!(batches.get(), 0);
+    }
+
+    #[test]
+    fn adaptive_opponent_sets_the_undercut_on_every_config() {
+        let plan = |argv: &[&str]| {
+            plan_for(
+                &Cli::parse_from(argv).run,
+                "fixture".to_string(),
+                PathBuf::from("fixture.so"),
+                "native".to_string(),
+            )
+            .unwrap()
+        };
+        let fixed = plan(&["run", "my_amm.rs", "--simulations", "2"]);
+        assert_eq!(fixed.undercut_bps, None);
+        assert!(fixed
+            .configs()
+            .iter()
+            .all(|c| c.opponent_undercut_bps.is_none()));
+        assert!(!fixed.to_string().contains("Undercut"));
+
+        let adaptive = plan(&[
+            "run",
+            "my_amm.rs",
+            "--simulations",
+            "2",
+            "--opponent",
+            "adaptive",
+            "--undercut-bps",
+            "12",
+        ]);
+        assert!(adaptive
+            .configs()
+            .iter()
+            .all(|c| c.opponent_undercut_bps == Some(12)));
+        let rendered = adaptive.to_string();
+        assert!(
+            rendered.contains("Opponent:    adaptive normalizer"),
+            "{rendered}"
+        );
+        assert!(rendered.contains("Undercut:    12 bps below the strategy's quoted fee"));
+    }
+
+    #[test]
+    fn ignore_after_swap_reaches_every_config_and_the_plan() {
+        let argv = [
+            "run",
+            "my_amm.rs",
+            "--simulations",
+            "3",
+            "--ignore-after-swap",
+        ];
+        let plan = plan_for(
+            &Cli::parse_from(argv).run,
+            "fixture".to_string(),
+            PathBuf::from("fixture.so"),
+            "native".to_string(),
+        )
+        .unwrap();
+        assert!(plan.configs().iter().all(|c| c.ignore_after_swap));
+        assert!(plan
+            .to_string()
+            .contains("After swap:  ignored (strategy runs stateless)"));
+    }
+
+    #[test]
+    fn plan_lists_resolved_seed_range() {
+        let rendered = plan().to_string();
+        assert!(rendered.contains("Strategy:    Fixture Strategy v1.2.0"));
+        assert!(rendered.contains("fixture.so"));
+        assert!(rendered.contains("10 + i*3 (10..=22)"));
+    }
+
+    #[test]
+    fn forced_failure_hints_the_failing_seed() {
+        fn failing_swap(_data: &[u8]) -> u64 {
+            panic!("forced failure")
+        }
+
+        let plan = plan();
+        let err = runner::run_batch_native(
+            failing_swap,
+            None,
+            normalizer_swap,
+            None,
+            plan.configs(),
+            plan.workers,
+        )
+        .expect_err("every simulation fails");
+        let failure = err.downcast_ref::<SimulationError>().unwrap();
+        assert!(plan.configs().iter().any(|c| c.seed == failure.config.seed));
+        let event = failure.disqualification();
+        assert_eq!(
+            (event.kind, event.step),
+            (DisqualificationKind::Panic, None)
+        );
+
+        let args = Cli::parse_from(["run", "my_amm.rs", "--steps", "100"]).run;
+        assert_eq!(
+            reproduction_hint(&args, &failure.config),
+            format!(
+                "prop-amm run my_amm.rs --simulations 1 --seed-start {} --steps 100",
+                failure.config.seed
+            )
+        );
+    }
+
+    /// Pools that start with this much X are quoted more than they hold; others quote normally.
+    const OVER_QUOTING_X: f64 = 123.0;
+
+    fn over_quoting_swap(data: &[u8]) -> u64 {
+        let reserve_x = u64::from_le_bytes(data[9..17].try_into().unwrap());
+        let reserve_y = u64::from_le_bytes(data[17..25].try_into().unwrap());
+        if reserve_x == f64_to_nano(OVER_QUOTING_X) {
+            reserve_x + reserve_y
+        } else {
+            normalizer_swap(data)
+        }
+    }
+
+    #[test]
+    fn disqualification_log_lists_over_quotes_by_seed_and_step() {
+        let steps = 20;
+        let mut configs = runner::seeded_configs(4, steps, 50, 1);
+        for i in [1, 3] {
+            // Every quote over-quotes, so nothing trades and the reserves never move.
+            configs[i].initial_x = OVER_QUOTING_X;
+        }
+        let result = runner::run_batch_native(
+            over_quoting_swap,
+            None,
+            normalizer_swap,
+            None,
+            configs,
+            None,
+        )
+        .unwrap();
+
+        let mut log = Vec::new();
+        let n = write_disqualifications(&mut log, batch_disqualifications(&result)).unwrap();
+        let events: Vec<Disqualification> = String::from_utf8(log)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(events.len(), n);
+
+        // The arbitrageur quotes the pool every step; each step logs its over-quote once.
+        let expected: Vec<(u64, Option<u32>)> = [51, 53]
+            .into_iter()
+            .flat_map(|seed| (0..steps).map(move |step| (seed, Some(step))))
+            .collect();
+        let logged: Vec<_> = events.iter().map(|e| (e.seed, e.step)).collect();
+        assert_eq!(logged, expected);
+        assert!(events
+            .iter()
+            .all(|e| e.kind == DisqualificationKind::OverQuote));
+    }
+
+    #[test]
+    fn only_failed_reruns_exactly_the_logged_seeds() {
+        let event = |seed, step| Disqualification {
+            seed,
+            step,
+            kind: DisqualificationKind::OverQuote,
+            detail: "quoted 2 from a pool holding 1".to_string(),
+        };
+        let events = [
+            event(53, Some(0)),
+            event(51, Some(0)),
+            event(53, Some(1)),
+            event(51, None),
+        ];
+        // The stamp record heading a real log is not an event.
+        let mut log = RunStamp::new(&events[..], b"", "normalizer")
+            .json_line()
+            .into_bytes();
+        write_disqualifications(&mut log, &events).unwrap();
+        assert_eq!(
+            failed_seeds(std::str::from_utf8(&log).unwrap()).unwrap(),
+            [51, 53]
+        );
+        assert!(failed_seeds("{\"seed\": 1}").is_err());
+
+        let path =
+            std::env::temp_dir().join(format!("prop-amm-only-failed-{}.jsonl", std::process::id()));
+        std::fs::write(&path, &log).unwrap();
+        let args = Cli::parse_from([
+            "run",
+            "my_amm.rs",
+            "--steps",
+            "20",
+            "--only-failed",
+            path.to_str().unwrap(),
+        ])
+        .run;
+        let plan = plan_for(
+            &args,
+            "fixture".to_string(),
+            PathBuf::from("fixture.so"),
+            "native".to_string(),
+        );
+        std::fs::remove_file(&path).unwrap();
+        let plan = plan.unwrap();
+        assert_eq!(plan.simulations, 2);
+        assert!(plan.to_string().contains("Seeds:       51, 53"));
+
+        let result = runner::run_batch_native(
+            normalizer_swap,
+            None,
+            normalizer_swap,
+            None,
+            plan.configs(),
+            plan.workers,
+        )
+        .unwrap();
+        let seeds: Vec<u64> = result.results.iter().map(|r| r.seed).collect();
+        assert_eq!(seeds, [51, 53]);
+
+        assert!(Cli::try_parse_from([
+            "run",
+            "my_amm.rs",
+            "--only-failed",
+            "x",
+            "--seed-start",
+            "3"
+        ])
+        .is_err());
+    }
+
+    fn batch_of_edges(edges: impl IntoIterator<Item = f64>) -> BatchResult {
+        BatchResult::from_results(
+            edges
+                .into_iter()
+                .enumerate()
+                .map(|(seed, edge)| SimResult {
+                    seed: seed as u64,
+                    steps_run: 100,
+                    submission_edge: edge,
+                    phases: Default::default(),
+                    k_growth: 0.0,
+                    impermanent_loss: 0.0,
+                    k_series: None,
+                    waterfall: Default::default(),
+                    disqualifications: Vec::new(),
+                    total_quote_slippage: 0.0,
+                    max_quote_slippage: 0.0,
+                    inventory_rejected: 0.0,
+                    storage_never_written: false,
+                    recording_capped: false,
+                    recording_bytes: 0,
+                    initial_value: 20_000.0,
+                    lp_return: Default::default(),
+                    retail_volume: [0.0; 2],
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn min_p5_edge_flags_a_fat_left_tail_despite_a_higher_mean() {
+        // 36 seeds at +120 and 4 at -300: mean 78, but the worst 10% lose heavily.
+        let lottery = batch_of_edges((0..40).map(|i| if i % 10 == 0 { -300.0 } else { 120.0 }));
+        // Every seed within 20 +- 5: mean 20.
+        let steady = batch_of_edges((0..40).map(|i| 15.0 + (i % 11) as f64));
+        assert!(lottery.avg_edge().unwrap() > 3.0 * steady.avg_edge().unwrap());
+        assert_eq!(lottery.edge_quantile(0.05), Some(-300.0));
+        assert_eq!(steady.edge_quantile(0.05), Some(15.0));
+
+        let err = check_p5_edge(&lottery, Some(0.0)).unwrap_err();
+        assert!(
+            err.to_string().contains("5th-percentile edge -300.000000"),
+            "{err}"
+        );
+        assert!(check_p5_edge(&steady, Some(0.0)).is_ok());
+        assert!(check_p5_edge(&lottery, None).is_ok());
+
+        let args = Cli::parse_from(["run", "my_amm.rs", "--min-p5-edge", "-50"]).run;
+        assert_eq!(args.min_p5_edge, Some(-50.0));
+    }
+
+    #[test]
+    fn empty_batches_are_rejected_with_the_reason() {
+        let check = |flags: &[&str]| {
+            let args = Cli::parse_from(["run", "my_amm.rs"].iter().chain(flags)).run;
+            check_batch_args(&args).map_err(|e| e.to_string())
+        };
+        assert!(check(&[]).is_ok());
+        assert!(check(&["--simulations", "0"])
+            .unwrap_err()
+            .contains("empty batch"));
+        assert!(check(&["--steps", "0"])
+            .unwrap_err()
+            .contains("exactly 0 edge"));
+
+        let empty = BatchResult::from_results(Vec::new());
+        assert!(check_p5_edge(&empty, None).is_ok());
+        assert!(check_p5_edge(&empty, Some(0.0)).is_err());
+    }
+
+    #[test]
+    fn interruption_notice_reports_completed_count() {
+        assert_eq!(
+            interruption_notice(true, 37, 1000).as_deref(),
+            Some("interrupted after 37/1000 simulations; partial results below.")
+        );
+        // A fail-fast abort also stops short, but nobody pressed Ctrl-C.
+        assert_eq!(interruption_notice(false, 37, 1000), None);
+        assert_eq!(interruption_notice(true, 1000, 1000), None);
+    }
+}
@@ -0,0 +1,461 @@
+use std::fmt::Write as _;
+
+use clap::Args;
+use prop_amm_executor::{AfterSwapFn, SwapFn};
+use prop_amm_shared::config::SimulationConfig;
+use prop_amm_shared::instruction::STORAGE_SIZE;
+use prop_amm_shared::normalizer::{after_swap as normalizer_after_swap, compute_swap};
+use prop_amm_shared::result::{Phase, PhaseSplit};
+use prop_amm_sim::engine::Simulation;
+use prop_amm_sim::runner;
+use serde_json::Value;
+
+use super::{compile, loader};
+use crate::stamp::RunStamp;
+
+#[derive(Args)]
+pub struct StorageTraceArgs {
+    /// Path to the .rs source file
+    pub file: String,
+    /// Seed of the simulation to trace
+    #[arg(long, default_value = "0")]
+    pub seed: u64,
+    /// Number of steps to simulate
+    #[arg(long, default_value = "10000")]
+    pub steps: u32,
+    /// Leading steps to tag `warmup` and leave out of the measured edge
+    #[arg(long, default_value = "0")]
+    pub warmup_steps: u32,
+    /// Print one JSON object per step instead of CSV
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    U64,
+    I64,
+}
+
+/// One little-endian field of a storage schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageField {
+    pub name: String,
+    pub ty: FieldType,
+    pub offset: usize,
+}
+
+/// Named fields a strategy keeps in its storage, declared as
+/// `const STORAGE_SCHEMA: &str = "ema:u64@0, inventory:i64@8";` (`name:type@offset`, with
+/// `type` one of `u64` / `i64`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageSchema {
+    pub fields: Vec<StorageField>,
+}
+
+impl StorageSchema {
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let mut fields: Vec<StorageField> = Vec::new();
+        for entry in text.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let bad = || anyhow::anyhow!("bad storage schema field `{}`", entry);
+            let (name, rest) = entry.split_once(':').ok_or_else(bad)?;
+            let (ty, offset) = rest.split_once('@').ok_or_else(bad)?;
+            let name = name.trim();
+            let ty = match ty.trim() {
+                "u64" => FieldType::U64,
+                "i64" => FieldType::I64,
+                other => anyhow::bail!("unknown type `{}` for storage field `{}`", other, name),
+            };
+            let offset: usize = offset.trim().parse().map_err(|_| bad())?;
+            if name.is_empty() || name == "step" {
+                return Err(bad());
+            }
+            if offset + 8 > STORAGE_SIZE {
+                anyhow::bail!(
+                    "storage field `{}` at offset {} runs past the {}-byte storage",
+                    name,
+                    offset,
+                    STORAGE_SIZE
+                );
+            }
+            if fields.iter().any(|f| f.name == name) {
+                anyhow::bail!("storage field `{}` is declared twice", name);
+            }
+            fields.push(StorageField {
+                name: name.to_string(),
+                ty,
+                offset,
+            });
+        }
+        if fields.is_empty() {
+            anyhow::bail!("storage schema declares no fields");
+        }
+        Ok(Self { fields })
+    }
+
+    /// `name:type@offset` for every field, as declared.
+    pub fn describe(&self) -> String {
+        let fields: Vec<String> = self
+            .fields
+            .iter()
+            .map(|f| {
+                let ty = match f.ty {
+                    FieldType::U64 => "u64",
+                    FieldType::I64 => "i64",
+                };
+                format!("{}:{}@{}", f.name, ty, f.offset)
+            })
+            .collect();
+        fields.join(", ")
+    }
+
+    /// Offsets of the non-zero bytes of `storage` no field covers. Storage starts zeroed, so
+    /// these are bytes the strategy wrote outside its declared layout.
+    pub fn undeclared_bytes(&self, storage: &[u8]) -> Vec<usize> {
+        let declared = |at: usize| {
+            self.fields
+                .iter()
+                .any(|f| (f.offset..f.offset + 8).contains(&at))
+        };
+        (0..storage.len())
+            .filter(|&at| storage[at] != 0 && !declared(at))
+            .collect()
+    }
+}
+
+/// `0..8, 100` for offsets `[0, 1, .., 7, 100]`.
+fn byte_ranges(offsets: &[usize]) -> String {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for &at in offsets {
+        match ranges.last_mut() {
+            Some((_, end)) if *end == at => *end = at + 1,
+            _ => ranges.push((at, at + 1)),
+        }
+    }
+    let ranges: Vec<String> = ranges
+        .iter()
+        .map(|&(start, end)| {
+            if end - start == 1 {
+                start.to_string()
+            } else {
+                format!("{}..{}", start, end)
+            }
+        })
+        .collect();
+    ranges.join(", ")
+}
+
+/// Column names after `step`: the schema's fields, or `storage_hex` without one.
+fn columns(schema: Option<&StorageSchema>) -> Vec<String> {
+    match schema {
+        Some(schema) => schema.fields.iter().map(|f| f.name.clone()).collect(),
+        None => vec!["storage_hex".to_string()],
+    }
+}
+
+/// Decode `storage` into one value per column. The raw fallback is the storage as hex with
+/// trailing zero bytes dropped.
+fn decode(schema: Option<&StorageSchema>, storage: &[u8]) -> Vec<Value> {
+    let Some(schema) = schema else {
+        let used = storage.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+        let hex = storage[..used].iter().fold(String::new(), |mut out, b| {
+            let _ = write!(out, "{:02x}", b);
+            out
+        });
+        return vec![Value::String(hex)];
+    };
+    schema
+        .fields
+        .iter()
+        .map(|field| {
+            let bytes: [u8; 8] = storage[field.offset..field.offset + 8].try_into().unwrap();
+            match field.ty {
+                FieldType::U64 => Value::from(u64::from_le_bytes(bytes)),
+                FieldType::I64 => Value::from(i64::from_le_bytes(bytes)),
+            }
+        })
+        .collect()
+}
+
+/// Storage of the strategy after every step of one seed against the normalizer.
+pub struct StorageTrace {
+    pub columns: Vec<String>,
+    /// `(step, phase, values)`, one entry per step, values in `columns` order.
+    pub rows: Vec<(u32, Phase, Vec<Value>)>,
+    /// Steps and edge of the warmup and the measured part of the run.
+    pub phases: PhaseSplit,
+    /// First step after which storage held bytes outside the schema's fields, and their
+    /// offsets; always `None` without a schema.
+    pub out_of_schema: Option<(u32, Vec<usize>)>,
+}
+
+impl StorageTrace {
+    /// One row per step, then a `# warmup: ..` and a `# measured: ..` comment line with
+    /// each phase's steps and edge.
+    pub fn to_csv(&self) -> String {
+        let mut out = format!("step,phase,{}\n", self.columns.join(","));
+        for (step, phase, values) in &self.rows {
+            let cells: Vec<String> = values
+                .iter()
+                .map(|value| match value {
+                    Value::String(text) => text.clone(),
+                    other => other.to_string(),
+                })
+                .collect();
+            let _ = writeln!(out, "{},{},{}", step, phase.as_str(), cells.join(","));
+        }
+        let PhaseSplit { warmup, measured } = self.phases;
+        for (phase, totals) in [(Phase::Warmup, warmup), (Phase::Measured, measured)] {
+            let _ = writeln!(
+                out,
+                "# {}: steps={} edge={}",
+                phase.as_str(),
+                totals.steps,
+                totals.edge
+            );
+        }
+        out
+    }
+
+    /// One `{"step": .., "phase": .., <column>: ..}` object per line, then a
+    /// `{"phases": ..}` line with each phase's steps and edge.
+    pub fn to_json_lines(&self) -> String {
+        let mut out = String::new();
+        for (step, phase, values) in &self.rows {
+            let mut object = serde_json::Map::new();
+            object.insert("step".to_string(), Value::from(*step));
+            object.insert("phase".to_string(), Value::from(phase.as_str()));
+            for (column, value) in self.columns.iter().zip(values) {
+                object.insert(column.clone(), value.clone());
+            }
+            let _ = writeln!(out, "{}", Value::Object(object));
+        }
+        let _ = writeln!(out, "{}", serde_json::json!({ "phases": self.phases }));
+        out
+    }
+}
+
+/// The traced seed's config, its first `warmup_steps` steps unscored.
+fn config(seed: u64, steps: u32, warmup_steps: u32) -> SimulationConfig {
+    SimulationConfig {
+        warmup_steps,
+        ..runner::seeded_configs(1, steps, seed, 1).remove(0)
+    }
+}
+
+fn trace(
+    swap_fn: SwapFn,
+    after_swap_fn: Option<AfterSwapFn>,
+    schema: Option<&StorageSchema>,
+    seed: u64,
+    steps: u32,
+    warmup_steps: u32,
+) -> StorageTrace {
+    let config = &config(seed, steps, warmup_steps);
+    let mut sim = Simulation::native(
+        swap_fn,
+        after_swap_fn,
+        compute_swap,
+        Some(normalizer_after_swap),
+        config,
+    );
+    let mut rows = Vec::with_capacity(steps as usize);
+    let mut out_of_schema = None;
+    while sim.step() {
+        let step = sim.current_step() - 1;
+        let storage = sim.submission().storage();
+        if let Some(schema) = schema.filter(|_| out_of_schema.is_none()) {
+            let stray = schema.undeclared_bytes(storage);
+            if !stray.is_empty() {
+                out_of_schema = Some((step, stray));
+            }
+        }
+        rows.push((step, config.phase(step), decode(schema, storage)));
+    }
+    StorageTrace {
+        columns: columns(schema),
+        rows,
+        phases: sim.phases(),
+        out_of_schema,
+    }
+}
+
+pub fn run(args: StorageTraceArgs) -> anyhow::Result<()> {
+    eprintln!("Compiling {} (native)...", args.file);
+    let native_path = compile::compile_native(&args.file)?;
+    let lib = loader::open_library(&native_path)?;
+    let (swap_fn, after_swap_fn) = loader::install(loader::resolve_symbols(lib)?)?;
+    let schema = loader::read_storage_schema(lib)
+        .map(|text| StorageSchema::parse(&text))
+        .transpose()?;
+    if schema.is_none() {
+        eprintln!("No STORAGE_SCHEMA declared; printing raw storage as hex.");
+    }
+
+    let trace = trace(
+        swap_fn,
+        after_swap_fn,
+        schema.as_ref(),
+        args.seed,
+        args.steps,
+        args.warmup_steps,
+    );
+    let configs = [config(args.seed, args.steps, args.warmup_steps)];
+    let stamp = RunStamp::for_artifact(&configs, &native_path, "normalizer")?;
+    if args.json {
+        print!("{}{}", stamp.json_line(), trace.to_json_lines());
+    } else {
+        print!("{}{}", stamp.csv_comment(), trace.to_csv());
+    }
+    if let Some((step, stray)) = &trace.out_of_schema {
+        eprintln!(
+            "Warning: by step {} the strategy had written storage bytes {} outside its STORAGE_SCHEMA; they are not shown.",
+            step,
+            byte_ranges(stray)
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use prop_amm_shared::normalizer::compute_swap;
+
+    use super::{byte_ranges, trace, FieldType, Phase, StorageSchema};
+    use crate::commands::loader::fixtures::{storage_schema_ffi, FixtureLibrary};
+    use crate::commands::{compile, loader};
+
+    /// Counts trades as a u64 at 0 and stores `step - 100` as an i64 at 8.
+    fn schema_after_swap(data: &[u8], storage: &mut [u8]) {
+        let trades = u64::from_le_bytes(storage[0..8].try_into().unwrap()) + 1;
+        storage[0..8].copy_from_slice(&trades.to_le_bytes());
+        let step = u64::from_le_bytes(data[34..42].try_into().unwrap()) as i64;
+        storage[8..16].copy_from_slice(&(step - 100).to_le_bytes());
+    }
+
+    #[test]
+    fn declared_schema_labels_the_columns() {
+        let library = FixtureLibrary::starter().with(
+            compile::NATIVE_STORAGE_SCHEMA_SYMBOL,
+            storage_schema_ffi as *mut (),
+        );
+        let schema = StorageSchema::parse(&loader::read_storage_schema(&library).unwrap()).unwrap();
+        assert_eq!(schema.fields.len(), 2);
+        assert_eq!(
+            (schema.fields[1].ty, schema.fields[1].offset),
+            (FieldType::I64, 8)
+        );
+
+        let trace = trace(
+            compute_swap,
+            Some(schema_after_swap),
+            Some(&schema),
+            3,
+            50,
+            0,
+        );
+        let csv = trace.to_csv();
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("step,phase,trades,last_step"));
+        assert_eq!(lines.filter(|l| !l.starts_with('#')).count(), 50);
+        let (_, _, last) = trace.rows.last().unwrap();
+        assert!(last[0].as_u64().unwrap() > 0, "the strategy should trade");
+        // `last_step - 100` is negative this early, which only an i64 column can show.
+        assert!(last[1].as_i64().unwrap() < 0);
+        assert!(csv.lines().nth(50).unwrap().contains(",-"));
+
+        assert_eq!(trace.out_of_schema, None);
+
+        let json = trace.to_json_lines();
+        let first: serde_json::Value = serde_json::from_str(json.lines().next().unwrap()).unwrap();
+        assert!(first.get("trades").is_some_and(|v| v.is_u64()));
+        assert!(first.get("last_step").is_some_and(|v| v.is_i64()));
+    }
+
+    #[test]
+    fn schema_errors_and_raw_fallback() {
+        assert!(StorageSchema::parse("ema:f32@0").is_err());
+        assert!(StorageSchema::parse("ema:u64@1020").is_err());
+        assert!(StorageSchema::parse("a:u64@0, a:i64@8").is_err());
+        assert!(StorageSchema::parse("").is_err());
+
+        let trace = trace(compute_swap, Some(schema_after_swap), None, 3, 20, 0);
+        assert_eq!(trace.columns, ["storage_hex"]);
+        let last = trace.to_csv().lines().nth(20).unwrap().to_string();
+        assert!(last.starts_with("19,measured,"), "{last}");
+    }
+
+    /// `schema_after_swap`, plus a scratch byte at offset 100 from step 5 on.
+    fn stray_after_swap(data: &[u8], storage: &mut [u8]) {
+        schema_after_swap(data, storage);
+        if u64::from_le_bytes(data[34..42].try_into().unwrap()) >= 5 {
+            storage[100] = 1;
+        }
+    }
+
+    #[test]
+    fn writes_outside_the_schema_are_flagged_with_their_step_and_bytes() {
+        let schema = StorageSchema::parse("trades:u64@0, last_step:i64@8").unwrap();
+        let flagged = trace(
+            compute_swap,
+            Some(stray_after_swap),
+            Some(&schema),
+            3,
+            50,
+            0,
+        );
+        let (step, stray) = flagged.out_of_schema.expect("byte 100 is undeclared");
+        assert!(step >= 5, "flagged at step {step}");
+        assert_eq!(stray, [100]);
+        // The declared fields still decode.
+        assert!(flagged.rows.last().unwrap().2[0].as_u64().unwrap() > 0);
+
+        // Without a schema there is nothing to check against.
+        let raw = trace(compute_swap, Some(stray_after_swap), None, 3, 50, 0);
+        assert_eq!(raw.out_of_schema, None);
+
+        assert_eq!(schema.describe(), "trades:u64@0, last_step:i64@8");
+        assert_eq!(byte_ranges(&[0, 1, 2, 7, 100, 101]), "0..3, 7, 100..102");
+    }
+
+    #[test]
+    fn exactly_the_warmup_steps_are_tagged_warmup() {
+        let schema = StorageSchema::parse("trades:u64@0, last_step:i64@8").unwrap();
+        let trace = trace(
+            compute_swap,
+            Some(schema_after_swap),
+            Some(&schema),
+            3,
+            40,
+            15,
+        );
+        let warmup: Vec<u32> = trace
+            .rows
+            .iter()
+            .filter(|(_, phase, _)| *phase == Phase::Warmup)
+            .map(|(step, _, _)| *step)
+            .collect();
+        assert_eq!(warmup, (0..15).collect::<Vec<_>>());
+        assert_eq!(
+            (trace.phases.warmup.steps, trace.phases.measured.steps),
+            (15, 25)
+        );
+
+        let csv = trace.to_csv();
+        assert_eq!(csv.lines().filter(|l| l.contains(",warmup,")).count(), 15);
+        assert_eq!(csv.lines().filter(|l| l.contains(",measured,")).count(), 25);
+        assert!(csv.contains("\n# warmup: steps=15 edge="), "{csv}");
+        assert!(csv.contains("\n# measured: steps=25 edge="), "{csv}");
+
+        let json = trace.to_json_lines();
+        let lines: Vec<serde_json::Value> = json
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 41);
+        let tagged = lines.iter().filter(|l| l["phase"] == "warmup").count();
+        assert_eq!(tagged, 15);
+        assert_eq!(lines[40]["phases"]["warmup"]["steps"], 15);
+        assert_eq!(lines[40]["phases"]["measured"]["steps"], 25);
+    }
+}
@@ -0,0 +1,156 @@
+use clap::Args;
+use prop_amm_sim::opponents::Opponent;
+use prop_amm_sim::runner;
+use prop_amm_sim::stress::{self, Survival, STRESS_SIGMA};
+
+use super::tournament::selected_opponents;
+use super::{compile, loader};
+
+#[derive(Args)]
+pub struct StressArgs {
+    /// Path to the .rs source file
+    pub file: String,
+    /// A pool is ruined once it is worth less than this fraction of its starting reserves held
+    #[arg(long, default_value = "0.5")]
+    pub ruin_threshold: f64,
+    /// Number of simulations per strategy
+    #[arg(long, default_value = "100")]
+    pub simulations: u32,
+    /// Step cap per simulation; pools still standing count as surviving this long
+    #[arg(long, default_value = "10000")]
+    pub steps: u32,
+    /// Per-step volatility of the adverse market
+    #[arg(long, default_value_t = STRESS_SIGMA)]
+    pub sigma: f64,
+    /// Number of parallel workers, respected exactly (0 = one per core, at most 8)
+    #[arg(long, default_value = "0")]
+    pub workers: usize,
+    /// Starting seed for simulation config generation
+    #[arg(long, default_value = "0")]
+    pub seed_start: u64,
+    /// Seed step between simulations
+    #[arg(long, default_value = "1")]
+    pub seed_stride: u64,
+    /// Also race these built-in strategies (repeatable; default: all of them)
+    #[arg(long = "against")]
+    pub against: Vec<String>,
+}
+
+pub fn run(args: StressArgs) -> anyhow::Result<()> {
+    if args.seed_stride == 0 {
+        anyhow::bail!("--seed-stride must be >= 1");
+    }
+    if args.simulations == 0 {
+        anyhow::bail!("--simulations must be >= 1");
+    }
+    if !(args.ruin_threshold > 0.0 && args.ruin_threshold < 1.0) {
+        anyhow::bail!(
+            "--ruin-threshold must be between 0 and 1, got {}",
+            args.ruin_threshold
+        );
+    }
+    let mut candidates = selected_opponents(&args.against)?;
+
+    println!("Compiling {} (native)...", args.file);
+    let native_path = compile::compile_native(&args.file)?;
+    let lib = loader::open_library(&native_path)?;
+    let (swap_fn, after_swap_fn) = loader::install(loader::resolve_symbols(lib)?)?;
+    let strategy = loader::read_metadata(lib).title(&args.file);
+    candidates.insert(
+        0,
+        Opponent {
+            name: "submission",
+            description: "the strategy under test",
+            swap: swap_fn,
+            after_swap: after_swap_fn,
+        },
+    );
+
+    println!(
+        "Stressing {} and {} built-in strategies at sigma {} per step, {} simulations (up to {} steps each), seeds {} + i*{}...",
+        strategy,
+        candidates.len() - 1,
+        args.sigma,
+        args.simulations,
+        args.steps,
+        args.seed_start,
+        args.seed_stride,
+    );
+    let configs: Vec<_> = runner::seeded_configs(
+        args.simulations,
+        args.steps,
+        args.seed_start,
+        args.seed_stride,
+    )
+    .iter()
+    .map(|config| stress::adverse(config, args.sigma))
+    .collect();
+    let workers = (args.workers != 0).then_some(args.workers);
+    let rows = stress::race(&candidates, &configs, args.ruin_threshold, workers)?;
+    print!("{}", render(&strategy, args.ruin_threshold, &rows));
+    Ok(())
+}
+
+fn render(strategy: &str, threshold: f64, rows: &[Survival]) -> String {
+    let mut out = String::new();
+    out.push_str("\n========================================\n");
+    out.push_str(&format!("  Strategy: {}\n", strategy));
+    out.push_str(&format!(
+        "  Ruin: worth < {}% of the starting reserves held\n",
+        threshold * 100.0
+    ));
+    out.push_str(&format!(
+        "  {:<4} {:<14} {:>16} {:>10}\n",
+        "Rank", "Strategy", "Steps to ruin", "Ruined"
+    ));
+    for (rank, row) in rows.iter().enumerate() {
+        // Survivors are counted at the step cap, so the mean is only a lower bound.
+        let bound = if row.ruined() < row.ruin_steps.len() {
+            ">= "
+        } else {
+            ""
+        };
+        out.push_str(&format!(
+            "  {:<4} {:<14} {:>16} {:>10}\n",
+            rank + 1,
+            row.name,
+            format!("{}{:.1}", bound, row.mean_steps),
+            format!("{}/{}", row.ruined(), row.ruin_steps.len())
+        ));
+    }
+    out.push_str("========================================\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use prop_amm_sim::stress::Survival;
+
+    use super::render;
+
+    #[test]
+    fn table_ranks_longest_lived_first_and_bounds_means_with_survivors() {
+        let rows = [
+            Survival {
+                name: "starter",
+                ruin_steps: vec![None, Some(900)],
+                mean_steps: 950.0,
+            },
+            Survival {
+                name: "submission",
+                ruin_steps: vec![Some(4), Some(6)],
+                mean_steps: 5.0,
+            },
+        ];
+        let table = render("Fixture Strategy v1.2.0", 0.5, &rows);
+        assert!(table.contains("Ruin: worth < 50% of the starting reserves held"));
+        let starter = table.lines().find(|l| l.contains("starter")).unwrap();
+        let submission = table.lines().find(|l| l.contains("submission")).unwrap();
+        assert!(
+            starter.contains(">= 950.0") && starter.contains("1/2"),
+            "{starter}"
+        );
+        assert!(submission.contains(" 5.0") && !submission.contains(">="));
+        assert!(table.find("starter").unwrap() < table.find("submission").unwrap());
+    }
+}
@@ -0,0 +1,307 @@
+use std::fmt::Write as _;
+
+use clap::Args;
+use prop_amm_executor::{AfterSwapFn, SwapFn};
+use prop_amm_shared::config::SimulationConfig;
+use prop_amm_shared::normalizer::{
+    after_swap as normalizer_after_swap_fn, compute_swap as normalizer_swap,
+};
+use prop_amm_shared::result::BatchResult;
+use prop_amm_sim::runner;
+
+use super::{compile, loader};
+use crate::output::{self, fmt_f64, fmt_stat};
+use crate::stamp::RunStamp;
+
+#[derive(Args)]
+pub struct SweepArgs {
+    /// Path to the .rs source file
+    pub file: String,
+    /// Normalizer fee values to sweep, in bps (comma-separated; default: sampled per seed)
+    #[arg(long, value_delimiter = ',')]
+    pub fee_bps: Vec<u16>,
+    /// GBM volatility values to sweep (comma-separated; default: sampled per seed)
+    #[arg(long, value_delimiter = ',')]
+    pub volatility: Vec<f64>,
+    /// Minimum arbitrage profit values to sweep, in Y (comma-separated; default: 0.01)
+    #[arg(long, value_delimiter = ',')]
+    pub min_arb_profit: Vec<f64>,
+    /// Number of simulations per grid cell
+    #[arg(long, default_value = "100")]
+    pub simulations: u32,
+    /// Number of steps per simulation
+    #[arg(long, default_value = "10000")]
+    pub steps: u32,
+    /// Number of parallel workers, respected exactly (0 = one per core, at most 8)
+    #[arg(long, default_value = "0")]
+    pub workers: usize,
+    /// Starting seed for simulation config generation
+    #[arg(long, default_value = "0")]
+    pub seed_start: u64,
+    /// Seed step between simulations
+    #[arg(long, default_value = "1")]
+    pub seed_stride: u64,
+    /// Print the grid as CSV instead of a table
+    #[arg(long)]
+    pub csv: bool,
+}
+
+/// One combination of swept parameters; `None` leaves the per-seed config value alone.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct GridCell {
+    pub fee_bps: Option<u16>,
+    pub volatility: Option<f64>,
+    pub min_arb_profit: Option<f64>,
+}
+
+impl GridCell {
+    fn apply(&self, config: &SimulationConfig) -> SimulationConfig {
+        let mut config = config.clone();
+        if let Some(fee_bps) = self.fee_bps {
+            config.norm_fee_bps = fee_bps;
+        }
+        if let Some(volatility) = self.volatility {
+            config.gbm_sigma = volatility;
+        }
+        if let Some(min_arb_profit) = self.min_arb_profit {
+            config.min_arb_profit = min_arb_profit;
+        }
+        config
+    }
+
+    /// `(column, value)` for every parameter the cell sets.
+    fn labels(&self) -> Vec<(&'static str, String)> {
+        let mut labels = Vec::new();
+        if let Some(fee_bps) = self.fee_bps {
+            labels.push(("fee_bps", fee_bps.to_string()));
+        }
+        if let Some(volatility) = self.volatility {
+            labels.push(("volatility", fmt_f64(volatility)));
+        }
+        if let Some(min_arb_profit) = self.min_arb_profit {
+            labels.push(("min_arb_profit", fmt_f64(min_arb_profit)));
+        }
+        labels
+    }
+}
+
+/// Cross product of the swept values, fee outermost. A parameter with no values is not swept.
+fn grid(args: &SweepArgs) -> Vec<GridCell> {
+    fn axis<T: Copy>(values: &[T]) -> Vec<Option<T>> {
+        if values.is_empty() {
+            vec![None]
+        } else {
+            values.iter().copied().map(Some).collect()
+        }
+    }
+    let mut cells = Vec::new();
+    for fee_bps in axis(&args.fee_bps) {
+        for volatility in axis(&args.volatility) {
+            for min_arb_profit in axis(&args.min_arb_profit) {
+                cells.push(GridCell {
+                    fee_bps,
+                    volatility,
+                    min_arb_profit,
+                });
+            }
+        }
+    }
+    cells
+}
+
+/// Result of every seed in one grid cell.
+pub struct SweepRow {
+    pub cell: GridCell,
+    pub batch: BatchResult,
+}
+
+/// Run `configs` in every cell of `cells` as one batch, so the workers are shared across
+/// cells as well as seeds. Rows come back in grid order.
+fn run_grid(
+    swap_fn: SwapFn,
+    after_swap_fn: Option<AfterSwapFn>,
+    cells: &[GridCell],
+    configs: &[SimulationConfig],
+    n_workers: Option<usize>,
+) -> anyhow::Result<Vec<SweepRow>> {
+    let all_configs = cells
+        .iter()
+        .flat_map(|cell| configs.iter().map(|config| cell.apply(config)))
+        .collect();
+    let batch = runner::run_batch_native(
+        swap_fn,
+        after_swap_fn,
+        normalizer_swap,
+        Some(normalizer_after_swap_fn),
+        all_configs,
+        n_workers,
+    )?;
+    let mut results = batch.results.into_iter();
+    Ok(cells
+        .iter()
+        .map(|&cell| SweepRow {
+            cell,
+            batch: BatchResult::from_results(results.by_ref().take(configs.len()).collect()),
+        })
+        .collect())
+}
+
+fn min_edge(batch: &BatchResult) -> f64 {
+    batch
+        .results
+        .iter()
+        .map(|r| r.submission_edge)
+        .fold(f64::INFINITY, f64::min)
+}
+
+/// CSV of the swept parameters followed by `avg_edge,min_edge`, one row per cell.
+fn render_csv(rows: &[SweepRow]) -> String {
+    let mut out = String::new();
+    let Some(first) = rows.first() else {
+        return out;
+    };
+    for (column, _) in first.cell.labels() {
+        let _ = write!(out, "{},", column);
+    }
+    out.push_str("avg_edge,min_edge\n");
+    for row in rows {
+        for (_, value) in row.cell.labels() {
+            let _ = write!(out, "{},", value);
+        }
+        let _ = writeln!(
+            out,
+            "{},{}",
+            fmt_stat(row.batch.avg_edge()),
+            fmt_f64(min_edge(&row.batch))
+        );
+    }
+    out
+}
+
+fn render_table(rows: &[SweepRow]) -> String {
+    let mut out = String::new();
+    let Some(first) = rows.first() else {
+        return out;
+    };
+    out.push_str("\n========================================\n ");
+    for (column, _) in first.cell.labels() {
+        let _ = write!(out, " {:>14}", column);
+    }
+    let _ = writeln!(out, " {:>16} {:>16}", "Avg edge", "Worst seed");
+    for row in rows {
+        out.push(' ');
+        for (_, value) in row.cell.labels() {
+            let _ = write!(out, " {:>14}", value);
+        }
+        let _ = writeln!(
+            out,
+            " {:>16} {:>16}",
+            fmt_stat(row.batch.avg_edge()),
+            fmt_f64(min_edge(&row.batch))
+        );
+    }
+    out.push_str("========================================\n");
+    out
+}
+
+pub fn run(args: SweepArgs) -> anyhow::Result<()> {
+    if args.seed_stride == 0 {
+        anyhow::bail!("--seed-stride must be >= 1");
+    }
+    if args.simulations == 0 {
+        anyhow::bail!("--simulations must be >= 1: an empty batch has no edge to report");
+    }
+    if args.steps == 0 {
+        anyhow::bail!(
+            "--steps must be >= 1: with no steps nothing trades and every seed scores exactly 0 edge"
+        );
+    }
+    let cells = grid(&args);
+
+    eprintln!("Compiling {} (native)...", args.file);
+    let native_path = compile::compile_native(&args.file)?;
+    let lib = loader::open_library(&native_path)?;
+    let (swap_fn, after_swap_fn) = loader::install(loader::resolve_symbols(lib)?)?;
+
+    eprintln!(
+        "Sweeping {} grid cells, {} simulations ({} steps each) per cell, seeds {} + i*{}...",
+        cells.len(),
+        args.simulations,
+        args.steps,
+        args.seed_start,
+        args.seed_stride,
+    );
+    let configs = runner::seeded_configs(
+        args.simulations,
+        args.steps,
+        args.seed_start,
+        args.seed_stride,
+    );
+    let starts: Vec<SimulationConfig> = cells
+        .iter()
+        .flat_map(|cell| configs.iter().map(|config| cell.apply(config)))
+        .collect();
+    if let Some(warning) = output::start_warning(&starts) {
+        eprintln!("{}", warning);
+    }
+    let workers = (args.workers != 0).then_some(args.workers);
+    let rows = run_grid(swap_fn, after_swap_fn, &cells, &configs, workers)?;
+    if args.csv {
+        let stamp = RunStamp::for_artifact(&starts, &native_path, "normalizer")?;
+        print!("{}{}", stamp.csv_comment(), render_csv(&rows));
+    } else {
+        print!("{}", render_table(&rows));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use prop_amm_shared::normalizer::{after_swap, compute_swap};
+    use prop_amm_sim::runner;
+
+    use super::{grid, render_csv, run_grid, SweepArgs};
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        sweep: SweepArgs,
+    }
+
+    #[test]
+    fn two_by_two_grid_yields_four_labelled_rows() {
+        let args = Cli::parse_from([
+            "sweep",
+            "strategy.rs",
+            "--fee-bps",
+            "10,50",
+            "--volatility",
+            "0.001,0.003",
+        ])
+        .sweep;
+        let cells = grid(&args);
+        assert_eq!(cells.len(), 4);
+
+        let configs = runner::seeded_configs(2, 50, 0, 1);
+        let rows = run_grid(compute_swap, Some(after_swap), &cells, &configs, Some(2)).unwrap();
+        for row in &rows {
+            assert_eq!(row.batch.n_sims(), 2);
+            for result in &row.batch.results {
+                assert!(result.submission_edge.is_finite());
+            }
+        }
+        let csv = render_csv(&rows);
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0], "fee_bps,volatility,avg_edge,min_edge");
+        let labels: Vec<_> = lines[1..]
+            .iter()
+            .map(|line| line.splitn(3, ',').take(2).collect::<Vec<_>>().join(","))
+            .collect();
+        assert_eq!(
+            labels,
+            ["10,0.001000", "10,0.003000", "50,0.001000", "50,0.003000"]
+        );
+    }
+}
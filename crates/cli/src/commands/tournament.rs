@@ -0,0 +1,173 @@
+use clap::Args;
+use prop_amm_sim::opponents::{self, Opponent, Tournament, OPPONENTS};
+use prop_amm_sim::{corpus, runner};
+
+use super::{compile, loader};
+use crate::output::fmt_f64;
+
+#[derive(Args)]
+pub struct TournamentArgs {
+    /// Path to the .rs source file
+    pub file: String,
+    /// Number of simulations per opponent
+    #[arg(long, default_value = "100")]
+    pub simulations: u32,
+    /// Number of steps per simulation
+    #[arg(long, default_value = "10000")]
+    pub steps: u32,
+    /// Number of parallel workers, respected exactly (0 = one per core, at most 8)
+    #[arg(long, default_value = "0")]
+    pub workers: usize,
+    /// Starting seed for simulation config generation
+    #[arg(long, default_value = "0")]
+    pub seed_start: u64,
+    /// Seed step between simulations
+    #[arg(long, default_value = "1")]
+    pub seed_stride: u64,
+    /// Only play these opponents (repeatable; default: all built-in opponents)
+    #[arg(long = "opponent")]
+    pub opponents: Vec<String>,
+    /// Play the fixed benchmark corpus instead of generated seeds, for scores comparable
+    /// across users and versions
+    #[arg(long, conflicts_with_all = ["simulations", "steps", "seed_start", "seed_stride"])]
+    pub corpus: bool,
+}
+
+pub fn selected_opponents(names: &[String]) -> anyhow::Result<Vec<Opponent>> {
+    if names.is_empty() {
+        return Ok(OPPONENTS.to_vec());
+    }
+    names
+        .iter()
+        .map(|name| {
+            opponents::find(name).copied().ok_or_else(|| {
+                let known: Vec<_> = OPPONENTS.iter().map(|o| o.name).collect();
+                anyhow::anyhow!(
+                    "unknown opponent `{}` (available: {})",
+                    name,
+                    known.join(", ")
+                )
+            })
+        })
+        .collect()
+}
+
+pub fn run(args: TournamentArgs) -> anyhow::Result<()> {
+    if args.seed_stride == 0 {
+        anyhow::bail!("--seed-stride must be >= 1");
+    }
+    if args.simulations == 0 {
+        anyhow::bail!("--simulations must be >= 1: an empty batch has no edge to report");
+    }
+    if args.steps == 0 {
+        anyhow::bail!(
+            "--steps must be >= 1: with no steps nothing trades and every seed scores exactly 0 edge"
+        );
+    }
+    let opponents = selected_opponents(&args.opponents)?;
+
+    println!("Compiling {} (native)...", args.file);
+    let native_path = compile::compile_native(&args.file)?;
+    let lib = loader::open_library(&native_path)?;
+    let (swap_fn, after_swap_fn) = loader::install(loader::resolve_symbols(lib)?)?;
+    let strategy = loader::read_metadata(lib).title(&args.file);
+
+    let configs = if args.corpus {
+        println!(
+            "Playing {} against {} opponents on benchmark corpus v{} ({} scenarios: {})...",
+            strategy,
+            opponents.len(),
+            corpus::CORPUS_VERSION,
+            corpus::standard().len(),
+            corpus::REGIMES.join(", "),
+        );
+        corpus::standard()
+    } else {
+        println!(
+            "Playing {} against {} opponents, {} simulations ({} steps each) per opponent, seeds {} + i*{}...",
+            strategy,
+            opponents.len(),
+            args.simulations,
+            args.steps,
+            args.seed_start,
+            args.seed_stride,
+        );
+        runner::seeded_configs(
+            args.simulations,
+            args.steps,
+            args.seed_start,
+            args.seed_stride,
+        )
+    };
+    let workers = (args.workers != 0).then_some(args.workers);
+    let tournament =
+        opponents::run_tournament(swap_fn, after_swap_fn, &opponents, &configs, workers)?;
+    print!("{}", render(&strategy, &tournament));
+    Ok(())
+}
+
+fn render(strategy: &str, tournament: &Tournament) -> String {
+    let mut out = String::new();
+    out.push_str("\n========================================\n");
+    out.push_str(&format!("  Strategy: {}\n", strategy));
+    out.push_str(&format!(
+        "  {:<4} {:<14} {:>16} {:>16}\n",
+        "Rank", "Opponent", "Avg edge", "Worst seed"
+    ));
+    for (rank, row) in tournament.rows.iter().enumerate() {
+        out.push_str(&format!(
+            "  {:<4} {:<14} {:>16} {:>16}\n",
+            rank + 1,
+            row.opponent,
+            fmt_f64(row.avg_edge),
+            fmt_f64(row.min_edge)
+        ));
+    }
+    out.push_str(&format!(
+        "  Robustness (worst avg edge): {}\n",
+        fmt_f64(tournament.robustness())
+    ));
+    out.push_str("========================================\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use prop_amm_sim::opponents::{Tournament, TournamentRow, OPPONENTS};
+
+    use super::{render, selected_opponents};
+
+    #[test]
+    fn opponent_filter_rejects_unknown_names() {
+        assert_eq!(selected_opponents(&[]).unwrap().len(), OPPONENTS.len());
+        let picked = selected_opponents(&["starter".to_string()]).unwrap();
+        assert_eq!(picked.len(), 1);
+        assert_eq!(picked[0].name, "starter");
+        let err = selected_opponents(&["nope".to_string()]).unwrap_err();
+        assert!(err.to_string().contains("available: normalizer"));
+    }
+
+    #[test]
+    fn table_lists_rows_in_rank_order() {
+        let tournament = Tournament {
+            rows: vec![
+                TournamentRow {
+                    opponent: "starter",
+                    avg_edge: 40.0,
+                    min_edge: 12.5,
+                },
+                TournamentRow {
+                    opponent: "normalizer",
+                    avg_edge: -3.25,
+                    min_edge: -20.0,
+                },
+            ],
+        };
+        let table = render("Fixture Strategy v1.2.0", &tournament);
+        assert!(table.contains("Strategy: Fixture Strategy v1.2.0"));
+        let starter = table.find("starter").unwrap();
+        let normalizer = table.find("normalizer").unwrap();
+        assert!(starter < normalizer);
+        assert!(table.contains("Robustness (worst avg edge): -3.250000"));
+    }
+}
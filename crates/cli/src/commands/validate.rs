@@ -1,51 +1,27 @@
 use std::path::Path;
-use std::sync::atomic::{AtomicPtr, Ordering};
 
 use anyhow::Context;
-use prop_amm_executor::{AfterSwapFn, BpfExecutor, BpfProgram};
+use prop_amm_executor::{BpfExecutor, BpfProgram};
 use prop_amm_shared::instruction::STORAGE_SIZE;
 use prop_amm_shared::nano::{f64_to_nano, nano_to_f64};
 use prop_amm_shared::normalizer::{
     after_swap as normalizer_after_swap, compute_swap as normalizer_swap,
 };
-use prop_amm_sim::runner;
+use prop_amm_sim::{audit, runner};
 use syn::{Expr, Item, Lit, Type};
 
-use super::compile;
-
-type FfiSwapFn = unsafe extern "C" fn(*const u8, usize) -> u64;
-type FfiAfterSwapFn = unsafe extern "C" fn(*const u8, usize, *mut u8, usize);
-
-static LOADED_SWAP: AtomicPtr<()> = AtomicPtr::new(std::ptr::null_mut());
-static LOADED_AFTER_SWAP: AtomicPtr<()> = AtomicPtr::new(std::ptr::null_mut());
+use super::{compile, loader};
+use crate::output::fmt_f64;
 
 const PARITY_SIMS: u32 = 12;
 const PARITY_STEPS: u32 = 2_000;
 const PARITY_SEED_START: u64 = 9_001;
 const PARITY_SEED_STRIDE: u64 = 7;
 const PARITY_ABS_TOL: f64 = 1e-6;
+const LOCKSTEP_SIMS: u32 = 3;
 const CONCAVITY_DELTA_NANO: u64 = 1_000_000;
 const CONCAVITY_STEP_TOL_NANO: i128 = 1;
 
-fn dynamic_swap(data: &[u8]) -> u64 {
-    let ptr = LOADED_SWAP.load(Ordering::Relaxed);
-    let f: FfiSwapFn = unsafe { std::mem::transmute(ptr) };
-    unsafe { f(data.as_ptr(), data.len()) }
-}
-
-fn dynamic_after_swap(data: &[u8], storage: &mut [u8]) {
-    let ptr = LOADED_AFTER_SWAP.load(Ordering::Relaxed);
-    let f: FfiAfterSwapFn = unsafe { std::mem::transmute(ptr) };
-    unsafe {
-        f(
-            data.as_ptr(),
-            data.len(),
-            storage.as_mut_ptr(),
-            storage.len(),
-        )
-    }
-}
-
 pub fn run(file: &str) -> anyhow::Result<()> {
     let metadata = validate_submission_metadata(file)?;
     println!("  [PASS] Name: {}", metadata.name);
@@ -69,6 +45,7 @@ pub fn run(file: &str) -> anyhow::Result<()> {
     println!("  [PASS] ELF loaded and verified");
 
     let parity_program = program.clone();
+    let lockstep_program = program.clone();
     let mut executor = BpfExecutor::new(program);
     let storage = [0u8; STORAGE_SIZE];
 
@@ -83,8 +60,8 @@ pub fn run(file: &str) -> anyhow::Result<()> {
         anyhow::bail!("FAIL: Buy X returned zero output");
     }
     println!(
-        "  [PASS] Buy X: input_y=10.0 -> output_x={:.6}",
-        nano_to_f64(buy_output)
+        "  [PASS] Buy X: input_y=10.0 -> output_x={}",
+        fmt_f64(nano_to_f64(buy_output))
     );
 
     let sell_output = executor
@@ -94,8 +71,8 @@ pub fn run(file: &str) -> anyhow::Result<()> {
         anyhow::bail!("FAIL: Sell X returned zero output");
     }
     println!(
-        "  [PASS] Sell X: input_x=1.0 -> output_y={:.6}",
-        nano_to_f64(sell_output)
+        "  [PASS] Sell X: input_x=1.0 -> output_y={}",
+        fmt_f64(nano_to_f64(sell_output))
     );
 
     // Monotonicity check: larger input -> larger output
@@ -200,8 +177,8 @@ pub fn run(file: &str) -> anyhow::Result<()> {
     println!("  Checking randomized reserve/storage states...");
     for seed in 0..32u64 {
         let mut storage = [0u8; STORAGE_SIZE];
-        for i in 0..32usize {
-            storage[i] = (mix(seed.wrapping_add(i as u64)) & 0xFF) as u8;
+        for (i, byte) in storage.iter_mut().take(32).enumerate() {
+            *byte = (mix(seed.wrapping_add(i as u64)) & 0xFF) as u8;
         }
 
         let rx = 1_000_000_000u64 + (mix(seed ^ 0x0123_4567_89AB_CDEF) % 2_000_000_000_000u64);
@@ -221,6 +198,7 @@ pub fn run(file: &str) -> anyhow::Result<()> {
     println!("  [PASS] Randomized reserve/storage checks");
 
     run_native_bpf_parity_check(parity_program, &native_path)?;
+    run_storage_lockstep_check(lockstep_program, &native_path)?;
 
     println!("\nAll validation checks passed!");
     Ok(())
@@ -232,10 +210,11 @@ fn run_native_bpf_parity_check(program: BpfProgram, native_path: &Path) -> anyho
         PARITY_SIMS, PARITY_STEPS, PARITY_SEED_START, PARITY_SEED_STRIDE
     );
 
-    let submission_after_swap = load_native_submission(native_path)?;
+    let lib = loader::open_library(native_path)?;
+    let (submission_swap, submission_after_swap) = loader::install(loader::resolve_symbols(lib)?)?;
 
     let native = runner::run_default_batch_native_seeded(
-        dynamic_swap,
+        submission_swap,
         submission_after_swap,
         normalizer_swap,
         Some(normalizer_after_swap),
@@ -257,7 +236,12 @@ fn run_native_bpf_parity_check(program: BpfProgram, native_path: &Path) -> anyho
     )?;
 
     let total_delta = (native.total_edge - bpf.total_edge).abs();
-    let avg_delta = (native.avg_edge() - bpf.avg_edge()).abs();
+    // Both batches are PARITY_SIMS long, so neither average is missing.
+    let (native_avg, bpf_avg) = (
+        native.avg_edge().unwrap_or_default(),
+        bpf.avg_edge().unwrap_or_default(),
+    );
+    let avg_delta = (native_avg - bpf_avg).abs();
 
     println!(
         "    native_total={:.9} bpf_total={:.9} delta={:.9} tol={:.9}",
@@ -265,10 +249,7 @@ fn run_native_bpf_parity_check(program: BpfProgram, native_path: &Path) -> anyho
     );
     println!(
         "    native_avg={:.9} bpf_avg={:.9} delta={:.9} tol={:.9}",
-        native.avg_edge(),
-        bpf.avg_edge(),
-        avg_delta,
-        PARITY_ABS_TOL
+        native_avg, bpf_avg, avg_delta, PARITY_ABS_TOL
     );
 
     if total_delta > PARITY_ABS_TOL || avg_delta > PARITY_ABS_TOL {
@@ -284,44 +265,57 @@ fn run_native_bpf_parity_check(program: BpfProgram, native_path: &Path) -> anyho
     Ok(())
 }
 
-fn load_native_submission(native_path: &Path) -> anyhow::Result<Option<AfterSwapFn>> {
-    let lib = Box::new(
-        unsafe { libloading::Library::new(native_path) }.map_err(|e| {
-            anyhow::anyhow!(
-                "Failed to load native library {}: {}",
-                native_path.display(),
-                e
-            )
-        })?,
+/// Replay a few parity seeds' `after_swap` calls on both builds and diff storage after each.
+fn run_storage_lockstep_check(program: BpfProgram, native_path: &Path) -> anyhow::Result<()> {
+    let lib = loader::open_library(native_path)?;
+    let (submission_swap, submission_after_swap) = loader::install(loader::resolve_symbols(lib)?)?;
+    let Some(submission_after_swap) = submission_after_swap else {
+        println!("  [SKIP] Native/BPF storage lockstep (no after_swap)");
+        return Ok(());
+    };
+    println!(
+        "  Checking native/BPF storage after every after_swap ({} sims, {} steps)...",
+        LOCKSTEP_SIMS, PARITY_STEPS
     );
-    let lib = Box::leak(lib);
 
-    let swap_fn: libloading::Symbol<FfiSwapFn> = unsafe {
-        lib.get(compile::NATIVE_SWAP_SYMBOL)
-            .or_else(|_| lib.get(b"compute_swap_ffi"))
+    let mut calls = 0;
+    let configs = runner::seeded_configs(
+        LOCKSTEP_SIMS,
+        PARITY_STEPS,
+        PARITY_SEED_START,
+        PARITY_SEED_STRIDE,
+    );
+    for config in &configs {
+        let lockstep = audit::storage_lockstep(
+            program.clone(),
+            submission_swap,
+            submission_after_swap,
+            normalizer_swap,
+            Some(normalizer_after_swap),
+            config,
+        )?;
+        calls += lockstep.after_swap_calls;
+        if let Some(d) = lockstep.divergence {
+            anyhow::bail!(
+                "FAIL: Native/BPF storage diverged on seed {} at step {} (after_swap #{}): byte {} is {} native, {} BPF",
+                config.seed,
+                d.step,
+                d.call,
+                d.offset,
+                d.native,
+                d.bpf
+            );
+        }
     }
-    .map_err(|e| anyhow::anyhow!("Missing native swap symbol: {}", e))?;
-    LOADED_SWAP.store(*swap_fn as *mut (), Ordering::Relaxed);
-
-    let has_after_swap = if let Ok(after_fn) = unsafe {
-        lib.get::<FfiAfterSwapFn>(compile::NATIVE_AFTER_SWAP_SYMBOL)
-            .or_else(|_| lib.get::<FfiAfterSwapFn>(b"after_swap_ffi"))
-    } {
-        LOADED_AFTER_SWAP.store(*after_fn as *mut (), Ordering::Relaxed);
-        true
-    } else {
-        false
-    };
-
-    Ok(if has_after_swap {
-        Some(dynamic_after_swap)
-    } else {
-        None
-    })
+    println!(
+        "  [PASS] Native/BPF storage lockstep ({} after_swap calls)",
+        calls
+    );
+    Ok(())
 }
 
 #[inline]
-fn mix(mut z: u64) -> u64 {
+pub(crate) fn mix(mut z: u64) -> u64 {
     z ^= z >> 30;
     z = z.wrapping_mul(0xbf58_476d_1ce4_e5b9);
     z ^= z >> 27;
@@ -356,10 +350,8 @@ fn validate_submission_metadata(file: &str) -> anyhow::Result<SubmissionMetadata
                         .context("`MODEL_USED` must be a string literal constant")?;
                 }
             }
-            Item::Fn(item_fn) => {
-                if item_fn.sig.ident == "get_model_used" {
-                    has_get_model_used = true;
-                }
+            Item::Fn(item_fn) if item_fn.sig.ident == "get_model_used" => {
+                has_get_model_used = true;
             }
             _ => {}
         }
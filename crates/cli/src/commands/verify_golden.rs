@@ -0,0 +1,300 @@
+use std::path::Path;
+
+use clap::Args;
+use prop_amm_shared::config::SimulationConfig;
+use prop_amm_shared::normalizer::{after_swap as normalizer_after_swap, compute_swap};
+use prop_amm_shared::result::{Phase, PhaseSplit};
+use prop_amm_sim::engine::Simulation;
+use prop_amm_sim::{opponents, runner};
+use serde::{Deserialize, Serialize};
+
+use crate::output::fmt_f64;
+
+const TRACE: &str = "starter vs normalizer";
+const TRACE_SEED: u64 = 23;
+const TRACE_STEPS: u32 = 300;
+
+#[derive(Args)]
+pub struct VerifyGoldenArgs {
+    /// Golden trace JSON to compare against
+    #[arg(default_value = "crates/cli/tests/fixtures/golden_trace.json")]
+    pub golden: String,
+    /// Overwrite the golden trace with the current engine's instead of comparing
+    #[arg(long)]
+    pub update: bool,
+    /// With --update, leave this many leading steps unscored (replays use the golden's own)
+    #[arg(long, default_value = "0", requires = "update")]
+    pub warmup_steps: u32,
+}
+
+/// State after one step. Everything is compared exactly: any change in the engine's
+/// arithmetic shows up at the step where it first happens.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceStep {
+    pub step: u32,
+    pub phase: Phase,
+    pub fair_price: f64,
+    pub submission_x: f64,
+    pub submission_y: f64,
+    pub normalizer_x: f64,
+    pub normalizer_y: f64,
+    pub submission_edge: f64,
+}
+
+/// Every step of one starter-vs-normalizer simulation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GoldenTrace {
+    pub trace: String,
+    pub seed: u64,
+    pub steps: u32,
+    pub warmup_steps: u32,
+    pub rows: Vec<TraceStep>,
+    /// Steps and edge of the warmup and the measured part of the run.
+    pub phases: PhaseSplit,
+}
+
+/// Run the traced simulation: seed `TRACE_SEED`, `TRACE_STEPS` steps, the first
+/// `warmup_steps` of them unscored.
+pub fn record(warmup_steps: u32) -> GoldenTrace {
+    let starter = opponents::find("starter").expect("starter is a built-in opponent");
+    let config = &SimulationConfig {
+        warmup_steps,
+        ..runner::seeded_configs(1, TRACE_STEPS, TRACE_SEED, 1).remove(0)
+    };
+    let mut sim = Simulation::native(
+        starter.swap,
+        starter.after_swap,
+        compute_swap,
+        Some(normalizer_after_swap),
+        config,
+    );
+    let mut rows = Vec::with_capacity(TRACE_STEPS as usize);
+    while sim.step() {
+        let (submission, normalizer) = (sim.submission(), sim.normalizer());
+        let step = sim.current_step() - 1;
+        rows.push(TraceStep {
+            step,
+            phase: config.phase(step),
+            fair_price: sim.fair_price(),
+            submission_x: submission.reserve_x,
+            submission_y: submission.reserve_y,
+            normalizer_x: normalizer.reserve_x,
+            normalizer_y: normalizer.reserve_y,
+            submission_edge: sim.submission_edge(),
+        });
+    }
+    GoldenTrace {
+        trace: TRACE.to_string(),
+        seed: TRACE_SEED,
+        steps: TRACE_STEPS,
+        warmup_steps,
+        rows,
+        phases: sim.phases(),
+    }
+}
+
+/// The first step where `actual` differs from `expected`, described field by field.
+fn first_divergence(expected: &GoldenTrace, actual: &GoldenTrace) -> Option<String> {
+    if expected.trace != actual.trace
+        || expected.seed != actual.seed
+        || expected.steps != actual.steps
+        || expected.warmup_steps != actual.warmup_steps
+    {
+        return Some(format!(
+            "trace mismatch: golden is `{}` (seed {}, {} steps, {} warmup), current is `{}` (seed {}, {} steps, {} warmup)",
+            expected.trace,
+            expected.seed,
+            expected.steps,
+            expected.warmup_steps,
+            actual.trace,
+            actual.seed,
+            actual.steps,
+            actual.warmup_steps
+        ));
+    }
+    for (want, got) in expected.rows.iter().zip(&actual.rows) {
+        if want == got {
+            continue;
+        }
+        let fields = [
+            ("fair_price", want.fair_price, got.fair_price),
+            ("submission_x", want.submission_x, got.submission_x),
+            ("submission_y", want.submission_y, got.submission_y),
+            ("normalizer_x", want.normalizer_x, got.normalizer_x),
+            ("normalizer_y", want.normalizer_y, got.normalizer_y),
+            ("submission_edge", want.submission_edge, got.submission_edge),
+        ];
+        let mut diffs: Vec<String> = fields
+            .iter()
+            .filter(|(_, want, got)| want.to_bits() != got.to_bits())
+            .map(|(name, want, got)| format!("{} {} -> {} ({:e})", name, want, got, got - want))
+            .collect();
+        if want.phase != got.phase {
+            diffs.push(format!(
+                "phase {} -> {}",
+                want.phase.as_str(),
+                got.phase.as_str()
+            ));
+        }
+        return Some(format!(
+            "step {} diverged: {}",
+            want.step,
+            if diffs.is_empty() {
+                format!("step index {} -> {}", want.step, got.step)
+            } else {
+                diffs.join(", ")
+            }
+        ));
+    }
+    if expected.rows.len() != actual.rows.len() {
+        return Some(format!(
+            "golden has {} steps, current run has {}",
+            expected.rows.len(),
+            actual.rows.len()
+        ));
+    }
+    let (want, got) = (&expected.phases, &actual.phases);
+    [
+        (Phase::Warmup, want.warmup, got.warmup),
+        (Phase::Measured, want.measured, got.measured),
+    ]
+    .into_iter()
+    .find(|(_, want, got)| want.steps != got.steps || want.edge.to_bits() != got.edge.to_bits())
+    .map(|(phase, want, got)| {
+        format!(
+            "{} totals diverged: {} steps, edge {} -> {} steps, edge {}",
+            phase.as_str(),
+            want.steps,
+            want.edge,
+            got.steps,
+            got.edge
+        )
+    })
+}
+
+fn load(path: &Path) -> anyhow::Result<GoldenTrace> {
+    serde_json::from_str(&std::fs::read_to_string(path)?)
+        .map_err(|e| anyhow::anyhow!("{}: invalid golden trace: {}", path.display(), e))
+}
+
+/// Re-record the trace with the golden's own warmup and compare it against the golden at
+/// `path`.
+pub fn verify(path: &Path) -> anyhow::Result<()> {
+    let expected = load(path)?;
+    if let Some(divergence) = first_divergence(&expected, &record(expected.warmup_steps)) {
+        anyhow::bail!("{} no longer reproduces: {}", path.display(), divergence);
+    }
+    Ok(())
+}
+
+pub fn run(args: VerifyGoldenArgs) -> anyhow::Result<()> {
+    let path = Path::new(&args.golden);
+    if args.update {
+        let trace = record(args.warmup_steps);
+        std::fs::write(path, serde_json::to_string_pretty(&trace)? + "\n")?;
+        println!(
+            "Wrote {} steps of {} (seed {}, {} warmup) to {}; measured edge {}",
+            trace.rows.len(),
+            TRACE,
+            TRACE_SEED,
+            trace.warmup_steps,
+            path.display(),
+            fmt_f64(trace.phases.measured.edge)
+        );
+        return Ok(());
+    }
+
+    let warmup_steps = load(path)?.warmup_steps;
+    println!(
+        "Replaying {} (seed {}, {} steps, {} warmup) against {}...",
+        TRACE,
+        TRACE_SEED,
+        TRACE_STEPS,
+        warmup_steps,
+        path.display()
+    );
+    verify(path)?;
+    println!("Golden trace reproduced exactly at every step.");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{first_divergence, load, record, verify, Phase};
+
+    fn fixture(name: &str) -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name)
+    }
+
+    #[test]
+    fn committed_golden_matches_current_engine() {
+        verify(&fixture("golden_trace.json")).unwrap();
+    }
+
+    #[test]
+    fn committed_warmup_golden_matches_current_engine() {
+        let path = fixture("golden_trace_warmup.json");
+        assert_eq!(load(&path).unwrap().warmup_steps, 50);
+        verify(&path).unwrap();
+    }
+
+    #[test]
+    fn divergence_is_reported_at_its_first_step() {
+        let expected = record(0);
+        assert_eq!(first_divergence(&expected, &expected.clone()), None);
+
+        let mut actual = expected.clone();
+        actual.rows[57].normalizer_y += 1e-9;
+        actual.rows[120].fair_price *= 2.0;
+        let divergence = first_divergence(&expected, &actual).unwrap();
+        assert!(
+            divergence.starts_with("step 57 diverged: normalizer_y"),
+            "{divergence}"
+        );
+
+        actual.rows.truncate(57);
+        assert_eq!(
+            first_divergence(&expected, &actual).unwrap(),
+            "golden has 300 steps, current run has 57"
+        );
+    }
+
+    #[test]
+    fn warmup_rows_are_tagged_and_their_totals_checked() {
+        let expected = record(50);
+        let warmup = expected
+            .rows
+            .iter()
+            .take_while(|r| r.phase == Phase::Warmup)
+            .count();
+        assert_eq!(warmup, 50);
+        assert!(expected.rows[warmup..]
+            .iter()
+            .all(|r| r.phase == Phase::Measured));
+        assert_eq!(expected.phases.warmup.steps, 50);
+        // Warmup fills are not scored.
+        assert_eq!(expected.rows[warmup - 1].submission_edge, 0.0);
+
+        let mut actual = expected.clone();
+        actual.rows[10].phase = Phase::Measured;
+        let divergence = first_divergence(&expected, &actual).unwrap();
+        assert_eq!(divergence, "step 10 diverged: phase warmup -> measured");
+
+        let mut actual = expected.clone();
+        actual.phases.warmup.edge += 1.0;
+        let divergence = first_divergence(&expected, &actual).unwrap();
+        assert!(
+            divergence.starts_with("warmup totals diverged"),
+            "{divergence}"
+        );
+
+        let plain = record(0);
+        assert!(first_divergence(&plain, &expected)
+            .unwrap()
+            .starts_with("trace mismatch"));
+    }
+}
@@ -0,0 +1,411 @@
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::io::BufRead;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+
+use clap::Args;
+use prop_amm_shared::normalizer::{after_swap as normalizer_after_swap, compute_swap};
+use prop_amm_shared::result::SimResult;
+use prop_amm_sim::engine::Simulation;
+use prop_amm_sim::objective::{MarketCtx, Objective, StepRecord, TradeSource};
+use prop_amm_sim::runner;
+
+use super::{compile, loader};
+use crate::output::{fmt_f64, fmt_signed_f64};
+
+/// Steps simulated between redraws.
+const REDRAW_STEPS: u32 = 250;
+/// Worst fills kept on the board.
+const WORST_TRADES: usize = 5;
+/// Bars in the edge histogram.
+const HISTOGRAM_BINS: usize = 10;
+/// Seed distance between the runs before and after a reseed.
+const RESEED_JUMP: u64 = 1_000_000;
+/// Size of the probe quotes behind the spread, as a fraction of the input reserve.
+const SPREAD_PROBE: f64 = 1e-4;
+
+#[derive(Args)]
+pub struct WatchArgs {
+    /// Path to the .rs source file
+    pub file: String,
+    /// Number of steps per simulation
+    #[arg(long, default_value = "10000")]
+    pub steps: u32,
+    /// Seed of the first simulation; later ones count up from it
+    #[arg(long, default_value = "0")]
+    pub seed_start: u64,
+    /// Finished simulations the edge distribution is taken over
+    #[arg(long, default_value = "200")]
+    pub window: usize,
+}
+
+/// One fill against the strategy, kept when it is among the worst seen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorstTrade {
+    pub seed: u64,
+    pub step: u32,
+    pub source: TradeSource,
+    pub edge: f64,
+}
+
+/// Everything the dashboard shows, fed step by step (as an `Objective`) and result by
+/// result, so it can be tested without a terminal.
+pub struct Dashboard {
+    window: usize,
+    /// Edges of the last `window` finished simulations, oldest first.
+    edges: VecDeque<f64>,
+    finished: u64,
+    seed: u64,
+    step: u32,
+    fair_price: f64,
+    reserves: (f64, f64),
+    spread_bps: Option<f64>,
+    /// Most negative fills since the last reseed, worst first.
+    worst: Vec<WorstTrade>,
+    paused: bool,
+}
+
+impl Dashboard {
+    pub fn new(seed: u64, window: usize) -> Self {
+        Self {
+            window: window.max(1),
+            edges: VecDeque::new(),
+            finished: 0,
+            seed,
+            step: 0,
+            fair_price: 0.0,
+            reserves: (0.0, 0.0),
+            spread_bps: None,
+            worst: Vec::new(),
+            paused: false,
+        }
+    }
+
+    /// A simulation of `seed` is starting.
+    pub fn start(&mut self, seed: u64) {
+        self.seed = seed;
+        self.step = 0;
+    }
+
+    /// A simulation finished: its edge joins the rolling distribution.
+    pub fn record(&mut self, result: &SimResult) {
+        if self.edges.len() == self.window {
+            self.edges.pop_front();
+        }
+        self.edges.push_back(result.submission_edge);
+        self.finished += 1;
+    }
+
+    pub fn set_spread_bps(&mut self, spread_bps: Option<f64>) {
+        self.spread_bps = spread_bps;
+    }
+
+    /// Forget every finished simulation and trade, e.g. after a reseed.
+    pub fn clear(&mut self) {
+        self.edges.clear();
+        self.worst.clear();
+        self.finished = 0;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn mean_edge(&self) -> Option<f64> {
+        (!self.edges.is_empty()).then(|| self.edges.iter().sum::<f64>() / self.edges.len() as f64)
+    }
+
+    /// `HISTOGRAM_BINS` equal-width bins spanning the window's edges, as `(low, count)`.
+    pub fn histogram(&self) -> Vec<(f64, usize)> {
+        let Some(lo) = self.edges.iter().copied().reduce(f64::min) else {
+            return Vec::new();
+        };
+        let hi = self.edges.iter().copied().fold(lo, f64::max);
+        let width = (hi - lo) / HISTOGRAM_BINS as f64;
+        let mut counts = vec![0; HISTOGRAM_BINS];
+        for edge in &self.edges {
+            let bin = if width > 0.0 {
+                (((edge - lo) / width) as usize).min(HISTOGRAM_BINS - 1)
+            } else {
+                0
+            };
+            counts[bin] += 1;
+        }
+        counts
+            .into_iter()
+            .enumerate()
+            .map(|(i, count)| (lo + i as f64 * width, count))
+            .collect()
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "seed {}  step {}  finished {}{}",
+            self.seed,
+            self.step,
+            self.finished,
+            if self.paused { "  [PAUSED]" } else { "" }
+        );
+        let _ = writeln!(
+            out,
+            "fair price {}  reserves X {} / Y {}  spread {} bps",
+            fmt_f64(self.fair_price),
+            fmt_f64(self.reserves.0),
+            fmt_f64(self.reserves.1),
+            self.spread_bps.map_or_else(|| "-".to_string(), fmt_f64)
+        );
+        match self.mean_edge() {
+            Some(mean) => {
+                let _ = writeln!(
+                    out,
+                    "\nedge over last {} sims: mean {}",
+                    self.edges.len(),
+                    fmt_f64(mean)
+                );
+            }
+            None => out.push_str("\nno finished simulations yet\n"),
+        }
+        let histogram = self.histogram();
+        let tallest = histogram.iter().map(|&(_, n)| n).max().unwrap_or(0).max(1);
+        for (low, count) in histogram {
+            let _ = writeln!(
+                out,
+                "  {:>14} | {:<40} {}",
+                fmt_f64(low),
+                "#".repeat(count * 40 / tallest),
+                count
+            );
+        }
+        out.push_str("\nworst fills:\n");
+        for trade in &self.worst {
+            let _ = writeln!(
+                out,
+                "  seed {} step {} {:?}: {}",
+                trade.seed,
+                trade.step,
+                trade.source,
+                fmt_signed_f64(trade.edge)
+            );
+        }
+        out.push_str("\n[p]ause  [r]eseed  [q]uit, then Enter\n");
+        out
+    }
+}
+
+impl Objective for Dashboard {
+    fn accumulate(&mut self, step: &StepRecord, ctx: &MarketCtx) {
+        self.step = step.step + 1;
+        self.fair_price = ctx.fair_price;
+        self.reserves = (ctx.submission_reserve_x, ctx.submission_reserve_y);
+        for trade in step.trades {
+            let worst_kept = self.worst.last().map_or(f64::INFINITY, |t| t.edge);
+            if self.worst.len() < WORST_TRADES || trade.edge < worst_kept {
+                self.worst.push(WorstTrade {
+                    seed: self.seed,
+                    step: step.step,
+                    source: trade.source,
+                    edge: trade.edge,
+                });
+                self.worst.sort_by(|a, b| a.edge.total_cmp(&b.edge));
+                self.worst.truncate(WORST_TRADES);
+            }
+        }
+    }
+
+    fn finalize(&self) -> f64 {
+        self.mean_edge().unwrap_or(0.0)
+    }
+}
+
+enum Key {
+    Pause,
+    Reseed,
+    Quit,
+}
+
+/// Commands typed on stdin, one per line; raw keypresses would need a terminal backend.
+fn spawn_key_reader() -> Receiver<Key> {
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let key = match line.as_deref().map(str::trim) {
+                Ok("p") => Key::Pause,
+                Ok("r") => Key::Reseed,
+                Ok("q") | Err(_) => Key::Quit,
+                Ok(_) => continue,
+            };
+            let quit = matches!(key, Key::Quit);
+            if tx.send(key).is_err() || quit {
+                return;
+            }
+        }
+        let _ = tx.send(Key::Quit);
+    });
+    rx
+}
+
+fn redraw(dashboard: &Dashboard, strategy: &str) {
+    // Clear the screen and home the cursor.
+    print!("\x1b[2J\x1b[H{}\n\n{}", strategy, dashboard.render());
+}
+
+pub fn run(args: WatchArgs) -> anyhow::Result<()> {
+    println!("Compiling {} (native)...", args.file);
+    let native_path = compile::compile_native(&args.file)?;
+    let lib = loader::open_library(&native_path)?;
+    let (swap_fn, after_swap_fn) = loader::install(loader::resolve_symbols(lib)?)?;
+    let strategy = loader::read_metadata(lib).title(&args.file);
+
+    let keys = spawn_key_reader();
+    let mut dashboard = Dashboard::new(args.seed_start, args.window);
+    let mut next_seed = args.seed_start;
+    'runs: loop {
+        let config = &runner::seeded_configs(1, args.steps, next_seed, 1)[0];
+        let mut sim = Simulation::native(
+            swap_fn,
+            after_swap_fn,
+            compute_swap,
+            Some(normalizer_after_swap),
+            config,
+        );
+        // Routing quotes the pool, then fills the same order at the same reserves.
+        sim.submission_mut().cache_quotes();
+        dashboard.start(next_seed);
+        next_seed = next_seed.wrapping_add(1);
+        loop {
+            loop {
+                match keys.try_recv() {
+                    Ok(Key::Pause) => dashboard.toggle_pause(),
+                    Ok(Key::Reseed) => {
+                        next_seed = next_seed.wrapping_add(RESEED_JUMP);
+                        dashboard.clear();
+                        continue 'runs;
+                    }
+                    Ok(Key::Quit) | Err(TryRecvError::Disconnected) => break 'runs,
+                    Err(TryRecvError::Empty) => break,
+                }
+            }
+            if dashboard.paused() {
+                redraw(&dashboard, &strategy);
+                std::thread::sleep(std::time::Duration::from_millis(100));
+                continue;
+            }
+            let mut stepped = 0;
+            while stepped < REDRAW_STEPS && sim.step_with(&mut dashboard) {
+                stepped += 1;
+            }
+            dashboard.set_spread_bps(sim.submission_mut().quoted_spread_bps(SPREAD_PROBE));
+            redraw(&dashboard, &strategy);
+            if sim.is_finished() {
+                break;
+            }
+        }
+        let result = sim.run_with(&mut dashboard)?;
+        dashboard.record(&result);
+    }
+    println!();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use prop_amm_shared::result::SimResult;
+    use prop_amm_sim::objective::{MarketCtx, Objective, StepRecord, SubmissionTrade, TradeSource};
+
+    use super::{Dashboard, WORST_TRADES};
+
+    fn result(edge: f64) -> SimResult {
+        SimResult {
+            seed: 0,
+            steps_run: 10,
+            submission_edge: edge,
+            phases: Default::default(),
+            k_growth: 0.0,
+            impermanent_loss: 0.0,
+            k_series: None,
+            waterfall: Default::default(),
+            disqualifications: Vec::new(),
+            total_quote_slippage: 0.0,
+            max_quote_slippage: 0.0,
+            inventory_rejected: 0.0,
+            storage_never_written: false,
+            recording_capped: false,
+            recording_bytes: 0,
+            initial_value: 20_000.0,
+            lp_return: Default::default(),
+            retail_volume: [0.0; 2],
+        }
+    }
+
+    fn fill(edge: f64) -> SubmissionTrade {
+        SubmissionTrade {
+            source: TradeSource::Retail,
+            amm_buys_x: true,
+            amount_x: 1.0,
+            amount_y: 100.0,
+            edge,
+        }
+    }
+
+    #[test]
+    fn dashboard_tracks_a_stream_of_steps_and_results() {
+        let mut board = Dashboard::new(7, 3);
+        board.start(7);
+        let ctx = MarketCtx {
+            fair_price: 101.0,
+            submission_reserve_x: 99.0,
+            submission_reserve_y: 10_050.0,
+            normalizer_reserve_x: 100.0,
+            normalizer_reserve_y: 10_000.0,
+        };
+        for step in 0..4u32 {
+            let trades: Vec<_> = (0..3)
+                .map(|i| fill(-(step as f64) - i as f64 * 0.1))
+                .collect();
+            board.accumulate(
+                &StepRecord {
+                    step,
+                    trades: &trades,
+                },
+                &ctx,
+            );
+        }
+        let worst = &board.worst;
+        assert_eq!(worst.len(), WORST_TRADES);
+        assert!(worst.windows(2).all(|w| w[0].edge <= w[1].edge));
+        assert_eq!((worst[0].seed, worst[0].step, worst[0].edge), (7, 3, -3.2));
+
+        for edge in [1.0, 2.0, 3.0, 10.0] {
+            board.record(&result(edge));
+        }
+        // The window keeps the last three.
+        assert_eq!(
+            board.edges.iter().copied().collect::<Vec<_>>(),
+            [2.0, 3.0, 10.0]
+        );
+        assert_eq!(board.mean_edge(), Some(5.0));
+        let histogram = board.histogram();
+        assert_eq!(histogram.iter().map(|&(_, n)| n).sum::<usize>(), 3);
+        assert_eq!(histogram[0], (2.0, 1));
+        assert_eq!(histogram.last().unwrap().1, 1);
+
+        let text = board.render();
+        assert!(text.contains("seed 7  step 4  finished 4"), "{text}");
+        assert!(
+            text.contains("reserves X 99.000000 / Y 10050.000000"),
+            "{text}"
+        );
+
+        board.toggle_pause();
+        assert!(board.render().contains("[PAUSED]"));
+        board.clear();
+        assert_eq!(board.mean_edge(), None);
+        assert!(board.worst.is_empty() && board.histogram().is_empty());
+    }
+}
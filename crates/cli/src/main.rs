@@ -1,5 +1,6 @@
 mod commands;
 mod output;
+mod stamp;
 
 use clap::{Parser, Subcommand};
 
@@ -23,32 +24,43 @@ enum Commands {
         file: String,
     },
     /// Run simulation batch
-    Run {
-        /// Path to the .rs source file
-        file: String,
-        /// Number of simulations
-        #[arg(long, default_value = "1000")]
-        simulations: u32,
-        /// Number of steps per simulation
-        #[arg(long, default_value = "10000")]
-        steps: u32,
-        /// Number of parallel workers (0 = auto)
-        #[arg(long, default_value = "0")]
-        workers: usize,
-        /// Starting seed for simulation config generation
-        #[arg(long, default_value = "0")]
-        seed_start: u64,
-        /// Seed step between simulations
-        #[arg(long, default_value = "1")]
-        seed_stride: u64,
-        /// Use BPF runtime instead of native (slower, for validation)
-        #[arg(long)]
-        bpf: bool,
-        /// Path to a prebuilt BPF .so to use when running with --bpf (skips compilation).
-        /// Useful on machines without the Solana SBF toolchain installed.
-        #[arg(long)]
-        bpf_so: Option<String>,
-    },
+    #[command(visible_alias = "score")]
+    Run(commands::run::RunArgs),
+    /// Build (skipped when the source is unchanged) and run in one step
+    Bench(commands::run::RunArgs),
+    /// Export a strategy's quote curve as CSV for plotting
+    Curve(commands::curve::CurveArgs),
+    /// Profile swap cost (native ns or BPF CU) across a grid of input buckets
+    ProfileInputs(commands::profile_inputs::ProfileInputsArgs),
+    /// Rank a strategy's edge against every built-in reference strategy
+    Tournament(commands::tournament::TournamentArgs),
+    /// Replay a fixed simulation step by step and report where it departs from the golden trace
+    VerifyGolden(commands::verify_golden::VerifyGoldenArgs),
+    /// Time a fixed starter-vs-normalizer workload and print engine throughput as JSON
+    BenchEngine(commands::bench_engine::BenchEngineArgs),
+    /// Record or check the edges of a fixed starter-vs-normalizer battery
+    Baseline(commands::baseline::BaselineArgs),
+    /// Narrate the most impactful steps of a single simulation
+    Explain(commands::explain::ExplainArgs),
+    /// Break a strategy's edge over the normalizer down by trade size, side and volatility
+    Attribute(commands::attribute::AttributeArgs),
+    /// Replay two versions of a strategy through identical flow and report where they diverge
+    DiffVersions(commands::diff_versions::DiffVersionsArgs),
+    /// Check that compute_swap returns the same output for the same input on every call
+    CheckPurity(commands::check_purity::CheckPurityArgs),
+    /// Report which hooks and metadata a strategy exports, without simulating
+    Inspect(commands::inspect::InspectArgs),
+    /// Run a batch for every combination of the given config parameter values
+    Sweep(commands::sweep::SweepArgs),
+    /// Print a strategy's storage after every step of one seed, decoded by its STORAGE_SCHEMA
+    StorageTrace(commands::storage_trace::StorageTraceArgs),
+    /// Live terminal dashboard of a strategy's rolling edge, reserves, spread and worst fills
+    Watch(commands::watch::WatchArgs),
+    /// Race a strategy and the built-in ones through a volatile market; report steps to ruin
+    Stress(commands::stress::StressArgs),
+    /// Play every strategy in a directory against every other and the normalizer
+    #[command(name = "roundrobin")]
+    RoundRobin(commands::roundrobin::RoundRobinArgs),
 }
 
 fn main() -> anyhow::Result<()> {
@@ -57,24 +69,38 @@ fn main() -> anyhow::Result<()> {
     match cli.command {
         Commands::Build { file } => commands::build::run(&file),
         Commands::Validate { file } => commands::validate::run(&file),
-        Commands::Run {
-            file,
-            simulations,
-            steps,
-            workers,
-            seed_start,
-            seed_stride,
-            bpf,
-            bpf_so,
-        } => commands::run::run(
-            &file,
-            simulations,
-            steps,
-            workers,
-            seed_start,
-            seed_stride,
-            bpf,
-            bpf_so.as_deref(),
-        ),
+        Commands::Run(args) => commands::run::run(args),
+        Commands::Bench(args) => commands::bench::run(args),
+        Commands::Curve(args) => commands::curve::run(args),
+        Commands::ProfileInputs(args) => commands::profile_inputs::run(args),
+        Commands::Tournament(args) => commands::tournament::run(args),
+        Commands::VerifyGolden(args) => commands::verify_golden::run(args),
+        Commands::BenchEngine(args) => commands::bench_engine::run(args),
+        Commands::Baseline(args) => commands::baseline::run(args),
+        Commands::Explain(args) => commands::explain::run(args),
+        Commands::Attribute(args) => commands::attribute::run(args),
+        Commands::DiffVersions(args) => commands::diff_versions::run(args),
+        Commands::CheckPurity(args) => commands::check_purity::run(args),
+        Commands::Inspect(args) => commands::inspect::run(args),
+        Commands::Sweep(args) => commands::sweep::run(args),
+        Commands::StorageTrace(args) => commands::storage_trace::run(args),
+        Commands::Watch(args) => commands::watch::run(args),
+        Commands::Stress(args) => commands::stress::run(args),
+        Commands::RoundRobin(args) => commands::roundrobin::run(args),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cli, Commands};
+    use clap::Parser;
+
+    #[test]
+    fn score_is_run_under_another_name() {
+        let cli = Cli::parse_from(["prop-amm", "score", "my_amm.rs", "--min-p5-edge", "-50"]);
+        let Commands::Run(args) = cli.command else {
+            panic!("score should parse as run");
+        };
+        assert_eq!(args.min_p5_edge, Some(-50.0));
     }
 }
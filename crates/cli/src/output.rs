@@ -1,13 +1,116 @@
-use prop_amm_shared::result::BatchResult;
+use clap::ValueEnum;
+use prop_amm_shared::config::SimulationConfig;
+use prop_amm_shared::nano::NANO_SCALE_F64;
+use prop_amm_shared::result::{BatchResult, LpReturn, SimResult};
 use std::time::Duration;
 
+use crate::stamp::RunStamp;
+
+/// Decimal places of every simulated quantity the CLI prints.
+pub const DECIMALS: usize = 6;
+
+/// Canonical text for a float in text and CSV output: exactly `DECIMALS` places, with a
+/// value that rounds to zero always printed unsigned, so the same run is byte-identical
+/// across platforms and diffs only show real changes.
+pub fn fmt_f64(value: f64) -> String {
+    let text = format!("{:.*}", DECIMALS, value);
+    match text.strip_prefix('-') {
+        Some(magnitude) if magnitude.bytes().all(|b| matches!(b, b'0' | b'.')) => {
+            magnitude.to_string()
+        }
+        _ => text,
+    }
+}
+
+/// `fmt_f64`, or `-` for a statistic an empty batch doesn't have.
+pub fn fmt_stat(value: Option<f64>) -> String {
+    value.map_or_else(|| "-".to_string(), fmt_f64)
+}
+
+/// `fmt_f64` with an explicit `+` on positive values.
+pub fn fmt_signed_f64(value: f64) -> String {
+    let text = fmt_f64(value);
+    if text.starts_with('-') || value.is_nan() {
+        text
+    } else {
+        format!("+{}", text)
+    }
+}
+
+/// Unit the run report states edge (and every other Y amount) in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum EdgeUnit {
+    /// Nano units of Y, the integer unit strategies quote in
+    Nano,
+    /// Whole units of Y, the token the simulation keeps its books in
+    #[default]
+    Token,
+    /// Basis points of the submission's initial pool value, comparable across pool sizes
+    Bps,
+}
+
+impl EdgeUnit {
+    pub fn label(self) -> &'static str {
+        match self {
+            EdgeUnit::Nano => "nano (1e-9 Y)",
+            EdgeUnit::Token => "token (Y)",
+            EdgeUnit::Bps => "bps of initial pool value",
+        }
+    }
+
+    /// `result` with every Y amount expressed in this unit; bps are taken per simulation,
+    /// against its own initial pool value.
+    pub fn convert(self, result: &BatchResult) -> BatchResult {
+        BatchResult::from_results(
+            result
+                .results
+                .iter()
+                .map(|r| {
+                    let factor = self.factor(r);
+                    SimResult {
+                        submission_edge: r.submission_edge * factor,
+                        phases: r.phases.scaled(factor),
+                        impermanent_loss: r.impermanent_loss * factor,
+                        waterfall: r.waterfall.scaled(factor),
+                        total_quote_slippage: r.total_quote_slippage * factor,
+                        max_quote_slippage: r.max_quote_slippage * factor,
+                        inventory_rejected: r.inventory_rejected * factor,
+                        lp_return: LpReturn {
+                            fee_revenue: r.lp_return.fee_revenue * factor,
+                            divergence: r.lp_return.divergence * factor,
+                            price_move: r.lp_return.price_move * factor,
+                            ..r.lp_return
+                        },
+                        ..r.clone()
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    fn factor(self, result: &SimResult) -> f64 {
+        match self {
+            EdgeUnit::Nano => NANO_SCALE_F64,
+            EdgeUnit::Token => 1.0,
+            EdgeUnit::Bps if result.initial_value > 0.0 => 10_000.0 / result.initial_value,
+            EdgeUnit::Bps => 0.0,
+        }
+    }
+}
+
 pub struct RunTimings {
     pub compile_or_load: Duration,
     pub simulation: Duration,
     pub total: Duration,
 }
 
-pub fn print_results(result: &BatchResult, timings: RunTimings) {
+pub fn print_results(
+    result: &BatchResult,
+    strategy: &str,
+    stamp: &RunStamp,
+    timings: RunTimings,
+    unit: EdgeUnit,
+) {
     let seed_range = result
         .results
         .iter()
@@ -18,6 +121,8 @@ pub fn print_results(result: &BatchResult, timings: RunTimings) {
         });
 
     println!("\n========================================");
+    println!("  Strategy:    {}", strategy);
+    print!("{}", stamp.lines());
     println!("  Simulations: {}", result.n_sims());
     if let Some((seed_start, seed_end)) = seed_range {
         println!("  Seed range:  {}..={}", seed_start, seed_end);
@@ -28,37 +133,298 @@ pub fn print_results(result: &BatchResult, timings: RunTimings) {
     );
     println!("  Simulation:  {:>8.2}s", timings.simulation.as_secs_f64());
     println!("  Total:       {:>8.2}s", timings.total.as_secs_f64());
-    println!("  Avg edge:    {:.2}", result.avg_edge());
-    println!("  Total edge:  {:.2}", result.total_edge);
+    if result.recording_bytes() > 0 {
+        println!(
+            "  Recording:   {:>8.2} MB",
+            result.recording_bytes() as f64 / 1e6
+        );
+    }
+    println!("  Units:       {}", unit.label());
+    print!("{}", edge_summary(&unit.convert(result)));
+    if let Some(share) = result.retail_share() {
+        println!("  Retail share:{:>8.1}%", share * 100.0);
+    }
+    if let Some(warmup) = warmup_summary(&unit.convert(result)) {
+        println!("  Warmup:      {}", warmup);
+    }
     println!("========================================");
+    if result.results.iter().any(|r| r.storage_never_written) {
+        println!(
+            "\nWarning: after_swap present but storage never modified — is your state update wired up?"
+        );
+    }
+    if let Some(warning) = recording_warning(result) {
+        println!("\n{}", warning);
+    }
 
     if let Some(stats) = prop_amm_sim::search_stats::snapshot_if_enabled() {
         let arb_calls = stats.arb_golden_calls.max(1);
         let router_calls = stats.router_calls.max(1);
         println!("\nSearch stats (PROP_AMM_SEARCH_STATS=1):");
         println!(
-            "  Arb golden:  calls={} iters={} (avg {:.2}/call) evals={} (avg {:.2}/call) early_stop_amount_tol={}",
+            "  Arb golden:  calls={} iters={} (avg {}/call) evals={} (avg {}/call) early_stop_amount_tol={}",
             stats.arb_golden_calls,
             stats.arb_golden_iters,
-            stats.arb_golden_iters as f64 / arb_calls as f64,
+            fmt_f64(stats.arb_golden_iters as f64 / arb_calls as f64),
             stats.arb_golden_evals,
-            stats.arb_golden_evals as f64 / arb_calls as f64,
+            fmt_f64(stats.arb_golden_evals as f64 / arb_calls as f64),
             stats.arb_early_stop_amount_tol,
         );
         println!(
-            "  Arb bracket: calls={} evals={} (avg {:.2}/call)",
+            "  Arb bracket: calls={} evals={} (avg {}/call)",
             stats.arb_bracket_calls,
             stats.arb_bracket_evals,
-            stats.arb_bracket_evals as f64 / stats.arb_bracket_calls.max(1) as f64,
+            fmt_f64(stats.arb_bracket_evals as f64 / stats.arb_bracket_calls.max(1) as f64),
         );
         println!(
-            "  Router:     calls={} iters={} (avg {:.2}/call) evals={} (avg {:.2}/call) early_stop_rel_gap={}",
+            "  Router:     calls={} iters={} (avg {}/call) evals={} (avg {}/call) early_stop_rel_gap={}",
             stats.router_calls,
             stats.router_golden_iters,
-            stats.router_golden_iters as f64 / router_calls as f64,
+            fmt_f64(stats.router_golden_iters as f64 / router_calls as f64),
             stats.router_evals,
-            stats.router_evals as f64 / router_calls as f64,
+            fmt_f64(stats.router_evals as f64 / router_calls as f64),
             stats.router_early_stop_rel_gap,
         );
     }
 }
+
+/// Warning for simulations whose per-step recording stopped at `max_recorded_steps`.
+fn recording_warning(result: &BatchResult) -> Option<String> {
+    let capped = result.results.iter().filter(|r| r.recording_capped).count();
+    (capped > 0).then(|| {
+        format!(
+            "Warning: recording hit max_recorded_steps in {} of {} simulations; later steps were not recorded.",
+            capped,
+            result.n_sims()
+        )
+    })
+}
+
+/// Warning for configs whose starting price or spread looks like a typo
+/// (`SimulationConfig::sanity_warnings`): the first such seed's problems and fixes, and how
+/// many seeds are affected.
+pub fn start_warning(configs: &[SimulationConfig]) -> Option<String> {
+    let mut flagged = configs
+        .iter()
+        .map(|config| (config.seed, config.sanity_warnings()))
+        .filter(|(_, warnings)| !warnings.is_empty());
+    let (seed, warnings) = flagged.next()?;
+    let mut text = format!(
+        "Warning: the starting market of {} of {} simulations looks misconfigured (first: seed {}):",
+        flagged.count() + 1,
+        configs.len(),
+        seed
+    );
+    for warning in warnings {
+        text.push_str("\n  - ");
+        text.push_str(&warning);
+    }
+    Some(text)
+}
+
+/// One-line summary of the market the configs start from, printed before a run so a price
+/// of 0.01 where 100 was meant is obvious: each value is a `lo..hi` range across seeds, or a
+/// single number when every seed agrees. `None` for an empty batch.
+pub fn start_summary(configs: &[SimulationConfig], opponent: &str) -> Option<String> {
+    let first = configs.first()?;
+    let span = |value: fn(&SimulationConfig) -> f64, places: usize| {
+        let (lo, hi) = configs
+            .iter()
+            .map(value)
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| {
+                (lo.min(v), hi.max(v))
+            });
+        if lo == hi {
+            format!("{:.*}", places, lo)
+        } else {
+            format!("{:.*}..{:.*}", places, lo, places, hi)
+        }
+    };
+    let (seed_lo, seed_hi) = configs
+        .iter()
+        .fold((first.seed, first.seed), |(lo, hi), c| {
+            (lo.min(c.seed), hi.max(c.seed))
+        });
+    Some(format!(
+        "Start: X {} / Y {} (price {}), opponent fee {} bps, volatility {}, seeds {}..={}; opponent {}",
+        span(|c| c.initial_x, 2),
+        span(|c| c.initial_y, 2),
+        span(SimulationConfig::starting_price, 4),
+        span(|c| c.norm_fee_bps as f64, 0),
+        span(|c| c.gbm_sigma, 6),
+        seed_lo,
+        seed_hi,
+        opponent
+    ))
+}
+
+/// Edge lines of the run report; everything here is a pure function of the seeds.
+fn edge_summary(result: &BatchResult) -> String {
+    let Some(waterfall) = result.avg_waterfall() else {
+        return "  No simulations ran: no edge to report.\n".to_string();
+    };
+    let mut out = String::new();
+    out.push_str(&format!("  Avg edge:    {}\n", fmt_stat(result.avg_edge())));
+    out.push_str(&format!("  Total edge:  {}\n", fmt_f64(result.total_edge)));
+    out.push_str("  P&L waterfall (avg per sim):\n");
+    let mut rows = vec![
+        ("Retail spread:    ", waterfall.retail_spread),
+        ("Arb spread:       ", waterfall.arb_spread),
+        ("Adverse selection:", waterfall.adverse_selection),
+    ];
+    if waterfall.inventory_cost != 0.0 {
+        rows.push(("Rebalancing:      ", waterfall.inventory_cost));
+    }
+    rows.push(("= Edge:           ", waterfall.total()));
+    for (label, value) in rows {
+        out.push_str(&format!("    {} {:>16}\n", label, fmt_signed_f64(value)));
+    }
+    out.push_str(&format!(
+        "  Quote slippage: {} avg per sim, {} max per order\n",
+        fmt_stat(result.avg_quote_slippage()),
+        fmt_stat(result.max_quote_slippage())
+    ));
+    out
+}
+
+/// Unscored warmup of the batch: its steps per simulation and the average edge it left out.
+fn warmup_summary(result: &BatchResult) -> Option<String> {
+    let steps = result.results.first()?.phases.warmup.steps;
+    (steps > 0).then(|| {
+        let edge = result
+            .results
+            .iter()
+            .map(|r| r.phases.warmup.edge)
+            .sum::<f64>()
+            / result.n_sims() as f64;
+        format!(
+            "{} steps/sim, avg edge {} (not scored)",
+            steps,
+            fmt_f64(edge)
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use prop_amm_shared::normalizer::{after_swap, compute_swap};
+    use prop_amm_shared::result::BatchResult;
+    use prop_amm_sim::runner;
+
+    use super::{
+        edge_summary, fmt_f64, fmt_signed_f64, fmt_stat, recording_warning, start_warning, EdgeUnit,
+    };
+
+    #[test]
+    fn floats_print_with_fixed_places_and_unsigned_zero() {
+        assert_eq!(fmt_f64(1.5), "1.500000");
+        assert_eq!(fmt_f64(-2.0 / 3.0), "-0.666667");
+        assert_eq!(fmt_f64(-0.0), "0.000000");
+        assert_eq!(fmt_f64(-1e-9), "0.000000");
+        assert_eq!(fmt_f64(1e21), "1000000000000000000000.000000");
+        assert_eq!(fmt_signed_f64(0.25), "+0.250000");
+        assert_eq!(fmt_signed_f64(-0.25), "-0.250000");
+        assert_eq!(fmt_signed_f64(-1e-9), "+0.000000");
+        assert_eq!(fmt_stat(Some(0.5)), "0.500000");
+        assert_eq!(fmt_stat(None), "-");
+    }
+
+    #[test]
+    fn same_config_formats_identically() {
+        let run = || {
+            let result = runner::run_batch_native(
+                compute_swap,
+                Some(after_swap),
+                compute_swap,
+                Some(after_swap),
+                runner::seeded_configs(3, 300, 7, 1),
+                Some(2),
+            )
+            .unwrap();
+            edge_summary(&result)
+        };
+        let first = run();
+        assert!(first.contains("Avg edge:"));
+        assert_eq!(first, run());
+
+        let empty = edge_summary(&BatchResult::from_results(Vec::new()));
+        assert!(empty.contains("No simulations ran"), "{empty}");
+    }
+
+    #[test]
+    fn nano_and_token_reports_differ_by_exactly_the_nano_scale() {
+        let result = runner::run_batch_native(
+            compute_swap,
+            Some(after_swap),
+            compute_swap,
+            Some(after_swap),
+            runner::seeded_configs(3, 300, 7, 1),
+            Some(2),
+        )
+        .unwrap();
+        let token = EdgeUnit::Token.convert(&result);
+        let nano = EdgeUnit::Nano.convert(&result);
+        let bps = EdgeUnit::Bps.convert(&result);
+        for ((t, n), (b, raw)) in token
+            .results
+            .iter()
+            .zip(&nano.results)
+            .zip(bps.results.iter().zip(&result.results))
+        {
+            assert_eq!(t.submission_edge, raw.submission_edge);
+            assert_eq!(n.submission_edge, t.submission_edge * 1e9);
+            assert_eq!(n.waterfall.retail_spread, t.waterfall.retail_spread * 1e9);
+            assert_eq!(
+                b.submission_edge,
+                raw.submission_edge * 10_000.0 / raw.initial_value
+            );
+        }
+        let (t, n) = (token.avg_edge().unwrap(), nano.avg_edge().unwrap());
+        assert!((n - t * 1e9).abs() <= 1e-12 * n.abs(), "{n} vs {t}");
+        assert_ne!(edge_summary(&token), edge_summary(&nano));
+    }
+
+    #[test]
+    fn capped_recording_is_bounded_and_warned_about() {
+        let mut configs = runner::seeded_configs(2, 500, 7, 1);
+        for config in &mut configs {
+            config.record_k_series = true;
+            config.max_recorded_steps = 50;
+        }
+        let result = runner::run_batch_native(
+            compute_swap,
+            Some(after_swap),
+            compute_swap,
+            Some(after_swap),
+            configs,
+            Some(1),
+        )
+        .unwrap();
+
+        for sim in &result.results {
+            assert_eq!(sim.k_series.as_ref().unwrap().len(), 50);
+            assert!(sim.recording_capped);
+        }
+        assert_eq!(result.recording_bytes(), 2 * 50 * 8);
+        let warning = recording_warning(&result).expect("cap was hit");
+        assert!(
+            warning.contains("max_recorded_steps in 2 of 2"),
+            "{warning}"
+        );
+    }
+
+    #[test]
+    fn mistyped_fee_is_flagged_before_the_run_and_sane_seeds_pass_silently() {
+        let mut configs = runner::seeded_configs(20, 100, 0, 1);
+        assert_eq!(start_warning(&configs), None);
+
+        configs[3].norm_fee_bps = 3_000;
+        configs[7].norm_fee_bps = 5_000;
+        let warning = start_warning(&configs).expect("fee typo");
+        assert!(
+            warning.contains("2 of 20 simulations") && warning.contains("first: seed 3"),
+            "{warning}"
+        );
+        assert!(warning.contains("did you mean 30?"), "{warning}");
+    }
+}
@@ -0,0 +1,195 @@
+//! Provenance stamped on run reports: which build, configs, strategy binary and opponent
+//! produced them, and when.
+
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+/// FNV-1a offset basis and prime (64-bit). Unlike `DefaultHasher` it is fixed by
+/// specification, so IDs stay comparable across toolchains.
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// What a report was produced from. Everything but `generated_at` is deterministic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunStamp {
+    pub version: &'static str,
+    /// Hash of the serialized inputs of the run: every simulation config, or whatever else
+    /// parameterizes the command.
+    pub config_hash: u64,
+    /// Hash of the strategy artifact the run loaded (native library or BPF program).
+    pub binary_hash: u64,
+    pub opponent: String,
+    /// UTC time the stamp was taken, e.g. `2026-10-16T09:30:00Z`.
+    pub generated_at: String,
+}
+
+impl RunStamp {
+    /// Stamp a run of `configs` against `opponent` with the strategy artifact `binary`.
+    /// `configs` is hashed through its JSON form, whose field order is the declaration order,
+    /// so the hash only moves when a value (or the config layout) does.
+    pub fn new(configs: &(impl Serialize + ?Sized), binary: &[u8], opponent: &str) -> Self {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            config_hash: fnv1a(
+                FNV_OFFSET,
+                &serde_json::to_vec(configs).expect("configs serialize to JSON"),
+            ),
+            binary_hash: fnv1a(FNV_OFFSET, binary),
+            opponent: opponent.to_string(),
+            generated_at: utc_timestamp(seconds),
+        }
+    }
+
+    /// `new`, hashing the strategy artifact at `artifact`.
+    pub fn for_artifact(
+        configs: &(impl Serialize + ?Sized),
+        artifact: &Path,
+        opponent: &str,
+    ) -> anyhow::Result<Self> {
+        let binary = std::fs::read(artifact)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", artifact.display(), e))?;
+        Ok(Self::new(configs, &binary, opponent))
+    }
+
+    /// Same for every run of this build with the same configs, binary and opponent.
+    pub fn id(&self) -> String {
+        let mut hash = fnv1a(FNV_OFFSET, self.version.as_bytes());
+        hash = fnv1a(hash, &self.config_hash.to_le_bytes());
+        hash = fnv1a(hash, &self.binary_hash.to_le_bytes());
+        hash = fnv1a(hash, self.opponent.as_bytes());
+        format!("{:016x}", hash)
+    }
+
+    /// Report lines, indented like the rest of the run report.
+    pub fn lines(&self) -> String {
+        format!(
+            "  Run ID:      {} (prop-amm {})\n  Binary:      {:016x}\n  Generated:   {}\n",
+            self.id(),
+            self.version,
+            self.binary_hash,
+            self.generated_at
+        )
+    }
+
+    /// The stamp as a JSON object, hashes in hex like `id`.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "run_id": self.id(),
+            "version": self.version,
+            "config_hash": format!("{:016x}", self.config_hash),
+            "binary_hash": format!("{:016x}", self.binary_hash),
+            "opponent": self.opponent,
+            "generated_at": self.generated_at,
+        })
+    }
+
+    /// Comment line heading a CSV report; readers that skip `#` lines see plain CSV.
+    pub fn csv_comment(&self) -> String {
+        format!(
+            "# run_id={} version={} config_hash={:016x} binary_hash={:016x} opponent={} generated_at={}\n",
+            self.id(),
+            self.version,
+            self.config_hash,
+            self.binary_hash,
+            self.opponent,
+            self.generated_at
+        )
+    }
+
+    /// `{"stamp": {..}}` record heading a JSON-lines report.
+    pub fn json_line(&self) -> String {
+        format!("{}\n", json!({ "stamp": self.to_json() }))
+    }
+}
+
+/// `seconds` since the Unix epoch as an RFC 3339 UTC timestamp.
+fn utc_timestamp(seconds: u64) -> String {
+    let (days, rem) = (seconds / 86_400, seconds % 86_400);
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm).
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use prop_amm_sim::runner;
+
+    use super::{utc_timestamp, RunStamp};
+
+    #[test]
+    fn id_depends_on_configs_and_binary_but_not_the_time() {
+        let configs = runner::seeded_configs(4, 100, 0, 1);
+        let first = RunStamp::new(&configs, b"strategy v1", "normalizer");
+        let again = RunStamp {
+            generated_at: "1999-01-01T00:00:00Z".to_string(),
+            ..RunStamp::new(&configs, b"strategy v1", "normalizer")
+        };
+        assert_eq!(first.id(), again.id());
+        assert_eq!(first.id().len(), 16);
+
+        let rebuilt = RunStamp::new(&configs, b"strategy v2", "normalizer");
+        assert_ne!(rebuilt.id(), first.id());
+        assert_ne!(rebuilt.binary_hash, first.binary_hash);
+        let shifted = runner::seeded_configs(4, 100, 1, 1);
+        let other_seeds = RunStamp::new(&shifted, b"strategy v1", "normalizer");
+        assert_ne!(other_seeds.id(), first.id());
+        assert!(first
+            .lines()
+            .contains(&format!("Run ID:      {}", first.id())));
+    }
+
+    #[test]
+    fn csv_and_json_forms_carry_the_same_stamp() {
+        let configs = runner::seeded_configs(2, 100, 0, 1);
+        let stamp = RunStamp::new(&configs, b"strategy", "normalizer");
+        assert_eq!(
+            stamp.config_hash,
+            RunStamp::new(&configs[..], b"", "").config_hash
+        );
+
+        let comment = stamp.csv_comment();
+        assert!(comment.starts_with(&format!("# run_id={} ", stamp.id())));
+        assert_eq!(comment.lines().count(), 1);
+
+        let line: serde_json::Value = serde_json::from_str(stamp.json_line().trim()).unwrap();
+        assert_eq!(line["stamp"], stamp.to_json());
+        assert_eq!(line["stamp"]["run_id"], stamp.id());
+        assert_eq!(line["stamp"]["opponent"], "normalizer");
+        assert!(comment.contains(line["stamp"]["binary_hash"].as_str().unwrap()));
+    }
+
+    #[test]
+    fn timestamps_are_rfc3339_utc() {
+        assert_eq!(utc_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(utc_timestamp(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(utc_timestamp(1_792_143_045), "2026-10-16T09:30:45Z");
+    }
+}
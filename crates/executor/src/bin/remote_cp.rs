@@ -0,0 +1,30 @@
+//! Fee-free constant-product strategy served over the remote protocol (see
+//! `RemoteExecutor`). Its after_swap counts trades in the first 8 bytes of storage. Used by
+//! the protocol tests, and a starting point for hosting a strategy out of process.
+
+use prop_amm_executor::remote::serve;
+use prop_amm_shared::instruction::decode_instruction;
+
+fn compute_swap(data: &[u8]) -> u64 {
+    let (side, amount, rx, ry) = decode_instruction(data);
+    let (reserve_in, reserve_out) = if side == 0 { (ry, rx) } else { (rx, ry) };
+    let denominator = reserve_in as u128 + amount as u128;
+    if denominator == 0 {
+        return 0;
+    }
+    (reserve_out as u128 * amount as u128 / denominator) as u64
+}
+
+fn after_swap(_data: &[u8], storage: &mut [u8]) {
+    let trades = u64::from_le_bytes(storage[..8].try_into().unwrap());
+    storage[..8].copy_from_slice(&(trades + 1).to_le_bytes());
+}
+
+fn main() -> std::io::Result<()> {
+    serve(
+        compute_swap,
+        Some(after_swap),
+        std::io::stdin().lock(),
+        std::io::stdout().lock(),
+    )
+}
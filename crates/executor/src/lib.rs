@@ -1,8 +1,10 @@
 pub mod loader;
 pub mod native;
+pub mod remote;
 pub mod syscalls;
 pub mod vm;
 
 pub use loader::{BpfProgram, ExecutorError};
 pub use native::{AfterSwapFn, NativeExecutor, SwapFn};
+pub use remote::RemoteExecutor;
 pub use vm::BpfExecutor;
@@ -1,5 +1,6 @@
 use std::sync::Arc;
 
+use prop_amm_shared::instruction::DecodeError;
 use solana_rbpf::{
     elf::Executable,
     program::{BuiltinFunction, BuiltinProgram, FunctionRegistry},
@@ -22,10 +23,18 @@ pub enum ExecutorError {
     JitCompilation(String),
     #[error("Execution failed: {0}")]
     Execution(String),
+    #[error("Compute budget exceeded")]
+    ComputeBudgetExceeded,
     #[error("No return data")]
     NoReturnData,
+    #[error("Return data is {actual} bytes, expected {expected}")]
+    ShortReturnData { expected: usize, actual: usize },
     #[error("Program aborted")]
     Aborted,
+    #[error("Remote strategy failed: {0}")]
+    Remote(String),
+    #[error("Malformed instruction: {0}")]
+    Decode(#[from] DecodeError),
 }
 
 #[derive(Clone)]
@@ -37,6 +46,24 @@ pub struct BpfProgram {
 
 impl BpfProgram {
     pub fn load(elf_bytes: &[u8]) -> Result<Self, ExecutorError> {
+        let loader = Self::build_loader()?;
+
+        let executable = Executable::<SyscallContext>::from_elf(elf_bytes, loader.clone())
+            .map_err(|e| ExecutorError::ElfLoad(e.to_string()))?;
+
+        Self::from_executable(executable, loader)
+    }
+
+    /// Assemble a program from rbpf assembly text against the same syscall set as `load`,
+    /// for hand-written test fixtures.
+    pub fn from_asm(src: &str) -> Result<Self, ExecutorError> {
+        let loader = Self::build_loader()?;
+        let executable = solana_rbpf::assembler::assemble(src, loader.clone())
+            .map_err(ExecutorError::ElfLoad)?;
+        Self::from_executable(executable, loader)
+    }
+
+    fn build_loader() -> Result<Arc<BuiltinProgram<SyscallContext>>, ExecutorError> {
         let mut function_registry = FunctionRegistry::<BuiltinFunction<SyscallContext>>::default();
 
         function_registry
@@ -64,15 +91,16 @@ impl BpfProgram {
             .register_function_hashed(*b"sol_memset_", SyscallMemset::vm)
             .map_err(|e| ExecutorError::ElfLoad(e.to_string()))?;
 
-        let loader = Arc::new(BuiltinProgram::new_loader(
+        Ok(Arc::new(BuiltinProgram::new_loader(
             Config::default(),
             function_registry,
-        ));
-
-        #[allow(unused_mut)]
-        let mut executable = Executable::<SyscallContext>::from_elf(elf_bytes, loader.clone())
-            .map_err(|e| ExecutorError::ElfLoad(e.to_string()))?;
+        )))
+    }
 
+    fn from_executable(
+        #[allow(unused_mut)] mut executable: Executable<SyscallContext>,
+        loader: Arc<BuiltinProgram<SyscallContext>>,
+    ) -> Result<Self, ExecutorError> {
         executable
             .verify::<RequisiteVerifier>()
             .map_err(|e| ExecutorError::Verification(e.to_string()))?;
@@ -1,4 +1,6 @@
-use prop_amm_shared::instruction::{encode_after_swap, encode_swap_instruction, STORAGE_SIZE};
+use prop_amm_shared::instruction::{
+    encode_after_swap, encode_swap_instruction_with_oracle, Oracle, STORAGE_SIZE,
+};
 
 /// A swap function signature: takes instruction data (with storage appended), returns output amount.
 pub type SwapFn = fn(&[u8]) -> u64;
@@ -21,13 +23,33 @@ impl NativeExecutor {
         }
     }
 
+    /// Whether the strategy exports `after_swap`; without it `execute_after_swap` is a no-op.
+    pub fn has_after_swap(&self) -> bool {
+        self.after_swap_fn.is_some()
+    }
+
     #[inline]
     pub fn execute(&self, side: u8, amount: u64, rx: u64, ry: u64, storage: &[u8]) -> u64 {
-        let data = encode_swap_instruction(side, amount, rx, ry, storage);
+        self.execute_with_oracle(side, amount, rx, ry, storage, Oracle::default())
+    }
+
+    /// `execute`, appending whichever of `oracle`'s words are given to the instruction data.
+    #[inline]
+    pub fn execute_with_oracle(
+        &self,
+        side: u8,
+        amount: u64,
+        rx: u64,
+        ry: u64,
+        storage: &[u8],
+        oracle: Oracle,
+    ) -> u64 {
+        let data = encode_swap_instruction_with_oracle(side, amount, rx, ry, storage, oracle);
         (self.swap_fn)(&data)
     }
 
     #[inline]
+    #[allow(clippy::too_many_arguments)]
     pub fn execute_after_swap(
         &self,
         side: u8,
@@ -0,0 +1,236 @@
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use prop_amm_shared::instruction::{
+    encode_after_swap, encode_swap_instruction_with_oracle, Oracle, AFTER_SWAP_SIZE,
+    STORAGE_SIZE,
+};
+
+use crate::loader::ExecutorError;
+use crate::native::{AfterSwapFn, SwapFn};
+
+/// First byte of an after_swap request; compute_swap requests start with the side (0 / 1).
+pub const AFTER_SWAP_TAG: u8 = 2;
+
+/// Largest request payload `serve` accepts; anything bigger is a framing error.
+const MAX_REQUEST_SIZE: usize = AFTER_SWAP_SIZE + 64;
+/// Largest response payload the executor accepts: an after_swap's storage.
+const MAX_RESPONSE_SIZE: usize = STORAGE_SIZE;
+/// How long the child gets to answer one request by default.
+pub const DEFAULT_RESPONSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Executor that forwards every call to a strategy in a child process, so untrusted code
+/// never runs inside the judge.
+///
+/// The child speaks a framed protocol on stdin/stdout: each message is a `u32` little-endian
+/// payload length followed by the payload.
+///
+/// | Request payload                         | Response payload                     |
+/// |-----------------------------------------|--------------------------------------|
+/// | compute_swap data (byte 0 = side 0 / 1) | output amount, `u64` LE (8 bytes)    |
+/// | after_swap data (byte 0 = tag 2)        | updated storage (`STORAGE_SIZE`)     |
+///
+/// Payloads use the same layouts as in-process calls (see `prop_amm_shared::instruction`).
+/// The child answers requests in order and exits when stdin closes.
+///
+/// A child that breaks the protocol (a response of the wrong length, or none within the
+/// response timeout) is killed on the spot, and every later call fails without reaching it.
+pub struct RemoteExecutor {
+    child: Child,
+    stdin: BufWriter<ChildStdin>,
+    /// Response frames, read off the child's stdout by a background thread so a silent child
+    /// can be timed out.
+    responses: Receiver<io::Result<Vec<u8>>>,
+    timeout: Duration,
+    /// Why the child was killed, once it has been.
+    killed: Option<String>,
+}
+
+impl RemoteExecutor {
+    /// Spawn `command` with piped stdin/stdout (stderr is inherited).
+    pub fn spawn(mut command: Command) -> Result<Self, ExecutorError> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| ExecutorError::Remote(format!("spawn failed: {}", e)))?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let (sender, responses) = mpsc::channel();
+        thread::spawn(move || read_responses(BufReader::new(stdout), |frame| sender.send(frame)));
+        Ok(Self {
+            child,
+            stdin: BufWriter::new(stdin),
+            responses,
+            timeout: DEFAULT_RESPONSE_TIMEOUT,
+            killed: None,
+        })
+    }
+
+    /// Give the child `timeout` to answer each request instead of `DEFAULT_RESPONSE_TIMEOUT`.
+    pub fn with_response_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// `NativeExecutor::execute_with_oracle`, answered by the child.
+    pub fn execute_with_oracle(
+        &mut self,
+        side: u8,
+        amount: u64,
+        rx: u64,
+        ry: u64,
+        storage: &[u8],
+        oracle: Oracle,
+    ) -> Result<u64, ExecutorError> {
+        let data = encode_swap_instruction_with_oracle(side, amount, rx, ry, storage, oracle);
+        let response = self.round_trip(&data, 8)?;
+        Ok(u64::from_le_bytes(response.try_into().unwrap()))
+    }
+
+    /// `NativeExecutor::execute_after_swap`, answered by the child; `storage` is replaced
+    /// with the storage it sends back.
+    #[allow(clippy::too_many_arguments)]
+    pub fn execute_after_swap(
+        &mut self,
+        side: u8,
+        input_amount: u64,
+        output_amount: u64,
+        rx: u64,
+        ry: u64,
+        step: u64,
+        storage: &mut [u8],
+    ) -> Result<(), ExecutorError> {
+        let data = encode_after_swap(side, input_amount, output_amount, rx, ry, step, storage);
+        let response = self.round_trip(&data, STORAGE_SIZE)?;
+        let copy_len = storage.len().min(STORAGE_SIZE);
+        storage[..copy_len].copy_from_slice(&response[..copy_len]);
+        Ok(())
+    }
+
+    /// Send one request and wait for a response that must be exactly `expected_len` bytes.
+    /// Anything else kills the child: its stream can no longer be trusted to line up.
+    fn round_trip(
+        &mut self,
+        request: &[u8],
+        expected_len: usize,
+    ) -> Result<Vec<u8>, ExecutorError> {
+        if let Some(reason) = &self.killed {
+            return Err(ExecutorError::Remote(format!(
+                "child was killed: {}",
+                reason
+            )));
+        }
+        let sent = write_frame(&mut self.stdin, request).and_then(|()| self.stdin.flush());
+        let violation = match sent {
+            Err(e) => e.to_string(),
+            Ok(()) => match self.responses.recv_timeout(self.timeout) {
+                Ok(Ok(response)) if response.len() == expected_len => return Ok(response),
+                Ok(Ok(response)) => format!(
+                    "expected a {}-byte response, got {} bytes",
+                    expected_len,
+                    response.len()
+                ),
+                Ok(Err(e)) => e.to_string(),
+                Err(RecvTimeoutError::Timeout) => {
+                    format!("no response within {:?}", self.timeout)
+                }
+                Err(RecvTimeoutError::Disconnected) => "stdout closed".to_string(),
+            },
+        };
+        self.kill();
+        self.killed = Some(violation.clone());
+        Err(ExecutorError::Remote(violation))
+    }
+
+    fn kill(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl Drop for RemoteExecutor {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+/// Hand every response frame on `stdout` to `deliver` until the stream ends, a frame is
+/// longer than any valid response, or nobody is listening any more.
+fn read_responses<E>(
+    mut stdout: impl Read,
+    mut deliver: impl FnMut(io::Result<Vec<u8>>) -> Result<(), E>,
+) {
+    loop {
+        let frame = read_len(&mut stdout).and_then(|len| {
+            if len > MAX_RESPONSE_SIZE {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("response length {} exceeds {}", len, MAX_RESPONSE_SIZE),
+                ));
+            }
+            let mut response = vec![0u8; len];
+            stdout.read_exact(&mut response)?;
+            Ok(response)
+        });
+        let last = frame.is_err();
+        if deliver(frame).is_err() || last {
+            return;
+        }
+    }
+}
+
+/// Answer requests from `input` with `swap_fn` / `after_swap_fn` until `input` closes. This
+/// is the child side of the protocol: a strategy host calls it with its stdin and stdout.
+pub fn serve(
+    swap_fn: SwapFn,
+    after_swap_fn: Option<AfterSwapFn>,
+    input: impl Read,
+    output: impl Write,
+) -> io::Result<()> {
+    let mut input = BufReader::new(input);
+    let mut output = BufWriter::new(output);
+    loop {
+        let len = match read_len(&mut input) {
+            Ok(len) => len,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        if len == 0 || len > MAX_REQUEST_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("bad request length {}", len),
+            ));
+        }
+        let mut data = vec![0u8; len];
+        input.read_exact(&mut data)?;
+        if data[0] == AFTER_SWAP_TAG {
+            let mut storage = data
+                .get(AFTER_SWAP_SIZE - STORAGE_SIZE..)
+                .unwrap_or_default()
+                .to_vec();
+            storage.resize(STORAGE_SIZE, 0);
+            if let Some(after_swap) = after_swap_fn {
+                after_swap(&data, &mut storage);
+            }
+            write_frame(&mut output, &storage)?;
+        } else {
+            write_frame(&mut output, &swap_fn(&data).to_le_bytes())?;
+        }
+        output.flush()?;
+    }
+}
+
+fn write_frame(writer: &mut impl Write, payload: &[u8]) -> io::Result<()> {
+    writer.write_all(&(payload.len() as u32).to_le_bytes())?;
+    writer.write_all(payload)
+}
+
+fn read_len(reader: &mut impl Read) -> io::Result<usize> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)?;
+    Ok(u32::from_le_bytes(len) as usize)
+}
@@ -5,7 +5,7 @@ use solana_rbpf::{
     vm::ContextObject,
 };
 
-use prop_amm_shared::instruction::STORAGE_SIZE;
+use prop_amm_shared::instruction::{MAX_BATCH, STORAGE_SIZE};
 use std::sync::OnceLock;
 
 fn meter_disabled() -> bool {
@@ -13,8 +13,13 @@ fn meter_disabled() -> bool {
     *DISABLED.get_or_init(|| std::env::var_os("PROP_AMM_BPF_DISABLE_METER").is_some())
 }
 
+/// Largest return data a program may set: one u64 per request of a full batch.
+pub const MAX_RETURN_DATA: usize = 8 * MAX_BATCH;
+
 pub struct SyscallContext {
-    pub return_data: [u8; 8],
+    pub return_data: [u8; MAX_RETURN_DATA],
+    /// Bytes set by the last `sol_set_return_data`; `return_data` is zero-padded to 8.
+    pub return_data_len: usize,
     pub has_return_data: bool,
     pub storage_data: Vec<u8>,
     pub has_storage_update: bool,
@@ -24,7 +29,8 @@ pub struct SyscallContext {
 impl SyscallContext {
     pub fn new(remaining: u64) -> Self {
         Self {
-            return_data: [0u8; 8],
+            return_data: [0u8; MAX_RETURN_DATA],
+            return_data_len: 0,
             has_return_data: false,
             storage_data: vec![0u8; STORAGE_SIZE],
             has_storage_update: false,
@@ -76,7 +82,7 @@ declare_builtin_function!(
         _arg5: u64,
         memory_mapping: &mut MemoryMapping,
     ) -> Result<u64, Box<dyn std::error::Error>> {
-        if len > 8 {
+        if len > MAX_RETURN_DATA as u64 {
             return Err(Box::new(EbpfError::AccessViolation(
                 AccessType::Load,
                 addr,
@@ -88,8 +94,10 @@ declare_builtin_function!(
             memory_mapping.map(AccessType::Load, addr, len).into();
         let host_addr = host_addr?;
         let slice = unsafe { std::slice::from_raw_parts(host_addr as *const u8, len as usize) };
-        context_object.return_data = [0u8; 8];
-        context_object.return_data[..len as usize].copy_from_slice(slice);
+        let len = len as usize;
+        context_object.return_data[..len.max(8)].fill(0);
+        context_object.return_data[..len].copy_from_slice(slice);
+        context_object.return_data_len = len;
         context_object.has_return_data = true;
         Ok(0)
     }
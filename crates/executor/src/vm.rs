@@ -1,43 +1,60 @@
 use solana_rbpf::{
     aligned_memory::AlignedMemory,
     ebpf,
+    error::EbpfError,
     memory_region::{MemoryMapping, MemoryRegion},
     vm::EbpfVm,
 };
 
 use crate::loader::{BpfProgram, ExecutorError};
 use crate::syscalls::SyscallContext;
-use prop_amm_shared::instruction::{AFTER_SWAP_SIZE, STORAGE_SIZE, SWAP_INSTRUCTION_SIZE};
+use prop_amm_shared::instruction::{
+    batch_instruction_size, encode_batch_instruction, Oracle, SwapRequest, AFTER_SWAP_SIZE,
+    MAX_BATCH_INSTRUCTION_SIZE, STORAGE_SIZE, SWAP_INSTRUCTION_SIZE,
+};
 
 /// Solana input buffer layout for 0 accounts:
 /// [0..8]   u64 num_accounts = 0
 /// [8..16]  u64 instruction_data_len
-/// [16..]   instruction_data (up to AFTER_SWAP_SIZE bytes)
+/// [16..]   instruction_data (up to MAX_BATCH_INSTRUCTION_SIZE bytes)
 /// [..]     program_id (32 bytes, zeros)
-const INPUT_BUF_SIZE: usize = 8 + 8 + AFTER_SWAP_SIZE + 32; // 1106
+const INPUT_BUF_SIZE: usize = 8 + 8 + MAX_BATCH_INSTRUCTION_SIZE + 32; // 1874
+const _: () = assert!(MAX_BATCH_INSTRUCTION_SIZE >= AFTER_SWAP_SIZE);
+const HEAP_SIZE: usize = 32 * 1024;
 
+/// Executes swaps against one BPF program.
+///
+/// The input buffer, stack, heap and syscall context are allocated once in `new`. Each call
+/// wipes the stack and heap, as a fresh on-chain invocation would see them, and maps the
+/// buffers into a VM that borrows them for that call only.
 pub struct BpfExecutor {
     program: BpfProgram,
     input_buf: Vec<u8>,
     stack: AlignedMemory<{ ebpf::HOST_ALIGN }>,
     heap: AlignedMemory<{ ebpf::HOST_ALIGN }>,
     context: SyscallContext,
+    last_instruction_count: u64,
 }
 
 impl BpfExecutor {
     pub fn new(program: BpfProgram) -> Self {
         let config = program.executable().get_config();
-        let input_buf = vec![0u8; INPUT_BUF_SIZE];
 
         Self {
             stack: AlignedMemory::zero_filled(config.stack_size()),
-            heap: AlignedMemory::zero_filled(32 * 1024),
+            heap: AlignedMemory::zero_filled(HEAP_SIZE),
             program,
-            input_buf,
+            input_buf: vec![0u8; INPUT_BUF_SIZE],
             context: SyscallContext::new(100_000),
+            last_instruction_count: 0,
         }
     }
 
+    /// Compute units (BPF instructions) consumed by the most recent call.
+    pub fn last_instruction_count(&self) -> u64 {
+        self.last_instruction_count
+    }
+
     fn run_vm(&mut self, instr_data_len: usize) -> Result<(), ExecutorError> {
         // Write instruction data length
         self.input_buf[8..16].copy_from_slice(&(instr_data_len as u64).to_le_bytes());
@@ -45,8 +62,11 @@ impl BpfExecutor {
         // Reset context flags without reallocating storage Vec.
         self.context.reset(100_000);
 
+        // Nothing a previous call left on the stack or heap may leak into this one.
+        self.stack.as_slice_mut().fill(0);
+        self.heap.as_slice_mut().fill(0);
+
         let executable = self.program.executable();
-        let loader = self.program.loader();
         let config = executable.get_config();
         let sbpf_version = executable.get_sbpf_version();
         let stack_len = self.stack.len();
@@ -57,12 +77,11 @@ impl BpfExecutor {
             MemoryRegion::new_writable(self.heap.as_slice_mut(), ebpf::MM_HEAP_START),
             MemoryRegion::new_writable(&mut self.input_buf, ebpf::MM_INPUT_START),
         ];
-
         let memory_mapping = MemoryMapping::new(regions, config, sbpf_version)
             .map_err(|e| ExecutorError::Execution(e.to_string()))?;
 
         let mut vm = EbpfVm::new(
-            loader.clone(),
+            self.program.loader().clone(),
             sbpf_version,
             &mut self.context,
             memory_mapping,
@@ -70,10 +89,14 @@ impl BpfExecutor {
         );
 
         let use_interpreter = !self.program.jit_available();
-        let (_instruction_count, result) = vm.execute_program(executable, use_interpreter);
+        let (instruction_count, result) = vm.execute_program(executable, use_interpreter);
+        self.last_instruction_count = instruction_count;
 
         let result: Result<u64, _> = result.into();
-        result.map_err(|e| ExecutorError::Execution(e.to_string()))?;
+        result.map_err(|e| match e {
+            EbpfError::ExceededMaxInstructions => ExecutorError::ComputeBudgetExceeded,
+            e => ExecutorError::Execution(e.to_string()),
+        })?;
 
         Ok(())
     }
@@ -85,6 +108,19 @@ impl BpfExecutor {
         rx: u64,
         ry: u64,
         storage: &[u8],
+    ) -> Result<u64, ExecutorError> {
+        self.execute_with_oracle(side, amount, rx, ry, storage, Oracle::default())
+    }
+
+    /// `execute`, appending whichever of `oracle`'s words are given to the instruction data.
+    pub fn execute_with_oracle(
+        &mut self,
+        side: u8,
+        amount: u64,
+        rx: u64,
+        ry: u64,
+        storage: &[u8],
+        oracle: Oracle,
     ) -> Result<u64, ExecutorError> {
         self.input_buf.fill(0);
 
@@ -99,15 +135,65 @@ impl BpfExecutor {
             self.input_buf[41 + copy_len..41 + STORAGE_SIZE].fill(0);
         }
 
-        self.run_vm(SWAP_INSTRUCTION_SIZE)?;
+        oracle.encode_into(&mut self.input_buf[16 + SWAP_INSTRUCTION_SIZE..]);
+        self.run_vm(SWAP_INSTRUCTION_SIZE + oracle.encoded_len())?;
 
-        if !self.context.has_return_data {
+        let context = &self.context;
+        if !context.has_return_data {
             return Err(ExecutorError::NoReturnData);
         }
 
-        Ok(u64::from_le_bytes(self.context.return_data))
+        Ok(u64::from_le_bytes(
+            context.return_data[..8].try_into().unwrap(),
+        ))
+    }
+
+    /// `execute` for a call whose program answers with a signed value: the same 8 return
+    /// bytes, read as a two's-complement little-endian `i64` (`set_return_data_i64` in the
+    /// SDK) instead of a `u64`.
+    pub fn execute_signed(
+        &mut self,
+        side: u8,
+        amount: u64,
+        rx: u64,
+        ry: u64,
+        storage: &[u8],
+    ) -> Result<i64, ExecutorError> {
+        let word = self.execute(side, amount, rx, ry, storage)?;
+        Ok(i64::from_le_bytes(word.to_le_bytes()))
+    }
+
+    /// Quote every request in one VM invocation using the batched layout (see
+    /// `prop_amm_shared::instruction::BATCH_TAG`), returning one output per request. Only
+    /// for programs that implement it; the invocation's compute budget covers the batch.
+    pub fn execute_batch(
+        &mut self,
+        requests: &[SwapRequest],
+        storage: &[u8],
+    ) -> Result<Vec<u64>, ExecutorError> {
+        let data = encode_batch_instruction(requests, storage)?;
+        self.input_buf.fill(0);
+        self.input_buf[16..16 + data.len()].copy_from_slice(&data);
+        self.run_vm(batch_instruction_size(requests.len()))?;
+
+        let context = &self.context;
+        if !context.has_return_data {
+            return Err(ExecutorError::NoReturnData);
+        }
+        let expected = 8 * requests.len();
+        if context.return_data_len < expected {
+            return Err(ExecutorError::ShortReturnData {
+                expected,
+                actual: context.return_data_len,
+            });
+        }
+        Ok(context.return_data[..expected]
+            .chunks_exact(8)
+            .map(|word| u64::from_le_bytes(word.try_into().unwrap()))
+            .collect())
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn execute_after_swap(
         &mut self,
         side: u8,
@@ -137,11 +223,271 @@ impl BpfExecutor {
 
         self.run_vm(AFTER_SWAP_SIZE)?;
 
-        if self.context.has_storage_update {
+        let context = &self.context;
+        if context.has_storage_update {
             let out_len = storage.len().min(STORAGE_SIZE);
-            storage[..out_len].copy_from_slice(&self.context.storage_data[..out_len]);
+            storage[..out_len].copy_from_slice(&context.storage_data[..out_len]);
         }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use prop_amm_shared::instruction::{SwapRequest, MAX_BATCH};
+
+    use super::BpfExecutor;
+    use crate::loader::{BpfProgram, ExecutorError};
+
+    /// Returns `amount + ry + storage[0..8] + 8 * side`; aborts on a zero amount.
+    const SWAP_ASM: &str = "
+        ldxdw r2, [r1+17]
+        jeq r2, 0, fail
+        ldxb r3, [r1+16]
+        lsh64 r3, 3
+        add64 r2, r3
+        ldxdw r3, [r1+33]
+        add64 r2, r3
+        ldxdw r3, [r1+41]
+        add64 r2, r3
+        stxdw [r10-8], r2
+        mov64 r1, r10
+        add64 r1, -8
+        mov64 r2, 8
+        mov64 r3, 0
+        syscall sol_set_return_data
+        mov64 r0, 0
+        exit
+        fail:
+        syscall abort
+        exit";
+
+    /// `SWAP_ASM`, plus the batched layout: the same formula for each request, with the
+    /// storage word read after the requests.
+    const BATCH_SWAP_ASM: &str = "
+        ldxb r3, [r1+16]
+        jeq r3, 5, batch
+        ldxdw r2, [r1+17]
+        jeq r2, 0, fail
+        lsh64 r3, 3
+        add64 r2, r3
+        ldxdw r3, [r1+33]
+        add64 r2, r3
+        ldxdw r3, [r1+41]
+        add64 r2, r3
+        stxdw [r10-8], r2
+        mov64 r1, r10
+        add64 r1, -8
+        mov64 r2, 8
+        mov64 r3, 0
+        syscall sol_set_return_data
+        mov64 r0, 0
+        exit
+        batch:
+        ldxb r4, [r1+17]
+        mov64 r5, r4
+        lsh64 r5, 4
+        mov64 r6, r4
+        lsh64 r6, 3
+        add64 r5, r6
+        add64 r5, r4
+        add64 r5, r1
+        ldxdw r6, [r5+18]
+        mov64 r7, r1
+        add64 r7, 18
+        mov64 r8, r10
+        add64 r8, -256
+        mov64 r9, r8
+        next:
+        jeq r4, 0, done
+        ldxdw r2, [r7+1]
+        jeq r2, 0, fail
+        ldxb r3, [r7+0]
+        lsh64 r3, 3
+        add64 r2, r3
+        ldxdw r3, [r7+17]
+        add64 r2, r3
+        add64 r2, r6
+        stxdw [r9+0], r2
+        add64 r9, 8
+        add64 r7, 25
+        add64 r4, -1
+        ja next
+        done:
+        mov64 r1, r8
+        mov64 r2, r9
+        sub64 r2, r8
+        mov64 r3, 0
+        syscall sol_set_return_data
+        mov64 r0, 0
+        exit
+        fail:
+        syscall abort
+        exit";
+
+    /// Returns `-(amount + ry)` as a signed word.
+    const SIGNED_ASM: &str = "
+        ldxdw r2, [r1+17]
+        ldxdw r3, [r1+33]
+        add64 r2, r3
+        mov64 r3, 0
+        sub64 r3, r2
+        stxdw [r10-8], r3
+        mov64 r1, r10
+        add64 r1, -8
+        mov64 r2, 8
+        mov64 r3, 0
+        syscall sol_set_return_data
+        mov64 r0, 0
+        exit";
+
+    /// Bumps a counter at the start of the heap (where the SDK's bump allocator keeps its
+    /// cursor) and adds the word a previous call left at `[r10-16]`, then leaves the sum
+    /// there: returns 1 on every call only if each starts from a clean heap and stack.
+    const DIRTY_MEMORY_ASM: &str = "
+        mov64 r2, 3
+        lsh64 r2, 32
+        ldxdw r3, [r2+0]
+        add64 r3, 1
+        stxdw [r2+0], r3
+        ldxdw r4, [r10-16]
+        add64 r3, r4
+        stxdw [r10-16], r3
+        stxdw [r10-8], r3
+        mov64 r1, r10
+        add64 r1, -8
+        mov64 r2, 8
+        mov64 r3, 0
+        syscall sol_set_return_data
+        mov64 r0, 0
+        exit";
+
+    fn expected(side: u8, amount: u64, ry: u64, storage: &[u8]) -> u64 {
+        let word = u64::from_le_bytes(storage[..8].try_into().unwrap());
+        amount + ry + word + 8 * side as u64
+    }
+
+    #[test]
+    fn reused_vm_matches_fresh_executor_across_calls() {
+        let program = BpfProgram::from_asm(SWAP_ASM).expect("assemble test program");
+        let mut reused = BpfExecutor::new(program.clone());
+        let mut storage = [0u8; 1024];
+
+        for i in 0..5_000u64 {
+            let side = (i % 2) as u8;
+            let amount = i * 31 % 997;
+            let ry = 1_000_000 + i;
+            storage[..8].copy_from_slice(&(i * 3).to_le_bytes());
+
+            let fresh = BpfExecutor::new(program.clone()).execute(side, amount, i, ry, &storage);
+            let out = reused.execute(side, amount, i, ry, &storage);
+            match (out, fresh) {
+                (Ok(out), Ok(fresh)) => {
+                    assert_eq!(out, fresh, "call {i}");
+                    assert_eq!(out, expected(side, amount, ry, &storage), "call {i}");
+                }
+                // A failed call must not leave state behind for the next one.
+                (Err(ExecutorError::Execution(_)), Err(ExecutorError::Execution(_))) => {
+                    assert_eq!(amount, 0, "call {i}");
+                }
+                (out, fresh) => panic!("call {i}: reused {out:?}, fresh {fresh:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn every_call_starts_from_a_clean_stack_and_heap() {
+        let program = BpfProgram::from_asm(DIRTY_MEMORY_ASM).expect("assemble test program");
+        let mut exec = BpfExecutor::new(program);
+        let storage = [0u8; 1024];
+        for call in 0..100 {
+            assert_eq!(
+                exec.execute(0, 1, 0, 0, &storage).unwrap(),
+                1,
+                "call {call}"
+            );
+        }
+    }
+
+    #[test]
+    fn executor_can_move_to_a_worker_thread() {
+        let program = BpfProgram::from_asm(SWAP_ASM).expect("assemble test program");
+        let mut exec = BpfExecutor::new(program);
+        let storage = [0u8; 1024];
+        let before = exec.execute(1, 5, 0, 7, &storage).unwrap();
+        let after = std::thread::spawn(move || exec.execute(1, 5, 0, 7, &storage).unwrap())
+            .join()
+            .unwrap();
+        assert_eq!(after, before);
+    }
+
+    #[test]
+    fn batch_matches_single_calls() {
+        let program = BpfProgram::from_asm(BATCH_SWAP_ASM).expect("assemble test program");
+        let mut exec = BpfExecutor::new(program);
+        let mut storage = [0u8; 1024];
+        storage[..8].copy_from_slice(&77u64.to_le_bytes());
+
+        for n in [1, 7, MAX_BATCH] {
+            let requests: Vec<SwapRequest> = (0..n as u64)
+                .map(|i| SwapRequest {
+                    side: (i % 2) as u8,
+                    input_amount: 1 + i * 31,
+                    reserve_x: 1_000 + i,
+                    reserve_y: 1_000_000 + i * i,
+                })
+                .collect();
+            let batch = exec.execute_batch(&requests, &storage).unwrap();
+            let singles: Vec<u64> = requests
+                .iter()
+                .map(|r| {
+                    exec.execute(r.side, r.input_amount, r.reserve_x, r.reserve_y, &storage)
+                        .unwrap()
+                })
+                .collect();
+            assert_eq!(batch, singles, "batch of {n}");
+            assert_eq!(singles[0], expected(0, 1, 1_000_000, &storage));
+        }
+
+        assert!(matches!(
+            exec.execute_batch(&[], &storage),
+            Err(ExecutorError::Decode(_))
+        ));
+        // A program without batch support answers with a single word.
+        let mut single_only = BpfExecutor::new(BpfProgram::from_asm(SWAP_ASM).unwrap());
+        let request = SwapRequest {
+            side: 0,
+            input_amount: 5,
+            reserve_x: 1,
+            reserve_y: 2,
+        };
+        assert!(matches!(
+            single_only.execute_batch(&[request; 2], &storage),
+            Err(ExecutorError::ShortReturnData {
+                expected: 16,
+                actual: 8
+            })
+        ));
+    }
+
+    #[test]
+    fn negative_signed_return_round_trips() {
+        let program = BpfProgram::from_asm(SIGNED_ASM).expect("assemble test program");
+        let mut exec = BpfExecutor::new(program);
+        let storage = [0u8; 1024];
+
+        assert_eq!(exec.execute_signed(0, 5, 0, 7, &storage).unwrap(), -12);
+        assert_eq!(exec.execute_signed(1, 0, 0, 0, &storage).unwrap(), 0);
+        let large = i64::MAX as u64;
+        assert_eq!(
+            exec.execute_signed(0, large, 0, 1, &storage).unwrap(),
+            i64::MIN
+        );
+        // Read unsigned, the same bytes are the two's-complement encoding.
+        assert_eq!(
+            exec.execute(0, 5, 0, 7, &storage).unwrap(),
+            u64::from_le_bytes((-12i64).to_le_bytes())
+        );
+    }
+}
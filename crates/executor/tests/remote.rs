@@ -0,0 +1,90 @@
+use std::process::Command;
+use std::time::Duration;
+
+use prop_amm_executor::{ExecutorError, RemoteExecutor};
+use prop_amm_shared::instruction::{Oracle, STORAGE_SIZE};
+
+fn constant_product_out(amount: u64, reserve_in: u64, reserve_out: u64) -> u64 {
+    (reserve_out as u128 * amount as u128 / (reserve_in as u128 + amount as u128)) as u64
+}
+
+fn spawn_cp() -> RemoteExecutor {
+    RemoteExecutor::spawn(Command::new(env!("CARGO_BIN_EXE_remote_cp"))).unwrap()
+}
+
+#[test]
+fn remote_quotes_round_trip() {
+    let mut exec = spawn_cp();
+    let storage = [0u8; STORAGE_SIZE];
+    let (rx, ry) = (100_000_000_000, 10_000_000_000_000);
+    for amount in [1, 1_000_000_000, 123_456_789_000, 5_000_000_000_000] {
+        let buy = exec
+            .execute_with_oracle(0, amount, rx, ry, &storage, Oracle::default())
+            .unwrap();
+        assert_eq!(buy, constant_product_out(amount, ry, rx));
+        // The oracle word only lengthens the payload; the helper ignores it.
+        let sell = exec
+            .execute_with_oracle(1, amount, rx, ry, &storage, Oracle::twap(99_000_000_000))
+            .unwrap();
+        assert_eq!(sell, constant_product_out(amount, rx, ry));
+    }
+}
+
+#[test]
+fn remote_after_swap_returns_updated_storage() {
+    let mut exec = spawn_cp();
+    let mut storage = [0xABu8; STORAGE_SIZE];
+    storage[..8].copy_from_slice(&41u64.to_le_bytes());
+    exec.execute_after_swap(0, 10, 20, 30, 40, 7, &mut storage)
+        .unwrap();
+    assert_eq!(u64::from_le_bytes(storage[..8].try_into().unwrap()), 42);
+    assert!(storage[8..].iter().all(|&b| b == 0xAB));
+}
+
+#[test]
+fn exited_child_is_an_error() {
+    let mut exec = RemoteExecutor::spawn(Command::new("true")).unwrap();
+    let err = exec
+        .execute_with_oracle(0, 1, 2, 3, &[0u8; STORAGE_SIZE], Oracle::default())
+        .unwrap_err();
+    assert!(matches!(err, ExecutorError::Remote(_)), "{err}");
+}
+
+/// A child that runs `script` under `sh`, with a short response timeout.
+fn spawn_sh(script: &str) -> RemoteExecutor {
+    let mut command = Command::new("sh");
+    command.args(["-c", script]);
+    RemoteExecutor::spawn(command)
+        .unwrap()
+        .with_response_timeout(Duration::from_millis(200))
+}
+
+fn quote(exec: &mut RemoteExecutor) -> Result<u64, ExecutorError> {
+    exec.execute_with_oracle(0, 1, 2, 3, &[0u8; STORAGE_SIZE], Oracle::default())
+}
+
+#[test]
+fn silent_child_times_out_and_is_killed() {
+    let mut exec = spawn_sh("cat > /dev/null");
+    let err = quote(&mut exec).unwrap_err();
+    assert!(err.to_string().contains("no response within"), "{err}");
+    let err = quote(&mut exec).unwrap_err();
+    assert!(err.to_string().contains("child was killed"), "{err}");
+}
+
+#[test]
+fn wrong_length_responses_kill_the_child() {
+    // A well-framed 4-byte answer to a quote, which must be 8 bytes.
+    let mut exec = spawn_sh(r"printf '\004\000\000\000abcd'; cat > /dev/null");
+    let err = quote(&mut exec).unwrap_err();
+    assert!(
+        err.to_string().contains("expected a 8-byte response"),
+        "{err}"
+    );
+    assert!(quote(&mut exec).is_err());
+
+    // A 4 GiB length is refused before anything is allocated for it.
+    let mut exec = spawn_sh(r"printf '\377\377\377\377'; cat > /dev/null");
+    let err = quote(&mut exec).unwrap_err();
+    assert!(err.to_string().contains("exceeds"), "{err}");
+}
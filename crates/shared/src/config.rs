@@ -1,6 +1,11 @@
 use rand::Rng;
 use rand::SeedableRng;
 use rand_pcg::Pcg64;
+use serde::Serialize;
+
+use crate::nano::NANO_DECIMALS;
+use crate::normalizer;
+use crate::result::Phase;
 
 // Baseline simulation parameters
 pub const BASELINE_STEPS: u32 = 10_000;
@@ -16,48 +21,518 @@ pub const RETAIL_MEAN_SIZE: f64 = 20.0; // midpoint of [12, 28]
 pub const RETAIL_SIZE_SIGMA: f64 = 1.2;
 pub const RETAIL_BUY_PROB: f64 = 0.5;
 pub const MIN_ARB_PROFIT: f64 = 0.01; // 1 cent in quote token (Y)
+pub const ARB_SOLVER_ITERS: u32 = 12;
+pub const ARB_SOLVER_TOLERANCE: f64 = 1e-2; // relative bracket width in trade-size space
+pub const PRICE_CORRELATION: f64 = 0.0;
+pub const MAX_IMPACT_BPS: f64 = 100.0; // only used with `partial_fill`
+pub const MAX_RECORDED_STEPS: u32 = 1_000_000; // 8 MB per f64 series
+pub const STEPS_PER_YEAR: f64 = 525_600.0; // one step a minute: GBM_SIGMA is ~69% a year
+/// Widest starting round-trip spread of the reference pool `sanity_warnings` lets through.
+pub const MAX_SANE_SPREAD_BPS: f64 = 5_000.0;
+/// Largest factor the pools' opening price may sit above or below `initial_price` by.
+pub const MAX_SANE_PRICE_RATIO: f64 = 2.0;
+/// Mixed into the seed for the `seeded_reserves` draws.
+const SEEDED_RESERVES_STREAM: u64 = 0x7e5e_7e5e_5eed_5eed;
 
-#[derive(Debug, Clone)]
+/// One arbitrageur competing to close mispricings.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize)]
+pub struct ArbAgent {
+    /// Per-notional trading cost in basis points. The agent values X at `fair * (1 - c)` when
+    /// buying and `fair * (1 + c)` when selling, so it only trades once the pool is mispriced
+    /// by more than its threshold.
+    pub threshold_bps: f64,
+}
+
+impl ArbAgent {
+    pub fn new(threshold_bps: f64) -> Self {
+        Self { threshold_bps }
+    }
+
+    /// `n` agents with thresholds drawn uniformly from `[min_bps, max_bps]`.
+    pub fn uniform_thresholds(n: usize, min_bps: f64, max_bps: f64, seed: u64) -> Vec<Self> {
+        let mut rng = Pcg64::seed_from_u64(seed);
+        (0..n)
+            .map(|_| {
+                let threshold_bps = if max_bps > min_bps {
+                    rng.gen_range(min_bps..=max_bps)
+                } else {
+                    min_bps
+                };
+                Self { threshold_bps }
+            })
+            .collect()
+    }
+}
+
+/// How trades are scheduled within a step.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize)]
+pub enum TradeArrival {
+    /// One price move per step, then every arbitrageur, then the step's retail orders.
+    #[default]
+    PerStep,
+    /// Retail orders and arbitrage passes arrive as independent Poisson processes with
+    /// these rates per step (per `gbm_dt`); the price diffuses continuously between
+    /// arrivals, so long gaps let the pools drift further from fair before an arb lands.
+    Poisson { retail_rate: f64, arb_rate: f64 },
+}
+
+/// Where the arbitrage pass falls among a step's retail orders under `TradeArrival::PerStep`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub enum StepOrder {
+    /// Arbitrageurs first, then the retail orders.
+    #[default]
+    ArbFirst,
+    /// The retail orders first, then arbitrageurs.
+    RetailFirst,
+    /// A uniformly random slot among the retail orders, drawn each step from a dedicated
+    /// stream seeded by `seed`.
+    Shuffled,
+}
+
+/// Price fills against the submission are marked at when scoring edge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub enum ReferenceMode {
+    /// The fair price at the moment of the fill.
+    #[default]
+    Spot,
+    /// The mean of the current fair price and the fair prices at the end of the previous
+    /// `n - 1` steps, which smooths out moves the pool had no time to react to.
+    Twap(u32),
+}
+
+/// How a step's retail orders reach the pools under `TradeArrival::PerStep`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub enum RetailNetting {
+    /// Each order is routed on its own, in the order it was drawn, so a buy and a sell in
+    /// the same step see each other's price impact.
+    #[default]
+    Sequential,
+    /// The step's buys and sells (all sized in Y) are netted into a single order for the
+    /// residual, so offsetting flow leaves the pools as if it had crossed off-pool.
+    Net,
+}
+
+/// Range `SimulationConfig::seeded_reserves` draws each seed's starting pools from.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ReserveRange {
+    /// Bounds of the factor both initial reserves (and so both pools) and the retail order
+    /// size are scaled by, drawn log-uniformly so halving and doubling are equally likely.
+    pub scale_min: f64,
+    pub scale_max: f64,
+    /// Largest initial mispricing of the submission pool, drawn uniformly in
+    /// `±max_mispricing_bps`.
+    pub max_mispricing_bps: f64,
+}
+
+impl Default for ReserveRange {
+    fn default() -> Self {
+        Self {
+            scale_min: 0.1,
+            scale_max: 10.0,
+            max_mispricing_bps: 50.0,
+        }
+    }
+}
+
+/// Off-exchange rebalancing of the submission's inventory; see
+/// `SimulationConfig::inventory_rebalance`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct InventoryRebalance {
+    /// How far the X reserve may drift from where it started, as a fraction of that, before
+    /// it is traded back.
+    pub band: f64,
+    /// Cost of the rebalancing trade at the external venue (spread plus fees), in bps of its
+    /// notional at the fair price.
+    pub cost_bps: f64,
+}
+
+/// Momentum-chasing (or, with a negative strength, contrarian) retail; see
+/// `SimulationConfig::retail_momentum`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct RetailMomentum {
+    /// Steps of fair-price history the retail crowd looks back over.
+    pub window: u32,
+    /// Direction bias a move of several standard deviations adds, approached as `tanh` of
+    /// the move; negative leans against it.
+    pub strength: f64,
+}
+
+/// When a simulation stops.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize)]
+pub enum StopCondition {
+    /// Run exactly `n_steps` steps.
+    #[default]
+    Steps,
+    /// Compare the mean per-step edge of each block of `window` steps with the previous
+    /// block's, and stop at the end of the first block where they differ by less than
+    /// `tolerance`; `n_steps` is the cap.
+    EdgeConverged { window: u32, tolerance: f64 },
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct SimulationConfig {
     pub n_steps: u32,
+    /// Leading steps that trade as usual but are not scored (default 0): their edge is kept
+    /// out of `submission_edge` and its waterfall, so a strategy can settle its state first.
+    /// They count towards `n_steps`.
+    pub warmup_steps: u32,
+    /// Fixed `n_steps` (default), or stop early once the edge settles.
+    pub stop_condition: StopCondition,
     pub initial_price: f64,
     pub initial_x: f64,
     pub initial_y: f64,
+    /// Start the submission pool mispriced: its spot price is `initial_price * (1 + bps / 1e4)`
+    /// with `k = initial_x * initial_y` unchanged (default 0). The normalizer starts at fair.
+    pub initial_mispricing_bps: f64,
+    /// Derive `initial_x`, `initial_y` and `initial_mispricing_bps` from the seed within this
+    /// range (default `None`, fixed reserves). `with_seeded_reserves` resolves it, and
+    /// `HyperparameterVariance::apply` does so for every config it generates; the engine
+    /// refuses an unresolved config.
+    pub seeded_reserves: Option<ReserveRange>,
     pub gbm_mu: f64,
     pub gbm_sigma: f64,
     pub gbm_dt: f64,
+    /// Calendar length of a step, used only to annualize `SimResult::lp_return` (default
+    /// `STEPS_PER_YEAR`, one step a minute).
+    pub steps_per_year: f64,
+    /// Mean-reversion speed of the fair price per unit of `gbm_dt` (default 0, plain GBM):
+    /// each step's log return gains `-mean_reversion * ln(price / initial_price) * dt`,
+    /// pulling the price back toward where it started.
+    pub mean_reversion: f64,
+    /// Price grid (default `None`, continuous): the fair price is reported rounded to the
+    /// nearest multiple, and arbitrageurs leave a pool alone while its spot price is within
+    /// one tick of it.
+    pub tick_size: Option<f64>,
+    /// Oracle latency (default 0): arbitrageurs trade against the fair price from this many
+    /// steps ago, while edge is still marked at the current one.
+    pub oracle_lag_steps: usize,
+    /// Log-return correlation of the optional second reference asset with the primary one.
+    /// A non-zero value makes the price source emit a correlated pair; single-asset runs only
+    /// ever consume the first path, so their results are unaffected.
+    pub correlation: f64,
     pub retail_arrival_rate: f64,
+    /// Trade timing within a step (default one-trade-per-step; `retail_arrival_rate` only
+    /// applies there, `TradeArrival::Poisson` carries its own rates).
+    pub trade_arrival: TradeArrival,
+    /// Order of the arbitrage pass and the retail orders within a step (default arb first).
+    pub step_order: StepOrder,
+    /// Whether a step's offsetting retail orders are routed one by one (default) or netted.
+    pub retail_netting: RetailNetting,
+    /// Reference price edge is marked against (default spot). Routing, arbitrage and the
+    /// end-of-run valuation always use the fair price itself.
+    pub reference_mode: ReferenceMode,
     pub retail_mean_size: f64,
     pub retail_size_sigma: f64,
     pub retail_buy_prob: f64,
+    /// Lean of retail order direction (default 0, `retail_buy_prob` alone): from -1, every
+    /// order sells X, to +1, every order buys X, moving the buy probability that far
+    /// towards the extreme.
+    pub retail_direction_bias: f64,
+    /// Retail that chases recent moves (default `None`, off): at the start of every step
+    /// the fair price's log return over the last `window` steps, in units of its expected
+    /// move under `gbm_sigma`, adds `strength * tanh(z)` to `retail_direction_bias`.
+    pub retail_momentum: Option<RetailMomentum>,
     pub min_arb_profit: f64,
+    /// Dust filter: retail orders and arb trades with a notional below this many Y are not
+    /// filled (default 0, i.e. every trade goes through).
+    pub min_trade_size: f64,
+    /// Partial-fill mode (default off): a retail order is filled on each pool only until its
+    /// average price is `max_impact_bps` worse than a small trade's; the rest is dropped.
+    pub partial_fill: bool,
+    pub max_impact_bps: f64,
+    /// Fee the input token takes on its way into the submission pool (default 0): the
+    /// strategy quotes the gross input, but only `1 - bps / 1e4` of it lands in reserves.
+    pub transfer_fee_bps: f64,
+    /// Liquidity rebate paid to the submission pool on every fill, in bps of the input and
+    /// credited to the input reserve (default 0). Separate from the strategy's own fee.
+    pub maker_rebate_bps: f64,
+    /// Fixed cost in Y charged against the submission's edge for every fill it makes, retail
+    /// or arb (default 0): the gas LPs effectively bear, so many tiny trades cost more than
+    /// a few large ones. The reserves are unaffected.
+    pub per_trade_cost: f64,
+    /// Hard cap on the submission's X reserve (default `None`, unlimited): a fill that would
+    /// leave more X in the pool is refused outright and counted in
+    /// `SimResult::inventory_rejected`.
+    pub max_inventory_x: Option<f64>,
+    /// Hard cap on the submission's Y reserve (default `None`); see `max_inventory_x`.
+    pub max_inventory_y: Option<f64>,
+    /// Inventory management (default `None`, off): at the end of any step where the
+    /// submission's X reserve sits more than `band` away from where it started, the engine
+    /// trades it back there against Y at the fair price, off-exchange, and charges `cost_bps`
+    /// of the notional against edge. The trade stays inside `max_inventory_x` /
+    /// `max_inventory_y` and above the locked reserves, and `after_swap` reports it.
+    pub inventory_rebalance: Option<InventoryRebalance>,
+    /// Protocol-owned X locked in the submission's pool for good (default 0): quotes, fills
+    /// and the reserves the strategy sees only cover what sits above the lock, so the pool
+    /// trades as if it were that much shallower and can never be drained below it.
+    pub locked_reserve_x: f64,
+    /// Protocol-owned Y locked in the submission's pool (default 0); see `locked_reserve_x`.
+    pub locked_reserve_y: f64,
+    /// Decimals of the X token (default 9, the nano scale). Amounts passed to strategies are
+    /// truncated to whole units of the token, so a 6-decimal token moves in steps of 1000
+    /// nano; values above 9 behave as 9.
+    pub decimals_x: u32,
+    /// Decimals of the Y token (default 9); see `decimals_x`.
+    pub decimals_y: u32,
+    /// Arbitrageurs active each step (default: a single zero-threshold agent). With several
+    /// agents the order they reach the pools is shuffled every step.
+    pub arb_agents: Vec<ArbAgent>,
+    /// Iteration cap for the arbitrageur's golden-section search (default 12).
+    pub arb_solver_iters: u32,
+    /// Relative bracket width at which the arbitrage search stops early (default 1e-2,
+    /// i.e. the trade size is pinned down to within ~1%). Any other value also stops once
+    /// the bracket is narrower than 3e-4, below which nano rounding swamps the quotes.
+    pub arb_solver_tolerance: f64,
     pub seed: u64,
     pub norm_fee_bps: u16,
     pub norm_liquidity_mult: f64,
+    /// Quote the reference pool at zero fee instead of `norm_fee_bps` (default off), so the
+    /// edge gap to a normal run isolates what the fee earns from what the curve earns. Only
+    /// the native normalizer reads its fee from storage; other references are unaffected.
+    pub zero_fee_reference: bool,
+    /// Lowest fee in bps the native normalizer may quote at (default 0): `norm_fee_bps`, a
+    /// zero-fee reference and any subsidy are all clamped so its effective fee stays at or
+    /// above this floor, like a real AMM with costs. Other references are unaffected.
+    pub opponent_min_spread_bps: u16,
+    /// Re-price the native normalizer at the start of every step to undercut the submission
+    /// (default `None`, fixed fee): its fee becomes the submission's quoted fee (half its
+    /// round-trip spread on a small probe) less this many bps, never below
+    /// `opponent_min_spread_bps`. Retail still routes to whichever pool quotes better, so a
+    /// wide strategy loses its flow to the competitor. Other references are unaffected.
+    pub opponent_undercut_bps: Option<u16>,
+    /// Liquidity-mining subsidy on the reference pool (default 0): every fill's input is
+    /// topped up by this many bps of itself before it reaches the curve, so the effective
+    /// fee is `norm_fee_bps - norm_subsidy_bps` and may be negative. The top-up lands in the
+    /// input reserve and the curve still never pays out its whole output reserve.
+    pub norm_subsidy_bps: f64,
+    /// Total subsidy the reference pool may receive, in Y (default `None`). Required for a
+    /// non-zero `norm_subsidy_bps`; once spent, fills are no longer subsidized.
+    pub norm_subsidy_budget: Option<f64>,
+    /// Opt-in convenience oracle (default `None`): with `Some(n)`, each pool's quotes carry
+    /// the average of its own spot price at the end of the last `n` steps (seeded with the
+    /// initial spot), appended to the compute_swap data. Strategies never need it; it saves
+    /// reimplementing a TWAP in storage.
+    pub twap_window: Option<u32>,
+    /// Hand the submission an observation of the fair price with every quote (default off),
+    /// appended to the compute_swap data after the TWAP slot. It is re-observed whenever the
+    /// price moves; edge is still marked against the true price.
+    pub price_oracle: bool,
+    /// Noise on the `price_oracle` observation (default 0, a perfect oracle): each one is the
+    /// fair price times `1 + z * oracle_noise_stddev` for a standard normal `z` drawn from a
+    /// stream of its own seeded by `seed`.
+    pub oracle_noise_stddev: f64,
+    /// Record the submission's `k` after every step into `SimResult::k_series` (default off).
+    pub record_k_series: bool,
+    /// Most steps any per-step recording keeps (default `MAX_RECORDED_STEPS`). Recording
+    /// stops there and sets `SimResult::recording_capped`, so a long run with recording
+    /// left on stays bounded.
+    pub max_recorded_steps: u32,
+    /// Debug check (default off): replay every fill on the normalizer pool through its
+    /// closed-form curve in floating point and panic once the simulated reserves drift from
+    /// that replay by more than the engine's per-fill rounding bound.
+    pub check_normalizer_sync: bool,
+    /// Diagnostic (default off): watch whether the submission's `after_swap` ever changes a
+    /// byte of its storage, and set `SimResult::storage_never_written` if it ran but never did.
+    pub check_storage_writes: bool,
+    /// Ablation (default off): never call the submission's `after_swap`, so it quotes from
+    /// its initial storage throughout. The edge gap to a normal run is what its state
+    /// updates are worth.
+    pub ignore_after_swap: bool,
 }
 
 impl Default for SimulationConfig {
     fn default() -> Self {
         Self {
             n_steps: BASELINE_STEPS,
+            warmup_steps: 0,
+            stop_condition: StopCondition::Steps,
             initial_price: INITIAL_PRICE,
             initial_x: INITIAL_X,
             initial_y: INITIAL_Y,
+            initial_mispricing_bps: 0.0,
+            seeded_reserves: None,
             gbm_mu: GBM_MU,
             gbm_sigma: GBM_SIGMA,
             gbm_dt: GBM_DT,
+            steps_per_year: STEPS_PER_YEAR,
+            mean_reversion: 0.0,
+            tick_size: None,
+            oracle_lag_steps: 0,
+            correlation: PRICE_CORRELATION,
             retail_arrival_rate: RETAIL_ARRIVAL_RATE,
+            trade_arrival: TradeArrival::PerStep,
+            step_order: StepOrder::ArbFirst,
+            retail_netting: RetailNetting::Sequential,
+            reference_mode: ReferenceMode::Spot,
             retail_mean_size: RETAIL_MEAN_SIZE,
             retail_size_sigma: RETAIL_SIZE_SIGMA,
             retail_buy_prob: RETAIL_BUY_PROB,
+            retail_direction_bias: 0.0,
+            retail_momentum: None,
             min_arb_profit: MIN_ARB_PROFIT,
+            min_trade_size: 0.0,
+            partial_fill: false,
+            max_impact_bps: MAX_IMPACT_BPS,
+            transfer_fee_bps: 0.0,
+            maker_rebate_bps: 0.0,
+            per_trade_cost: 0.0,
+            max_inventory_x: None,
+            max_inventory_y: None,
+            inventory_rebalance: None,
+            locked_reserve_x: 0.0,
+            locked_reserve_y: 0.0,
+            decimals_x: NANO_DECIMALS,
+            decimals_y: NANO_DECIMALS,
+            arb_agents: vec![ArbAgent::default()],
+            arb_solver_iters: ARB_SOLVER_ITERS,
+            arb_solver_tolerance: ARB_SOLVER_TOLERANCE,
             seed: 0,
             norm_fee_bps: 30,
             norm_liquidity_mult: 1.0,
+            zero_fee_reference: false,
+            opponent_min_spread_bps: 0,
+            opponent_undercut_bps: None,
+            norm_subsidy_bps: 0.0,
+            norm_subsidy_budget: None,
+            twap_window: None,
+            price_oracle: false,
+            oracle_noise_stddev: 0.0,
+            record_k_series: false,
+            max_recorded_steps: MAX_RECORDED_STEPS,
+            check_normalizer_sync: false,
+            check_storage_writes: false,
+            ignore_after_swap: false,
         }
     }
 }
 
+impl SimulationConfig {
+    /// Whether `step` (0-based) falls in the unscored warmup.
+    pub fn phase(&self, step: u32) -> Phase {
+        if step < self.warmup_steps {
+            Phase::Warmup
+        } else {
+            Phase::Measured
+        }
+    }
+
+    /// Fee word written to the normalizer's `storage[0..2]`.
+    pub fn normalizer_fee_word(&self) -> u16 {
+        let word = if self.zero_fee_reference {
+            normalizer::ZERO_FEE_WORD
+        } else {
+            self.norm_fee_bps
+        };
+        if normalizer::fee_bps(word) < self.opponent_min_spread_bps {
+            self.opponent_min_spread_bps
+        } else {
+            word
+        }
+    }
+
+    /// This config with `seeded_reserves` resolved: the initial reserves, and with them the
+    /// retail order size and the arb and dust thresholds, scaled by one factor, and the
+    /// mispricing drawn, both from a stream of their own seeded by `seed` so the other seeded
+    /// draws are unchanged. Scaling the whole market keeps edge per unit of capital
+    /// comparable across seeds. A config without `seeded_reserves` comes back as is.
+    pub fn with_seeded_reserves(&self) -> SimulationConfig {
+        let Some(range) = self.seeded_reserves else {
+            return self.clone();
+        };
+        let mut rng = Pcg64::seed_from_u64(self.seed ^ SEEDED_RESERVES_STREAM);
+        let (lo, hi) = (range.scale_min.ln(), range.scale_max.ln());
+        let scale = if hi > lo { rng.gen_range(lo..hi).exp() } else { range.scale_min };
+        let max_bps = range.max_mispricing_bps.abs();
+        let mispricing = if max_bps > 0.0 {
+            rng.gen_range(-max_bps..=max_bps)
+        } else {
+            0.0
+        };
+        SimulationConfig {
+            initial_x: self.initial_x * scale,
+            initial_y: self.initial_y * scale,
+            retail_mean_size: self.retail_mean_size * scale,
+            min_arb_profit: self.min_arb_profit * scale,
+            min_trade_size: self.min_trade_size * scale,
+            initial_mispricing_bps: mispricing,
+            seeded_reserves: None,
+            ..self.clone()
+        }
+    }
+
+    /// Price the submission pool opens at: `initial_price` moved by `initial_mispricing_bps`
+    /// when that is set (the engine re-balances the reserves to it), else `initial_y / initial_x`.
+    pub fn starting_price(&self) -> f64 {
+        if self.initial_mispricing_bps != 0.0 {
+            self.initial_price * (1.0 + self.initial_mispricing_bps / 10_000.0)
+        } else {
+            self.initial_y / self.initial_x
+        }
+    }
+
+    /// Round-trip spread the reference pool opens with, in bps: its effective fee (after the
+    /// floor and any subsidy) paid once on the way in and once on the way out.
+    pub fn starting_spread_bps(&self) -> f64 {
+        let fee = normalizer::fee_bps(self.normalizer_fee_word()) as f64;
+        let subsidy = if self.norm_subsidy_budget.is_some() {
+            self.normalizer_subsidy_bps()
+        } else {
+            0.0
+        };
+        2.0 * (fee - subsidy)
+    }
+
+    /// Fat-finger checks on the starting market, each naming what looks wrong and a likely
+    /// fix: a reference spread above `MAX_SANE_SPREAD_BPS`, or pools opening more than
+    /// `MAX_SANE_PRICE_RATIO` away from `initial_price`. Empty for a sane config.
+    pub fn sanity_warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let reserves_ok = [self.initial_x, self.initial_y]
+            .iter()
+            .all(|r| r.is_finite() && *r > 0.0);
+        if !reserves_ok {
+            warnings.push(format!(
+                "initial reserves {} X / {} Y must both be positive; the default pool is {} X / {} Y",
+                self.initial_x, self.initial_y, INITIAL_X, INITIAL_Y
+            ));
+        } else {
+            let price = self.starting_price();
+            let ratio = price / self.initial_price;
+            let sane = 1.0 / MAX_SANE_PRICE_RATIO..=MAX_SANE_PRICE_RATIO;
+            if !sane.contains(&ratio) {
+                warnings.push(format!(
+                    "pools open at price {} but the fair price starts at {}; for initial_x {} set initial_y to {} (or initial_price to {})",
+                    price,
+                    self.initial_price,
+                    self.initial_x,
+                    self.initial_x * self.initial_price,
+                    price
+                ));
+            }
+        }
+        let spread = self.starting_spread_bps();
+        if spread > MAX_SANE_SPREAD_BPS {
+            warnings.push(format!(
+                "the reference pool opens with a {} bps round-trip spread (norm_fee_bps {} each way); fees are in bps, so 30 means 0.3% — did you mean {}?",
+                spread,
+                self.norm_fee_bps,
+                self.norm_fee_bps / 100
+            ));
+        }
+        warnings
+    }
+
+    /// `norm_subsidy_bps`, cut back so the subsidized fee stays at `opponent_min_spread_bps`.
+    pub fn normalizer_subsidy_bps(&self) -> f64 {
+        if self.opponent_min_spread_bps == 0 {
+            return self.norm_subsidy_bps;
+        }
+        let fee = normalizer::fee_bps(self.normalizer_fee_word());
+        let room = fee.saturating_sub(self.opponent_min_spread_bps);
+        self.norm_subsidy_bps.min(room as f64)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct HyperparameterVariance {
     pub gbm_sigma_min: f64,
@@ -108,6 +583,7 @@ impl HyperparameterVariance {
             seed,
             ..base.clone()
         }
+        .with_seeded_reserves()
     }
 
     pub fn generate_configs(&self, n: u32) -> Vec<SimulationConfig> {
@@ -118,11 +594,100 @@ impl HyperparameterVariance {
 
 #[cfg(test)]
 mod tests {
-    use super::SimulationConfig;
+    use super::{HyperparameterVariance, ReserveRange, SimulationConfig};
 
     #[test]
     fn default_min_arb_profit_is_one_cent() {
         let config = SimulationConfig::default();
         assert!((config.min_arb_profit - 0.01).abs() < 1e-12);
     }
+
+    #[test]
+    fn spread_floor_clamps_fee_and_subsidy() {
+        let config = SimulationConfig {
+            norm_fee_bps: 40,
+            norm_subsidy_bps: 25.0,
+            opponent_min_spread_bps: 20,
+            ..SimulationConfig::default()
+        };
+        assert_eq!(config.normalizer_fee_word(), 40);
+        assert_eq!(config.normalizer_subsidy_bps(), 20.0);
+
+        let zero_fee = SimulationConfig {
+            zero_fee_reference: true,
+            ..config.clone()
+        };
+        assert_eq!(zero_fee.normalizer_fee_word(), 20);
+        assert_eq!(zero_fee.normalizer_subsidy_bps(), 0.0);
+        // The default fee word 0 means 30 bps, already above the floor.
+        let default_fee = SimulationConfig {
+            norm_fee_bps: 0,
+            ..config
+        };
+        assert_eq!(default_fee.normalizer_fee_word(), 0);
+    }
+
+    #[test]
+    fn seeded_reserves_are_deterministic_per_seed_and_within_range() {
+        let range = ReserveRange::default();
+        let resolve = |seed| {
+            SimulationConfig {
+                seed,
+                seeded_reserves: Some(range),
+                ..SimulationConfig::default()
+            }
+            .with_seeded_reserves()
+        };
+        let first = resolve(7);
+        assert_eq!(first.initial_x, resolve(7).initial_x);
+        assert_eq!(first.initial_mispricing_bps, resolve(7).initial_mispricing_bps);
+        assert_ne!(first.initial_x, resolve(8).initial_x);
+        // Resolving is idempotent and leaves the price and the reserve ratio alone.
+        assert_eq!(first.with_seeded_reserves().initial_x, first.initial_x);
+        assert!(first.seeded_reserves.is_none());
+        let default = SimulationConfig::default();
+        for seed in 0..100 {
+            let config = resolve(seed);
+            let scale = config.initial_x / default.initial_x;
+            assert!((range.scale_min..=range.scale_max).contains(&scale), "{scale}");
+            assert!((config.initial_y / default.initial_y - scale).abs() < 1e-9);
+            assert!((config.retail_mean_size / default.retail_mean_size - scale).abs() < 1e-9);
+            assert!(config.initial_mispricing_bps.abs() <= range.max_mispricing_bps);
+            assert_eq!(config.gbm_sigma, default.gbm_sigma);
+        }
+    }
+
+    #[test]
+    fn absurd_fee_or_reserves_warn_and_a_sane_start_is_silent() {
+        let default = SimulationConfig::default();
+        assert!(default.sanity_warnings().is_empty());
+        assert_eq!(default.starting_price(), 100.0);
+        assert_eq!(default.starting_spread_bps(), 60.0);
+        let varied = HyperparameterVariance::default();
+        assert!((0..50).all(|seed| varied.apply(&default, seed).sanity_warnings().is_empty()));
+
+        // A fee entered as a percentage times 100 too many: 30% each way.
+        let fee = SimulationConfig {
+            norm_fee_bps: 3_000,
+            ..SimulationConfig::default()
+        };
+        let warnings = fee.sanity_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("6000 bps") && warnings[0].contains("did you mean 30?"));
+
+        // Y reserve typed with three zeros too many: the pool opens at 100000.
+        let reserves = SimulationConfig {
+            initial_y: 10_000_000.0,
+            ..SimulationConfig::default()
+        };
+        let warnings = reserves.sanity_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("set initial_y to 10000"), "{}", warnings[0]);
+
+        let empty = SimulationConfig {
+            initial_x: 0.0,
+            ..SimulationConfig::default()
+        };
+        assert!(empty.sanity_warnings()[0].contains("must both be positive"));
+    }
 }
@@ -1,15 +1,63 @@
 /// Instruction data layout for compute_swap (25 bytes base + 1024 storage):
 /// | Offset    | Size | Field        | Type | Description                    |
 /// |-----------|------|--------------|------|--------------------------------|
-/// | 0         | 1    | side         | u8   | 0=buy X (Y input), 1=sell X   |
+/// | 0         | 1    | side         | u8   | 0=buy X (Y input), 1=sell X; see `Side` |
 /// | 1         | 8    | input_amount | u64  | Input token amount (1e9 scale) |
 /// | 9         | 8    | reserve_x    | u64  | Current X reserve (1e9 scale)  |
 /// | 17        | 8    | reserve_y    | u64  | Current Y reserve (1e9 scale)  |
 /// | 25        | 1024 | storage      | [u8] | Read-only strategy storage     |
-
+/// | 1049      | 8    | twap         | u64  | Optional: spot-price TWAP      |
+/// | 1057      | 8    | price        | u64  | Optional: reference-price oracle |
+///
+/// The trailing fields (1e9 scale, Y per X) are only present when the simulation enables
+/// them; strategies detect them by the data length. `twap` is present from
+/// `SWAP_INSTRUCTION_WITH_ORACLE_SIZE` on, and `price` adds a second word
+/// (`SWAP_INSTRUCTION_WITH_PRICE_SIZE`), with a zero `twap` when there is no TWAP.
 pub const INSTRUCTION_SIZE: usize = 25;
 pub const STORAGE_SIZE: usize = 1024;
 pub const SWAP_INSTRUCTION_SIZE: usize = INSTRUCTION_SIZE + STORAGE_SIZE; // 1049
+pub const ORACLE_SIZE: usize = 8;
+pub const SWAP_INSTRUCTION_WITH_ORACLE_SIZE: usize = SWAP_INSTRUCTION_SIZE + ORACLE_SIZE; // 1057
+pub const SWAP_INSTRUCTION_WITH_PRICE_SIZE: usize = SWAP_INSTRUCTION_WITH_ORACLE_SIZE + ORACLE_SIZE; // 1065
+
+/// Oracle words appended to a compute_swap payload, each in 1e9 scale (Y per X).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Oracle {
+    /// The pool's own spot-price TWAP.
+    pub twap: Option<u64>,
+    /// An observation of the reference price, possibly noisy.
+    pub price: Option<u64>,
+}
+
+impl Oracle {
+    /// Only a TWAP.
+    pub fn twap(twap: u64) -> Self {
+        Self {
+            twap: Some(twap),
+            price: None,
+        }
+    }
+
+    /// The trailing bytes these oracles add to a payload.
+    pub fn encoded_len(&self) -> usize {
+        match (self.twap, self.price) {
+            (_, Some(_)) => 2 * ORACLE_SIZE,
+            (Some(_), None) => ORACLE_SIZE,
+            (None, None) => 0,
+        }
+    }
+
+    /// Write the trailing words into `out`, which must hold `encoded_len` bytes.
+    pub fn encode_into(&self, out: &mut [u8]) {
+        if self.encoded_len() == 0 {
+            return;
+        }
+        out[..ORACLE_SIZE].copy_from_slice(&self.twap.unwrap_or(0).to_le_bytes());
+        if let Some(price) = self.price {
+            out[ORACLE_SIZE..2 * ORACLE_SIZE].copy_from_slice(&price.to_le_bytes());
+        }
+    }
+}
 
 /// after_swap instruction layout (1066 bytes):
 /// | Offset    | Size | Field         | Type | Description                    |
@@ -24,6 +72,105 @@ pub const SWAP_INSTRUCTION_SIZE: usize = INSTRUCTION_SIZE + STORAGE_SIZE; // 104
 /// | 42        | 1024 | storage       | [u8] | Current storage state          |
 pub const AFTER_SWAP_SIZE: usize = 42 + STORAGE_SIZE; // 1066
 
+/// Optional batched compute_swap layout (2 + 25*N + 1024 bytes):
+/// | Offset    | Size | Field    | Type | Description                              |
+/// |-----------|------|----------|------|------------------------------------------|
+/// | 0         | 1    | tag      | u8   | Always 5 (`BATCH_TAG`)                   |
+/// | 1         | 1    | count    | u8   | N requests, 1..=`MAX_BATCH`              |
+/// | 2         | 25*N | requests | -    | side, input, reserve_x, reserve_y each, as in compute_swap |
+/// | 2 + 25*N  | 1024 | storage  | [u8] | Read-only strategy storage               |
+///
+/// A program that supports it returns N little-endian u64 outputs (8*N bytes) in request
+/// order. Programs that don't are never sent it: single-op payloads stay the default.
+pub const BATCH_TAG: u8 = 5;
+pub const MAX_BATCH: usize = 32;
+pub const BATCH_HEADER_SIZE: usize = 2;
+pub const MAX_BATCH_INSTRUCTION_SIZE: usize = batch_instruction_size(MAX_BATCH); // 1826
+
+/// Size of a batched payload carrying `count` requests.
+pub const fn batch_instruction_size(count: usize) -> usize {
+    BATCH_HEADER_SIZE + count * INSTRUCTION_SIZE + STORAGE_SIZE
+}
+
+/// The `side` byte. It shares byte 0 with the other instruction tags (2 = after_swap,
+/// 3 = name, 4 = model, 5 = `BATCH_TAG`), so the exact-out reservation sits past them. The
+/// engine only ever sends `BuyX` and `SellX`; exact-out quotes
+/// (`BpfAmm::quote_buy_x_exact_out`) are computed host-side by inverting the exact-in
+/// curve, so strategies never see a desired output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Side {
+    BuyX = 0,
+    SellX = 1,
+    ExactOutBuy = 6,
+    ExactOutSell = 7,
+}
+
+impl Side {
+    /// `side` if it is one the engine sends today, `BuyX` or `SellX`.
+    pub fn exact_in(side: u8) -> Result<Self, DecodeError> {
+        match Side::try_from(side)? {
+            side @ (Side::BuyX | Side::SellX) => Ok(side),
+            _ => Err(DecodeError::ReservedSide(side)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// A side byte outside `Side`.
+    InvalidSide(u8),
+    /// An exact-out side byte, reserved but not sent yet.
+    ReservedSide(u8),
+    /// A batch request count outside `1..=MAX_BATCH`.
+    InvalidBatchCount(usize),
+    /// Fewer bytes than the layout needs.
+    Truncated { expected: usize, actual: usize },
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::InvalidSide(side) => {
+                write!(f, "invalid side byte {} (expected 0, 1, 6 or 7)", side)
+            }
+            DecodeError::ReservedSide(side) => {
+                write!(f, "side byte {} is reserved for exact-out quotes", side)
+            }
+            DecodeError::InvalidBatchCount(count) => {
+                write!(
+                    f,
+                    "batch of {} requests (expected 1..={})",
+                    count, MAX_BATCH
+                )
+            }
+            DecodeError::Truncated { expected, actual } => {
+                write!(
+                    f,
+                    "{} bytes of instruction data, expected {}",
+                    actual, expected
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl TryFrom<u8> for Side {
+    type Error = DecodeError;
+
+    fn try_from(side: u8) -> Result<Self, DecodeError> {
+        match side {
+            0 => Ok(Side::BuyX),
+            1 => Ok(Side::SellX),
+            6 => Ok(Side::ExactOutBuy),
+            7 => Ok(Side::ExactOutSell),
+            other => Err(DecodeError::InvalidSide(other)),
+        }
+    }
+}
+
 pub fn encode_instruction(
     side: u8,
     input_amount: u64,
@@ -46,6 +193,12 @@ pub fn decode_instruction(data: &[u8]) -> (u8, u64, u64, u64) {
     (side, input_amount, reserve_x, reserve_y)
 }
 
+/// `decode_instruction` with the side checked against `Side`.
+pub fn try_decode_instruction(data: &[u8]) -> Result<(Side, u64, u64, u64), DecodeError> {
+    let (side, input_amount, reserve_x, reserve_y) = decode_instruction(data);
+    Ok((Side::try_from(side)?, input_amount, reserve_x, reserve_y))
+}
+
 pub fn encode_swap_instruction(
     side: u8,
     input_amount: u64,
@@ -63,6 +216,102 @@ pub fn encode_swap_instruction(
     data
 }
 
+/// `encode_swap_instruction`, followed by whichever of `oracle`'s words are given.
+pub fn encode_swap_instruction_with_oracle(
+    side: u8,
+    input_amount: u64,
+    reserve_x: u64,
+    reserve_y: u64,
+    storage: &[u8],
+    oracle: Oracle,
+) -> Vec<u8> {
+    let mut data = encode_swap_instruction(side, input_amount, reserve_x, reserve_y, storage);
+    data.resize(SWAP_INSTRUCTION_SIZE + oracle.encoded_len(), 0);
+    oracle.encode_into(&mut data[SWAP_INSTRUCTION_SIZE..]);
+    data
+}
+
+/// The TWAP oracle of a compute_swap payload, if the simulation provides one.
+pub fn decode_oracle(data: &[u8]) -> Option<u64> {
+    let bytes = data.get(SWAP_INSTRUCTION_SIZE..SWAP_INSTRUCTION_WITH_ORACLE_SIZE)?;
+    Some(u64::from_le_bytes(bytes.try_into().unwrap())).filter(|&twap| twap != 0)
+}
+
+/// The reference-price oracle of a compute_swap payload, if the simulation provides one.
+pub fn decode_price_oracle(data: &[u8]) -> Option<u64> {
+    let bytes = data.get(SWAP_INSTRUCTION_WITH_ORACLE_SIZE..SWAP_INSTRUCTION_WITH_PRICE_SIZE)?;
+    Some(u64::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// One compute_swap request inside a batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwapRequest {
+    pub side: u8,
+    pub input_amount: u64,
+    pub reserve_x: u64,
+    pub reserve_y: u64,
+}
+
+/// Batched compute_swap payload for `requests`, which all see the same `storage`.
+pub fn encode_batch_instruction(
+    requests: &[SwapRequest],
+    storage: &[u8],
+) -> Result<Vec<u8>, DecodeError> {
+    if requests.is_empty() || requests.len() > MAX_BATCH {
+        return Err(DecodeError::InvalidBatchCount(requests.len()));
+    }
+    let mut data = vec![0u8; batch_instruction_size(requests.len())];
+    data[0] = BATCH_TAG;
+    data[1] = requests.len() as u8;
+    for (chunk, request) in data[BATCH_HEADER_SIZE..]
+        .chunks_exact_mut(INSTRUCTION_SIZE)
+        .zip(requests)
+    {
+        chunk.copy_from_slice(&encode_instruction(
+            request.side,
+            request.input_amount,
+            request.reserve_x,
+            request.reserve_y,
+        ));
+    }
+    let at = BATCH_HEADER_SIZE + requests.len() * INSTRUCTION_SIZE;
+    let copy_len = storage.len().min(STORAGE_SIZE);
+    data[at..at + copy_len].copy_from_slice(&storage[..copy_len]);
+    Ok(data)
+}
+
+/// Requests and storage of a batched payload, with every side checked against `Side`.
+pub fn decode_batch_instruction(data: &[u8]) -> Result<(Vec<SwapRequest>, &[u8]), DecodeError> {
+    let count = *data.get(1).ok_or(DecodeError::Truncated {
+        expected: batch_instruction_size(1),
+        actual: data.len(),
+    })? as usize;
+    if count == 0 || count > MAX_BATCH {
+        return Err(DecodeError::InvalidBatchCount(count));
+    }
+    let expected = batch_instruction_size(count);
+    if data.len() < expected {
+        return Err(DecodeError::Truncated {
+            expected,
+            actual: data.len(),
+        });
+    }
+    let storage_at = BATCH_HEADER_SIZE + count * INSTRUCTION_SIZE;
+    let requests = data[BATCH_HEADER_SIZE..storage_at]
+        .chunks_exact(INSTRUCTION_SIZE)
+        .map(|chunk| {
+            let (side, input_amount, reserve_x, reserve_y) = try_decode_instruction(chunk)?;
+            Ok(SwapRequest {
+                side: side as u8,
+                input_amount,
+                reserve_x,
+                reserve_y,
+            })
+        })
+        .collect::<Result<_, DecodeError>>()?;
+    Ok((requests, &data[storage_at..expected]))
+}
+
 pub fn encode_after_swap(
     side: u8,
     input_amount: u64,
@@ -124,6 +373,37 @@ mod tests {
         assert_eq!(y, ry);
     }
 
+    #[test]
+    fn test_side_decoding() {
+        for (byte, side) in [
+            (0, Side::BuyX),
+            (1, Side::SellX),
+            (6, Side::ExactOutBuy),
+            (7, Side::ExactOutSell),
+        ] {
+            let encoded = encode_instruction(byte, 5, 6, 7);
+            assert_eq!(try_decode_instruction(&encoded), Ok((side, 5, 6, 7)));
+            assert_eq!(side as u8, byte);
+        }
+
+        // The after_swap, name, model and batch tags are never sides.
+        for tag in [2, 3, 4, BATCH_TAG, 8] {
+            let encoded = encode_instruction(tag, 5, 6, 7);
+            assert_eq!(
+                try_decode_instruction(&encoded),
+                Err(DecodeError::InvalidSide(tag))
+            );
+        }
+        assert_eq!(
+            DecodeError::InvalidSide(4).to_string(),
+            "invalid side byte 4 (expected 0, 1, 6 or 7)"
+        );
+
+        assert_eq!(Side::exact_in(1), Ok(Side::SellX));
+        assert_eq!(Side::exact_in(6), Err(DecodeError::ReservedSide(6)));
+        assert_eq!(Side::exact_in(2), Err(DecodeError::InvalidSide(2)));
+    }
+
     #[test]
     fn test_swap_instruction_with_storage() {
         let storage = [0xAB; STORAGE_SIZE];
@@ -137,6 +417,93 @@ mod tests {
         assert_eq!(&data[25..], &storage[..]);
     }
 
+    #[test]
+    fn test_oracle_is_appended_after_storage() {
+        let storage = [0xAB; STORAGE_SIZE];
+        let plain = encode_swap_instruction_with_oracle(1, 10, 20, 30, &storage, Oracle::default());
+        assert_eq!(plain, encode_swap_instruction(1, 10, 20, 30, &storage));
+        assert_eq!(decode_oracle(&plain), None);
+        assert_eq!(decode_price_oracle(&plain), None);
+
+        let data = encode_swap_instruction_with_oracle(
+            1,
+            10,
+            20,
+            30,
+            &storage,
+            Oracle::twap(99_500_000_000),
+        );
+        assert_eq!(data.len(), SWAP_INSTRUCTION_WITH_ORACLE_SIZE);
+        assert_eq!(decode_instruction(&data), (1, 10, 20, 30));
+        assert_eq!(&data[25..SWAP_INSTRUCTION_SIZE], &storage[..]);
+        assert_eq!(decode_oracle(&data), Some(99_500_000_000));
+        assert_eq!(decode_price_oracle(&data), None);
+    }
+
+    #[test]
+    fn test_price_oracle_follows_the_twap_slot() {
+        let storage = [0xAB; STORAGE_SIZE];
+        let price_only = Oracle {
+            twap: None,
+            price: Some(101_000_000_000),
+        };
+        let data = encode_swap_instruction_with_oracle(0, 10, 20, 30, &storage, price_only);
+        assert_eq!(data.len(), SWAP_INSTRUCTION_WITH_PRICE_SIZE);
+        assert_eq!(decode_oracle(&data), None);
+        assert_eq!(decode_price_oracle(&data), Some(101_000_000_000));
+
+        let both = Oracle {
+            twap: Some(99_000_000_000),
+            ..price_only
+        };
+        let data = encode_swap_instruction_with_oracle(0, 10, 20, 30, &storage, both);
+        assert_eq!(decode_oracle(&data), Some(99_000_000_000));
+        assert_eq!(decode_price_oracle(&data), Some(101_000_000_000));
+    }
+
+    #[test]
+    fn test_batch_roundtrip() {
+        let storage = [0xEF; STORAGE_SIZE];
+        let requests: Vec<SwapRequest> = (0..MAX_BATCH as u64)
+            .map(|i| SwapRequest {
+                side: (i % 2) as u8,
+                input_amount: 1_000 * (i + 1),
+                reserve_x: 100_000_000_000 + i,
+                reserve_y: 10_000_000_000_000 - i,
+            })
+            .collect();
+
+        let data = encode_batch_instruction(&requests, &storage).unwrap();
+        assert_eq!(data.len(), MAX_BATCH_INSTRUCTION_SIZE);
+        assert_eq!(data[0], BATCH_TAG);
+        // Each request is laid out exactly like a single compute_swap header.
+        assert_eq!(
+            data[2 + 25..2 + 50],
+            encode_instruction(1, 2_000, 100_000_000_001, 9_999_999_999_999)
+        );
+        let (decoded, decoded_storage) = decode_batch_instruction(&data).unwrap();
+        assert_eq!(decoded, requests);
+        assert_eq!(decoded_storage, &storage[..]);
+
+        assert_eq!(
+            encode_batch_instruction(&[], &storage),
+            Err(DecodeError::InvalidBatchCount(0))
+        );
+        assert_eq!(
+            decode_batch_instruction(&data[..100]),
+            Err(DecodeError::Truncated {
+                expected: MAX_BATCH_INSTRUCTION_SIZE,
+                actual: 100
+            })
+        );
+        let mut bad_side = data.clone();
+        bad_side[2 + 25] = 9;
+        assert_eq!(
+            decode_batch_instruction(&bad_side),
+            Err(DecodeError::InvalidSide(9))
+        );
+    }
+
     #[test]
     fn test_after_swap_roundtrip() {
         let storage = [0xCD; STORAGE_SIZE];
@@ -3,3 +3,5 @@ pub mod instruction;
 pub mod nano;
 pub mod normalizer;
 pub mod result;
+pub mod strategies;
+pub mod sum;
@@ -1,8 +1,32 @@
 pub const NANO_SCALE: u64 = 1_000_000_000;
 pub const NANO_SCALE_F64: f64 = 1_000_000_000.0;
+/// Decimals of the nano scale: one nano is `10^-NANO_DECIMALS` of a token.
+pub const NANO_DECIMALS: u32 = 9;
 
+/// How `f64_to_nano_with` settles a fractional nano.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoundingMode {
+    /// Down: what an on-chain program should pay out.
+    Floor,
+    /// Up: what an on-chain program should charge as input.
+    Ceil,
+    /// To the closest nano, halves away from zero.
+    Nearest,
+    /// Drop the fraction; the same as `Floor`, since negative values clamp to zero.
+    TowardZero,
+}
+
+/// `value` in nano units, truncated (`RoundingMode::TowardZero`). NaN and non-positive
+/// values give 0; anything at or past `u64::MAX` nano saturates.
 #[inline]
 pub fn f64_to_nano(value: f64) -> u64 {
+    f64_to_nano_with(value, RoundingMode::TowardZero)
+}
+
+/// `f64_to_nano` with an explicit rounding of the fractional nano. Uses no float
+/// intrinsics, so it works unchanged in `no_std` strategy code.
+#[inline]
+pub fn f64_to_nano_with(value: f64, mode: RoundingMode) -> u64 {
     if value.is_nan() || value <= 0.0 {
         return 0;
     }
@@ -11,10 +35,26 @@ pub fn f64_to_nano(value: f64) -> u64 {
     }
     let scaled = value * NANO_SCALE_F64;
     if scaled >= u64::MAX as f64 {
-        u64::MAX
-    } else {
-        scaled as u64
+        return u64::MAX;
+    }
+    let whole = scaled as u64;
+    let fraction = scaled - whole as f64;
+    let up = match mode {
+        RoundingMode::Floor | RoundingMode::TowardZero => false,
+        RoundingMode::Ceil => fraction > 0.0,
+        RoundingMode::Nearest => fraction >= 0.5,
+    };
+    whole.saturating_add(up as u64)
+}
+
+/// `value` in nano units (truncated), or `None` when it is NaN, negative, infinite or at or
+/// past `u64::MAX` nano, where `f64_to_nano` would clamp or saturate.
+#[inline]
+pub fn checked_f64_to_nano(value: f64) -> Option<u64> {
+    if value.is_nan() || value < 0.0 || value * NANO_SCALE_F64 >= u64::MAX as f64 {
+        return None;
     }
+    Some(f64_to_nano(value))
 }
 
 #[inline]
@@ -41,10 +81,42 @@ mod tests {
         assert_eq!(nano_to_f64(NANO_SCALE), 1.0);
     }
 
+    #[test]
+    fn test_rounding_modes_split_at_half_nano() {
+        use RoundingMode::*;
+        let round = |value, mode| f64_to_nano_with(value, mode);
+        // 1.5 and 1_000_000_002.5 nano are exact in f64, so each mode's rule alone decides.
+        for (value, whole) in [(1.5e-9, 1), (1.000_000_002_5, 1_000_000_002)] {
+            assert_eq!(round(value, Floor), whole);
+            assert_eq!(round(value, TowardZero), whole);
+            assert_eq!(round(value, Ceil), whole + 1);
+            assert_eq!(round(value, Nearest), whole + 1);
+        }
+        assert_eq!(round(1.25e-9, Nearest), 1);
+        assert_eq!(round(1.25e-9, Ceil), 2);
+        for mode in [Floor, Ceil, Nearest, TowardZero] {
+            assert_eq!(round(2.0, mode), 2 * NANO_SCALE);
+            assert_eq!(round(-0.3e-9, mode), 0);
+            assert_eq!(round(f64::NAN, mode), 0);
+            assert_eq!(round(f64::INFINITY, mode), u64::MAX);
+        }
+        assert_eq!(f64_to_nano(1.5e-9), round(1.5e-9, TowardZero));
+    }
+
     #[test]
     fn test_invalid_values_clamp_to_zero() {
         assert_eq!(f64_to_nano(-1.0), 0);
         assert_eq!(f64_to_nano(f64::NAN), 0);
         assert_eq!(f64_to_nano(f64::INFINITY), u64::MAX);
     }
+
+    #[test]
+    fn test_checked_conversion_refuses_what_would_clamp_or_saturate() {
+        assert_eq!(checked_f64_to_nano(0.0), Some(0));
+        assert_eq!(checked_f64_to_nano(1.5), Some(f64_to_nano(1.5)));
+        assert_eq!(checked_f64_to_nano(1.8e10), Some(f64_to_nano(1.8e10)));
+        for value in [-1.0, f64::NAN, f64::INFINITY, 1.9e10, 1e300] {
+            assert_eq!(checked_f64_to_nano(value), None, "{value}");
+        }
+    }
 }
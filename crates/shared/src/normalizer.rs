@@ -1,3 +1,26 @@
+/// Fee charged when the storage fee word (`storage[0..2]`) is zero.
+pub const DEFAULT_FEE_BPS: u16 = 30;
+/// Storage fee word that makes the normalizer quote with no fee at all, since zero already
+/// means the default.
+pub const ZERO_FEE_WORD: u16 = u16::MAX;
+
+/// Fee in bps the normalizer charges for the storage fee word `word`.
+pub fn fee_bps(word: u16) -> u16 {
+    match word {
+        0 => DEFAULT_FEE_BPS,
+        ZERO_FEE_WORD => 0,
+        fee => fee,
+    }
+}
+
+/// Storage fee word that makes the normalizer charge `bps`; the inverse of `fee_bps`.
+pub fn fee_word(bps: u16) -> u16 {
+    match bps {
+        0 => ZERO_FEE_WORD,
+        bps => bps,
+    }
+}
+
 /// Native normalizer swap function (30bp CFMM).
 /// Takes instruction data (25+ bytes, extra storage bytes ignored), returns output amount.
 pub fn compute_swap(data: &[u8]) -> u64 {
@@ -21,10 +44,9 @@ pub fn compute_swap(data: &[u8]) -> u64 {
     }
 
     let fee_bps = if data.len() >= 27 {
-        let raw = u16::from_le_bytes([data[25], data[26]]);
-        if raw == 0 { 30u128 } else { raw as u128 }
+        fee_bps(u16::from_le_bytes([data[25], data[26]])) as u128
     } else {
-        30u128
+        DEFAULT_FEE_BPS as u128
     };
 
     let k = reserve_x * reserve_y;
@@ -33,12 +55,12 @@ pub fn compute_swap(data: &[u8]) -> u64 {
         0 => {
             let net = input_amount * (10000 - fee_bps) / 10000;
             let new_ry = reserve_y + net;
-            reserve_x.saturating_sub((k + new_ry - 1) / new_ry) as u64
+            reserve_x.saturating_sub(k.div_ceil(new_ry)) as u64
         }
         1 => {
             let net = input_amount * (10000 - fee_bps) / 10000;
             let new_rx = reserve_x + net;
-            reserve_y.saturating_sub((k + new_rx - 1) / new_rx) as u64
+            reserve_y.saturating_sub(k.div_ceil(new_rx)) as u64
         }
         _ => 0,
     }
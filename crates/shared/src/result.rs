@@ -1,7 +1,214 @@
+use serde::{Deserialize, Serialize};
+
+/// Where the submission's edge came from. The components sum to the edge: retail spread,
+/// plus the fee charged to arbs for rebalancing the pool, plus the mark-to-market of the
+/// inventory arbs hand over as the fair price moves (adverse selection, usually negative),
+/// less what rebalancing the inventory off-exchange cost.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PnlWaterfall {
+    /// Edge on retail fills, valued at the fair price.
+    pub retail_spread: f64,
+    /// Edge on arb fills, valued at the previous step's fair price.
+    pub arb_spread: f64,
+    /// Arb fills' inventory change marked from the previous fair price to the current one.
+    pub adverse_selection: f64,
+    /// Cost of `SimulationConfig::inventory_rebalance` trades, as a negative number.
+    #[serde(default)]
+    pub inventory_cost: f64,
+}
+
+impl PnlWaterfall {
+    pub fn total(&self) -> f64 {
+        self.retail_spread + self.arb_spread + self.adverse_selection + self.inventory_cost
+    }
+
+    pub fn add(&mut self, other: &Self) {
+        self.retail_spread += other.retail_spread;
+        self.arb_spread += other.arb_spread;
+        self.adverse_selection += other.adverse_selection;
+        self.inventory_cost += other.inventory_cost;
+    }
+
+    pub fn scaled(&self, factor: f64) -> Self {
+        Self {
+            retail_spread: self.retail_spread * factor,
+            arb_spread: self.arb_spread * factor,
+            adverse_selection: self.adverse_selection * factor,
+            inventory_cost: self.inventory_cost * factor,
+        }
+    }
+}
+
+/// What providing the submission's liquidity earned over holding the reserves it entered
+/// the measured steps with (its initial reserves without a warmup), both marked at the
+/// final fair price. Fee revenue plus divergence is that gain; the price move
+/// is what both the LP and the holder made from the price alone, reported for context.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct LpReturn {
+    /// Spread earned on retail and arb fills (`PnlWaterfall` retail plus arb spread).
+    pub fee_revenue: f64,
+    /// Everything else the pool's inventory did relative to holding: impermanent loss net of
+    /// fees, usually negative and growing with the price's excursion.
+    pub divergence: f64,
+    /// Value of those reserves at the final price minus at the fair price they were held from.
+    pub price_move: f64,
+    /// `ln(final pool value / held value)` scaled to a year by
+    /// `SimulationConfig::steps_per_year`: a continuously compounded annual rate.
+    pub annualized_rate: f64,
+}
+
+impl LpReturn {
+    /// Final pool value minus the held value, in Y.
+    pub fn total(&self) -> f64 {
+        self.fee_revenue + self.divergence
+    }
+}
+
+/// Part of a run a step belongs to, split at `SimulationConfig::warmup_steps`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    /// Traded but not scored.
+    Warmup,
+    /// Counted in `submission_edge`.
+    Measured,
+}
+
+impl Phase {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Phase::Warmup => "warmup",
+            Phase::Measured => "measured",
+        }
+    }
+}
+
+/// Steps run and edge earned in one `Phase`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PhaseTotals {
+    pub steps: u32,
+    pub edge: f64,
+}
+
+/// A run's steps and edge on either side of `SimulationConfig::warmup_steps`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PhaseSplit {
+    pub warmup: PhaseTotals,
+    /// `edge` here is the run's `submission_edge`.
+    pub measured: PhaseTotals,
+}
+
+impl PhaseSplit {
+    pub fn scaled(&self, factor: f64) -> Self {
+        let scale = |totals: PhaseTotals| PhaseTotals {
+            edge: totals.edge * factor,
+            ..totals
+        };
+        Self {
+            warmup: scale(self.warmup),
+            measured: scale(self.measured),
+        }
+    }
+}
+
+/// What a simulation caught the submission doing that counts against it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DisqualificationKind {
+    /// Quoted more than twice the output the pool holds (the sim's
+    /// `OVER_QUOTE_FLAG_MULTIPLE`); the quote was treated as zero, as is any smaller excess.
+    OverQuote,
+    /// A fill's input would have grown a reserve past what the nano scale represents; the
+    /// fill was refused.
+    ReserveOverflow,
+    /// A BPF call ran out of compute units.
+    CuExceeded,
+    /// A BPF call trapped or returned no data.
+    ExecutionFailed,
+    /// The simulation scored a non-finite edge.
+    NonFinite,
+    /// The simulation panicked, e.g. on a curve-shape violation.
+    Panic,
+    /// The simulation returned an error.
+    Failed,
+}
+
+/// One disqualifying event. `step` is `None` for failures that end the whole simulation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Disqualification {
+    pub seed: u64,
+    pub step: Option<u32>,
+    pub kind: DisqualificationKind,
+    pub detail: String,
+}
+
 #[derive(Debug, Clone)]
 pub struct SimResult {
     pub seed: u64,
+    /// Steps actually run: `n_steps`, or fewer when the edge converged first.
+    pub steps_run: u32,
+    /// Edge of the measured steps; warmup fills are left out (see `phases`).
     pub submission_edge: f64,
+    /// Steps and edge of the warmup and the measured part of the run.
+    pub phases: PhaseSplit,
+    /// Growth of the submission's invariant `k = reserve_x * reserve_y`: `k_final / k_initial - 1`.
+    pub k_growth: f64,
+    /// Final value of the submission's reserves minus the value of holding the reserves it
+    /// entered the measured steps with, both in Y at the final fair price (see
+    /// `impermanent_loss`).
+    pub impermanent_loss: f64,
+    /// Submission `k` after every step, recorded only when `SimulationConfig::record_k_series` is set.
+    pub k_series: Option<Vec<f64>>,
+    /// Breakdown of `submission_edge` by source, over the measured steps.
+    pub waterfall: PnlWaterfall,
+    /// Disqualifying events during the run, in step order.
+    pub disqualifications: Vec<Disqualification>,
+    /// Submission output quoted for retail orders but not paid out, summed over the measured
+    /// steps, in Y at the fair price. Zero unless partial fills cut orders short.
+    pub total_quote_slippage: f64,
+    /// Largest single-order shortfall included in `total_quote_slippage`.
+    pub max_quote_slippage: f64,
+    /// Input of submission fills refused by `max_inventory_x` / `max_inventory_y`, in Y (X at
+    /// the pool's spot price), over the measured steps. Zero without an inventory limit.
+    pub inventory_rejected: f64,
+    /// Under `SimulationConfig::check_storage_writes`: the submission exports `after_swap` and
+    /// it ran, but storage never changed, so every quote read the initial storage.
+    pub storage_never_written: bool,
+    /// Per-step recording hit `SimulationConfig::max_recorded_steps` and missed later steps.
+    pub recording_capped: bool,
+    /// Bytes held by per-step recording buffers at the end of the run.
+    pub recording_bytes: usize,
+    /// Value of the submission's starting reserves (after any mispricing or randomization)
+    /// in Y at the initial price: the capital its edge was earned on.
+    pub initial_value: f64,
+    /// The LP's return over holding, with its components, over the measured steps.
+    pub lp_return: LpReturn,
+    /// Retail volume in Y the router filled on the submission, then on the opponent pool:
+    /// each order goes to whichever quotes better, split when that beats either alone. Over
+    /// the measured steps.
+    pub retail_volume: [f64; 2],
+}
+
+impl SimResult {
+    /// Share of retail volume (in Y) the submission won; `None` when no retail order filled.
+    pub fn retail_share(&self) -> Option<f64> {
+        share(self.retail_volume)
+    }
+}
+
+fn share([won, lost]: [f64; 2]) -> Option<f64> {
+    let total = won + lost;
+    (total > 0.0).then(|| won / total)
+}
+
+/// Value of `final_reserves` minus the value of `initial` reserves, both `(x, y)` marked at
+/// `price` (Y per X): what providing liquidity gained over holding. Fees kept in the pool
+/// count toward it, so only a fee-free pool shows the textbook loss, e.g.
+/// `2 * sqrt(r) / (1 + r) - 1` of the held value for a constant-product pool after the price
+/// moves by a factor `r`.
+pub fn impermanent_loss(initial: (f64, f64), final_reserves: (f64, f64), price: f64) -> f64 {
+    let value = |(x, y): (f64, f64)| x * price + y;
+    value(final_reserves) - value(initial)
 }
 
 #[derive(Debug, Clone)]
@@ -11,23 +218,173 @@ pub struct BatchResult {
 }
 
 impl BatchResult {
+    /// Batch over `results` in any order. The total is summed in seed order, so it comes out
+    /// bit-identical however many workers ran the batch or in what order they finished.
     pub fn from_results(results: Vec<SimResult>) -> Self {
-        let total_edge = results.iter().map(|r| r.submission_edge).sum();
+        let mut edges: Vec<(u64, f64)> = results
+            .iter()
+            .map(|r| (r.seed, r.submission_edge))
+            .collect();
+        edges.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+        let total_edge = edges.iter().map(|&(_, edge)| edge).sum();
         Self {
             results,
             total_edge,
         }
     }
 
+    /// Combine two batches into one ordered by seed, e.g. to grow a sweep without rerunning
+    /// it. A seed present in both keeps the result from `self`.
+    pub fn merge(self, other: BatchResult) -> Self {
+        let mut results = self.results;
+        results.extend(other.results);
+        // Stable sort, so the first result for each seed is the one from `self`.
+        results.sort_by_key(|r| r.seed);
+        results.dedup_by_key(|r| r.seed);
+        Self::from_results(results)
+    }
+
     pub fn n_sims(&self) -> usize {
         self.results.len()
     }
 
-    pub fn avg_edge(&self) -> f64 {
+    /// Mean edge per simulation; `None` for an empty batch, which has no edge to average.
+    pub fn avg_edge(&self) -> Option<f64> {
+        (!self.results.is_empty()).then(|| self.total_edge / self.results.len() as f64)
+    }
+
+    /// Edge at quantile `q` (0 to 1) across the batch, interpolating linearly between
+    /// neighbouring seeds, e.g. `edge_quantile(0.05)` for the 5th-percentile edge. `None` for
+    /// an empty batch.
+    pub fn edge_quantile(&self, q: f64) -> Option<f64> {
+        let mut edges: Vec<f64> = self.results.iter().map(|r| r.submission_edge).collect();
+        if edges.is_empty() {
+            return None;
+        }
+        edges.sort_by(f64::total_cmp);
+        let rank = q.clamp(0.0, 1.0) * (edges.len() - 1) as f64;
+        let lo = rank.floor() as usize;
+        let hi = rank.ceil() as usize;
+        Some(edges[lo] + (edges[hi] - edges[lo]) * (rank - lo as f64))
+    }
+
+    /// Per-simulation average of `total_quote_slippage`; `None` for an empty batch.
+    pub fn avg_quote_slippage(&self) -> Option<f64> {
+        (!self.results.is_empty()).then(|| {
+            self.results
+                .iter()
+                .map(|r| r.total_quote_slippage)
+                .sum::<f64>()
+                / self.results.len() as f64
+        })
+    }
+
+    /// Largest single-order quote shortfall across the batch; `None` for an empty batch.
+    pub fn max_quote_slippage(&self) -> Option<f64> {
+        (!self.results.is_empty()).then(|| {
+            self.results
+                .iter()
+                .map(|r| r.max_quote_slippage)
+                .fold(0.0, f64::max)
+        })
+    }
+
+    /// Bytes held by every simulation's recording buffers.
+    pub fn recording_bytes(&self) -> usize {
+        self.results.iter().map(|r| r.recording_bytes).sum()
+    }
+
+    /// Share of all retail volume (in Y) across the batch that the submission won.
+    pub fn retail_share(&self) -> Option<f64> {
+        share(self.results.iter().fold([0.0, 0.0], |[won, lost], r| {
+            [won + r.retail_volume[0], lost + r.retail_volume[1]]
+        }))
+    }
+
+    /// Per-simulation average of each waterfall component; `None` for an empty batch.
+    pub fn avg_waterfall(&self) -> Option<PnlWaterfall> {
         if self.results.is_empty() {
-            0.0
-        } else {
-            self.total_edge / self.results.len() as f64
+            return None;
+        }
+        let mut total = PnlWaterfall::default();
+        for result in &self.results {
+            total.add(&result.waterfall);
+        }
+        Some(total.scaled(1.0 / self.results.len() as f64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{impermanent_loss, BatchResult, SimResult};
+
+    fn result(seed: u64, submission_edge: f64) -> SimResult {
+        SimResult {
+            seed,
+            steps_run: 100,
+            submission_edge,
+            phases: Default::default(),
+            k_growth: 0.0,
+            impermanent_loss: 0.0,
+            k_series: None,
+            waterfall: Default::default(),
+            disqualifications: Vec::new(),
+            total_quote_slippage: 0.0,
+            max_quote_slippage: 0.0,
+            inventory_rejected: 0.0,
+            storage_never_written: false,
+            recording_capped: false,
+            recording_bytes: 0,
+            initial_value: 20_000.0,
+            lp_return: Default::default(),
+            retail_volume: [0.0; 2],
+        }
+    }
+
+    #[test]
+    fn constant_product_loss_matches_textbook_formula() {
+        let (x0, y0): (f64, f64) = (100.0, 10_000.0);
+        let (k, p0) = (x0 * y0, y0 / x0);
+        for r in [0.25, 0.5, 1.0, 1.5, 4.0] {
+            // Arbs move a fee-free pool along x * y = k until its spot matches the new price.
+            let price = p0 * r;
+            let pool = ((k / price).sqrt(), (k * price).sqrt());
+            let held = x0 * price + y0;
+            let textbook = (2.0 * f64::sqrt(r) / (1.0 + r) - 1.0) * held;
+            let il = impermanent_loss((x0, y0), pool, price);
+            assert!(
+                (il - textbook).abs() < 1e-9 * held,
+                "r {r}: {il} vs {textbook}"
+            );
         }
+        assert_eq!(
+            impermanent_loss((x0, y0), (50.0, 20_000.0), 400.0),
+            -10_000.0
+        );
+    }
+
+    #[test]
+    fn total_edge_does_not_depend_on_result_order() {
+        // Added as listed, 1e16 + 1 rounds the 1 away and the total comes out 0; in seed
+        // order the large edges cancel first and it is 1. Workers finish in any order.
+        let edges = [(0, 1e16), (1, -1e16), (2, 1.0)];
+        let in_order = BatchResult::from_results(edges.map(|(s, e)| result(s, e)).to_vec());
+        let shuffled = [edges[0], edges[2], edges[1]].map(|(s, e)| result(s, e));
+        assert_eq!(in_order.total_edge, 1.0);
+        assert_eq!(BatchResult::from_results(shuffled.to_vec()).total_edge, 1.0);
+    }
+
+    #[test]
+    fn empty_batch_has_no_stats() {
+        let empty = BatchResult::from_results(Vec::new());
+        assert_eq!(empty.n_sims(), 0);
+        assert_eq!(empty.total_edge, 0.0);
+        assert_eq!(empty.avg_edge(), None);
+        assert_eq!(empty.edge_quantile(0.05), None);
+        assert_eq!(empty.avg_quote_slippage(), None);
+        assert_eq!(empty.max_quote_slippage(), None);
+        assert_eq!(empty.avg_waterfall(), None);
+        assert_eq!(empty.recording_bytes(), 0);
+        assert_eq!(empty.retail_share(), None);
     }
 }
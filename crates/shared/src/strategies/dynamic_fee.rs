@@ -0,0 +1,198 @@
+//! Constant product with a fee that follows realized volatility.
+//!
+//! `after_swap` turns each trade's move in the pool's spot price into a return, squares it,
+//! and folds it into an exponential moving average kept in storage. `compute_swap` quotes at
+//! `BASE_FEE_BPS` plus `VOL_FEE_MULTIPLIER` times the square root of that average, plus the
+//! spot's distance from the TWAP oracle when the simulation provides one, capped at
+//! `MAX_FEE_BPS`. A burst of large moves widens the spread; calm trades decay it back.
+//!
+//! Storage layout (little-endian):
+//! | Offset | Size | Field     | Description                                   |
+//! |--------|------|-----------|-----------------------------------------------|
+//! | 0      | 8    | last_spot | Post-trade spot of the last trade (1e9, Y/X)  |
+//! | 8      | 8    | variance  | EMA of squared returns, in bps²               |
+//!
+//! Only `core` is used, so `programs/dynamic_fee` builds this same file for BPF.
+
+/// Fee with no recorded volatility.
+pub const BASE_FEE_BPS: u64 = 30;
+/// Ceiling on the quoted fee.
+pub const MAX_FEE_BPS: u64 = 500;
+/// Fee bps added per bps of per-trade volatility.
+pub const VOL_FEE_MULTIPLIER: u64 = 2;
+/// The EMA keeps `1 - 2^-VARIANCE_EMA_SHIFT` of its value on every trade.
+pub const VARIANCE_EMA_SHIFT: u32 = 3;
+/// Largest single return folded into the average, in bps.
+const MAX_RETURN_BPS: u64 = 10_000;
+/// Offset of the optional TWAP oracle in a compute_swap payload (`SWAP_INSTRUCTION_SIZE`).
+const ORACLE_OFFSET: usize = 25 + 1024;
+const PRICE_SCALE: u128 = 1_000_000_000;
+
+/// Swap output for a compute_swap payload.
+pub fn compute_swap(data: &[u8]) -> u64 {
+    if data.len() < 25 {
+        return 0;
+    }
+    let side = data[0];
+    let input = read_u64(data, 1) as u128;
+    let reserve_x = read_u64(data, 9) as u128;
+    let reserve_y = read_u64(data, 17) as u128;
+    if reserve_x == 0 || reserve_y == 0 || side > 1 {
+        return 0;
+    }
+
+    let net = input * (10_000 - fee_bps(data) as u128) / 10_000;
+    let k = reserve_x * reserve_y;
+    let out = if side == 0 {
+        reserve_x.saturating_sub(k.div_ceil(reserve_y + net))
+    } else {
+        reserve_y.saturating_sub(k.div_ceil(reserve_x + net))
+    };
+    out as u64
+}
+
+/// Fee in bps a compute_swap payload is quoted at.
+pub fn fee_bps(data: &[u8]) -> u64 {
+    let variance = data.get(25..).map_or(0, |storage| read_u64(storage, 8));
+    let mut fee = BASE_FEE_BPS + VOL_FEE_MULTIPLIER * isqrt(variance);
+    if data.len() >= ORACLE_OFFSET + 8 {
+        // A spot away from its own TWAP is a move the average hasn't caught up with yet.
+        let spot = spot_price(read_u64(data, 9), read_u64(data, 17));
+        fee += return_bps(read_u64(data, ORACLE_OFFSET), spot);
+    }
+    fee.min(MAX_FEE_BPS)
+}
+
+/// Fold the trade's spot move into the variance average.
+pub fn after_swap(data: &[u8], storage: &mut [u8]) {
+    if data.len() < 34 || storage.len() < 16 {
+        return;
+    }
+    let spot = spot_price(read_u64(data, 18), read_u64(data, 26));
+    let last_spot = read_u64(storage, 0);
+    let mut variance = read_u64(storage, 8);
+    if last_spot != 0 {
+        let r = return_bps(last_spot, spot);
+        variance = variance - (variance >> VARIANCE_EMA_SHIFT) + ((r * r) >> VARIANCE_EMA_SHIFT);
+    }
+    storage[0..8].copy_from_slice(&spot.to_le_bytes());
+    storage[8..16].copy_from_slice(&variance.to_le_bytes());
+}
+
+fn read_u64(data: &[u8], at: usize) -> u64 {
+    match data.get(at..at + 8) {
+        Some(bytes) => u64::from_le_bytes([
+            bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7],
+        ]),
+        None => 0,
+    }
+}
+
+fn spot_price(reserve_x: u64, reserve_y: u64) -> u64 {
+    if reserve_x == 0 {
+        return 0;
+    }
+    (reserve_y as u128 * PRICE_SCALE / reserve_x as u128).min(u64::MAX as u128) as u64
+}
+
+/// `|to - from| / from` in bps, capped at `MAX_RETURN_BPS`.
+fn return_bps(from: u64, to: u64) -> u64 {
+    if from == 0 {
+        return 0;
+    }
+    let bps = from.abs_diff(to) as u128 * 10_000 / from as u128;
+    bps.min(MAX_RETURN_BPS as u128) as u64
+}
+
+/// Floor of the square root, by Newton's method.
+fn isqrt(n: u64) -> u64 {
+    if n < 2 {
+        return n;
+    }
+    let mut x = n;
+    let mut y = x / 2 + x % 2;
+    while y < x {
+        x = y;
+        y = (x + n / x) / 2;
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{after_swap, compute_swap, fee_bps, isqrt, BASE_FEE_BPS, ORACLE_OFFSET};
+    use crate::instruction::{
+        encode_after_swap, encode_swap_instruction, encode_swap_instruction_with_oracle, Oracle,
+        STORAGE_SIZE, SWAP_INSTRUCTION_SIZE,
+    };
+
+    const RESERVE_X: u64 = 100_000_000_000;
+
+    /// Pool with `RESERVE_X` of X at spot `price` (Y per X).
+    fn reserves(price: f64) -> (u64, u64) {
+        (RESERVE_X, (RESERVE_X as f64 * price) as u64)
+    }
+
+    fn trade(storage: &mut [u8], price: f64) {
+        let (rx, ry) = reserves(price);
+        let data = encode_after_swap(0, 1, 1, rx, ry, 0, storage);
+        after_swap(&data, storage);
+    }
+
+    /// Fraction of a small sale's fair value the pool keeps at spot `price`.
+    fn effective_spread(storage: &[u8], price: f64) -> f64 {
+        let (rx, ry) = reserves(price);
+        let input = RESERVE_X / 10_000;
+        let out = compute_swap(&encode_swap_instruction(1, input, rx, ry, storage));
+        1.0 - out as f64 / (input as f64 * price)
+    }
+
+    #[test]
+    fn spread_widens_after_a_volatility_burst_and_tightens_afterward() {
+        let mut storage = [0u8; STORAGE_SIZE];
+        for _ in 0..20 {
+            trade(&mut storage, 100.0);
+        }
+        let calm = effective_spread(&storage, 100.0);
+        let data = encode_swap_instruction(1, 1, RESERVE_X, RESERVE_X * 100, &storage);
+        assert_eq!(fee_bps(&data), BASE_FEE_BPS);
+
+        // Ten trades swinging the spot 2% each way.
+        for i in 0..10 {
+            trade(&mut storage, if i % 2 == 0 { 102.0 } else { 100.0 });
+        }
+        let volatile = effective_spread(&storage, 100.0);
+        assert!(volatile > calm + 0.01, "calm {calm}, volatile {volatile}");
+
+        for _ in 0..60 {
+            trade(&mut storage, 100.0);
+        }
+        let settled = effective_spread(&storage, 100.0);
+        assert!(
+            settled < volatile - 0.01,
+            "volatile {volatile}, settled {settled}"
+        );
+        assert!(settled < calm + 0.001, "calm {calm}, settled {settled}");
+    }
+
+    #[test]
+    fn spot_away_from_twap_widens_the_fee() {
+        let storage = [0u8; STORAGE_SIZE];
+        let (rx, ry) = reserves(100.0);
+        let at_twap =
+            encode_swap_instruction_with_oracle(1, 1, rx, ry, &storage, Oracle::twap(100_000_000_000));
+        let off_twap =
+            encode_swap_instruction_with_oracle(1, 1, rx, ry, &storage, Oracle::twap(95_000_000_000));
+        assert_eq!(fee_bps(&at_twap), BASE_FEE_BPS);
+        assert!(fee_bps(&off_twap) > BASE_FEE_BPS + 400);
+        assert_eq!(ORACLE_OFFSET, SWAP_INSTRUCTION_SIZE);
+    }
+
+    #[test]
+    fn isqrt_is_the_floor_square_root() {
+        for n in [0u64, 1, 2, 3, 4, 15, 16, 17, 1_000_000, u64::MAX] {
+            let r = isqrt(n) as u128;
+            assert!(r * r <= n as u128 && (r + 1) * (r + 1) > n as u128, "{n}");
+        }
+    }
+}
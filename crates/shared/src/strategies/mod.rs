@@ -0,0 +1,7 @@
+//! Reference strategies written against the raw instruction layouts.
+//!
+//! Each module only uses `core`, so the same file can back a native opponent in the simulator
+//! and a BPF program under `programs/`.
+
+pub mod dynamic_fee;
+pub mod starter;
@@ -0,0 +1,63 @@
+//! The starter template's curve: constant product with a flat 500 bps fee.
+//!
+//! `programs/starter` keeps its own wincode-decoding copy because submitters copy that file
+//! whole; this is the same arithmetic on the raw layout, for running the starter natively.
+
+/// Share of the input that reaches the curve, as `FEE_NUMERATOR / FEE_DENOMINATOR`.
+pub const FEE_NUMERATOR: u128 = 950;
+pub const FEE_DENOMINATOR: u128 = 1000;
+
+/// Swap output for a compute_swap payload.
+pub fn compute_swap(data: &[u8]) -> u64 {
+    if data.len() < 25 {
+        return 0;
+    }
+    let side = data[0];
+    let input = read_u64(data, 1) as u128;
+    let reserve_x = read_u64(data, 9) as u128;
+    let reserve_y = read_u64(data, 17) as u128;
+    if reserve_x == 0 || reserve_y == 0 || side > 1 {
+        return 0;
+    }
+
+    let net = input * FEE_NUMERATOR / FEE_DENOMINATOR;
+    let k = reserve_x * reserve_y;
+    let out = if side == 0 {
+        reserve_x.saturating_sub(k.div_ceil(reserve_y + net))
+    } else {
+        reserve_y.saturating_sub(k.div_ceil(reserve_x + net))
+    };
+    out as u64
+}
+
+/// The starter keeps no state.
+pub fn after_swap(_data: &[u8], _storage: &mut [u8]) {}
+
+fn read_u64(data: &[u8], at: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&data[at..at + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::compute_swap;
+    use crate::instruction::encode_instruction;
+
+    #[test]
+    fn quotes_constant_product_on_95_percent_of_the_input() {
+        let (rx, ry) = (100_000_000_000u64, 10_000_000_000_000u64);
+        // 1 X in: 0.95 X reaches the curve, out = ry - ceil(k / (rx + 0.95e9)).
+        let out = compute_swap(&encode_instruction(1, 1_000_000_000, rx, ry));
+        let k = rx as u128 * ry as u128;
+        assert_eq!(
+            out as u128,
+            ry as u128 - k.div_ceil(rx as u128 + 950_000_000)
+        );
+        assert_eq!(
+            compute_swap(&encode_instruction(2, 1_000_000_000, rx, ry)),
+            0
+        );
+        assert_eq!(compute_swap(&encode_instruction(0, 1, 0, ry)), 0);
+    }
+}
@@ -0,0 +1,86 @@
+//! Compensated summation for running totals that take millions of small terms.
+
+use std::ops::AddAssign;
+
+/// Running sum with Neumaier compensation: the low-order bits each addition rounds away are
+/// kept in a second term and added back on `value`, so the total stays accurate to about one
+/// rounding of the final result however many terms go in and in whatever magnitudes.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CompensatedSum {
+    sum: f64,
+    compensation: f64,
+}
+
+impl CompensatedSum {
+    /// Resume from the `parts` of an earlier sum.
+    pub fn from_parts(sum: f64, compensation: f64) -> Self {
+        Self { sum, compensation }
+    }
+
+    /// The naive running sum and the rounding error it still owes, e.g. for a snapshot.
+    pub fn parts(&self) -> (f64, f64) {
+        (self.sum, self.compensation)
+    }
+
+    pub fn add(&mut self, term: f64) {
+        let sum = self.sum + term;
+        self.compensation += if self.sum.abs() >= term.abs() {
+            (self.sum - sum) + term
+        } else {
+            (term - sum) + self.sum
+        };
+        self.sum = sum;
+    }
+
+    pub fn value(&self) -> f64 {
+        self.sum + self.compensation
+    }
+}
+
+impl AddAssign<f64> for CompensatedSum {
+    fn add_assign(&mut self, term: f64) {
+        self.add(term);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CompensatedSum;
+
+    #[test]
+    fn small_terms_after_a_large_one_are_not_lost() {
+        // 1e16 has an ulp of 2, so every naive `+ 1.0` rounds back down to 1e16.
+        let mut naive = 1e16;
+        let mut compensated = CompensatedSum::default();
+        compensated += 1e16;
+        for _ in 0..1_000_000 {
+            naive += 1.0;
+            compensated += 1.0;
+        }
+        naive += -1e16;
+        compensated += -1e16;
+        assert_eq!(naive, 0.0);
+        assert_eq!(compensated.value(), 1_000_000.0);
+
+        let (sum, compensation) = compensated.parts();
+        assert_eq!(CompensatedSum::from_parts(sum, compensation), compensated);
+    }
+
+    #[test]
+    fn many_edge_sized_terms_sum_to_the_exact_total() {
+        // 0.1 is not representable: ten million naive additions drift by whole units in
+        // the 7th significant digit; the compensated sum stays within one rounding.
+        let mut naive = 0.0;
+        let mut compensated = CompensatedSum::default();
+        for _ in 0..10_000_000 {
+            naive += 0.1;
+            compensated += 0.1;
+        }
+        assert!((naive - 1e6_f64).abs() > 1e-5, "{naive}");
+        assert!(
+            (compensated.value() - 1e6_f64).abs() < 1e-9,
+            "{}",
+            compensated.value()
+        );
+    }
+}
@@ -20,12 +20,12 @@ fn main() {
             0 => {
                 let net = input * 950 / 1000;
                 let new_ry = ry + net;
-                rx.saturating_sub((k + new_ry - 1) / new_ry) as u64
+                rx.saturating_sub(k.div_ceil(new_ry)) as u64
             }
             1 => {
                 let net = input * 950 / 1000;
                 let new_rx = rx + net;
-                ry.saturating_sub((k + new_rx - 1) / new_rx) as u64
+                ry.saturating_sub(k.div_ceil(new_rx)) as u64
             }
             _ => 0,
         }
@@ -48,7 +48,10 @@ fn main() {
     println!("========================================");
     println!("  Simulations: {}", result.n_sims());
     println!("  Time:        {:.2}s", elapsed.as_secs_f64());
-    println!("  Avg edge:    {:.2}", result.avg_edge());
+    println!(
+        "  Avg edge:    {:.2}",
+        result.avg_edge().unwrap_or_default()
+    );
     println!("  Total edge:  {:.2}", result.total_edge);
     println!("========================================");
 }
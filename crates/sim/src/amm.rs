@@ -1,12 +1,151 @@
-use prop_amm_executor::{AfterSwapFn, BpfExecutor, BpfProgram, NativeExecutor, SwapFn};
-use prop_amm_shared::instruction::STORAGE_SIZE;
-use prop_amm_shared::nano::{f64_to_nano, nano_to_f64};
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::process::Command;
+
+use prop_amm_executor::{
+    AfterSwapFn, BpfExecutor, BpfProgram, ExecutorError, NativeExecutor, RemoteExecutor, SwapFn,
+};
+use prop_amm_shared::instruction::{
+    encode_swap_instruction_with_oracle, Oracle, Side, STORAGE_SIZE,
+};
+use prop_amm_shared::nano::{
+    checked_f64_to_nano, f64_to_nano, nano_to_f64, NANO_DECIMALS, NANO_SCALE_F64,
+};
+use prop_amm_shared::normalizer;
+use prop_amm_shared::result::DisqualificationKind;
 
 const MIN_RESERVE: f64 = 1e-12;
 
+/// Outputs up to this many times the reserve are refused quietly: a strategy probed far past
+/// its depth (curve sweeps, exact-out searches) may overshoot and rely on the host cap. Only
+/// beyond it is the quote flagged as an `OverQuote`.
+pub const OVER_QUOTE_FLAG_MULTIPLE: u64 = 2;
+
 enum Backend {
-    Bpf(BpfExecutor),
+    Bpf(Box<BpfExecutor>),
     Native(NativeExecutor),
+    /// Strategy in a child process, reached over the remote wire protocol.
+    Remote(RemoteExecutor),
+}
+
+impl Backend {
+    #[inline]
+    fn quote(
+        &mut self,
+        side: u8,
+        amount: u64,
+        rx: u64,
+        ry: u64,
+        storage: &[u8],
+        oracle: Oracle,
+    ) -> Result<u64, ExecutorError> {
+        // Never hand a strategy a side it cannot interpret (or a reserved exact-out side, or
+        // another instruction's tag); it would quietly quote zero.
+        Side::exact_in(side)?;
+        match self {
+            Backend::Bpf(exec) => exec.execute_with_oracle(side, amount, rx, ry, storage, oracle),
+            Backend::Native(exec) => {
+                Ok(exec.execute_with_oracle(side, amount, rx, ry, storage, oracle))
+            }
+            Backend::Remote(exec) => {
+                exec.execute_with_oracle(side, amount, rx, ry, storage, oracle)
+            }
+        }
+    }
+}
+
+fn failure_kind(err: &ExecutorError) -> DisqualificationKind {
+    match err {
+        ExecutorError::ComputeBudgetExceeded => DisqualificationKind::CuExceeded,
+        _ => DisqualificationKind::ExecutionFailed,
+    }
+}
+
+/// One sampled point of a pool's price-impact curve.
+#[derive(Debug, Clone, Copy)]
+pub struct CurvePoint {
+    pub input: f64,
+    pub output: f64,
+    /// Average execution price in Y per X (`NAN` when the quote is zero).
+    pub effective_price: f64,
+}
+
+/// Floating-point replay of a normalizer pool: the reserves the closed-form fee-adjusted
+/// constant-product curve gives for the same sequence of fills.
+#[derive(Debug, Clone, Copy)]
+struct ReferenceCurve {
+    reserve_x: f64,
+    reserve_y: f64,
+    fills: u64,
+}
+
+/// Liquidity-mining subsidy: every fill's input is topped up by `bps` of itself on its way
+/// into the curve, paid from a budget in Y until it runs out.
+#[derive(Debug, Clone, Copy)]
+struct Subsidy {
+    bps: f64,
+    budget_y: f64,
+    paid_y: f64,
+}
+
+/// Hard caps on the reserves; fills that would exceed one are refused.
+#[derive(Debug, Clone, Copy)]
+struct InventoryLimit {
+    max_x: f64,
+    max_y: f64,
+    /// Input of refused fills so far, in Y (X at the spot price).
+    rejected_y: f64,
+}
+
+/// `after_swap` calls and whether any of them changed storage, kept only after
+/// `track_storage_writes`.
+#[derive(Debug, Clone, Copy, Default)]
+struct StorageWatch {
+    after_swap_calls: u64,
+    written: bool,
+}
+
+/// `(side, amount, reserve_x, reserve_y, storage hash, oracle)` of a `compute_swap` call.
+type QuoteKey = (u8, u64, u64, u64, u64, Oracle);
+
+/// Raw `compute_swap` outputs of the current step, kept only after `cache_quotes`. Cleared
+/// whenever the step or storage changes; reserves are part of the key.
+#[derive(Debug, Default)]
+struct QuoteCache {
+    outputs: HashMap<QuoteKey, u64>,
+    hits: u64,
+}
+
+/// One `compute_swap` call: the exact instruction bytes the strategy was handed (storage and
+/// oracle included) and the output it returned, kept only after `record_instructions`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstructionRecord {
+    pub step: u64,
+    pub data: Vec<u8>,
+    pub output: u64,
+}
+
+/// How far a pool's reserves have drifted from its `ReferenceCurve` replay.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReferenceDesync {
+    /// Larger of the X and Y reserve gaps, in nano units of X (Y converted at the pool's
+    /// spot price, so the figure does not scale with the price level).
+    pub max_gap_nano: f64,
+    /// Fills replayed so far.
+    pub fills: u64,
+}
+
+/// The last executed trade as the pool quoted it: pre-trade tradeable reserves and storage,
+/// the input and the output that was taken out of reserves. Only recorded in debug builds.
+#[derive(Debug, Clone)]
+struct ExecutedTrade {
+    side: u8,
+    input: f64,
+    output: f64,
+    reserve_x: f64,
+    reserve_y: f64,
+    storage: Vec<u8>,
+    oracle: Oracle,
 }
 
 pub struct BpfAmm {
@@ -16,18 +155,40 @@ pub struct BpfAmm {
     pub name: String,
     storage: Vec<u8>,
     current_step: u64,
+    /// Oracles appended to every quote's instruction data (nano scale), when enabled.
+    oracle: Oracle,
+    last_trade: Option<ExecutedTrade>,
+    /// Re-quote every fill in debug builds, set by `expect_pure_quotes`.
+    pure_quotes: bool,
+    /// Closed-form replay of every fill, kept only after `track_reference_curve`.
+    reference: Option<ReferenceCurve>,
+    subsidy: Option<Subsidy>,
+    inventory_limit: Option<InventoryLimit>,
+    /// Protocol-owned X and Y that never leave the pool, set by `set_locked_reserves`.
+    locked: [f64; 2],
+    /// Bps of every fill's input credited (or, negative, debited) on arrival, set by
+    /// `set_input_settlement`.
+    input_settlement_bps: f64,
+    storage_watch: Option<StorageWatch>,
+    /// Skip every `after_swap` call, set by `disable_after_swap`.
+    after_swap_disabled: bool,
+    quote_cache: Option<QuoteCache>,
+    /// Nano per smallest unit of X and Y: amounts are truncated to multiples of these.
+    unit_nano: [u64; 2],
+    /// Disqualifying events since the last `take_violations`, at most one per kind.
+    violations: Vec<(DisqualificationKind, String)>,
+    /// `compute_swap` calls since the last `take_instructions`, after `record_instructions`.
+    instructions: Option<Vec<InstructionRecord>>,
 }
 
 impl BpfAmm {
     pub fn new(program: BpfProgram, reserve_x: f64, reserve_y: f64, name: String) -> Self {
-        Self {
-            backend: Backend::Bpf(BpfExecutor::new(program)),
+        Self::with_backend(
+            Backend::Bpf(Box::new(BpfExecutor::new(program))),
             reserve_x,
             reserve_y,
             name,
-            storage: vec![0u8; STORAGE_SIZE],
-            current_step: 0,
-        }
+        )
     }
 
     pub fn new_native(
@@ -37,24 +198,152 @@ impl BpfAmm {
         reserve_y: f64,
         name: String,
     ) -> Self {
+        Self::with_backend(
+            Backend::Native(NativeExecutor::new(swap_fn, after_swap_fn)),
+            reserve_x,
+            reserve_y,
+            name,
+        )
+    }
+
+    /// Pool whose strategy runs in the child process `command`, isolated from the judge.
+    /// The child must speak the protocol documented on `RemoteExecutor`; if it dies, stalls
+    /// or misframes a response, it is killed, its quotes become zero and the failure is
+    /// flagged like a BPF execution error.
+    pub fn new_remote(
+        command: Command,
+        reserve_x: f64,
+        reserve_y: f64,
+        name: String,
+    ) -> Result<Self, ExecutorError> {
+        Ok(Self::with_backend(
+            Backend::Remote(RemoteExecutor::spawn(command)?),
+            reserve_x,
+            reserve_y,
+            name,
+        ))
+    }
+
+    fn with_backend(backend: Backend, reserve_x: f64, reserve_y: f64, name: String) -> Self {
         Self {
-            backend: Backend::Native(NativeExecutor::new(swap_fn, after_swap_fn)),
+            backend,
             reserve_x,
             reserve_y,
             name,
             storage: vec![0u8; STORAGE_SIZE],
             current_step: 0,
+            oracle: Oracle::default(),
+            last_trade: None,
+            pure_quotes: false,
+            reference: None,
+            subsidy: None,
+            inventory_limit: None,
+            locked: [0.0, 0.0],
+            input_settlement_bps: 0.0,
+            storage_watch: None,
+            after_swap_disabled: false,
+            quote_cache: None,
+            unit_nano: [1, 1],
+            violations: Vec::new(),
+            instructions: None,
         }
     }
 
     #[inline]
     fn call(&mut self, side: u8, amount: u64, rx: u64, ry: u64) -> u64 {
-        match &mut self.backend {
-            Backend::Bpf(exec) => exec
-                .execute(side, amount, rx, ry, &self.storage)
-                .unwrap_or(0),
-            Backend::Native(exec) => exec.execute(side, amount, rx, ry, &self.storage),
+        let key = self.quote_cache.is_some().then(|| {
+            let mut storage = DefaultHasher::new();
+            self.storage.hash(&mut storage);
+            (side, amount, rx, ry, storage.finish(), self.oracle)
+        });
+        let cached = key.and_then(|key| {
+            let cache = self.quote_cache.as_mut()?;
+            let output = cache.outputs.get(&key).copied()?;
+            cache.hits += 1;
+            Some(output)
+        });
+        let output = match cached {
+            Some(output) => output,
+            None => match self
+                .backend
+                .quote(side, amount, rx, ry, &self.storage, self.oracle)
+            {
+                Ok(output) => {
+                    if let (Some(cache), Some(key)) = (self.quote_cache.as_mut(), key) {
+                        cache.outputs.insert(key, output);
+                    }
+                    output
+                }
+                Err(err) => {
+                    self.flag(failure_kind(&err), format!("compute_swap: {}", err));
+                    0
+                }
+            },
+        };
+        if let Some(log) = &mut self.instructions {
+            log.push(InstructionRecord {
+                step: self.current_step,
+                data: encode_swap_instruction_with_oracle(
+                    side,
+                    amount,
+                    rx,
+                    ry,
+                    &self.storage,
+                    self.oracle,
+                ),
+                output,
+            });
+        }
+        // Nothing stops a native strategy returning any u64 (`u64::MAX` decodes to ~1.8e10
+        // tokens); refuse outputs beyond the reserve here so exact-out searches see them too.
+        let reserve = if side == 0 { rx } else { ry };
+        if output <= reserve {
+            return output;
+        }
+        if u128::from(output) > u128::from(reserve) * u128::from(OVER_QUOTE_FLAG_MULTIPLE) {
+            let (name, token) = if side == 0 {
+                ("buy_x", "X")
+            } else {
+                ("sell_x", "Y")
+            };
+            self.flag(
+                DisqualificationKind::OverQuote,
+                format!(
+                    "{} quoted {} {} against a reserve of {}",
+                    name,
+                    nano_to_f64(output),
+                    token,
+                    nano_to_f64(reserve)
+                ),
+            );
         }
+        0
+    }
+
+    /// Keep every `compute_swap` call from now on, for `take_instructions`.
+    pub fn record_instructions(&mut self) {
+        self.instructions.get_or_insert_with(Vec::new);
+    }
+
+    /// The `compute_swap` calls recorded since the last call, oldest first.
+    pub fn take_instructions(&mut self) -> Vec<InstructionRecord> {
+        self.instructions
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Nano amount of `token` (0 for X, 1 for Y), truncated to whole units of the token.
+    #[inline]
+    fn to_nano(&self, token: usize, amount: f64) -> u64 {
+        let nano = f64_to_nano(amount);
+        nano - nano % self.unit_nano[token]
+    }
+
+    /// Strategy output in `token`, truncated to whole units of the token.
+    #[inline]
+    fn output_amount(&self, token: usize, nano: u64) -> f64 {
+        nano_to_f64(nano - nano % self.unit_nano[token])
     }
 
     #[inline]
@@ -66,56 +355,116 @@ impl BpfAmm {
         rx: u64,
         ry: u64,
     ) {
-        match &mut self.backend {
+        if self.after_swap_disabled {
+            return;
+        }
+        self.invalidate_quotes();
+        let before = self
+            .storage_watch
+            .filter(|watch| !watch.written)
+            .map(|_| self.storage.clone());
+        let (step, storage) = (self.current_step, &mut self.storage);
+        let result = match &mut self.backend {
             Backend::Bpf(exec) => {
-                let _ = exec.execute_after_swap(
-                    side,
-                    input_amount,
-                    output_amount,
-                    rx,
-                    ry,
-                    self.current_step,
-                    &mut self.storage,
-                );
+                exec.execute_after_swap(side, input_amount, output_amount, rx, ry, step, storage)
             }
             Backend::Native(exec) => {
-                exec.execute_after_swap(
-                    side,
-                    input_amount,
-                    output_amount,
-                    rx,
-                    ry,
-                    self.current_step,
-                    &mut self.storage,
-                );
+                exec.execute_after_swap(side, input_amount, output_amount, rx, ry, step, storage);
+                Ok(())
             }
+            Backend::Remote(exec) => {
+                exec.execute_after_swap(side, input_amount, output_amount, rx, ry, step, storage)
+            }
+        };
+        if let Err(err) = result {
+            self.flag(failure_kind(&err), format!("after_swap: {}", err));
+        }
+        if let Some(watch) = self.storage_watch.as_mut() {
+            watch.after_swap_calls += 1;
+            watch.written |= before.is_some_and(|before| before != self.storage);
         }
     }
 
     pub fn set_current_step(&mut self, step: u64) {
+        if step != self.current_step {
+            self.invalidate_quotes();
+        }
         self.current_step = step;
     }
 
+    /// Memoize `compute_swap` within a step, for analysis that probes the same quote
+    /// repeatedly (spreads, curves, depth). Only worth it for expensive strategies, and only
+    /// sound for pure ones: a hit replays the output of a call with identical instruction
+    /// bytes.
+    pub fn cache_quotes(&mut self) {
+        self.quote_cache.get_or_insert_with(QuoteCache::default);
+    }
+
+    /// Quotes served from the cache so far; 0 unless `cache_quotes` was called.
+    pub fn quote_cache_hits(&self) -> u64 {
+        self.quote_cache.as_ref().map_or(0, |cache| cache.hits)
+    }
+
+    fn invalidate_quotes(&mut self) {
+        if let Some(cache) = self.quote_cache.as_mut() {
+            cache.outputs.clear();
+        }
+    }
+
+    /// Note a disqualifying event, keeping only the first of each kind until the next
+    /// `take_violations`.
+    fn flag(&mut self, kind: DisqualificationKind, detail: String) {
+        if self.violations.iter().all(|(k, _)| *k != kind) {
+            self.violations.push((kind, detail));
+        }
+    }
+
+    /// Disqualifying events since the previous call, in the order they happened.
+    pub fn take_violations(&mut self) -> Vec<(DisqualificationKind, String)> {
+        std::mem::take(&mut self.violations)
+    }
+
+    /// Provide (or, with `None`, stop providing) a TWAP price to the strategy's quotes.
+    pub fn set_twap(&mut self, twap: Option<f64>) {
+        self.oracle.twap = twap.filter(|p| p.is_finite() && *p > 0.0).map(f64_to_nano);
+    }
+
+    /// The TWAP price the strategy currently quotes with, if any.
+    pub fn twap(&self) -> Option<f64> {
+        self.oracle.twap.map(nano_to_f64)
+    }
+
+    /// Provide (or, with `None`, stop providing) an observed reference price to the
+    /// strategy's quotes.
+    pub fn set_price_oracle(&mut self, price: Option<f64>) {
+        self.oracle.price = price.filter(|p| p.is_finite() && *p > 0.0).map(f64_to_nano);
+    }
+
+    /// The reference price the strategy currently quotes with, if any.
+    pub fn price_oracle(&self) -> Option<f64> {
+        self.oracle.price.map(nano_to_f64)
+    }
+
     #[inline]
     pub fn quote_buy_x(&mut self, input_y: f64) -> f64 {
         if input_y <= 0.0 || !input_y.is_finite() {
             return 0.0;
         }
-        if self.reserve_x <= MIN_RESERVE
-            || self.reserve_y <= MIN_RESERVE
-            || !self.reserve_x.is_finite()
-            || !self.reserve_y.is_finite()
+        let (tradeable_x, tradeable_y) = self.tradeable_reserves();
+        if tradeable_x <= MIN_RESERVE
+            || tradeable_y <= MIN_RESERVE
+            || !tradeable_x.is_finite()
+            || !tradeable_y.is_finite()
         {
             return 0.0;
         }
 
-        let quoted = nano_to_f64(self.call(
-            0,
-            f64_to_nano(input_y),
-            f64_to_nano(self.reserve_x),
-            f64_to_nano(self.reserve_y),
-        ));
-        if !quoted.is_finite() || quoted <= 0.0 || quoted > self.reserve_x {
+        let curve_input = input_y + self.top_up(0, input_y);
+        let amount = self.to_nano(1, curve_input);
+        let (rx, ry) = (self.to_nano(0, tradeable_x), self.to_nano(1, tradeable_y));
+        let quoted = self.call(0, amount, rx, ry);
+        let quoted = self.output_amount(0, quoted);
+        if !quoted.is_finite() || quoted <= 0.0 || quoted > tradeable_x {
             0.0
         } else {
             quoted
@@ -127,85 +476,559 @@ impl BpfAmm {
         if input_x <= 0.0 || !input_x.is_finite() {
             return 0.0;
         }
-        if self.reserve_x <= MIN_RESERVE
-            || self.reserve_y <= MIN_RESERVE
-            || !self.reserve_x.is_finite()
-            || !self.reserve_y.is_finite()
+        let (tradeable_x, tradeable_y) = self.tradeable_reserves();
+        if tradeable_x <= MIN_RESERVE
+            || tradeable_y <= MIN_RESERVE
+            || !tradeable_x.is_finite()
+            || !tradeable_y.is_finite()
         {
             return 0.0;
         }
 
-        let quoted = nano_to_f64(self.call(
-            1,
-            f64_to_nano(input_x),
-            f64_to_nano(self.reserve_x),
-            f64_to_nano(self.reserve_y),
-        ));
-        if !quoted.is_finite() || quoted <= 0.0 || quoted > self.reserve_y {
+        let curve_input = input_x + self.top_up(1, input_x);
+        let amount = self.to_nano(0, curve_input);
+        let (rx, ry) = (self.to_nano(0, tradeable_x), self.to_nano(1, tradeable_y));
+        let quoted = self.call(1, amount, rx, ry);
+        let quoted = self.output_amount(1, quoted);
+        if !quoted.is_finite() || quoted <= 0.0 || quoted > tradeable_y {
             0.0
         } else {
             quoted
         }
     }
 
+    /// Exact-out quote: the Y input required to receive at least `desired_x`.
+    ///
+    /// Host-side only: strategies are never sent an exact-out side, so this inverts
+    /// `compute_swap` by bisection over nano inputs, relying on the monotonicity every
+    /// submission must satisfy. Retail and arbitrage orders stay exact-in. Returns
+    /// `f64::INFINITY` when no input yields `desired_x` (e.g. it exceeds the X reserve).
+    pub fn quote_buy_x_exact_out(&mut self, desired_x: f64) -> f64 {
+        self.required_input(0, desired_x)
+    }
+
+    /// Exact-out quote: the X input required to receive at least `desired_y`.
+    pub fn quote_sell_x_exact_out(&mut self, desired_y: f64) -> f64 {
+        self.required_input(1, desired_y)
+    }
+
+    fn required_input(&mut self, side: u8, desired_output: f64) -> f64 {
+        if !desired_output.is_finite() || desired_output <= 0.0 {
+            return 0.0;
+        }
+        let (tradeable_x, tradeable_y) = self.tradeable_reserves();
+        let output_reserve = if side == 0 { tradeable_x } else { tradeable_y };
+        if desired_output >= output_reserve
+            || tradeable_x <= MIN_RESERVE
+            || tradeable_y <= MIN_RESERVE
+            || !tradeable_x.is_finite()
+            || !tradeable_y.is_finite()
+        {
+            return f64::INFINITY;
+        }
+
+        let (input_token, output_token) = if side == 0 { (1, 0) } else { (0, 1) };
+        let (input_unit, output_unit) = (self.unit_nano[input_token], self.unit_nano[output_token]);
+        let desired = self.to_nano(output_token, desired_output).max(output_unit);
+        let rx = self.to_nano(0, tradeable_x);
+        let ry = self.to_nano(1, tradeable_y);
+        let delivers = |amm: &mut Self, units: u64| {
+            let output = amm.call(side, units.saturating_mul(input_unit), rx, ry);
+            output - output % output_unit >= desired
+        };
+
+        // Grow an upper bound, then bisect for the smallest input (in whole units of the
+        // input token) that delivers `desired`.
+        let mut hi = desired.div_ceil(input_unit);
+        while !delivers(self, hi) {
+            if hi.saturating_mul(input_unit) == u64::MAX {
+                return f64::INFINITY;
+            }
+            hi = hi.saturating_mul(2);
+        }
+        let mut lo = 0u64;
+        while hi - lo > 1 {
+            let mid = lo + (hi - lo) / 2;
+            if delivers(self, mid) {
+                hi = mid;
+            } else {
+                lo = mid;
+            }
+        }
+        nano_to_f64(hi.saturating_mul(input_unit))
+    }
+
     #[inline]
     pub fn execute_buy_x(&mut self, input_y: f64) -> f64 {
+        if self.refuse_overflow(0, input_y) {
+            return 0.0;
+        }
         let output_x = self.quote_buy_x(input_y);
         if input_y <= 0.0 || output_x <= 0.0 || !input_y.is_finite() || !output_x.is_finite() {
             return 0.0;
         }
-        if output_x >= self.reserve_x {
+        if output_x >= self.reserve_x - self.locked[0] {
             return 0.0;
         }
 
+        let top_up = self.top_up(0, input_y);
+        let settled = self.settlement(input_y);
         let new_rx = self.reserve_x - output_x;
-        let new_ry = self.reserve_y + input_y;
-        if new_rx <= MIN_RESERVE
-            || new_ry <= MIN_RESERVE
+        let new_ry = self.reserve_y + input_y + top_up + settled;
+        if new_rx - self.locked[0] <= MIN_RESERVE
+            || new_ry - self.locked[1] <= MIN_RESERVE
             || !new_rx.is_finite()
             || !new_ry.is_finite()
         {
             return 0.0;
         }
+        if self.refuse_over_limit(0, new_ry, input_y) {
+            return 0.0;
+        }
 
+        self.record_trade(0, input_y + top_up, output_x);
+        self.replay_reference(0, input_y + top_up);
+        self.pay_subsidy(top_up);
         self.reserve_x = new_rx;
         self.reserve_y = new_ry;
+        debug_assert!(
+            !self.pure_quotes || self.self_check(),
+            "{}: buy_x quote/execute mismatch",
+            self.name
+        );
 
-        let rx = f64_to_nano(self.reserve_x);
-        let ry = f64_to_nano(self.reserve_y);
-        self.call_after_swap(0, f64_to_nano(input_y), f64_to_nano(output_x), rx, ry);
+        let (tradeable_x, tradeable_y) = self.tradeable_reserves();
+        let (rx, ry) = (self.to_nano(0, tradeable_x), self.to_nano(1, tradeable_y));
+        let input_y = self.to_nano(1, input_y + top_up + settled);
+        self.call_after_swap(0, input_y, self.to_nano(0, output_x), rx, ry);
         output_x
     }
 
     #[inline]
     pub fn execute_sell_x(&mut self, input_x: f64) -> f64 {
+        if self.refuse_overflow(1, input_x) {
+            return 0.0;
+        }
         let output_y = self.quote_sell_x(input_x);
         if input_x <= 0.0 || output_y <= 0.0 || !input_x.is_finite() || !output_y.is_finite() {
             return 0.0;
         }
-        if output_y >= self.reserve_y {
+        if output_y >= self.reserve_y - self.locked[1] {
             return 0.0;
         }
 
-        let new_rx = self.reserve_x + input_x;
+        let top_up = self.top_up(1, input_x);
+        let settled = self.settlement(input_x);
+        let new_rx = self.reserve_x + input_x + top_up + settled;
         let new_ry = self.reserve_y - output_y;
-        if new_rx <= MIN_RESERVE
-            || new_ry <= MIN_RESERVE
+        if new_rx - self.locked[0] <= MIN_RESERVE
+            || new_ry - self.locked[1] <= MIN_RESERVE
             || !new_rx.is_finite()
             || !new_ry.is_finite()
         {
             return 0.0;
         }
+        if self.refuse_over_limit(1, new_rx, input_x * self.spot_price()) {
+            return 0.0;
+        }
 
+        let top_up_y = top_up * self.spot_price();
+        self.record_trade(1, input_x + top_up, output_y);
+        self.replay_reference(1, input_x + top_up);
+        self.pay_subsidy(top_up_y);
         self.reserve_x = new_rx;
         self.reserve_y = new_ry;
+        debug_assert!(
+            !self.pure_quotes || self.self_check(),
+            "{}: sell_x quote/execute mismatch",
+            self.name
+        );
 
-        let rx = f64_to_nano(self.reserve_x);
-        let ry = f64_to_nano(self.reserve_y);
-        self.call_after_swap(1, f64_to_nano(input_x), f64_to_nano(output_y), rx, ry);
+        let (tradeable_x, tradeable_y) = self.tradeable_reserves();
+        let (rx, ry) = (self.to_nano(0, tradeable_x), self.to_nano(1, tradeable_y));
+        let input_x = self.to_nano(0, input_x + top_up + settled);
+        self.call_after_swap(1, input_x, self.to_nano(1, output_y), rx, ry);
         output_y
     }
 
+    /// Move `sold_x` of X out of the pool (negative: into it) against Y at `price`, off the
+    /// strategy's curve, as an off-exchange rebalance would. The trade is cut back to what
+    /// the inventory limit allows and refused outright if it would reach the locked floor or
+    /// overflow a reserve; `after_swap` sees it as a fill on the matching side. Returns the X
+    /// actually moved.
+    pub fn rebalance_x(&mut self, sold_x: f64, price: f64) -> f64 {
+        if !sold_x.is_finite() || sold_x == 0.0 || !price.is_finite() || price <= 0.0 {
+            return 0.0;
+        }
+        let (max_x, max_y) = self
+            .inventory_limit
+            .map_or((f64::INFINITY, f64::INFINITY), |limit| {
+                (limit.max_x, limit.max_y)
+            });
+        // Only the reserve the trade grows can break its cap, as for a fill.
+        let moved = if sold_x > 0.0 {
+            sold_x.min((max_y - self.reserve_y) / price)
+        } else {
+            sold_x.max(self.reserve_x - max_x)
+        };
+        if moved == 0.0 || moved.signum() != sold_x.signum() {
+            return 0.0;
+        }
+
+        let new_rx = self.reserve_x - moved;
+        let new_ry = self.reserve_y + moved * price;
+        if new_rx - self.locked[0] <= MIN_RESERVE
+            || new_ry - self.locked[1] <= MIN_RESERVE
+            || checked_f64_to_nano(new_rx).is_none()
+            || checked_f64_to_nano(new_ry).is_none()
+            || (moved > 0.0 && new_ry > max_y)
+            || (moved < 0.0 && new_rx > max_x)
+        {
+            return 0.0;
+        }
+        self.reserve_x = new_rx;
+        self.reserve_y = new_ry;
+
+        let (tradeable_x, tradeable_y) = self.tradeable_reserves();
+        let (rx, ry) = (self.to_nano(0, tradeable_x), self.to_nano(1, tradeable_y));
+        let (amount_x, amount_y) = (
+            self.to_nano(0, moved.abs()),
+            self.to_nano(1, moved.abs() * price),
+        );
+        if moved > 0.0 {
+            // X left the pool for Y, as when a trader buys X.
+            self.call_after_swap(0, amount_y, amount_x, rx, ry);
+        } else {
+            self.call_after_swap(1, amount_x, amount_y, rx, ry);
+        }
+        moved
+    }
+
+    /// Whether a fill of `input` on `side` would grow its input reserve past `u64::MAX` nano,
+    /// where the strategy would see it saturated (or, far enough out, `inf`); if so, flag it.
+    /// The reserve-depletion guard covers the output side.
+    fn refuse_overflow(&mut self, side: u8, input: f64) -> bool {
+        if input.is_nan() || input <= 0.0 {
+            return false;
+        }
+        let (name, token, reserve) = if side == 0 {
+            ("buy_x", "Y", self.reserve_y)
+        } else {
+            ("sell_x", "X", self.reserve_x)
+        };
+        let arriving = input + self.top_up(side, input) + self.settlement(input);
+        if checked_f64_to_nano(reserve + arriving).is_some() {
+            return false;
+        }
+        self.flag(
+            DisqualificationKind::ReserveOverflow,
+            format!(
+                "{} input of {:e} {} would overflow a reserve of {}",
+                name, input, token, reserve
+            ),
+        );
+        true
+    }
+
+    /// Remember the trade about to mutate reserves, in debug builds after
+    /// `expect_pure_quotes` only.
+    #[inline]
+    fn record_trade(&mut self, side: u8, input: f64, output: f64) {
+        if cfg!(debug_assertions) && self.pure_quotes {
+            let (reserve_x, reserve_y) = self.tradeable_reserves();
+            self.last_trade = Some(ExecutedTrade {
+                side,
+                input,
+                output,
+                reserve_x,
+                reserve_y,
+                storage: self.storage.clone(),
+                oracle: self.oracle,
+            });
+        }
+    }
+
+    /// Subsidize every fill by `bps` of its input (see `SimulationConfig::norm_subsidy_bps`),
+    /// paying at most `budget_y` in total. Exact-out quotes ignore the subsidy.
+    pub fn set_subsidy(&mut self, bps: f64, budget_y: f64) {
+        self.subsidy = Some(Subsidy {
+            bps,
+            budget_y,
+            paid_y: 0.0,
+        });
+    }
+
+    /// Subsidy in bps a fill gets right now: zero without one or once its budget is spent.
+    pub fn subsidy_bps(&self) -> f64 {
+        match self.subsidy {
+            Some(subsidy) if subsidy.paid_y < subsidy.budget_y => subsidy.bps,
+            _ => 0.0,
+        }
+    }
+
+    /// Subsidy paid out so far, in Y.
+    pub fn subsidy_paid(&self) -> f64 {
+        self.subsidy.map_or(0.0, |subsidy| subsidy.paid_y)
+    }
+
+    /// Resume a subsidy that had already paid `paid_y`.
+    pub(crate) fn set_subsidy_paid(&mut self, paid_y: f64) {
+        if let Some(subsidy) = self.subsidy.as_mut() {
+            subsidy.paid_y = paid_y;
+        }
+    }
+
+    /// Input, in the input token, the subsidy adds to a fill of `input` on `side`: `bps` of
+    /// it, or whatever is left of the budget (Y converted at the spot price).
+    fn top_up(&self, side: u8, input: f64) -> f64 {
+        let Some(subsidy) = self.subsidy else {
+            return 0.0;
+        };
+        let left_y = (subsidy.budget_y - subsidy.paid_y).max(0.0);
+        let left = if side == 0 {
+            left_y
+        } else {
+            left_y / self.spot_price()
+        };
+        (input * subsidy.bps / 10_000.0).min(left).max(0.0)
+    }
+
+    fn pay_subsidy(&mut self, amount_y: f64) {
+        if let Some(subsidy) = self.subsidy.as_mut() {
+            subsidy.paid_y = (subsidy.paid_y + amount_y).min(subsidy.budget_y);
+        }
+    }
+
+    /// Credit every fill's input leg with `bps` of itself as it reaches the reserves (a
+    /// negative `bps` debits it): the maker rebate less the transfer fee, which the
+    /// strategy's gross quote never sees. The settled input goes through the same reserve,
+    /// lock and inventory checks as the rest of the fill, and `after_swap` reports it.
+    pub fn set_input_settlement(&mut self, bps: f64) {
+        self.input_settlement_bps = bps;
+    }
+
+    /// Input `set_input_settlement` adds to a fill of `input`, in the input token.
+    fn settlement(&self, input: f64) -> f64 {
+        input * self.input_settlement_bps / 10_000.0
+    }
+
+    /// Refuse every fill that would leave more than `max_x` / `max_y` in the reserves.
+    /// Quotes are unaffected: traders find out when the fill is refused.
+    pub fn set_inventory_limit(&mut self, max_x: Option<f64>, max_y: Option<f64>) {
+        self.inventory_limit = Some(InventoryLimit {
+            max_x: max_x.unwrap_or(f64::INFINITY),
+            max_y: max_y.unwrap_or(f64::INFINITY),
+            rejected_y: 0.0,
+        });
+    }
+
+    /// Input of fills refused by the inventory limit so far, in Y.
+    pub fn inventory_rejected(&self) -> f64 {
+        self.inventory_limit.map_or(0.0, |limit| limit.rejected_y)
+    }
+
+    pub(crate) fn set_inventory_rejected(&mut self, rejected_y: f64) {
+        if let Some(limit) = self.inventory_limit.as_mut() {
+            limit.rejected_y = rejected_y;
+        }
+    }
+
+    /// Whether a fill on `side` that grows its input reserve to `new_input_reserve` breaks
+    /// the inventory limit; if so, count its `input_y` as refused. The output reserve only
+    /// shrinks, so a fill can never break the other cap.
+    fn refuse_over_limit(&mut self, side: u8, new_input_reserve: f64, input_y: f64) -> bool {
+        let Some(limit) = self.inventory_limit.as_mut() else {
+            return false;
+        };
+        let max = if side == 0 { limit.max_y } else { limit.max_x };
+        if new_input_reserve <= max {
+            return false;
+        }
+        limit.rejected_y += input_y;
+        true
+    }
+
+    /// Lock `x` and `y` in the pool for good, as protocol-owned liquidity: the strategy quotes
+    /// and trades only against the reserves above the lock (see `tradeable_reserves`), and
+    /// the reserve-depletion guard keeps fills from reaching it.
+    pub fn set_locked_reserves(&mut self, x: f64, y: f64) {
+        self.invalidate_quotes();
+        self.locked = [x.max(0.0), y.max(0.0)];
+    }
+
+    /// The reserves above the locked floor: what `compute_swap` and `after_swap` see as
+    /// the pool's reserves. Equal to the reserves without a lock.
+    #[inline]
+    pub fn tradeable_reserves(&self) -> (f64, f64) {
+        (
+            self.reserve_x - self.locked[0],
+            self.reserve_y - self.locked[1],
+        )
+    }
+
+    /// Token decimals of the pair (9 is the nano scale, above 9 behaves as 9). Every amount
+    /// and reserve handed to the strategy, and every output it quotes, is truncated to whole
+    /// units of its token, as a program working in the token's native units would see them.
+    pub fn set_decimals(&mut self, decimals_x: u32, decimals_y: u32) {
+        let unit = |decimals: u32| 10u64.pow(NANO_DECIMALS - decimals.min(NANO_DECIMALS));
+        self.unit_nano = [unit(decimals_x), unit(decimals_y)];
+    }
+
+    /// Never call the strategy's `after_swap`, even if it exports one: storage keeps its
+    /// initial bytes and the strategy quotes statelessly.
+    pub fn disable_after_swap(&mut self) {
+        self.after_swap_disabled = true;
+    }
+
+    /// Start watching whether `after_swap` ever changes storage; see `storage_never_written`.
+    pub fn track_storage_writes(&mut self) {
+        self.storage_watch = Some(StorageWatch::default());
+    }
+
+    /// Whether the strategy exports `after_swap` and it ran since `track_storage_writes`,
+    /// but never changed a byte of storage: its quotes only ever saw the initial storage.
+    pub fn storage_never_written(&self) -> bool {
+        let has_after_swap = match &self.backend {
+            Backend::Native(exec) => exec.has_after_swap(),
+            Backend::Bpf(_) | Backend::Remote(_) => true,
+        };
+        has_after_swap
+            && self
+                .storage_watch
+                .is_some_and(|watch| watch.after_swap_calls > 0 && !watch.written)
+    }
+
+    /// `after_swap` calls and whether storage changed, for snapshots; `None` when not watching.
+    pub(crate) fn storage_watch(&self) -> Option<(u64, bool)> {
+        self.storage_watch
+            .map(|watch| (watch.after_swap_calls, watch.written))
+    }
+
+    pub(crate) fn set_storage_watch(&mut self, after_swap_calls: u64, written: bool) {
+        if let Some(watch) = self.storage_watch.as_mut() {
+            *watch = StorageWatch {
+                after_swap_calls,
+                written,
+            };
+        }
+    }
+
+    /// Fee the normalizer charges with this pool's storage, which `normalizer::compute_swap`
+    /// reads from `storage[0..2]`.
+    #[inline]
+    pub fn normalizer_fee_bps(&self) -> u16 {
+        match self.storage.get(0..2) {
+            Some(word) => normalizer::fee_bps(u16::from_le_bytes([word[0], word[1]])),
+            None => normalizer::DEFAULT_FEE_BPS,
+        }
+    }
+
+    /// Start replaying every fill through the closed-form normalizer curve in floating
+    /// point, from the current reserves. Only meaningful for a pool running the normalizer.
+    pub fn track_reference_curve(&mut self) {
+        self.reference = Some(ReferenceCurve {
+            reserve_x: self.reserve_x,
+            reserve_y: self.reserve_y,
+            fills: 0,
+        });
+    }
+
+    /// Drift of the reserves from the reference replay, if `track_reference_curve` is on.
+    pub fn reference_desync(&self) -> Option<ReferenceDesync> {
+        self.reference.map(|reference| ReferenceDesync {
+            max_gap_nano: (self.reserve_x - reference.reserve_x)
+                .abs()
+                .max((self.reserve_y - reference.reserve_y).abs() / self.spot_price())
+                * NANO_SCALE_F64,
+            fills: reference.fills,
+        })
+    }
+
+    /// Apply the fill about to mutate reserves to the reference replay, if there is one.
+    fn replay_reference(&mut self, side: u8, input: f64) {
+        let Some(mut reference) = self.reference else {
+            return;
+        };
+        let net = input * (10_000.0 - self.normalizer_fee_bps() as f64) / 10_000.0;
+        if side == 0 {
+            let output = reference.reserve_x * net / (reference.reserve_y + net);
+            reference.reserve_x -= output;
+            reference.reserve_y += input;
+        } else {
+            let output = reference.reserve_y * net / (reference.reserve_x + net);
+            reference.reserve_x += input;
+            reference.reserve_y -= output;
+        }
+        reference.fills += 1;
+        self.reference = Some(reference);
+    }
+
+    /// Assert, in debug builds, that every fill re-quotes to the output it took (see
+    /// `self_check`). Only for strategies known to be pure functions of their inputs, like
+    /// the reference strategies: a submission may legitimately quote differently on a
+    /// second call, and must not panic the judge for it.
+    pub fn expect_pure_quotes(&mut self) {
+        self.pure_quotes = true;
+    }
+
+    /// Re-quote the last executed trade from its pre-trade reserves and storage and confirm
+    /// the result is exactly the output that was taken out of reserves. Always `true` in
+    /// release builds and without `expect_pure_quotes`, which do not record trades.
+    pub fn self_check(&mut self) -> bool {
+        let Some(trade) = self.last_trade.as_ref() else {
+            return true;
+        };
+        let output_reserve = if trade.side == 0 {
+            trade.reserve_x
+        } else {
+            trade.reserve_y
+        };
+        let (input_token, output_token) = if trade.side == 0 { (1, 0) } else { (0, 1) };
+        let quoted = self
+            .backend
+            .quote(
+                trade.side,
+                self.to_nano(input_token, trade.input),
+                self.to_nano(0, trade.reserve_x),
+                self.to_nano(1, trade.reserve_y),
+                &trade.storage,
+                trade.oracle,
+            )
+            .unwrap_or(0);
+        let quoted = self.output_amount(output_token, quoted);
+        quoted.is_finite() && quoted > 0.0 && quoted <= output_reserve && quoted == trade.output
+    }
+
+    /// Quote `points` evenly spaced sizes in `(0, max_size]` at the current reserves and
+    /// storage, without trading: input sizes on the exact-in sides, desired outputs on the
+    /// exact-out ones (where an output out of reach costs an infinite input).
+    pub fn sample_curve(&mut self, side: Side, max_size: f64, points: usize) -> Vec<CurvePoint> {
+        (1..=points)
+            .map(|i| {
+                let size = max_size * i as f64 / points as f64;
+                let (input, output) = match side {
+                    Side::BuyX => (size, self.quote_buy_x(size)),
+                    Side::SellX => (size, self.quote_sell_x(size)),
+                    Side::ExactOutBuy => (self.quote_buy_x_exact_out(size), size),
+                    Side::ExactOutSell => (self.quote_sell_x_exact_out(size), size),
+                };
+                let effective_price = match side {
+                    Side::BuyX | Side::ExactOutBuy => input / output,
+                    Side::SellX | Side::ExactOutSell => output / input,
+                };
+                CurvePoint {
+                    input,
+                    output,
+                    effective_price: if output > 0.0 && input.is_finite() {
+                        effective_price
+                    } else {
+                        f64::NAN
+                    },
+                }
+            })
+            .collect()
+    }
+
     #[inline]
     pub fn spot_price(&self) -> f64 {
         if self.reserve_x <= MIN_RESERVE
@@ -218,21 +1041,72 @@ impl BpfAmm {
         }
     }
 
+    /// Round-trip spread in bps of spot: buy X with `probe` of the Y reserve, sell `probe`
+    /// of the X reserve, and compare the two average prices. `None` when either side
+    /// quotes nothing.
+    pub fn quoted_spread_bps(&mut self, probe: f64) -> Option<f64> {
+        let (buy_y, sell_x) = (self.reserve_y * probe, self.reserve_x * probe);
+        let (bought_x, sold_y) = (self.quote_buy_x(buy_y), self.quote_sell_x(sell_x));
+        let spot = self.spot_price();
+        (bought_x > 0.0 && sold_y > 0.0 && spot > 0.0)
+            .then(|| (buy_y / bought_x - sold_y / sell_x) / spot * 10_000.0)
+    }
+
     pub fn set_initial_storage(&mut self, bytes: &[u8]) {
+        self.invalidate_quotes();
         let n = bytes.len().min(self.storage.len());
         self.storage[..n].copy_from_slice(&bytes[..n]);
     }
 
+    /// Overwrite the start of storage mid-run; the engine re-prices an adaptive reference
+    /// pool this way. Never used on the submission, whose storage is its own.
+    pub(crate) fn overwrite_storage(&mut self, bytes: &[u8]) {
+        self.set_initial_storage(bytes);
+    }
+
+    /// Live strategy storage, borrowed without copying. Only the strategy's `after_swap`
+    /// (and `set_initial_storage` before a run) may write it.
     #[inline]
     pub fn storage(&self) -> &[u8] {
         &self.storage
     }
 
+    /// Put the pool back into a checkpointed state (reserves, storage and step).
+    pub(crate) fn restore_state(
+        &mut self,
+        reserve_x: f64,
+        reserve_y: f64,
+        storage: &[u8],
+        step: u64,
+    ) {
+        self.reserve_x = reserve_x;
+        self.reserve_y = reserve_y;
+        self.invalidate_quotes();
+        self.storage.fill(0);
+        let n = storage.len().min(self.storage.len());
+        self.storage[..n].copy_from_slice(&storage[..n]);
+        self.current_step = step;
+        self.last_trade = None;
+        self.violations.clear();
+        if self.reference.is_some() {
+            self.track_reference_curve();
+        }
+    }
+
     pub fn reset(&mut self, reserve_x: f64, reserve_y: f64) {
         self.reserve_x = reserve_x;
         self.reserve_y = reserve_y;
+        self.invalidate_quotes();
         self.storage.fill(0);
         self.current_step = 0;
+        self.last_trade = None;
+        self.violations.clear();
+        self.set_subsidy_paid(0.0);
+        self.set_inventory_rejected(0.0);
+        self.set_storage_watch(0, false);
+        if self.reference.is_some() {
+            self.track_reference_curve();
+        }
     }
 
     #[inline]
@@ -240,3 +1114,376 @@ impl BpfAmm {
         matches!(self.backend, Backend::Bpf(_))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::BpfAmm;
+    use prop_amm_shared::instruction::decode_instruction;
+    use prop_amm_shared::instruction::Side;
+    use prop_amm_shared::nano::NANO_SCALE_F64;
+    use prop_amm_shared::normalizer::compute_swap as normalizer_swap;
+    use prop_amm_shared::result::DisqualificationKind;
+
+    #[test]
+    fn exact_out_round_trips_with_exact_in_for_normalizer() {
+        let mut amm = BpfAmm::new_native(normalizer_swap, None, 100.0, 10_000.0, "n".into());
+        let one_nano = 1.0 / NANO_SCALE_F64;
+        for desired_x in [0.001, 0.5, 3.0, 40.0] {
+            let input_y = amm.quote_buy_x_exact_out(desired_x);
+            assert!(amm.quote_buy_x(input_y) >= desired_x - one_nano);
+            assert!(amm.quote_buy_x(input_y - one_nano) < desired_x);
+        }
+        for desired_y in [0.01, 50.0, 2_500.0] {
+            let input_x = amm.quote_sell_x_exact_out(desired_y);
+            assert!(amm.quote_sell_x(input_x) >= desired_y - one_nano);
+            assert!(amm.quote_sell_x(input_x - one_nano) < desired_y);
+        }
+        assert_eq!(amm.quote_buy_x_exact_out(100.0), f64::INFINITY);
+        assert_eq!((amm.reserve_x, amm.reserve_y), (100.0, 10_000.0));
+    }
+
+    #[test]
+    fn u64_max_quote_is_refused_and_flagged_as_an_over_quote() {
+        let mut amm = BpfAmm::new_native(|_| u64::MAX, None, 100.0, 10_000.0, "max".into());
+        assert_eq!(amm.quote_buy_x(10.0), 0.0);
+        assert_eq!(amm.execute_sell_x(1.0), 0.0);
+        assert_eq!(amm.quote_buy_x_exact_out(1.0), f64::INFINITY);
+        assert_eq!((amm.reserve_x, amm.reserve_y), (100.0, 10_000.0));
+
+        let violations = amm.take_violations();
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].0, DisqualificationKind::OverQuote);
+        assert_eq!(
+            violations[0].1,
+            "buy_x quoted 18446744073.709553 X against a reserve of 100"
+        );
+    }
+
+    #[test]
+    fn only_exact_in_sides_reach_the_strategy() {
+        use prop_amm_executor::ExecutorError;
+        use prop_amm_shared::instruction::{DecodeError, Oracle};
+
+        let mut amm = BpfAmm::new_native(normalizer_swap, None, 100.0, 10_000.0, "n".into());
+        let mut quote = |side| {
+            let storage = [0u8; super::STORAGE_SIZE];
+            amm.backend.quote(
+                side,
+                1_000_000_000,
+                100,
+                10_000,
+                &storage,
+                Oracle::default(),
+            )
+        };
+        assert!(quote(0).is_ok() && quote(1).is_ok());
+        // Reserved for exact-out, and the after_swap tag.
+        assert!(matches!(
+            quote(6),
+            Err(ExecutorError::Decode(DecodeError::ReservedSide(6)))
+        ));
+        assert!(matches!(
+            quote(2),
+            Err(ExecutorError::Decode(DecodeError::InvalidSide(2)))
+        ));
+    }
+
+    #[test]
+    fn overshoot_within_the_flag_multiple_is_refused_quietly() {
+        // Quotes half again the reserve on every side, as a naive formula might past its depth.
+        fn overshoot(data: &[u8]) -> u64 {
+            let (side, _, rx, ry) = decode_instruction(data);
+            let reserve = if side == 0 { rx } else { ry };
+            reserve + reserve / 2
+        }
+        let mut amm = BpfAmm::new_native(overshoot, None, 100.0, 10_000.0, "over".into());
+        assert_eq!(amm.quote_buy_x(10.0), 0.0);
+        assert_eq!(amm.quote_sell_x(1.0), 0.0);
+        assert!(amm.take_violations().is_empty());
+    }
+
+    #[test]
+    fn astronomical_sell_is_refused_and_flagged_as_a_reserve_overflow() {
+        let mut amm = BpfAmm::new_native(normalizer_swap, None, 100.0, 10_000.0, "n".into());
+        assert_eq!(amm.execute_sell_x(1e300), 0.0);
+        assert_eq!(amm.execute_sell_x(f64::INFINITY), 0.0);
+        assert_eq!(amm.execute_buy_x(2e10), 0.0);
+        assert!(amm.reserve_x.is_finite() && amm.reserve_y.is_finite());
+        assert_eq!((amm.reserve_x, amm.reserve_y), (100.0, 10_000.0));
+
+        let violations = amm.take_violations();
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].0, DisqualificationKind::ReserveOverflow);
+        assert_eq!(
+            violations[0].1,
+            "sell_x input of 1e300 X would overflow a reserve of 100"
+        );
+        // A large but representable sell still fills.
+        assert!(amm.execute_sell_x(1e6) > 0.0);
+        assert!(amm.take_violations().is_empty());
+    }
+
+    #[test]
+    fn locked_reserves_shrink_quoted_depth_and_survive_a_draining_strategy() {
+        // Gives away half of whatever X it is shown for any Y at all.
+        fn half_the_x(data: &[u8]) -> u64 {
+            let rx = u64::from_le_bytes(data[9..17].try_into().unwrap());
+            if data[0] == 0 {
+                rx / 2
+            } else {
+                0
+            }
+        }
+
+        let mut locked = BpfAmm::new_native(normalizer_swap, None, 100.0, 10_000.0, "l".into());
+        locked.set_locked_reserves(40.0, 0.0);
+        let mut shallow = BpfAmm::new_native(normalizer_swap, None, 60.0, 10_000.0, "s".into());
+        assert_eq!(locked.tradeable_reserves(), (60.0, 10_000.0));
+        assert_eq!(locked.quote_buy_x(500.0), shallow.quote_buy_x(500.0));
+        assert_eq!(locked.quote_sell_x(5.0), shallow.quote_sell_x(5.0));
+        assert_eq!(
+            locked.quote_buy_x_exact_out(10.0),
+            shallow.quote_buy_x_exact_out(10.0)
+        );
+        // The whole tradeable X is out of reach, even though the pool holds more.
+        assert_eq!(locked.quote_buy_x_exact_out(60.0), f64::INFINITY);
+
+        let drain = |lock: f64| {
+            let mut amm = BpfAmm::new_native(half_the_x, None, 100.0, 10_000.0, "d".into());
+            amm.set_locked_reserves(lock, 0.0);
+            for _ in 0..200 {
+                amm.execute_buy_x(1.0);
+            }
+            amm.reserve_x
+        };
+        assert!(drain(0.0) < 1e-6);
+        let floor = drain(40.0);
+        assert!(floor > 40.0 && floor < 40.0 + 1e-6, "{floor}");
+    }
+
+    #[test]
+    fn settled_input_reaches_after_swap_and_the_inventory_cap() {
+        use prop_amm_shared::instruction::decode_after_swap;
+        use prop_amm_shared::nano::nano_to_f64;
+
+        // Keeps the input and Y reserve its last after_swap was shown.
+        fn remember(data: &[u8], storage: &mut [u8]) {
+            let (_, input, _, _, ry, _, _) = decode_after_swap(data);
+            storage[0..8].copy_from_slice(&input.to_le_bytes());
+            storage[8..16].copy_from_slice(&ry.to_le_bytes());
+        }
+        let seen = |amm: &BpfAmm, at: usize| {
+            nano_to_f64(u64::from_le_bytes(
+                amm.storage()[at..at + 8].try_into().unwrap(),
+            ))
+        };
+
+        // A 1% transfer fee: 99 of every 100 Y paid in reaches the pool.
+        let mut amm =
+            BpfAmm::new_native(normalizer_swap, Some(remember), 100.0, 10_000.0, "t".into());
+        amm.set_input_settlement(-100.0);
+        assert!(amm.execute_buy_x(100.0) > 0.0);
+        assert_eq!(amm.reserve_y, 10_099.0);
+        assert_eq!((seen(&amm, 0), seen(&amm, 8)), (99.0, 10_099.0));
+
+        // A 1% rebate pushes a fill that would fit gross over the cap.
+        let mut amm = BpfAmm::new_native(normalizer_swap, None, 100.0, 10_000.0, "r".into());
+        amm.set_input_settlement(100.0);
+        amm.set_inventory_limit(None, Some(10_100.5));
+        assert_eq!(amm.execute_buy_x(100.0), 0.0);
+        assert_eq!((amm.reserve_x, amm.reserve_y), (100.0, 10_000.0));
+        assert_eq!(amm.inventory_rejected(), 100.0);
+    }
+
+    #[test]
+    fn rebalance_respects_the_lock_and_inventory_limit_and_reaches_after_swap() {
+        use prop_amm_shared::instruction::decode_after_swap;
+        use prop_amm_shared::nano::nano_to_f64;
+
+        // Counts after_swap calls and keeps the side, input and output of the last one.
+        fn remember(data: &[u8], storage: &mut [u8]) {
+            let (side, input, output, _, _, _, _) = decode_after_swap(data);
+            storage[0] += 1;
+            storage[1] = side;
+            storage[8..16].copy_from_slice(&input.to_le_bytes());
+            storage[16..24].copy_from_slice(&output.to_le_bytes());
+        }
+        let last = |amm: &BpfAmm| {
+            let word = |at: usize| {
+                nano_to_f64(u64::from_le_bytes(
+                    amm.storage()[at..at + 8].try_into().unwrap(),
+                ))
+            };
+            (amm.storage()[0], amm.storage()[1], word(8), word(16))
+        };
+
+        // Selling 10 X at 100 is capped at the 500 Y the inventory limit leaves room for.
+        let mut amm =
+            BpfAmm::new_native(normalizer_swap, Some(remember), 100.0, 10_000.0, "r".into());
+        amm.set_inventory_limit(None, Some(10_500.0));
+        assert_eq!(amm.rebalance_x(10.0, 100.0), 5.0);
+        assert_eq!((amm.reserve_x, amm.reserve_y), (95.0, 10_500.0));
+        assert_eq!(last(&amm), (1, 0, 500.0, 5.0));
+        assert_eq!(amm.rebalance_x(1.0, 100.0), 0.0);
+
+        // Buying X back is reported as a sell of X into the pool.
+        assert_eq!(amm.rebalance_x(-2.0, 100.0), -2.0);
+        assert_eq!((amm.reserve_x, amm.reserve_y), (97.0, 10_300.0));
+        assert_eq!(last(&amm), (2, 1, 2.0, 200.0));
+
+        // A rebalance that would dig into the locked X is refused and never reported.
+        amm.set_locked_reserves(96.0, 0.0);
+        assert_eq!(amm.rebalance_x(1.0, 100.0), 0.0);
+        assert_eq!((amm.reserve_x, amm.reserve_y), (97.0, 10_300.0));
+        assert_eq!(last(&amm).0, 2);
+    }
+
+    #[test]
+    fn repeated_probes_hit_the_quote_cache_until_the_pool_changes() {
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        static CALLS: AtomicU64 = AtomicU64::new(0);
+        fn counted_swap(data: &[u8]) -> u64 {
+            CALLS.fetch_add(1, Ordering::Relaxed);
+            normalizer_swap(data)
+        }
+        fn bump_storage(_data: &[u8], storage: &mut [u8]) {
+            storage[100] = storage[100].wrapping_add(1);
+        }
+
+        let mut amm = BpfAmm::new_native(
+            counted_swap,
+            Some(bump_storage),
+            100.0,
+            10_000.0,
+            "c".into(),
+        );
+        amm.cache_quotes();
+        let first = amm.quote_buy_x(20.0);
+        assert_eq!(amm.quote_buy_x(20.0), first);
+        assert_eq!(amm.quoted_spread_bps(0.01), amm.quoted_spread_bps(0.01));
+        assert_eq!(CALLS.load(Ordering::Relaxed), 3);
+        assert_eq!(amm.quote_cache_hits(), 3);
+
+        // New reserves are a new key; a new step or a storage write clears the cache.
+        let calls = || CALLS.load(Ordering::Relaxed);
+        amm.reserve_x = 101.0;
+        assert_ne!(amm.quote_buy_x(20.0), first);
+        assert_eq!(calls(), 4);
+        amm.set_current_step(1);
+        amm.quote_buy_x(20.0);
+        assert_eq!(calls(), 5);
+        amm.execute_buy_x(5.0);
+        let before = calls();
+        let after_trade = amm.quote_buy_x(20.0);
+        assert_eq!(amm.quote_buy_x(20.0), after_trade);
+        assert_eq!(calls(), before + 1);
+        assert_eq!(amm.quote_cache_hits(), 4);
+    }
+
+    #[test]
+    fn sampled_normalizer_curve_is_monotone_and_leaves_pool_untouched() {
+        let mut amm = BpfAmm::new_native(normalizer_swap, None, 100.0, 10_000.0, "n".into());
+        for side in [Side::BuyX, Side::SellX] {
+            let max_size = if side == Side::BuyX { 5_000.0 } else { 50.0 };
+            let curve = amm.sample_curve(side, max_size, 64);
+            assert_eq!(curve.len(), 64);
+            for pair in curve.windows(2) {
+                assert!(pair[1].output > pair[0].output, "{side:?}: {pair:?}");
+            }
+        }
+        // Exact-out: every larger desired output costs more, and the whole reserve is out of
+        // reach.
+        for (side, reserve) in [(Side::ExactOutBuy, 100.0), (Side::ExactOutSell, 10_000.0)] {
+            let curve = amm.sample_curve(side, reserve, 64);
+            for pair in curve[..63].windows(2) {
+                assert!(pair[1].input > pair[0].input, "{side:?}: {pair:?}");
+            }
+            assert_eq!(curve[63].input, f64::INFINITY);
+            assert!(curve[63].effective_price.is_nan());
+        }
+        assert_eq!((amm.reserve_x, amm.reserve_y), (100.0, 10_000.0));
+    }
+
+    #[test]
+    fn low_decimal_token_quotes_snap_to_its_grid() {
+        let sell_x_outputs = |decimals_y: u32| {
+            let mut amm = BpfAmm::new_native(normalizer_swap, None, 100.0, 10_000.0, "n".into());
+            amm.set_decimals(9, decimals_y);
+            (1..=200)
+                .map(|i| amm.quote_sell_x(0.001 + 0.001 * i as f64 / 200.0))
+                .collect::<Vec<_>>()
+        };
+        let distinct = |outputs: &[f64]| {
+            let mut outputs = outputs.to_vec();
+            outputs.dedup();
+            outputs.len()
+        };
+
+        // Two-decimal Y: every output is a whole cent and the ~0.1 Y range takes ~10 values.
+        let coarse = sell_x_outputs(2);
+        for output in &coarse {
+            let cents = output * 100.0;
+            assert!((cents - cents.round()).abs() < 1e-6, "{output}");
+        }
+        assert!(distinct(&coarse) <= 12, "{coarse:?}");
+
+        // Nine-decimal Y: every input step moves the output.
+        let fine = sell_x_outputs(9);
+        assert_eq!(distinct(&fine), fine.len());
+        assert!(fine
+            .iter()
+            .zip(&coarse)
+            .all(|(f, c)| f >= c && f - c < 0.01));
+    }
+
+    #[test]
+    fn self_check_holds_across_random_trades() {
+        use rand::{Rng, SeedableRng};
+
+        // Self-lagged quotes depend on storage its after_swap rewrites on every trade.
+        let strategy = crate::opponents::find("self-lagged").unwrap();
+        let mut amm = BpfAmm::new_native(
+            strategy.swap,
+            strategy.after_swap,
+            100.0,
+            10_000.0,
+            "submission".into(),
+        );
+        amm.expect_pure_quotes();
+        assert!(amm.self_check());
+        let mut rng = rand_pcg::Pcg64::seed_from_u64(7);
+        for _ in 0..2_000 {
+            if rng.gen_bool(0.5) {
+                amm.execute_buy_x(rng.gen_range(0.01..500.0));
+            } else {
+                amm.execute_sell_x(rng.gen_range(0.0001..5.0));
+            }
+            assert!(amm.self_check());
+        }
+
+        if let Some(trade) = amm.last_trade.as_mut() {
+            trade.output *= 1.0 + 1e-9;
+            assert!(!amm.self_check());
+        }
+    }
+
+    #[test]
+    fn impure_submission_quotes_do_not_panic_the_fill() {
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        // Quotes a little less on every call, so no fill ever re-quotes to itself.
+        static CALLS: AtomicU64 = AtomicU64::new(0);
+        fn drifting(data: &[u8]) -> u64 {
+            let calls = CALLS.fetch_add(1, Ordering::Relaxed);
+            normalizer_swap(data).saturating_sub(calls)
+        }
+
+        let mut amm = BpfAmm::new_native(drifting, None, 100.0, 10_000.0, "submission".into());
+        for _ in 0..10 {
+            assert!(amm.execute_buy_x(50.0) > 0.0);
+            assert!(amm.execute_sell_x(0.5) > 0.0);
+        }
+        assert!(amm.self_check());
+    }
+}
@@ -1,31 +1,30 @@
 use crate::amm::BpfAmm;
 use crate::curve_checks;
+use crate::price_process::round_to_tick;
+use crate::rng::{Draws, EngineRng};
 use crate::search_stats;
+use prop_amm_shared::config::{ARB_SOLVER_ITERS, ARB_SOLVER_TOLERANCE};
+use prop_amm_shared::instruction::Side;
 use prop_amm_shared::nano::NANO_SCALE_F64;
-use rand::SeedableRng;
 use rand_distr::{Distribution, LogNormal};
 use rand_pcg::Pcg64;
 
 const MIN_INPUT: f64 = 0.001;
 const GOLDEN_RATIO_CONJUGATE: f64 = 0.618_033_988_749_894_8;
-const GOLDEN_MAX_ITERS: usize = 12;
-// Stop once the bracket is narrow enough that the trade size is within ~1%.
-const GOLDEN_INPUT_REL_TOL: f64 = 1e-2;
+// Below this absolute bracket width, nano rounding of quotes dominates the sampled slopes, so
+// tighter user tolerances would only feed quantization noise into the shape checks. Applied
+// only under a user tolerance, so default runs stop exactly where they always did.
+const GOLDEN_INPUT_ABS_TOL: f64 = 3e-4;
 const BRACKET_MAX_STEPS: usize = 24;
 const BRACKET_GROWTH: f64 = 2.0;
 const MAX_INPUT_AMOUNT: f64 = (u64::MAX as f64 / NANO_SCALE_F64) * 0.999_999;
 // Ignore micro-arbs by requiring a minimum quote-token (Y) notional.
 const MIN_ARB_NOTIONAL_Y: f64 = 0.01;
 
-#[derive(Clone, Copy)]
-enum ArbSide {
-    BuyX,
-    SellX,
-}
-
 #[derive(Clone, Copy)]
 struct ArbCandidate {
-    side: ArbSide,
+    /// `BuyX` or `SellX`: arbitrageurs size their input, never their output.
+    side: Side,
     input_amount: f64,
     expected_profit: f64,
 }
@@ -37,43 +36,118 @@ pub struct ArbResult {
     pub edge: f64,
 }
 
-pub struct Arbitrageur {
+pub struct Arbitrageur<R = Pcg64> {
     min_arb_profit: f64,
-    rng: Pcg64,
+    // Per-notional cost (threshold_bps / 1e4) shading the agent's valuation of X.
+    cost_rate: f64,
+    solver_iters: usize,
+    // Stop once the bracket is narrow enough that the trade size is within this relative width.
+    solver_tolerance: f64,
+    // ...or narrower than this absolute width (`GOLDEN_INPUT_ABS_TOL` under a user tolerance).
+    solver_abs_tolerance: f64,
+    // Skip trades whose notional in Y is below this (dust filter).
+    min_trade_size: f64,
+    // Price grid: mispricings that stay within one tick of fair are not traded.
+    tick_size: Option<f64>,
+    rng: R,
     retail_size_dist: LogNormal<f64>,
 }
 
-impl Arbitrageur {
-    pub fn new(
-        min_arb_profit: f64,
-        retail_mean_size: f64,
-        retail_size_sigma: f64,
-        seed: u64,
-    ) -> Self {
+impl<R: EngineRng> Arbitrageur<R> {
+    pub fn new(min_arb_profit: f64, retail_mean_size: f64, retail_size_sigma: f64, rng: R) -> Self {
         let sigma = retail_size_sigma.max(0.01);
         let mu_ln = retail_mean_size.max(0.01).ln() - 0.5 * sigma * sigma;
         Self {
             min_arb_profit: min_arb_profit.max(0.0),
-            rng: Pcg64::seed_from_u64(seed),
+            cost_rate: 0.0,
+            solver_iters: ARB_SOLVER_ITERS as usize,
+            solver_tolerance: ARB_SOLVER_TOLERANCE,
+            solver_abs_tolerance: 0.0,
+            min_trade_size: 0.0,
+            tick_size: None,
+            rng,
             retail_size_dist: LogNormal::new(mu_ln, sigma).unwrap(),
         }
     }
 
+    /// Override the golden-section iteration cap and relative stopping tolerance. A tolerance
+    /// other than `ARB_SOLVER_TOLERANCE` also stops at `GOLDEN_INPUT_ABS_TOL`.
+    pub fn with_solver(mut self, max_iters: u32, rel_tolerance: f64) -> Self {
+        self.solver_iters = max_iters as usize;
+        self.solver_tolerance = if rel_tolerance.is_finite() && rel_tolerance > 0.0 {
+            rel_tolerance
+        } else {
+            ARB_SOLVER_TOLERANCE
+        };
+        self.solver_abs_tolerance = if self.solver_tolerance == ARB_SOLVER_TOLERANCE {
+            0.0
+        } else {
+            GOLDEN_INPUT_ABS_TOL
+        };
+        self
+    }
+
+    /// Only trade mispricings wider than `threshold_bps` of notional.
+    pub fn with_threshold_bps(mut self, threshold_bps: f64) -> Self {
+        self.cost_rate = if threshold_bps.is_finite() {
+            (threshold_bps / 10_000.0).clamp(0.0, 0.5)
+        } else {
+            0.0
+        };
+        self
+    }
+
+    /// Leave opportunities whose trade notional is below `min_trade_size` Y untouched.
+    pub fn with_min_trade_size(mut self, min_trade_size: f64) -> Self {
+        self.min_trade_size = if min_trade_size.is_finite() {
+            min_trade_size.max(0.0)
+        } else {
+            0.0
+        };
+        self
+    }
+
+    /// Ignore a pool whose spot price is within one `tick_size` of the (rounded) fair price.
+    pub fn with_tick_size(mut self, tick_size: Option<f64>) -> Self {
+        self.tick_size = tick_size.filter(|tick| tick.is_finite() && *tick > 0.0);
+        self
+    }
+
+    pub(crate) fn rng(&self) -> &R {
+        &self.rng
+    }
+
+    pub(crate) fn set_rng(&mut self, rng: R) {
+        self.rng = rng;
+    }
+
     pub fn execute_arb(&mut self, amm: &mut BpfAmm, fair_price: f64) -> Option<ArbResult> {
         if !fair_price.is_finite() || fair_price <= 0.0 {
             return None;
         }
+        if let Some(tick) = self.tick_size {
+            // Prices are only observable on the grid, so a gap under one tick is noise.
+            if (amm.spot_price() - round_to_tick(fair_price, tick)).abs() < tick {
+                return None;
+            }
+        }
+
+        // Prices at which this agent is willing to buy / sell X after its trading cost.
+        let buy_value = fair_price * (1.0 - self.cost_rate);
+        let sell_value = fair_price * (1.0 + self.cost_rate);
 
         let best = if amm.name == "normalizer" {
             // The normalizer is a known constant-product-with-fee curve. Keep it closed-form,
             // but evaluate both sides and execute whichever quote-implied trade is better.
             Self::best_candidate(
-                self.plan_normalizer_buy_x(amm, fair_price),
-                self.plan_normalizer_sell_x(amm, fair_price),
+                self.plan_normalizer_buy_x(amm, buy_value),
+                self.plan_normalizer_sell_x(amm, sell_value),
             )
         } else {
             // Evaluate both book sides from compute_swap quotes; reserve_y/reserve_x can be a
-            // misleading directional signal for non-CP strategies.
+            // misleading directional signal for non-CP strategies. Each side is searched on its
+            // own quotes, never mirrored from the other, so a strategy that skews its buy and
+            // sell curves (e.g. to shed inventory) is arbitraged at each side's own optimum.
             let min_buy_input = Self::min_buy_input_y();
             let min_sell_input = Self::min_sell_input_x(fair_price);
             let start_y = self
@@ -84,16 +158,26 @@ impl Arbitrageur {
                 .max(min_sell_input)
                 .min(MAX_INPUT_AMOUNT);
             Self::best_candidate(
-                self.plan_arb_buy_x(amm, fair_price, start_y, min_buy_input),
-                self.plan_arb_sell_x(amm, fair_price, start_x, min_sell_input),
+                self.plan_arb_buy_x(amm, buy_value, start_y, min_buy_input),
+                self.plan_arb_sell_x(amm, sell_value, start_x, min_sell_input),
             )
         }?;
 
+        let notional_y = match best.side {
+            Side::BuyX | Side::ExactOutBuy => best.input_amount,
+            Side::SellX | Side::ExactOutSell => best.input_amount * fair_price,
+        };
+        if notional_y < self.min_trade_size {
+            return None;
+        }
+
         self.execute_candidate(amm, fair_price, best)
     }
 
     fn sample_retail_size_y(&mut self) -> f64 {
-        self.retail_size_dist.sample(&mut self.rng).max(MIN_INPUT)
+        self.retail_size_dist
+            .sample(&mut Draws(&mut self.rng))
+            .max(MIN_INPUT)
     }
 
     #[inline]
@@ -109,7 +193,7 @@ impl Arbitrageur {
     fn plan_normalizer_buy_x(&self, amm: &mut BpfAmm, fair_price: f64) -> Option<ArbCandidate> {
         debug_assert_eq!(amm.name, "normalizer");
 
-        let fee_bps = Self::normalizer_fee_bps(amm) as f64;
+        let fee_bps = amm.normalizer_fee_bps() as f64 - amm.subsidy_bps();
         let gamma = (10_000.0 - fee_bps) / 10_000.0;
         if !gamma.is_finite() || gamma <= 0.0 {
             return None;
@@ -137,7 +221,7 @@ impl Arbitrageur {
                 return None;
             }
             Some(ArbCandidate {
-                side: ArbSide::BuyX,
+                side: Side::BuyX,
                 input_amount: input_y,
                 expected_profit: arb_profit,
             })
@@ -147,7 +231,7 @@ impl Arbitrageur {
     fn plan_normalizer_sell_x(&self, amm: &mut BpfAmm, fair_price: f64) -> Option<ArbCandidate> {
         debug_assert_eq!(amm.name, "normalizer");
 
-        let fee_bps = Self::normalizer_fee_bps(amm) as f64;
+        let fee_bps = amm.normalizer_fee_bps() as f64 - amm.subsidy_bps();
         let gamma = (10_000.0 - fee_bps) / 10_000.0;
         if !gamma.is_finite() || gamma <= 0.0 {
             return None;
@@ -175,29 +259,13 @@ impl Arbitrageur {
                 return None;
             }
             Some(ArbCandidate {
-                side: ArbSide::SellX,
+                side: Side::SellX,
                 input_amount: input_x,
                 expected_profit: arb_profit,
             })
         }
     }
 
-    #[inline]
-    fn normalizer_fee_bps(amm: &BpfAmm) -> u16 {
-        // normalizer::compute_swap reads fee_bps from data[25..27], i.e. storage[0..2].
-        let s = amm.storage();
-        if s.len() >= 2 {
-            let raw = u16::from_le_bytes([s[0], s[1]]);
-            if raw == 0 {
-                30
-            } else {
-                raw
-            }
-        } else {
-            30
-        }
-    }
-
     fn plan_arb_buy_x(
         &mut self,
         amm: &mut BpfAmm,
@@ -205,13 +273,17 @@ impl Arbitrageur {
         start_y: f64,
         min_buy_input: f64,
     ) -> Option<ArbCandidate> {
-        let mut sampled_curve = Vec::with_capacity(BRACKET_MAX_STEPS + GOLDEN_MAX_ITERS + 8);
+        let (iters, tol) = (
+            self.solver_iters,
+            (self.solver_tolerance, self.solver_abs_tolerance),
+        );
+        let mut sampled_curve = Vec::with_capacity(BRACKET_MAX_STEPS + iters + 8);
         let (lo, hi) = Self::bracket_maximum(start_y, min_buy_input, MAX_INPUT_AMOUNT, |input_y| {
             let output_x = amm.quote_buy_x(input_y);
             sampled_curve.push((input_y, output_x));
             output_x * fair_price - input_y
         });
-        let (optimal_y, _) = Self::golden_section_max(lo, hi, |input_y| {
+        let (optimal_y, _) = Self::golden_section_max(lo, hi, iters, tol, |input_y| {
             let output_x = amm.quote_buy_x(input_y);
             sampled_curve.push((input_y, output_x));
             output_x * fair_price - input_y
@@ -238,7 +310,7 @@ impl Arbitrageur {
         }
 
         Some(ArbCandidate {
-            side: ArbSide::BuyX,
+            side: Side::BuyX,
             input_amount: optimal_y,
             expected_profit: arb_profit,
         })
@@ -251,14 +323,18 @@ impl Arbitrageur {
         start_x: f64,
         min_sell_input: f64,
     ) -> Option<ArbCandidate> {
-        let mut sampled_curve = Vec::with_capacity(BRACKET_MAX_STEPS + GOLDEN_MAX_ITERS + 8);
+        let (iters, tol) = (
+            self.solver_iters,
+            (self.solver_tolerance, self.solver_abs_tolerance),
+        );
+        let mut sampled_curve = Vec::with_capacity(BRACKET_MAX_STEPS + iters + 8);
         let (lo, hi) =
             Self::bracket_maximum(start_x, min_sell_input, MAX_INPUT_AMOUNT, |input_x| {
             let output_y = amm.quote_sell_x(input_x);
             sampled_curve.push((input_x, output_y));
             output_y - input_x * fair_price
         });
-        let (optimal_x, _) = Self::golden_section_max(lo, hi, |input_x| {
+        let (optimal_x, _) = Self::golden_section_max(lo, hi, iters, tol, |input_x| {
             let output_y = amm.quote_sell_x(input_x);
             sampled_curve.push((input_x, output_y));
             output_y - input_x * fair_price
@@ -285,7 +361,7 @@ impl Arbitrageur {
         }
 
         Some(ArbCandidate {
-            side: ArbSide::SellX,
+            side: Side::SellX,
             input_amount: optimal_x,
             expected_profit: arb_profit,
         })
@@ -316,7 +392,8 @@ impl Arbitrageur {
         candidate: ArbCandidate,
     ) -> Option<ArbResult> {
         match candidate.side {
-            ArbSide::BuyX => {
+            Side::ExactOutBuy | Side::ExactOutSell => unreachable!("arbs only plan exact-in"),
+            Side::BuyX => {
                 let output_x = amm.execute_buy_x(candidate.input_amount);
                 if output_x <= 0.0 {
                     return None;
@@ -328,7 +405,7 @@ impl Arbitrageur {
                     edge: candidate.input_amount - output_x * fair_price,
                 })
             }
-            ArbSide::SellX => {
+            Side::SellX => {
                 let output_y = amm.execute_sell_x(candidate.input_amount);
                 if output_y <= 0.0 {
                     return None;
@@ -393,7 +470,13 @@ impl Arbitrageur {
         (lo, hi)
     }
 
-    fn golden_section_max<F>(lo: f64, hi: f64, mut objective: F) -> (f64, f64)
+    fn golden_section_max<F>(
+        lo: f64,
+        hi: f64,
+        max_iters: usize,
+        (rel_tol, abs_tol): (f64, f64),
+        mut objective: F,
+    ) -> (f64, f64)
     where
         F: FnMut(f64) -> f64,
     {
@@ -432,7 +515,7 @@ impl Arbitrageur {
             best_value = f2;
         }
 
-        for _ in 0..GOLDEN_MAX_ITERS {
+        for _ in 0..max_iters {
             search_stats::inc_arb_golden_iter();
             if f1 < f2 {
                 left = x1;
@@ -462,7 +545,7 @@ impl Arbitrageur {
             // the trade, not precisely maximizing profit.
             let mid = 0.5 * (left + right);
             let denom = mid.abs().max(MIN_INPUT);
-            if (right - left) <= GOLDEN_INPUT_REL_TOL * denom {
+            if (right - left) <= (rel_tol * denom).max(abs_tol) {
                 search_stats::inc_arb_early_stop_amount_tol();
                 break;
             }
@@ -487,6 +570,8 @@ mod tests {
     use super::Arbitrageur;
     use crate::amm::BpfAmm;
     use prop_amm_shared::normalizer::compute_swap as normalizer_swap;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64;
 
     const NANO_SCALE: f64 = 1_000_000_000.0;
 
@@ -558,12 +643,116 @@ mod tests {
         to_nano_u64(output)
     }
 
+    const SKEW_BUY_FEE_BPS: f64 = 10.0;
+    const SKEW_SELL_FEE_BPS: f64 = 300.0;
+
+    /// Constant product quoting `SKEW_BUY_FEE_BPS` to buyers of X and `SKEW_SELL_FEE_BPS` to
+    /// sellers, like a pool keen to shed X.
+    fn skewed_fee_swap(data: &[u8]) -> u64 {
+        if data.len() < 25 {
+            return 0;
+        }
+        let read = |at: usize| {
+            u64::from_le_bytes(data[at..at + 8].try_into().expect("u64")) as f64 / NANO_SCALE
+        };
+        let (input, rx, ry) = (read(1), read(9), read(17));
+        let output = match data[0] {
+            0 => rx - rx * ry / (ry + input * (1.0 - SKEW_BUY_FEE_BPS / 10_000.0)),
+            1 => ry - rx * ry / (rx + input * (1.0 - SKEW_SELL_FEE_BPS / 10_000.0)),
+            _ => 0.0,
+        };
+        to_nano_u64(output)
+    }
+
+    /// Y input that moves a constant-product pool with fee `fee_bps` to `fair_price`.
+    fn cp_optimal_buy_input(rx: f64, ry: f64, fee_bps: f64, fair_price: f64) -> f64 {
+        let gamma = 1.0 - fee_bps / 10_000.0;
+        ((fair_price * rx * gamma * ry).sqrt() - ry) / gamma
+    }
+
+    #[test]
+    fn skewed_strategy_is_arbitraged_on_each_sides_own_curve() {
+        let (rx, ry, fair_price) = (100.0, 10_000.0, 106.0);
+        let skewed = || BpfAmm::new_native(skewed_fee_swap, None, rx, ry, "test".to_string());
+        let buy_profit = |input_y: f64| skewed().quote_buy_x(input_y) * fair_price - input_y;
+
+        let mut amm = skewed();
+        let mut arb = Arbitrageur::new(0.0, 20.0, 1.2, Pcg64::seed_from_u64(5));
+        let result = arb.execute_arb(&mut amm, fair_price).expect("buy-X arb");
+        assert!(!result.amm_buys_x);
+
+        // The buy curve's own optimum, and the one a solver mirroring the sell curve would pick.
+        let own = cp_optimal_buy_input(rx, ry, SKEW_BUY_FEE_BPS, fair_price);
+        let mirrored = cp_optimal_buy_input(rx, ry, SKEW_SELL_FEE_BPS, fair_price);
+        assert!(
+            (result.amount_y - own).abs() < 1e-2 * own,
+            "traded {} Y, optimum {own}",
+            result.amount_y
+        );
+        let realized = -result.edge;
+        assert!((realized - buy_profit(own)).abs() < 1e-4 * realized);
+        // Sizing off the mirrored curve would leave a detectable share of the arb (and of the
+        // strategy's loss) unaccounted for.
+        assert!(
+            buy_profit(mirrored) < 0.8 * realized,
+            "mirrored sizing profit {} vs {realized}",
+            buy_profit(mirrored)
+        );
+
+        // Below fair the expensive sell side is traded on its own, much smaller, opportunity.
+        let mut amm = skewed();
+        let result = arb.execute_arb(&mut amm, 94.0).expect("sell-X arb");
+        assert!(result.amm_buys_x);
+        let gamma = 1.0 - SKEW_SELL_FEE_BPS / 10_000.0;
+        let own_x = ((ry * rx * gamma / 94.0).sqrt() - rx) / gamma;
+        assert!(
+            (result.amount_x - own_x).abs() < 1e-2 * own_x,
+            "traded {} X, optimum {own_x}",
+            result.amount_x
+        );
+    }
+
+    #[test]
+    fn absolute_stop_applies_only_under_a_user_tolerance() {
+        use super::GOLDEN_INPUT_ABS_TOL;
+        use prop_amm_shared::config::{ARB_SOLVER_ITERS, ARB_SOLVER_TOLERANCE};
+
+        let arb = |tolerance: f64| {
+            Arbitrageur::new(0.0, 20.0, 1.2, Pcg64::seed_from_u64(1))
+                .with_solver(ARB_SOLVER_ITERS, tolerance)
+                .solver_abs_tolerance
+        };
+        assert_eq!(arb(ARB_SOLVER_TOLERANCE), 0.0);
+        assert_eq!(arb(f64::NAN), 0.0);
+        assert_eq!(arb(1e-6), GOLDEN_INPUT_ABS_TOL);
+
+        // A peak narrower than the absolute stop is still pinned down to the relative one.
+        let peak = 0.0123;
+        let search = |abs_tol| {
+            let mut evals = 0;
+            let (best, _) = Arbitrageur::<Pcg64>::golden_section_max(
+                0.0,
+                0.05,
+                40,
+                (ARB_SOLVER_TOLERANCE, abs_tol),
+                |x| {
+                    evals += 1;
+                    -(x - peak) * (x - peak)
+                },
+            );
+            (best, evals)
+        };
+        let (best, evals) = search(0.0);
+        assert!((best - peak).abs() < ARB_SOLVER_TOLERANCE * peak);
+        assert!(search(GOLDEN_INPUT_ABS_TOL).1 < evals);
+    }
+
     #[test]
     fn min_arb_profit_blocks_profitable_trade_when_threshold_is_higher() {
         let fair_price = 101.0;
 
         let mut amm_without_floor = test_amm();
-        let mut no_floor = Arbitrageur::new(0.0, 20.0, 1.2, 42);
+        let mut no_floor = Arbitrageur::new(0.0, 20.0, 1.2, Pcg64::seed_from_u64(42));
         let result = no_floor
             .execute_arb(&mut amm_without_floor, fair_price)
             .expect("expected profitable arbitrage");
@@ -574,7 +763,8 @@ mod tests {
         );
 
         let mut amm_with_floor = test_amm();
-        let mut floor = Arbitrageur::new(realized_profit + 1e-9, 20.0, 1.2, 42);
+        let mut floor =
+            Arbitrageur::new(realized_profit + 1e-9, 20.0, 1.2, Pcg64::seed_from_u64(42));
         assert!(
             floor.execute_arb(&mut amm_with_floor, fair_price).is_none(),
             "trade should be skipped when profit ({realized_profit}) is below threshold"
@@ -601,7 +791,7 @@ mod tests {
         assert!(buy_probe_profit < 0.0, "buy side should be unprofitable");
         assert!(sell_probe_profit > 0.0, "sell side should be profitable");
 
-        let mut arb = Arbitrageur::new(0.01, 20.0, 1.2, 7);
+        let mut arb = Arbitrageur::new(0.01, 20.0, 1.2, Pcg64::seed_from_u64(7));
         let result = arb
             .execute_arb(&mut amm, fair_price)
             .expect("arb should execute profitable sell-X trade");
@@ -627,7 +817,7 @@ mod tests {
             "sell side should be clearly more profitable"
         );
 
-        let mut arb = Arbitrageur::new(0.01, 20.0, 1.2, 17);
+        let mut arb = Arbitrageur::new(0.01, 20.0, 1.2, Pcg64::seed_from_u64(17));
         let result = arb
             .execute_arb(&mut amm, fair_price)
             .expect("arb should execute one of the profitable trades");
@@ -655,7 +845,7 @@ mod tests {
         );
         assert!(floor_profit <= 0.0, "at-floor trade should be unprofitable");
 
-        let mut arb = Arbitrageur::new(0.01, 20.0, 1.2, 1234);
+        let mut arb = Arbitrageur::new(0.01, 20.0, 1.2, Pcg64::seed_from_u64(1234));
         assert!(
             arb.execute_arb(&mut amm, fair_price).is_none(),
             "arb should ignore opportunities below 0.01 Y notional floor"
@@ -0,0 +1,249 @@
+//! Where the submission's edge over the normalizer comes from.
+//!
+//! [`EdgeAttribution`] replays every fill the submission took on a shadow normalizer that
+//! starts from the submission's reserves and sees nothing else, so each fill has a
+//! counterfactual: the edge the normalizer would have made on the same flow. The difference
+//! is then bucketed by trade size, side and the volatility of the step it happened in.
+
+use prop_amm_shared::config::SimulationConfig;
+use prop_amm_shared::normalizer::{after_swap, compute_swap};
+
+use crate::amm::BpfAmm;
+use crate::objective::{MarketCtx, Objective, StepRecord};
+
+/// One submission fill next to its counterfactual.
+#[derive(Debug, Clone, Copy)]
+struct AttributedTrade {
+    /// Y notional of the fill.
+    size_y: f64,
+    amm_buys_x: bool,
+    /// Absolute log return of the fair price over the fill's step.
+    price_move: f64,
+    edge: f64,
+    normalizer_edge: f64,
+}
+
+/// Submission and counterfactual normalizer edge over one bucket of fills.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributionBucket {
+    /// "size", "side" or "volatility".
+    pub dimension: &'static str,
+    pub label: String,
+    pub trades: usize,
+    pub edge: f64,
+    pub normalizer_edge: f64,
+}
+
+impl AttributionBucket {
+    /// Edge the submission made beyond what the normalizer would have on the same fills.
+    pub fn advantage(&self) -> f64 {
+        self.edge - self.normalizer_edge
+    }
+}
+
+/// Objective whose headline number is the submission's total advantage over the
+/// counterfactual normalizer; `buckets` breaks it down.
+pub struct EdgeAttribution {
+    shadow: BpfAmm,
+    last_fair_price: f64,
+    trades: Vec<AttributedTrade>,
+}
+
+impl EdgeAttribution {
+    /// Shadow normalizer with `config`'s fee at the submission's initial reserves.
+    pub fn new(config: &SimulationConfig) -> Self {
+        let mut shadow = BpfAmm::new_native(
+            compute_swap,
+            Some(after_swap),
+            config.initial_x,
+            config.initial_y,
+            "counterfactual".to_string(),
+        );
+        shadow.set_initial_storage(&config.normalizer_fee_word().to_le_bytes());
+        Self {
+            shadow,
+            last_fair_price: config.initial_price,
+            trades: Vec::new(),
+        }
+    }
+
+    /// Advantage by size tercile, by side, and by volatility tercile, in that order. Tercile
+    /// bounds come from the fills themselves and are printed in the labels.
+    pub fn buckets(&self) -> Vec<AttributionBucket> {
+        let mut buckets = Vec::new();
+        let sizes = terciles(self.trades.iter().map(|t| t.size_y));
+        let size_labels = tercile_labels(["small", "medium", "large"], sizes, "Y", 1.0);
+        for (i, label) in size_labels.into_iter().enumerate() {
+            buckets.push(self.bucket("size", label, |t| tercile(t.size_y, sizes) == i));
+        }
+        for (label, buys_x) in [("AMM buys X", true), ("AMM sells X", false)] {
+            buckets.push(self.bucket("side", label.to_string(), |t| t.amm_buys_x == buys_x));
+        }
+        let moves = terciles(self.trades.iter().map(|t| t.price_move));
+        let move_labels = tercile_labels(["calm", "normal", "volatile"], moves, "bps", 1e4);
+        for (i, label) in move_labels.into_iter().enumerate() {
+            buckets.push(self.bucket("volatility", label, |t| tercile(t.price_move, moves) == i));
+        }
+        buckets
+    }
+
+    fn bucket(
+        &self,
+        dimension: &'static str,
+        label: String,
+        include: impl Fn(&AttributedTrade) -> bool,
+    ) -> AttributionBucket {
+        let mut bucket = AttributionBucket {
+            dimension,
+            label,
+            trades: 0,
+            edge: 0.0,
+            normalizer_edge: 0.0,
+        };
+        for trade in self.trades.iter().filter(|t| include(t)) {
+            bucket.trades += 1;
+            bucket.edge += trade.edge;
+            bucket.normalizer_edge += trade.normalizer_edge;
+        }
+        bucket
+    }
+}
+
+impl Objective for EdgeAttribution {
+    fn accumulate(&mut self, step: &StepRecord, ctx: &MarketCtx) {
+        let fair_price = ctx.fair_price;
+        let price_before = std::mem::replace(&mut self.last_fair_price, fair_price);
+        let price_move = (fair_price / price_before).ln().abs();
+        for trade in step.trades {
+            // The shadow pool takes the same input the submission did.
+            let normalizer_edge = if trade.amm_buys_x {
+                let output_y = self.shadow.execute_sell_x(trade.amount_x);
+                if output_y > 0.0 {
+                    trade.amount_x * fair_price - output_y
+                } else {
+                    0.0
+                }
+            } else {
+                let output_x = self.shadow.execute_buy_x(trade.amount_y);
+                if output_x > 0.0 {
+                    trade.amount_y - output_x * fair_price
+                } else {
+                    0.0
+                }
+            };
+            self.trades.push(AttributedTrade {
+                size_y: trade.amount_y,
+                amm_buys_x: trade.amm_buys_x,
+                price_move,
+                edge: trade.edge,
+                normalizer_edge,
+            });
+        }
+    }
+
+    fn finalize(&self) -> f64 {
+        self.trades.iter().map(|t| t.edge - t.normalizer_edge).sum()
+    }
+}
+
+/// Lower bounds of the middle and top thirds of `values`.
+fn terciles(values: impl Iterator<Item = f64>) -> [f64; 2] {
+    let mut sorted: Vec<f64> = values.collect();
+    if sorted.is_empty() {
+        return [0.0; 2];
+    }
+    sorted.sort_by(f64::total_cmp);
+    let n = sorted.len();
+    [sorted[n / 3], sorted[2 * n / 3]]
+}
+
+fn tercile(value: f64, bounds: [f64; 2]) -> usize {
+    bounds.iter().filter(|&&bound| value >= bound).count()
+}
+
+/// "small (< 2.4 Y)", "medium (2.4-8.1 Y)", "large (>= 8.1 Y)", with bounds times `scale`.
+fn tercile_labels(names: [&str; 3], bounds: [f64; 2], unit: &str, scale: f64) -> [String; 3] {
+    let [low, high] = bounds.map(|bound| bound * scale);
+    [
+        format!("{} (< {:.1} {})", names[0], low, unit),
+        format!("{} ({:.1}-{:.1} {})", names[1], low, high, unit),
+        format!("{} (>= {:.1} {})", names[2], high, unit),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use prop_amm_shared::config::SimulationConfig;
+
+    use super::EdgeAttribution;
+    use crate::amm::BpfAmm;
+    use crate::objective::{MarketCtx, Objective, StepRecord, SubmissionTrade, TradeSource};
+
+    #[test]
+    fn advantage_concentrated_in_large_trades_lands_in_the_large_bucket() {
+        let config = SimulationConfig::default();
+        let mut attribution = EdgeAttribution::new(&config);
+        // A twin of the shadow pool: fills earn exactly the counterfactual edge, plus a
+        // bonus of 0.5 Y on every fill above 50 Y.
+        let mut twin = BpfAmm::new_native(
+            prop_amm_shared::normalizer::compute_swap,
+            Some(prop_amm_shared::normalizer::after_swap),
+            config.initial_x,
+            config.initial_y,
+            "twin".into(),
+        );
+        twin.set_initial_storage(&config.normalizer_fee_word().to_le_bytes());
+        let fair_price = config.initial_price;
+        let ctx = MarketCtx {
+            fair_price,
+            submission_reserve_x: config.initial_x,
+            submission_reserve_y: config.initial_y,
+            normalizer_reserve_x: config.initial_x,
+            normalizer_reserve_y: config.initial_y,
+        };
+        for step in 0..90u32 {
+            let size_y = [5.0, 20.0, 120.0][step as usize % 3];
+            let amm_buys_x = step % 2 == 0;
+            let (amount_x, amount_y, edge) = if amm_buys_x {
+                let amount_x = size_y / fair_price;
+                let output_y = twin.execute_sell_x(amount_x);
+                (amount_x, output_y, amount_x * fair_price - output_y)
+            } else {
+                let output_x = twin.execute_buy_x(size_y);
+                (output_x, size_y, size_y - output_x * fair_price)
+            };
+            let bonus = if size_y > 50.0 { 0.5 } else { 0.0 };
+            let trades = [SubmissionTrade {
+                source: TradeSource::Retail,
+                amm_buys_x,
+                amount_x,
+                amount_y,
+                edge: edge + bonus,
+            }];
+            attribution.accumulate(
+                &StepRecord {
+                    step,
+                    trades: &trades,
+                },
+                &ctx,
+            );
+        }
+
+        assert!((attribution.finalize() - 15.0).abs() < 1e-9);
+        let buckets = attribution.buckets();
+        let size: Vec<_> = buckets.iter().filter(|b| b.dimension == "size").collect();
+        assert_eq!(size.len(), 3);
+        assert!(size[2].label.starts_with("large"), "{:?}", size[2]);
+        assert_eq!(size[2].trades, 30);
+        assert!((size[2].advantage() - 15.0).abs() < 1e-9);
+        for bucket in &size[..2] {
+            assert!(bucket.advantage().abs() < 1e-9, "{bucket:?}");
+        }
+        let sides: f64 = buckets
+            .iter()
+            .filter(|b| b.dimension == "side")
+            .map(|b| b.advantage())
+            .sum();
+        assert!((sides - 15.0).abs() < 1e-9);
+    }
+}
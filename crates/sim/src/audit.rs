@@ -0,0 +1,403 @@
+//! Determinism audit: replay a seed and check every fill against the submission matches.
+//!
+//! Arbitrage sizes come from a floating-point solver rather than an RNG, so they should be
+//! bit-for-bit reproducible. The audit runs each seed once on the calling thread and once on
+//! a fresh thread (fresh thread-locals, fresh executor) and reports the first step where any
+//! fill differs by more than one nano unit.
+//!
+//! `storage_lockstep` checks a different kind of reproducibility: that a strategy's BPF
+//! build persists the same storage as its native build after every `after_swap`.
+
+use rayon::prelude::*;
+
+use prop_amm_executor::{AfterSwapFn, BpfExecutor, BpfProgram, NativeExecutor, SwapFn};
+use prop_amm_shared::config::SimulationConfig;
+use prop_amm_shared::instruction::STORAGE_SIZE;
+use prop_amm_shared::nano::{f64_to_nano, NANO_SCALE_F64};
+
+use crate::engine::Simulation;
+use crate::objective::{MarketCtx, Objective, StepRecord, SubmissionTrade, TradeSource};
+use crate::runner;
+
+const ONE_NANO: f64 = 1.0 / NANO_SCALE_F64;
+
+/// Fills against the submission, per step.
+#[derive(Debug, Clone, Default)]
+pub struct FillLog {
+    pub steps: Vec<Vec<SubmissionTrade>>,
+}
+
+impl Objective for FillLog {
+    fn accumulate(&mut self, step: &StepRecord, _ctx: &MarketCtx) {
+        self.steps.push(step.trades.to_vec());
+    }
+
+    fn finalize(&self) -> f64 {
+        self.steps.iter().flatten().map(|t| t.edge).sum()
+    }
+}
+
+/// First point where two replays of the same seed disagree.
+#[derive(Debug, Clone)]
+pub struct Divergence {
+    pub step: u32,
+    pub description: String,
+}
+
+fn describe(trade: Option<&SubmissionTrade>) -> String {
+    match trade {
+        Some(t) => format!(
+            "{} {} x={:.9} y={:.9}",
+            match t.source {
+                TradeSource::Arbitrage => "arb",
+                TradeSource::Retail => "retail",
+            },
+            if t.amm_buys_x { "sell-x" } else { "buy-x" },
+            t.amount_x,
+            t.amount_y
+        ),
+        None => "no fill".to_string(),
+    }
+}
+
+fn same_fill(a: &SubmissionTrade, b: &SubmissionTrade) -> bool {
+    a.source == b.source
+        && a.amm_buys_x == b.amm_buys_x
+        && (a.amount_x - b.amount_x).abs() <= ONE_NANO
+        && (a.amount_y - b.amount_y).abs() <= ONE_NANO
+}
+
+/// Compare two fill logs step by step; fills must agree to within one nano unit.
+pub fn first_divergence(primary: &FillLog, reference: &FillLog) -> Option<Divergence> {
+    let n_steps = primary.steps.len().max(reference.steps.len());
+    for step in 0..n_steps {
+        let a = primary.steps.get(step).map(Vec::as_slice).unwrap_or(&[]);
+        let b = reference.steps.get(step).map(Vec::as_slice).unwrap_or(&[]);
+        for i in 0..a.len().max(b.len()) {
+            let (fa, fb) = (a.get(i), b.get(i));
+            let matches = matches!((fa, fb), (Some(x), Some(y)) if same_fill(x, y));
+            if !matches {
+                return Some(Divergence {
+                    step: step as u32,
+                    description: format!(
+                        "fill #{}: {} vs reference {}",
+                        i,
+                        describe(fa),
+                        describe(fb)
+                    ),
+                });
+            }
+        }
+    }
+    None
+}
+
+/// Replay one native seed on this thread and on a fresh thread and compare their fills.
+pub fn audit_seed_native(
+    submission_fn: SwapFn,
+    submission_after_swap: Option<AfterSwapFn>,
+    normalizer_fn: SwapFn,
+    normalizer_after_swap: Option<AfterSwapFn>,
+    config: &SimulationConfig,
+) -> anyhow::Result<Option<Divergence>> {
+    let replay = |config: &SimulationConfig| -> anyhow::Result<FillLog> {
+        let mut log = FillLog::default();
+        Simulation::native(
+            submission_fn,
+            submission_after_swap,
+            normalizer_fn,
+            normalizer_after_swap,
+            config,
+        )
+        .run_with(&mut log)?;
+        Ok(log)
+    };
+
+    let primary = replay(config)?;
+    let reference = std::thread::scope(|scope| {
+        scope
+            .spawn(|| replay(config))
+            .join()
+            .map_err(|_| anyhow::anyhow!("reference replay panicked"))?
+    })?;
+    Ok(first_divergence(&primary, &reference))
+}
+
+/// Audit every config in parallel; results are `(seed, divergence)` in config order.
+pub fn audit_batch_native(
+    submission_fn: SwapFn,
+    submission_after_swap: Option<AfterSwapFn>,
+    normalizer_fn: SwapFn,
+    normalizer_after_swap: Option<AfterSwapFn>,
+    configs: &[SimulationConfig],
+    n_workers: Option<usize>,
+) -> anyhow::Result<Vec<(u64, Option<Divergence>)>> {
+    let pool = runner::worker_pool(n_workers)?;
+    pool.install(|| {
+        configs
+            .par_iter()
+            .map(|config| {
+                audit_seed_native(
+                    submission_fn,
+                    submission_after_swap,
+                    normalizer_fn,
+                    normalizer_after_swap,
+                    config,
+                )
+                .map(|divergence| (config.seed, divergence))
+            })
+            .collect()
+    })
+}
+
+/// First `after_swap` after which the BPF and native builds hold different storage.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageDivergence {
+    pub step: u32,
+    /// Index of the `after_swap` call in the run, from 0.
+    pub call: usize,
+    /// First byte that differs.
+    pub offset: usize,
+    pub native: u8,
+    pub bpf: u8,
+}
+
+/// Outcome of `storage_lockstep`.
+#[derive(Debug, Clone)]
+pub struct StorageLockstep {
+    /// `after_swap` calls compared, including a diverging one.
+    pub after_swap_calls: usize,
+    pub divergence: Option<StorageDivergence>,
+}
+
+/// Run `config` with the native build, then replay every submission fill's `after_swap` on
+/// both builds in lockstep, each from zeroed storage of its own, and diff the storage after
+/// every call. Stops at the first divergence.
+pub fn storage_lockstep(
+    program: BpfProgram,
+    submission_fn: SwapFn,
+    submission_after_swap: AfterSwapFn,
+    normalizer_fn: SwapFn,
+    normalizer_after_swap: Option<AfterSwapFn>,
+    config: &SimulationConfig,
+) -> anyhow::Result<StorageLockstep> {
+    let sim = Simulation::native(
+        submission_fn,
+        Some(submission_after_swap),
+        normalizer_fn,
+        normalizer_after_swap,
+        config,
+    );
+    let (mut rx, mut ry) = (sim.submission().reserve_x, sim.submission().reserve_y);
+    let mut log = FillLog::default();
+    sim.run_with(&mut log)?;
+
+    let native = NativeExecutor::new(submission_fn, Some(submission_after_swap));
+    let mut bpf = BpfExecutor::new(program);
+    let mut native_storage = vec![0u8; STORAGE_SIZE];
+    let mut bpf_storage = vec![0u8; STORAGE_SIZE];
+    let mut calls = 0;
+    for (step, fills) in log.steps.iter().enumerate() {
+        for fill in fills {
+            // Side and amounts as the pool reports them: side 1 is a trader selling X.
+            let (side, input, output) = if fill.amm_buys_x {
+                rx += fill.amount_x;
+                ry -= fill.amount_y;
+                (1, fill.amount_x, fill.amount_y)
+            } else {
+                rx -= fill.amount_x;
+                ry += fill.amount_y;
+                (0, fill.amount_y, fill.amount_x)
+            };
+            let (input, output) = (f64_to_nano(input), f64_to_nano(output));
+            let (post_x, post_y) = (f64_to_nano(rx), f64_to_nano(ry));
+            native.execute_after_swap(
+                side,
+                input,
+                output,
+                post_x,
+                post_y,
+                step as u64,
+                &mut native_storage,
+            );
+            bpf.execute_after_swap(
+                side,
+                input,
+                output,
+                post_x,
+                post_y,
+                step as u64,
+                &mut bpf_storage,
+            )
+            .map_err(|e| anyhow::anyhow!("BPF after_swap #{} at step {}: {}", calls, step, e))?;
+            calls += 1;
+
+            let differs = native_storage
+                .iter()
+                .zip(&bpf_storage)
+                .position(|(a, b)| a != b);
+            if let Some(offset) = differs {
+                return Ok(StorageLockstep {
+                    after_swap_calls: calls,
+                    divergence: Some(StorageDivergence {
+                        step: step as u32,
+                        call: calls - 1,
+                        offset,
+                        native: native_storage[offset],
+                        bpf: bpf_storage[offset],
+                    }),
+                });
+            }
+        }
+    }
+    Ok(StorageLockstep {
+        after_swap_calls: calls,
+        divergence: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{audit_seed_native, first_divergence, storage_lockstep, FillLog, ONE_NANO};
+    use crate::objective::{SubmissionTrade, TradeSource};
+    use prop_amm_executor::BpfProgram;
+    use prop_amm_shared::config::SimulationConfig;
+    use prop_amm_shared::normalizer::{after_swap, compute_swap};
+
+    /// Constant-product curve with a sharp (but still concave) fee kink just above 1 Y of input,
+    /// where the arb objective's slope jumps and a fragile solver would wobble.
+    fn kinked_swap(data: &[u8]) -> u64 {
+        let side = data[0];
+        let input = u64::from_le_bytes(data[1..9].try_into().unwrap()) as u128;
+        let rx = u64::from_le_bytes(data[9..17].try_into().unwrap()) as u128;
+        let ry = u64::from_le_bytes(data[17..25].try_into().unwrap()) as u128;
+        let kink = if side == 0 { 1_000_000_001 } else { 10_000_001 };
+        // 10 bps below the kink, 90% haircut on the excess above it.
+        let net = if input <= kink {
+            input * 9_990 / 10_000
+        } else {
+            kink * 9_990 / 10_000 + (input - kink) / 10
+        };
+        let k = rx * ry;
+        match side {
+            0 => rx.saturating_sub(k.div_ceil(ry + net)) as u64,
+            _ => ry.saturating_sub(k.div_ceil(rx + net)) as u64,
+        }
+    }
+
+    /// The normalizer's curve at its default 30 bps, whatever the storage holds.
+    fn flat_fee_swap(data: &[u8]) -> u64 {
+        compute_swap(&data[..25])
+    }
+
+    /// Stateful fixture: an EMA of trade inputs in `storage[0..8]` (weight 1/8) and a call
+    /// counter in `storage[8..16]`.
+    fn ema_after_swap(data: &[u8], storage: &mut [u8]) {
+        let input = u64::from_le_bytes(data[2..10].try_into().unwrap());
+        let ema = u64::from_le_bytes(storage[0..8].try_into().unwrap());
+        let count = u64::from_le_bytes(storage[8..16].try_into().unwrap());
+        let ema = ema - (ema >> 3) + (input >> 3);
+        storage[0..8].copy_from_slice(&ema.to_le_bytes());
+        storage[8..16].copy_from_slice(&(count + 1).to_le_bytes());
+    }
+
+    /// `ema_after_swap` for BPF, persisting the first `len` bytes of storage.
+    fn ema_program(len: usize) -> BpfProgram {
+        let asm = format!(
+            "
+            ldxdw r2, [r1+18]
+            rsh64 r2, 3
+            ldxdw r3, [r1+58]
+            mov64 r4, r3
+            rsh64 r4, 3
+            sub64 r3, r4
+            add64 r3, r2
+            stxdw [r1+58], r3
+            ldxdw r5, [r1+66]
+            add64 r5, 1
+            stxdw [r1+66], r5
+            add64 r1, 58
+            mov64 r2, {len}
+            syscall sol_set_storage
+            mov64 r0, 0
+            exit"
+        );
+        BpfProgram::from_asm(&asm).expect("assemble fixture")
+    }
+
+    fn fill(amount_x: f64) -> SubmissionTrade {
+        SubmissionTrade {
+            source: TradeSource::Arbitrage,
+            amm_buys_x: true,
+            amount_x,
+            amount_y: 100.0,
+            edge: 0.0,
+        }
+    }
+
+    #[test]
+    fn kinked_curve_replays_identically() {
+        for seed in 0..4 {
+            let config = SimulationConfig {
+                n_steps: 1_000,
+                seed,
+                gbm_sigma: 0.003,
+                ..SimulationConfig::default()
+            };
+            let divergence =
+                audit_seed_native(kinked_swap, None, compute_swap, Some(after_swap), &config)
+                    .unwrap();
+            assert!(divergence.is_none(), "seed {seed}: {divergence:?}");
+        }
+    }
+
+    #[test]
+    fn reports_first_step_beyond_one_nano() {
+        let primary = FillLog {
+            steps: vec![vec![fill(1.0)], vec![], vec![fill(2.0)], vec![fill(3.0)]],
+        };
+        let mut reference = primary.clone();
+        reference.steps[0][0].amount_x += 0.5 * ONE_NANO;
+        reference.steps[2][0].amount_x += 2.0 * ONE_NANO;
+        reference.steps[3][0].amount_x += 5.0;
+
+        let divergence = first_divergence(&primary, &reference).expect("divergence");
+        assert_eq!(divergence.step, 2);
+
+        reference.steps[2].clear();
+        assert_eq!(first_divergence(&primary, &reference).unwrap().step, 2);
+        assert!(first_divergence(&primary, &primary).is_none());
+    }
+
+    #[test]
+    fn storage_lockstep_matches_a_faithful_bpf_build_and_flags_a_truncated_one() {
+        let config = SimulationConfig {
+            n_steps: 300,
+            seed: 4,
+            ..SimulationConfig::default()
+        };
+        let run = |len: usize| {
+            storage_lockstep(
+                ema_program(len),
+                flat_fee_swap,
+                ema_after_swap,
+                compute_swap,
+                Some(after_swap),
+                &config,
+            )
+            .unwrap()
+        };
+
+        let faithful = run(1024);
+        assert!(faithful.after_swap_calls > 10, "{faithful:?}");
+        assert_eq!(faithful.divergence, None);
+
+        // Persisting only the EMA zeroes the counter, which the native build keeps.
+        let truncated = run(8);
+        let divergence = truncated.divergence.expect("counter dropped");
+        assert_eq!(divergence.call, 0);
+        assert_eq!(divergence.offset, 8);
+        assert_eq!((divergence.native, divergence.bpf), (1, 0));
+        assert_eq!(truncated.after_swap_calls, 1);
+    }
+}
@@ -1,9 +1,16 @@
 use prop_amm_executor::{BpfExecutor, BpfProgram, NativeExecutor};
 use prop_amm_shared::instruction::STORAGE_SIZE;
 use prop_amm_shared::nano::f64_to_nano;
-use prop_amm_shared::normalizer::compute_swap as normalizer_swap;
+use prop_amm_shared::normalizer::{
+    after_swap as normalizer_after_swap, compute_swap as normalizer_swap,
+};
+use prop_amm_shared::strategies::starter;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Instant;
 
+use crate::runner;
+
 const NORMALIZER_SO_PATH: &str = concat!(
     env!("CARGO_MANIFEST_DIR"),
     "/../../programs/normalizer/target/deploy/normalizer.so"
@@ -66,6 +73,19 @@ pub fn run_profile() {
         1_000_000.0 / bpf_us
     );
 
+    // A fresh executor each swap (allocating its stack and heap) vs the reused one above
+    let n_fresh = n / 10;
+    let start = Instant::now();
+    for _ in 0..n_fresh {
+        let _ = BpfExecutor::new(program.clone()).execute(0, amount, rx, ry, &storage);
+    }
+    let fresh_us = start.elapsed().as_micros() as f64 / n_fresh as f64;
+    println!(
+        "BPF (fresh executor per call): {:.1}µs/call, reuse speedup {:.1}x",
+        fresh_us,
+        fresh_us / bpf_us
+    );
+
     // Native benchmark
     let start = Instant::now();
     for _ in 0..n {
@@ -119,3 +139,81 @@ pub fn run_profile() {
     println!("BPF+Native:    {:.0}s", mixed_proj);
     println!("Native+Native: {:.0}s", native_proj);
 }
+
+static SWAP_CALLS: AtomicU64 = AtomicU64::new(0);
+
+fn counted_starter_swap(data: &[u8]) -> u64 {
+    SWAP_CALLS.fetch_add(1, Ordering::Relaxed);
+    starter::compute_swap(data)
+}
+
+fn counted_normalizer_swap(data: &[u8]) -> u64 {
+    SWAP_CALLS.fetch_add(1, Ordering::Relaxed);
+    normalizer_swap(data)
+}
+
+/// Throughput of a starter-vs-normalizer batch (the `prop-amm baseline` battery): engine and
+/// executor speed only, the edges are discarded.
+#[derive(Debug, Clone, Serialize)]
+pub struct EngineThroughput {
+    pub simulations: u32,
+    pub steps: u32,
+    /// Worker threads the batch actually ran on.
+    pub workers: usize,
+    /// Whether each worker was pinned to its own CPU (`runner::set_pin_workers`).
+    pub pinned: bool,
+    /// Size of the process's CPU affinity mask; `None` where it can't be read.
+    pub allowed_cpus: Option<usize>,
+    pub elapsed_secs: f64,
+    /// `compute_swap` calls on either pool, quotes by the arbitrageur and router included.
+    pub swaps: u64,
+    pub swaps_per_sec: f64,
+    pub simulations_per_sec: f64,
+    /// Peak resident set size of the process in KiB; `None` where `/proc` is unavailable.
+    pub peak_rss_kib: Option<u64>,
+}
+
+pub fn measure_throughput(
+    n_sims: u32,
+    n_steps: u32,
+    n_workers: Option<usize>,
+) -> anyhow::Result<EngineThroughput> {
+    let configs = runner::seeded_configs(n_sims, n_steps, 0, 1);
+    let calls_before = SWAP_CALLS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    runner::run_batch_native(
+        counted_starter_swap,
+        None,
+        counted_normalizer_swap,
+        Some(normalizer_after_swap),
+        configs,
+        n_workers,
+    )?;
+    let elapsed_secs = start.elapsed().as_secs_f64();
+    let swaps = SWAP_CALLS.load(Ordering::Relaxed) - calls_before;
+    Ok(EngineThroughput {
+        simulations: n_sims,
+        steps: n_steps,
+        workers: runner::worker_count(n_workers),
+        pinned: runner::pin_workers() && runner::allowed_cpus().is_some(),
+        allowed_cpus: runner::allowed_cpus().map(|cpus| cpus.len()),
+        elapsed_secs,
+        swaps,
+        swaps_per_sec: swaps as f64 / elapsed_secs,
+        simulations_per_sec: n_sims as f64 / elapsed_secs,
+        peak_rss_kib: peak_rss_kib(),
+    })
+}
+
+/// `VmHWM` from `/proc/self/status`.
+pub fn peak_rss_kib() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()
+}
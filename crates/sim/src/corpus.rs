@@ -0,0 +1,145 @@
+//! A fixed benchmark corpus: the same scenarios for everyone, so scores compare across
+//! users and versions.
+//!
+//! Every scenario pins the market parameters to the baseline point estimates except the
+//! ones that define its regime, and takes its seed from a fixed list. Nothing is sampled,
+//! so `standard()` is identical on every machine and every release that keeps
+//! `CORPUS_VERSION`.
+//!
+//! On the standard corpus the starter strategy (constant product, 500 bps) averages an
+//! edge of about 128 against the normalizer; anything outside 110 to 150 means the
+//! scenarios or the engine changed.
+
+use prop_amm_shared::config::{SimulationConfig, BASELINE_STEPS, GBM_SIGMA};
+
+/// Bumped whenever a scenario changes, since scores from different versions don't compare.
+pub const CORPUS_VERSION: u32 = 1;
+/// Regime labels, in corpus order.
+pub const REGIMES: &[&str] = &["calm", "high-vol", "trending", "mean-reverting"];
+/// Seeds every regime is run on.
+pub const CORPUS_SEEDS: &[u64] = &[11, 23, 37, 41, 53];
+
+/// One corpus entry.
+#[derive(Debug, Clone)]
+pub struct Scenario {
+    pub regime: &'static str,
+    pub config: SimulationConfig,
+}
+
+/// Every scenario, regime by regime, each over `CORPUS_SEEDS`.
+pub fn scenarios() -> Vec<Scenario> {
+    REGIMES
+        .iter()
+        .flat_map(|&regime| {
+            CORPUS_SEEDS
+                .iter()
+                .enumerate()
+                .map(move |(i, &seed)| Scenario {
+                    regime,
+                    config: regime_config(regime, i, seed),
+                })
+        })
+        .collect()
+}
+
+/// The corpus configs alone, in `scenarios()` order.
+pub fn standard() -> Vec<SimulationConfig> {
+    scenarios().into_iter().map(|s| s.config).collect()
+}
+
+fn regime_config(regime: &str, index: usize, seed: u64) -> SimulationConfig {
+    let base = SimulationConfig {
+        n_steps: BASELINE_STEPS,
+        seed,
+        ..SimulationConfig::default()
+    };
+    match regime {
+        "calm" => SimulationConfig {
+            gbm_sigma: GBM_SIGMA / 3.0,
+            ..base
+        },
+        "high-vol" => SimulationConfig {
+            gbm_sigma: GBM_SIGMA * 3.0,
+            ..base
+        },
+        // Alternate up and down trends of about 40% over the run.
+        "trending" => SimulationConfig {
+            gbm_mu: if index.is_multiple_of(2) {
+                3.5e-5
+            } else {
+                -3.5e-5
+            },
+            ..base
+        },
+        // Half-life of about 70 steps back to the initial price.
+        "mean-reverting" => SimulationConfig {
+            mean_reversion: 0.01,
+            ..base
+        },
+        _ => unreachable!("unknown regime {regime}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prop_amm_shared::config::SimulationConfig;
+    use prop_amm_shared::normalizer;
+
+    use super::{scenarios, standard, CORPUS_SEEDS, REGIMES};
+    use crate::{opponents, runner};
+
+    fn fingerprint(config: &SimulationConfig) -> (u64, u32, u64, u64, u64) {
+        (
+            config.seed,
+            config.n_steps,
+            config.gbm_mu.to_bits(),
+            config.gbm_sigma.to_bits(),
+            config.mean_reversion.to_bits(),
+        )
+    }
+
+    #[test]
+    fn corpus_is_deterministic_and_spans_every_regime() {
+        let corpus = standard();
+        assert_eq!(corpus.len(), REGIMES.len() * CORPUS_SEEDS.len());
+        let again: Vec<_> = standard().iter().map(fingerprint).collect();
+        assert_eq!(corpus.iter().map(fingerprint).collect::<Vec<_>>(), again);
+
+        let scenarios = scenarios();
+        for regime in REGIMES {
+            assert_eq!(
+                scenarios.iter().filter(|s| s.regime == *regime).count(),
+                CORPUS_SEEDS.len(),
+                "{regime}"
+            );
+        }
+        let default = SimulationConfig::default();
+        for scenario in &scenarios {
+            let config = &scenario.config;
+            let differs = match scenario.regime {
+                "calm" => config.gbm_sigma < default.gbm_sigma,
+                "high-vol" => config.gbm_sigma > default.gbm_sigma,
+                "trending" => config.gbm_mu != 0.0,
+                "mean-reverting" => config.mean_reversion > 0.0,
+                other => panic!("unexpected regime {other}"),
+            };
+            assert!(differs, "{scenario:?}");
+        }
+    }
+
+    #[test]
+    fn starter_scores_within_the_documented_range() {
+        let starter = opponents::find("starter").unwrap();
+        let result = runner::run_batch_native(
+            starter.swap,
+            starter.after_swap,
+            normalizer::compute_swap,
+            Some(normalizer::after_swap),
+            standard(),
+            None,
+        )
+        .unwrap();
+        let edge = result.avg_edge().unwrap();
+        assert!((110.0..=150.0).contains(&edge), "starter avg edge {edge}");
+    }
+}
@@ -1,72 +1,1266 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+
 use prop_amm_executor::{AfterSwapFn, BpfProgram, SwapFn};
-use prop_amm_shared::config::SimulationConfig;
-use prop_amm_shared::result::SimResult;
+use prop_amm_shared::config::{
+    ReferenceMode, RetailNetting, SimulationConfig, StepOrder, StopCondition, TradeArrival,
+};
+use prop_amm_shared::nano::NANO_DECIMALS;
+use prop_amm_shared::normalizer;
+use prop_amm_shared::result::{
+    impermanent_loss, Disqualification, LpReturn, Phase, PhaseSplit, PhaseTotals, PnlWaterfall,
+    SimResult,
+};
+use prop_amm_shared::sum::CompensatedSum;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use rand_distr::{Distribution, Exp1, StandardNormal};
+use rand_pcg::Pcg64;
+use serde::{Deserialize, Serialize};
 
 use crate::amm::BpfAmm;
 use crate::arbitrageur::Arbitrageur;
-use crate::price_process::GBMPriceProcess;
-use crate::retail::RetailTrader;
+use crate::objective::{MarketCtx, Objective, StepRecord, SubmissionTrade, TradeSource};
+use crate::opponents::Opponent;
+use crate::price_process::{GBMPriceProcess, PriceState};
+use crate::retail::{RetailOrder, RetailTrader};
+use crate::rng::{Draws, EngineRng};
 use crate::router::OrderRouter;
+use crate::runner;
+use crate::tape::{TapeTrade, TradeTape};
 
-fn run_sim_inner(
-    mut amm_sub: BpfAmm,
-    mut amm_norm: BpfAmm,
-    config: &SimulationConfig,
-) -> anyhow::Result<SimResult> {
-    let mut price = GBMPriceProcess::new(
-        config.initial_price,
-        config.gbm_mu,
-        config.gbm_sigma,
-        config.gbm_dt,
-        config.seed,
-    );
-    let mut retail = RetailTrader::new(
-        config.retail_arrival_rate,
-        config.retail_mean_size,
-        config.retail_size_sigma,
-        config.retail_buy_prob,
-        config.seed.wrapping_add(1),
-    );
-    let mut arb = Arbitrageur::new(
-        config.min_arb_profit,
-        config.retail_mean_size,
-        config.retail_size_sigma,
-        config.seed.wrapping_add(2),
-    );
-    let router = OrderRouter::new();
-
-    let mut submission_edge = 0.0_f64;
-
-    for step in 0..config.n_steps {
-        amm_sub.set_current_step(step as u64);
-        amm_norm.set_current_step(step as u64);
-        let fair_price = price.step();
-
-        if let Some(result) = arb.execute_arb(&mut amm_sub, fair_price) {
-            submission_edge += result.edge;
-        }
-        arb.execute_arb(&mut amm_norm, fair_price);
-
-        let orders = retail.generate_orders();
-        for order in &orders {
-            let trades = router.route_order(order, &mut amm_sub, &mut amm_norm, fair_price);
-            for trade in trades {
-                if trade.is_submission {
-                    let trade_edge = if trade.amm_buys_x {
-                        trade.amount_x * fair_price - trade.amount_y
-                    } else {
-                        trade.amount_y - trade.amount_x * fair_price
-                    };
-                    submission_edge += trade_edge;
+/// Largest reserve gap, in nano X per fill replayed, allowed between the normalizer pool and
+/// its closed-form replay under `check_normalizer_sync`. The integer curve rounds the
+/// fee-adjusted input and the output down by under one nano each, always in the pool's
+/// favour, so the gap grows linearly with the fill count but never faster than this.
+pub const NORMALIZER_SYNC_NANO_PER_FILL: f64 = 2.0;
+
+/// Size of the probe quotes an adaptive opponent reads the submission's spread from, as a
+/// fraction of the input reserve: small enough that price impact adds well under 1 bp.
+const UNDERCUT_PROBE: f64 = 1e-6;
+/// Highest fee an adaptive opponent charges; at 10_000 bps it would quote nothing.
+const MAX_FEE_BPS: f64 = 9_999.0;
+
+/// Positions of every random stream in a simulation. Opaque; only meaningful to a
+/// simulation built from the same config.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RngState<R = Pcg64> {
+    price: PriceState<R>,
+    retail: R,
+    arbs: Vec<R>,
+    arb_order: Vec<usize>,
+    arb_order_rng: R,
+    arrival_rng: R,
+    step_order_rng: R,
+    #[serde(default = "Option::default")]
+    oracle_rng: Option<R>,
+}
+
+/// Progress towards `StopCondition::EdgeConverged`.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct EdgeConvergence {
+    /// Edge captured so far in the current block of `window` steps.
+    pub block_edge: f64,
+    /// Mean per-step edge of the last completed block.
+    pub last_block_mean: Option<f64>,
+    pub converged: bool,
+}
+
+/// Running totals a simulation carries between steps.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccumulatedMetrics {
+    pub fair_price: f64,
+    pub submission_edge: f64,
+    /// Rounding error `submission_edge` still owes (see `CompensatedSum`).
+    #[serde(default)]
+    pub submission_edge_compensation: f64,
+    /// Edge of the `warmup_steps` run so far, kept out of `submission_edge`.
+    #[serde(default)]
+    pub warmup_edge: f64,
+    pub k_initial: f64,
+    /// `(reserve_x, reserve_y)` the submission started from; `initial_value` is valued from
+    /// these.
+    #[serde(default)]
+    pub initial_reserves: Option<(f64, f64)>,
+    /// `(reserve_x, reserve_y, fair_price)` the measured part of the run started from.
+    #[serde(default)]
+    pub measured_from: Option<(f64, f64, f64)>,
+    pub k_series: Option<Vec<f64>>,
+    pub waterfall: PnlWaterfall,
+    /// Step-end spot prices inside the TWAP window, submission then normalizer.
+    pub spot_history: [VecDeque<f64>; 2],
+    pub price_history: VecDeque<f64>,
+    /// Step-end fair prices feeding a `ReferenceMode::Twap` mark.
+    #[serde(default)]
+    pub reference_history: VecDeque<f64>,
+    /// Step-end fair prices `retail_momentum` looks back over, oldest first.
+    #[serde(default)]
+    pub momentum_history: VecDeque<f64>,
+    pub disqualifications: Vec<Disqualification>,
+    pub convergence: EdgeConvergence,
+    pub total_quote_slippage: f64,
+    pub max_quote_slippage: f64,
+    /// Retail volume in Y filled so far on the submission, then on the normalizer.
+    #[serde(default)]
+    pub retail_volume: [f64; 2],
+    /// Rounding error each `retail_volume` total still owes.
+    #[serde(default)]
+    pub retail_volume_compensation: [f64; 2],
+    /// Last `price_oracle` observation handed to the submission.
+    #[serde(default)]
+    pub oracle_price: Option<f64>,
+    /// Subsidy the reference pool has received so far, in Y.
+    pub normalizer_subsidy_paid: f64,
+    /// Submission fills refused by its inventory limit so far, in Y.
+    pub submission_inventory_rejected: f64,
+    /// Submission `after_swap` calls so far and whether any changed its storage, under
+    /// `check_storage_writes`.
+    pub submission_storage_watch: Option<(u64, bool)>,
+}
+
+/// Complete mutable state of a `Simulation` between steps. Programs and config are not
+/// included: restore into a simulation built from the same strategies and config.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimulationSnapshot<R = Pcg64> {
+    /// `(reserve_x, reserve_y)` of the submission, then the normalizer.
+    pub reserves: [(f64, f64); 2],
+    /// Strategy storage of the submission, then the normalizer.
+    pub storage: [Vec<u8>; 2],
+    pub rng_state: RngState<R>,
+    pub step: u32,
+    pub accumulated_metrics: AccumulatedMetrics,
+}
+
+/// One simulation that can be advanced a step at a time, so tools can inspect the pools
+/// (reserves, strategy storage) between steps. `run` drives it to completion. Every random
+/// stream comes from an `R`; see `new_with_rng`.
+pub struct Simulation<R = Pcg64> {
+    config: SimulationConfig,
+    amm_sub: BpfAmm,
+    amm_norm: BpfAmm,
+    price: GBMPriceProcess<R>,
+    retail: RetailTrader<R>,
+    arbs: Vec<Arbitrageur<R>>,
+    arb_order: Vec<usize>,
+    arb_order_rng: R,
+    // Inter-arrival times under `TradeArrival::Poisson`; untouched per-step.
+    arrival_rng: R,
+    // Arb-pass slot among the retail orders under `StepOrder::Shuffled`.
+    step_order_rng: R,
+    // Noise on the submission's `price_oracle` observations.
+    oracle_rng: R,
+    router: OrderRouter,
+    step: u32,
+    fair_price: f64,
+    submission_edge: CompensatedSum,
+    waterfall: PnlWaterfall,
+    // Edge of the steps run under `warmup_steps`, which `submission_edge` leaves out.
+    warmup_edge: f64,
+    k_initial: f64,
+    // Submission reserves after construction (mispricing and randomized reserves applied).
+    initial_reserves: (f64, f64),
+    // Submission reserves and fair price as the first measured step starts; impermanent loss
+    // and the LP return are measured from here. The initial ones without a warmup.
+    measured_from: (f64, f64, f64),
+    // X reserve the submission started with, which `inventory_rebalance` trades back to.
+    inventory_target: f64,
+    k_series: Option<Vec<f64>>,
+    // Step-end spot prices feeding the TWAP oracle; empty unless `twap_window` is set.
+    spot_history: [VecDeque<f64>; 2],
+    // Last `oracle_lag_steps` step-end fair prices (seeded with the initial one); arbs trade
+    // against the oldest.
+    price_history: VecDeque<f64>,
+    // Last `n - 1` step-end fair prices under `ReferenceMode::Twap(n)`; empty under spot.
+    reference_history: VecDeque<f64>,
+    // Last `window + 1` step-end fair prices under `retail_momentum` (seeded with the
+    // initial one); empty without it.
+    momentum_history: VecDeque<f64>,
+    disqualifications: Vec<Disqualification>,
+    convergence: EdgeConvergence,
+    // Submission output quoted for retail splits but not paid out, in Y.
+    total_quote_slippage: f64,
+    max_quote_slippage: f64,
+    // Retail volume in Y filled on the submission, then on the normalizer.
+    retail_volume: [CompensatedSum; 2],
+    step_trades: Vec<SubmissionTrade>,
+    // Recorded trades still to replay, one per step, in place of synthetic flow.
+    tape: VecDeque<TapeTrade>,
+}
+
+/// Totals only measured steps add to: a warmup step's fills still trade, but its changes to
+/// these are rolled back when it ends.
+#[derive(Clone, Copy)]
+struct Scored {
+    edge: CompensatedSum,
+    waterfall: PnlWaterfall,
+    total_quote_slippage: f64,
+    max_quote_slippage: f64,
+    retail_volume: [CompensatedSum; 2],
+    inventory_rejected: f64,
+}
+
+impl Simulation {
+    pub fn new(amm_sub: BpfAmm, amm_norm: BpfAmm, config: &SimulationConfig) -> Self {
+        Self::new_with_rng(amm_sub, amm_norm, config)
+    }
+
+    /// Native submission against the native normalizer, both seeded from `config`.
+    pub fn native(
+        submission_fn: SwapFn,
+        submission_after_swap: Option<AfterSwapFn>,
+        normalizer_fn: SwapFn,
+        normalizer_after_swap: Option<AfterSwapFn>,
+        config: &SimulationConfig,
+    ) -> Self {
+        Self::native_with_rng(
+            submission_fn,
+            submission_after_swap,
+            normalizer_fn,
+            normalizer_after_swap,
+            config,
+        )
+    }
+
+    /// BPF submission against the native normalizer, seeded from `config`.
+    pub fn mixed(
+        submission_program: BpfProgram,
+        normalizer_fn: SwapFn,
+        normalizer_after_swap: Option<AfterSwapFn>,
+        config: &SimulationConfig,
+    ) -> Self {
+        let amm_sub = BpfAmm::new(
+            submission_program,
+            config.initial_x,
+            config.initial_y,
+            "submission".to_string(),
+        );
+        let norm_x = config.initial_x * config.norm_liquidity_mult;
+        let norm_y = config.initial_y * config.norm_liquidity_mult;
+        let amm_norm = BpfAmm::new_native(
+            normalizer_fn,
+            normalizer_after_swap,
+            norm_x,
+            norm_y,
+            "normalizer".to_string(),
+        );
+        Self::with_normalizer_fee(amm_sub, amm_norm, config)
+    }
+}
+
+impl<R: EngineRng> Simulation<R> {
+    /// `new`, drawing every random stream from an `R` seeded exactly as the default `Pcg64`
+    /// streams are: `Simulation::<MyRng>::new_with_rng(..)`.
+    pub fn new_with_rng(
+        mut amm_sub: BpfAmm,
+        mut amm_norm: BpfAmm,
+        config: &SimulationConfig,
+    ) -> Self {
+        if config.initial_mispricing_bps != 0.0 {
+            let spot = config.initial_price * (1.0 + config.initial_mispricing_bps / 10_000.0);
+            if spot.is_finite() && spot > 0.0 {
+                let k = amm_sub.reserve_x * amm_sub.reserve_y;
+                amm_sub.reserve_x = (k / spot).sqrt();
+                amm_sub.reserve_y = (k * spot).sqrt();
+            }
+        }
+        if amm_norm.name == "normalizer" {
+            amm_norm.expect_pure_quotes();
+        }
+        if config.check_normalizer_sync && amm_norm.name == "normalizer" {
+            amm_norm.track_reference_curve();
+        }
+        if let Some(budget) = config.norm_subsidy_budget {
+            amm_norm.set_subsidy(config.normalizer_subsidy_bps(), budget);
+        }
+        if config.max_inventory_x.is_some() || config.max_inventory_y.is_some() {
+            amm_sub.set_inventory_limit(config.max_inventory_x, config.max_inventory_y);
+        }
+        if config.maker_rebate_bps != config.transfer_fee_bps {
+            amm_sub.set_input_settlement(config.maker_rebate_bps - config.transfer_fee_bps);
+        }
+        if config.locked_reserve_x > 0.0 || config.locked_reserve_y > 0.0 {
+            amm_sub.set_locked_reserves(config.locked_reserve_x, config.locked_reserve_y);
+        }
+        if config.check_storage_writes {
+            amm_sub.track_storage_writes();
+        }
+        if config.opponent_undercut_bps.is_some() {
+            // `adapt_opponent` probes the submission's spread on top of the arbs' and
+            // router's quotes every step.
+            amm_sub.cache_quotes();
+        }
+        if config.ignore_after_swap {
+            amm_sub.disable_after_swap();
+        }
+        if (config.decimals_x, config.decimals_y) != (NANO_DECIMALS, NANO_DECIMALS) {
+            amm_sub.set_decimals(config.decimals_x, config.decimals_y);
+            amm_norm.set_decimals(config.decimals_x, config.decimals_y);
+        }
+        let mut price = GBMPriceProcess::new(
+            config.initial_price,
+            config.gbm_mu,
+            config.gbm_sigma,
+            config.gbm_dt,
+            R::seed_from_u64(config.seed),
+        )
+        .with_tick_size(config.tick_size)
+        .with_mean_reversion(config.mean_reversion, config.initial_price, config.gbm_dt);
+        if config.correlation != 0.0 {
+            price = price.with_correlated_pair(
+                config.initial_price,
+                config.correlation,
+                R::seed_from_u64(config.seed.wrapping_add(3)),
+            );
+        }
+        let mut retail = RetailTrader::new(
+            config.retail_arrival_rate,
+            config.retail_mean_size,
+            config.retail_size_sigma,
+            config.retail_buy_prob,
+            R::seed_from_u64(config.seed.wrapping_add(1)),
+        );
+        retail.set_direction_bias(config.retail_direction_bias);
+        // The first agent keeps the historical arb seed; extra agents draw from its substreams.
+        let arb_seed = config.seed.wrapping_add(2);
+        let arbs: Vec<Arbitrageur<R>> = config
+            .arb_agents
+            .iter()
+            .enumerate()
+            .map(|(i, agent)| {
+                let seed = if i == 0 {
+                    arb_seed
+                } else {
+                    runner::substream_seed(arb_seed, i as u64)
+                };
+                Arbitrageur::new(
+                    config.min_arb_profit,
+                    config.retail_mean_size,
+                    config.retail_size_sigma,
+                    R::seed_from_u64(seed),
+                )
+                .with_solver(config.arb_solver_iters, config.arb_solver_tolerance)
+                .with_threshold_bps(agent.threshold_bps)
+                .with_min_trade_size(config.min_trade_size)
+                .with_tick_size(config.tick_size)
+            })
+            .collect();
+
+        let fair_price = price.current_price();
+        let spot_history = match config.twap_window {
+            Some(window) if window > 0 => [
+                VecDeque::from([amm_sub.spot_price()]),
+                VecDeque::from([amm_norm.spot_price()]),
+            ],
+            _ => Default::default(),
+        };
+        let k_initial = amm_sub.reserve_x * amm_sub.reserve_y;
+        let initial_reserves = (amm_sub.reserve_x, amm_sub.reserve_y);
+        let inventory_target = amm_sub.reserve_x;
+        let k_series = if config.record_k_series {
+            Some(Vec::with_capacity(
+                config.n_steps.min(config.max_recorded_steps) as usize,
+            ))
+        } else {
+            None
+        };
+
+        let mut sim = Self {
+            config: config.clone(),
+            amm_sub,
+            amm_norm,
+            price,
+            retail,
+            arb_order: (0..arbs.len()).collect(),
+            arbs,
+            arb_order_rng: R::seed_from_u64(runner::substream_seed(arb_seed, 0)),
+            arrival_rng: R::seed_from_u64(config.seed.wrapping_add(4)),
+            step_order_rng: R::seed_from_u64(config.seed.wrapping_add(5)),
+            oracle_rng: R::seed_from_u64(config.seed.wrapping_add(6)),
+            router: if config.partial_fill {
+                OrderRouter::new().with_partial_fill(config.max_impact_bps)
+            } else {
+                OrderRouter::new()
+            },
+            step: 0,
+            fair_price,
+            submission_edge: CompensatedSum::default(),
+            waterfall: PnlWaterfall::default(),
+            warmup_edge: 0.0,
+            k_initial,
+            initial_reserves,
+            measured_from: (initial_reserves.0, initial_reserves.1, config.initial_price),
+            inventory_target,
+            k_series,
+            spot_history,
+            price_history: VecDeque::from([fair_price]),
+            reference_history: VecDeque::new(),
+            momentum_history: match config.retail_momentum {
+                Some(_) => VecDeque::from([fair_price]),
+                None => VecDeque::new(),
+            },
+            disqualifications: Vec::new(),
+            convergence: EdgeConvergence::default(),
+            total_quote_slippage: 0.0,
+            max_quote_slippage: 0.0,
+            retail_volume: [CompensatedSum::default(); 2],
+            step_trades: Vec::new(),
+            tape: VecDeque::new(),
+        };
+        sim.observe_price();
+        sim
+    }
+
+    /// Replay `tape` instead of simulating flow: step `i` moves the fair price to trade
+    /// `i`'s reference price, lets the arbitrageurs close the gap on both pools, then fills
+    /// the trade against the submission pool alone. The run lasts exactly one step per trade.
+    pub fn with_tape(mut self, tape: &TradeTape) -> Self {
+        self.tape = tape.trades().iter().copied().collect();
+        self.config.n_steps = tape.len().min(u32::MAX as usize) as u32;
+        self
+    }
+
+    /// `native`, with every random stream drawn from an `R`.
+    pub fn native_with_rng(
+        submission_fn: SwapFn,
+        submission_after_swap: Option<AfterSwapFn>,
+        normalizer_fn: SwapFn,
+        normalizer_after_swap: Option<AfterSwapFn>,
+        config: &SimulationConfig,
+    ) -> Self {
+        let amm_sub = BpfAmm::new_native(
+            submission_fn,
+            submission_after_swap,
+            config.initial_x,
+            config.initial_y,
+            "submission".to_string(),
+        );
+        let amm_norm = BpfAmm::new_native(
+            normalizer_fn,
+            normalizer_after_swap,
+            config.initial_x * config.norm_liquidity_mult,
+            config.initial_y * config.norm_liquidity_mult,
+            "normalizer".to_string(),
+        );
+        Self::with_normalizer_fee(amm_sub, amm_norm, config)
+    }
+
+    fn with_normalizer_fee(
+        amm_sub: BpfAmm,
+        mut amm_norm: BpfAmm,
+        config: &SimulationConfig,
+    ) -> Self {
+        amm_norm.set_initial_storage(&config.normalizer_fee_word().to_le_bytes());
+        Self::new_with_rng(amm_sub, amm_norm, config)
+    }
+
+    /// Advance one step (price move, arbitrage, retail flow; interleaved in continuous time
+    /// under `TradeArrival::Poisson`). Returns `false` once all
+    /// `n_steps` have run (or the edge converged), without doing anything.
+    pub fn step(&mut self) -> bool {
+        self.step_with(&mut ())
+    }
+
+    /// `step`, reporting the submission's fills for the step to `objective` unless it is a
+    /// warmup step.
+    pub fn step_with<O: Objective + ?Sized>(&mut self, objective: &mut O) -> bool {
+        if self.is_finished() {
+            return false;
+        }
+        self.step_trades.clear();
+        let step = self.step;
+        let warmup = self.config.phase(step) == Phase::Warmup;
+        if step > 0 && step == self.config.warmup_steps {
+            self.measured_from = (
+                self.amm_sub.reserve_x,
+                self.amm_sub.reserve_y,
+                self.fair_price,
+            );
+        }
+        let scored_before = self.scored();
+        self.amm_sub.set_current_step(step as u64);
+        self.amm_norm.set_current_step(step as u64);
+        self.publish_twap();
+        self.adapt_opponent();
+        self.steer_retail();
+        if let Some(trade) = self.tape.pop_front() {
+            self.tape_step(&trade);
+        } else {
+            match self.config.trade_arrival {
+                TradeArrival::PerStep => {
+                    let prev_fair_price = self.fair_price;
+                    self.fair_price = self.price.step();
+                    self.observe_price();
+                    let orders = self.retail.generate_orders();
+                    let orders = self.net_orders(orders);
+                    let arb_slot = self.arb_slot(orders.len());
+                    for (i, order) in orders.iter().enumerate() {
+                        if i == arb_slot {
+                            self.arb_pass(prev_fair_price);
+                        }
+                        self.fill_retail(order);
+                    }
+                    if arb_slot >= orders.len() {
+                        self.arb_pass(prev_fair_price);
+                    }
                 }
+                TradeArrival::Poisson {
+                    retail_rate,
+                    arb_rate,
+                } => self.poisson_step(retail_rate, arb_rate),
+            }
+        }
+        self.rebalance_inventory();
+        let fair_price = self.fair_price;
+        self.check_normalizer_sync();
+        for (kind, detail) in self.amm_sub.take_violations() {
+            self.disqualifications.push(Disqualification {
+                seed: self.config.seed,
+                step: Some(step),
+                kind,
+                detail,
+            });
+        }
+
+        if !warmup {
+            objective.accumulate(
+                &StepRecord {
+                    step,
+                    trades: &self.step_trades,
+                },
+                &MarketCtx {
+                    fair_price,
+                    submission_reserve_x: self.amm_sub.reserve_x,
+                    submission_reserve_y: self.amm_sub.reserve_y,
+                    normalizer_reserve_x: self.amm_norm.reserve_x,
+                    normalizer_reserve_y: self.amm_norm.reserve_y,
+                },
+            );
+        }
+
+        self.record_spot();
+        self.record_price();
+        self.record_reference();
+        self.record_momentum();
+        if let Some(series) = self
+            .k_series
+            .as_mut()
+            .filter(|series| series.len() < self.config.max_recorded_steps as usize)
+        {
+            series.push(self.amm_sub.reserve_x * self.amm_sub.reserve_y);
+        }
+        self.step += 1;
+        let step_edge = self.submission_edge.value() - scored_before.edge.value();
+        if warmup {
+            // The fills stand; only the score forgets them.
+            self.warmup_edge += step_edge;
+            self.restore_scored(scored_before);
+        } else {
+            self.track_convergence(step_edge);
+        }
+        true
+    }
+
+    fn scored(&self) -> Scored {
+        Scored {
+            edge: self.submission_edge,
+            waterfall: self.waterfall,
+            total_quote_slippage: self.total_quote_slippage,
+            max_quote_slippage: self.max_quote_slippage,
+            retail_volume: self.retail_volume,
+            inventory_rejected: self.amm_sub.inventory_rejected(),
+        }
+    }
+
+    fn restore_scored(&mut self, scored: Scored) {
+        self.submission_edge = scored.edge;
+        self.waterfall = scored.waterfall;
+        self.total_quote_slippage = scored.total_quote_slippage;
+        self.max_quote_slippage = scored.max_quote_slippage;
+        self.retail_volume = scored.retail_volume;
+        self.amm_sub
+            .set_inventory_rejected(scored.inventory_rejected);
+    }
+
+    /// Under `StopCondition::EdgeConverged`, close a block every `window` steps and mark the
+    /// run converged once a block's mean edge is within `tolerance` of the previous one's.
+    fn track_convergence(&mut self, step_edge: f64) {
+        let StopCondition::EdgeConverged { window, tolerance } = self.config.stop_condition else {
+            return;
+        };
+        if window == 0 {
+            return;
+        }
+        let convergence = &mut self.convergence;
+        convergence.block_edge += step_edge;
+        if !self.step.is_multiple_of(window) {
+            return;
+        }
+        let mean = convergence.block_edge / window as f64;
+        convergence.converged = convergence
+            .last_block_mean
+            .is_some_and(|last| (mean - last).abs() < tolerance);
+        convergence.last_block_mean = Some(mean);
+        convergence.block_edge = 0.0;
+    }
+
+    /// Under `inventory_rebalance`, trade the submission's X reserve back to where it started
+    /// once it has drifted past the band, at the fair price, and book the venue's cost on
+    /// what the pool actually moved (see `BpfAmm::rebalance_x`). A buy-back is cut short
+    /// rather than spend the last of the Y reserve.
+    fn rebalance_inventory(&mut self) {
+        let Some(rebalance) = self.config.inventory_rebalance else {
+            return;
+        };
+        let target = self.inventory_target;
+        let drift = self.amm_sub.reserve_x - target;
+        if drift.abs() <= rebalance.band * target || !self.fair_price.is_finite() {
+            return;
+        }
+        // Buying X back may cost at most half the Y reserve.
+        let wanted = drift.max(-0.5 * self.amm_sub.reserve_y / self.fair_price);
+        let sold_x = self.amm_sub.rebalance_x(wanted, self.fair_price);
+        let cost = sold_x.abs() * self.fair_price * rebalance.cost_bps / 10_000.0;
+        self.submission_edge += -cost;
+        self.waterfall.inventory_cost -= cost;
+    }
+
+    /// Under `price_oracle`, hand the submission a fresh observation of the fair price,
+    /// perturbed by `oracle_noise_stddev`.
+    fn observe_price(&mut self) {
+        if !self.config.price_oracle {
+            return;
+        }
+        let z: f64 = StandardNormal.sample(&mut Draws(&mut self.oracle_rng));
+        let observed = self.fair_price * (1.0 + z * self.config.oracle_noise_stddev);
+        self.amm_sub.set_price_oracle(Some(observed));
+    }
+
+    /// Hand each pool the mean of its recorded step-end spot prices for this step's quotes.
+    fn publish_twap(&mut self) {
+        let [sub, norm] = &self.spot_history;
+        let mean = |history: &VecDeque<f64>| {
+            (!history.is_empty()).then(|| history.iter().sum::<f64>() / history.len() as f64)
+        };
+        self.amm_sub.set_twap(mean(sub));
+        self.amm_norm.set_twap(mean(norm));
+    }
+
+    /// Append both pools' step-end spot prices to the TWAP window.
+    fn record_spot(&mut self) {
+        let Some(window) = self.config.twap_window.filter(|&n| n > 0) else {
+            return;
+        };
+        let spots = [self.amm_sub.spot_price(), self.amm_norm.spot_price()];
+        for (history, spot) in self.spot_history.iter_mut().zip(spots) {
+            history.push_back(spot);
+            while history.len() > window as usize {
+                history.pop_front();
+            }
+        }
+    }
+
+    /// Under `check_normalizer_sync`, panic if the normalizer pool has drifted from its
+    /// closed-form replay by more than `NORMALIZER_SYNC_NANO_PER_FILL` per fill so far.
+    fn check_normalizer_sync(&self) {
+        if let Some(desync) = self.amm_norm.reference_desync() {
+            let bound = NORMALIZER_SYNC_NANO_PER_FILL * desync.fills as f64;
+            assert!(
+                desync.max_gap_nano <= bound,
+                "normalizer desynced from its closed-form curve at step {}: {} nano after {} fills (bound {})",
+                self.step,
+                desync.max_gap_nano,
+                desync.fills,
+                bound
+            );
+        }
+    }
+
+    /// This step's retail orders as `RetailNetting` routes them: unchanged, or collapsed
+    /// into one order for the net Y notional (none when buys and sells cancel exactly).
+    fn net_orders(&self, orders: Vec<RetailOrder>) -> Vec<RetailOrder> {
+        if self.config.retail_netting == RetailNetting::Sequential || orders.len() < 2 {
+            return orders;
+        }
+        let net: f64 = orders
+            .iter()
+            .map(|o| if o.is_buy { o.size } else { -o.size })
+            .sum();
+        if net == 0.0 {
+            return Vec::new();
+        }
+        vec![RetailOrder {
+            is_buy: net > 0.0,
+            size: net.abs(),
+        }]
+    }
+
+    /// How many of this step's `n_orders` retail orders fill before the arbitrage pass.
+    fn arb_slot(&mut self, n_orders: usize) -> usize {
+        match self.config.step_order {
+            StepOrder::ArbFirst => 0,
+            StepOrder::RetailFirst => n_orders,
+            StepOrder::Shuffled => Draws(&mut self.step_order_rng).gen_range(0..=n_orders),
+        }
+    }
+
+    /// Keep the step-end fair prices arbitrageurs see under `oracle_lag_steps`.
+    fn record_price(&mut self) {
+        let lag = self.config.oracle_lag_steps;
+        if lag == 0 {
+            return;
+        }
+        self.price_history.push_back(self.fair_price);
+        while self.price_history.len() > lag {
+            self.price_history.pop_front();
+        }
+    }
+
+    /// Keep the step-end fair prices a `ReferenceMode::Twap` mark averages over.
+    fn record_reference(&mut self) {
+        let ReferenceMode::Twap(window) = self.config.reference_mode else {
+            return;
+        };
+        let keep = window.saturating_sub(1) as usize;
+        self.reference_history.push_back(self.fair_price);
+        while self.reference_history.len() > keep {
+            self.reference_history.pop_front();
+        }
+    }
+
+    /// Keep the step-end fair prices `retail_momentum` looks back over.
+    fn record_momentum(&mut self) {
+        let Some(momentum) = self.config.retail_momentum else {
+            return;
+        };
+        self.momentum_history.push_back(self.fair_price);
+        while self.momentum_history.len() > momentum.window as usize + 1 {
+            self.momentum_history.pop_front();
+        }
+    }
+
+    /// Under `retail_momentum`, lean this step's retail flow with the fair price's move
+    /// over the recorded window, on top of `retail_direction_bias`.
+    fn steer_retail(&mut self) {
+        let Some(momentum) = self.config.retail_momentum else {
+            return;
+        };
+        let (Some(&first), Some(&last)) =
+            (self.momentum_history.front(), self.momentum_history.back())
+        else {
+            return;
+        };
+        let expected_move =
+            self.config.gbm_sigma * ((self.momentum_history.len() - 1) as f64).sqrt();
+        let z = if expected_move > 0.0 {
+            (last / first).ln() / expected_move
+        } else {
+            0.0
+        };
+        self.retail
+            .set_direction_bias(self.config.retail_direction_bias + momentum.strength * z.tanh());
+    }
+
+    /// The price a fill is marked at while the fair price is `price`: `price` itself, or
+    /// under `ReferenceMode::Twap` its mean with the recent step-end fair prices.
+    fn mark(&self, price: f64) -> f64 {
+        match self.config.reference_mode {
+            ReferenceMode::Spot => price,
+            ReferenceMode::Twap(_) => {
+                let sum: f64 = self.reference_history.iter().sum();
+                (sum + price) / (self.reference_history.len() + 1) as f64
+            }
+        }
+    }
+
+    /// The price arbitrageurs act on: the current fair price, or the step-end fair price
+    /// `oracle_lag_steps` steps back.
+    fn arb_price(&self) -> f64 {
+        match self.config.oracle_lag_steps {
+            0 => self.fair_price,
+            _ => self
+                .price_history
+                .front()
+                .copied()
+                .unwrap_or(self.fair_price),
+        }
+    }
+
+    /// Under `opponent_undercut_bps`, re-price the native normalizer to the submission's
+    /// current quoted fee less the undercut, floored at `opponent_min_spread_bps`.
+    fn adapt_opponent(&mut self) {
+        let Some(undercut) = self.config.opponent_undercut_bps else {
+            return;
+        };
+        if self.amm_norm.name != "normalizer" {
+            return;
+        }
+        let Some(spread) = self.amm_sub.quoted_spread_bps(UNDERCUT_PROBE) else {
+            return;
+        };
+        let fee = (spread / 2.0 - undercut as f64)
+            .round()
+            .clamp(self.config.opponent_min_spread_bps as f64, MAX_FEE_BPS);
+        self.amm_norm
+            .overwrite_storage(&normalizer::fee_word(fee as u16).to_le_bytes());
+    }
+
+    /// Every arbitrageur, in this step's shuffled order, trades against both pools at the
+    /// (possibly stale) arb price; fills are marked at the current fair price (or its
+    /// `ReferenceMode` mark). `prev_fair_price` is where the price stood before it last moved.
+    fn arb_pass(&mut self, prev_fair_price: f64) {
+        let fair_price = self.mark(self.fair_price);
+        let prev_fair_price = self.mark(prev_fair_price);
+        let arb_price = self.arb_price();
+        if self.arb_order.len() > 1 {
+            self.arb_order.shuffle(&mut Draws(&mut self.arb_order_rng));
+        }
+        for &i in &self.arb_order {
+            if let Some(result) = self.arbs[i].execute_arb(&mut self.amm_sub, arb_price) {
+                // The arbitrageur scored the fill at the price it saw; re-mark a stale one.
+                let marked = if arb_price == fair_price {
+                    result.edge
+                } else if result.amm_buys_x {
+                    result.amount_x * fair_price - result.amount_y
+                } else {
+                    result.amount_y - result.amount_x * fair_price
+                };
+                let edge = marked
+                    + settlement_edge(
+                        &self.config,
+                        fair_price,
+                        result.amm_buys_x,
+                        result.amount_x,
+                        result.amount_y,
+                    );
+                self.submission_edge += edge;
+                let inventory_x = if result.amm_buys_x {
+                    result.amount_x
+                } else {
+                    -result.amount_x
+                };
+                let mark_to_market = inventory_x * (fair_price - prev_fair_price);
+                self.waterfall.arb_spread += edge - mark_to_market;
+                self.waterfall.adverse_selection += mark_to_market;
+                self.step_trades.push(SubmissionTrade {
+                    source: TradeSource::Arbitrage,
+                    amm_buys_x: result.amm_buys_x,
+                    amount_x: result.amount_x,
+                    amount_y: result.amount_y,
+                    edge,
+                });
+            }
+            self.arbs[i].execute_arb(&mut self.amm_norm, arb_price);
+        }
+    }
+
+    /// Route one retail order at the current fair price, unless it is below the dust filter.
+    fn fill_retail(&mut self, order: &RetailOrder) {
+        if order.size < self.config.min_trade_size {
+            return;
+        }
+        let fair_price = self.fair_price;
+        let routed =
+            self.router
+                .route_order(order, &mut self.amm_sub, &mut self.amm_norm, fair_price);
+        self.total_quote_slippage += routed.submission_quote_slippage;
+        self.max_quote_slippage = self
+            .max_quote_slippage
+            .max(routed.submission_quote_slippage);
+        for trade in routed.trades {
+            if trade.is_submission {
+                self.credit_retail_fill(trade.amm_buys_x, trade.amount_x, trade.amount_y);
+            } else {
+                self.retail_volume[1] += trade.amount_y;
+            }
+        }
+    }
+
+    /// Mark a retail fill on the submission pool at the current fair price and book its edge.
+    fn credit_retail_fill(&mut self, amm_buys_x: bool, amount_x: f64, amount_y: f64) {
+        let fair_price = self.mark(self.fair_price);
+        let marked = if amm_buys_x {
+            amount_x * fair_price - amount_y
+        } else {
+            amount_y - amount_x * fair_price
+        };
+        let trade_edge =
+            marked + settlement_edge(&self.config, fair_price, amm_buys_x, amount_x, amount_y);
+        self.submission_edge += trade_edge;
+        self.waterfall.retail_spread += trade_edge;
+        self.retail_volume[0] += amount_y;
+        self.step_trades.push(SubmissionTrade {
+            source: TradeSource::Retail,
+            amm_buys_x,
+            amount_x,
+            amount_y,
+            edge: trade_edge,
+        });
+    }
+
+    /// One step of a `with_tape` replay: the fair price jumps to the trade's reference
+    /// price, the arbitrageurs close the gap, and the trade fills on the submission pool.
+    fn tape_step(&mut self, trade: &TapeTrade) {
+        let prev_fair_price = self.fair_price;
+        self.fair_price = trade.price;
+        self.observe_price();
+        self.arb_pass(prev_fair_price);
+        if trade.size * trade.price < self.config.min_trade_size {
+            return;
+        }
+        if trade.is_buy {
+            let input_y = trade.size * trade.price;
+            let output_x = self.amm_sub.execute_buy_x(input_y);
+            if output_x > 0.0 {
+                self.credit_retail_fill(false, output_x, input_y);
+            }
+        } else {
+            let output_y = self.amm_sub.execute_sell_x(trade.size);
+            if output_y > 0.0 {
+                self.credit_retail_fill(true, trade.size, output_y);
+            }
+        }
+    }
+
+    /// One step of continuous time: retail and arb arrivals are independent Poisson
+    /// processes, and the price diffuses over each gap before the next arrival trades.
+    fn poisson_step(&mut self, retail_rate: f64, arb_rate: f64) {
+        let mut elapsed = 0.0;
+        let mut next_retail = self.next_arrival(retail_rate);
+        let mut next_arb = self.next_arrival(arb_rate);
+        loop {
+            let at = next_retail.min(next_arb);
+            if at >= 1.0 {
+                break;
+            }
+            let prev_fair_price = self.fair_price;
+            self.fair_price = self.price.advance(at - elapsed);
+            self.observe_price();
+            elapsed = at;
+            if next_arb <= next_retail {
+                self.arb_pass(prev_fair_price);
+                next_arb = at + self.next_arrival(arb_rate);
+            } else {
+                let order = self.retail.sample_order();
+                self.fill_retail(&order);
+                next_retail = at + self.next_arrival(retail_rate);
             }
         }
+        self.fair_price = self.price.advance(1.0 - elapsed);
+        self.observe_price();
+    }
+
+    /// Exponential wait, in steps, until the next arrival at `rate` per step.
+    fn next_arrival(&mut self, rate: f64) -> f64 {
+        if !rate.is_finite() || rate <= 0.0 {
+            return f64::INFINITY;
+        }
+        let wait: f64 = Exp1.sample(&mut Draws(&mut self.arrival_rng));
+        wait / rate
     }
 
-    Ok(SimResult {
-        seed: config.seed,
-        submission_edge,
-    })
+    /// Number of steps run so far.
+    pub fn current_step(&self) -> u32 {
+        self.step
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.step >= self.config.n_steps || self.convergence.converged
+    }
+
+    /// Fair price of the most recent step (the initial price before the first one).
+    pub fn fair_price(&self) -> f64 {
+        self.fair_price
+    }
+
+    pub fn submission_edge(&self) -> f64 {
+        self.submission_edge.value()
+    }
+
+    /// Running breakdown of `submission_edge` by source.
+    pub fn waterfall(&self) -> PnlWaterfall {
+        self.waterfall
+    }
+
+    /// Steps run and edge earned so far in the warmup and in the measured part of the run.
+    pub fn phases(&self) -> PhaseSplit {
+        let warmup_steps = self.step.min(self.config.warmup_steps);
+        PhaseSplit {
+            warmup: PhaseTotals {
+                steps: warmup_steps,
+                edge: self.warmup_edge,
+            },
+            measured: PhaseTotals {
+                steps: self.step - warmup_steps,
+                edge: self.submission_edge.value(),
+            },
+        }
+    }
+
+    /// Read-only view of the submission pool, including its live strategy storage.
+    pub fn submission(&self) -> &BpfAmm {
+        &self.amm_sub
+    }
+
+    /// The submission pool, e.g. to record or drain its `compute_swap` calls between steps.
+    pub fn submission_mut(&mut self) -> &mut BpfAmm {
+        &mut self.amm_sub
+    }
+
+    pub fn normalizer(&self) -> &BpfAmm {
+        &self.amm_norm
+    }
+
+    /// Capture everything needed to resume this simulation from the current step.
+    pub fn snapshot(&self) -> SimulationSnapshot<R> {
+        SimulationSnapshot {
+            reserves: [
+                (self.amm_sub.reserve_x, self.amm_sub.reserve_y),
+                (self.amm_norm.reserve_x, self.amm_norm.reserve_y),
+            ],
+            storage: [
+                self.amm_sub.storage().to_vec(),
+                self.amm_norm.storage().to_vec(),
+            ],
+            rng_state: RngState {
+                price: self.price.state(),
+                retail: self.retail.rng().clone(),
+                arbs: self.arbs.iter().map(|arb| arb.rng().clone()).collect(),
+                arb_order: self.arb_order.clone(),
+                arb_order_rng: self.arb_order_rng.clone(),
+                arrival_rng: self.arrival_rng.clone(),
+                step_order_rng: self.step_order_rng.clone(),
+                oracle_rng: Some(self.oracle_rng.clone()),
+            },
+            step: self.step,
+            accumulated_metrics: AccumulatedMetrics {
+                fair_price: self.fair_price,
+                submission_edge: self.submission_edge.parts().0,
+                submission_edge_compensation: self.submission_edge.parts().1,
+                warmup_edge: self.warmup_edge,
+                waterfall: self.waterfall,
+                k_initial: self.k_initial,
+                initial_reserves: Some(self.initial_reserves),
+                measured_from: Some(self.measured_from),
+                k_series: self.k_series.clone(),
+                spot_history: self.spot_history.clone(),
+                price_history: self.price_history.clone(),
+                reference_history: self.reference_history.clone(),
+                momentum_history: self.momentum_history.clone(),
+                disqualifications: self.disqualifications.clone(),
+                convergence: self.convergence,
+                total_quote_slippage: self.total_quote_slippage,
+                max_quote_slippage: self.max_quote_slippage,
+                retail_volume: self.retail_volume.map(|volume| volume.parts().0),
+                retail_volume_compensation: self.retail_volume.map(|volume| volume.parts().1),
+                oracle_price: self.amm_sub.price_oracle(),
+                normalizer_subsidy_paid: self.amm_norm.subsidy_paid(),
+                submission_inventory_rejected: self.amm_sub.inventory_rejected(),
+                submission_storage_watch: self.amm_sub.storage_watch(),
+            },
+        }
+    }
+
+    /// Resume from `snapshot`. The simulation must have been built from the same
+    /// strategies and config as the one that took it; continuing then matches running
+    /// straight through.
+    pub fn restore(&mut self, snapshot: SimulationSnapshot<R>) -> anyhow::Result<()> {
+        let rng = snapshot.rng_state;
+        anyhow::ensure!(
+            rng.arbs.len() == self.arbs.len() && rng.arb_order.len() == self.arbs.len(),
+            "snapshot has {} arb agents, simulation has {}",
+            rng.arbs.len(),
+            self.arbs.len()
+        );
+        anyhow::ensure!(
+            snapshot.step <= self.config.n_steps,
+            "snapshot step {} is past n_steps {}",
+            snapshot.step,
+            self.config.n_steps
+        );
+
+        let [(sub_x, sub_y), (norm_x, norm_y)] = snapshot.reserves;
+        let [sub_storage, norm_storage] = snapshot.storage;
+        let step = snapshot.step as u64;
+        self.amm_sub.restore_state(sub_x, sub_y, &sub_storage, step);
+        self.amm_norm
+            .restore_state(norm_x, norm_y, &norm_storage, step);
+
+        self.price.restore_state(&rng.price);
+        self.retail.set_rng(rng.retail);
+        for (arb, arb_rng) in self.arbs.iter_mut().zip(rng.arbs) {
+            arb.set_rng(arb_rng);
+        }
+        self.arb_order = rng.arb_order;
+        self.arb_order_rng = rng.arb_order_rng;
+        self.arrival_rng = rng.arrival_rng;
+        self.step_order_rng = rng.step_order_rng;
+        if let Some(oracle_rng) = rng.oracle_rng {
+            self.oracle_rng = oracle_rng;
+        }
+
+        let metrics = snapshot.accumulated_metrics;
+        self.step = snapshot.step;
+        self.fair_price = metrics.fair_price;
+        self.submission_edge = CompensatedSum::from_parts(
+            metrics.submission_edge,
+            metrics.submission_edge_compensation,
+        );
+        self.waterfall = metrics.waterfall;
+        self.warmup_edge = metrics.warmup_edge;
+        self.k_initial = metrics.k_initial;
+        if let Some(initial_reserves) = metrics.initial_reserves {
+            self.initial_reserves = initial_reserves;
+        }
+        if let Some(measured_from) = metrics.measured_from {
+            self.measured_from = measured_from;
+        }
+        self.k_series = metrics.k_series;
+        self.spot_history = metrics.spot_history;
+        self.price_history = metrics.price_history;
+        self.reference_history = metrics.reference_history;
+        self.momentum_history = metrics.momentum_history;
+        self.disqualifications = metrics.disqualifications;
+        self.convergence = metrics.convergence;
+        self.total_quote_slippage = metrics.total_quote_slippage;
+        self.max_quote_slippage = metrics.max_quote_slippage;
+        self.retail_volume = [0, 1].map(|i| {
+            CompensatedSum::from_parts(
+                metrics.retail_volume[i],
+                metrics.retail_volume_compensation[i],
+            )
+        });
+        self.amm_sub.set_price_oracle(metrics.oracle_price);
+        self.amm_norm
+            .set_subsidy_paid(metrics.normalizer_subsidy_paid);
+        self.amm_sub
+            .set_inventory_rejected(metrics.submission_inventory_rejected);
+        if let Some((calls, written)) = metrics.submission_storage_watch {
+            self.amm_sub.set_storage_watch(calls, written);
+        }
+        Ok(())
+    }
+
+    /// Run any remaining steps and summarize.
+    pub fn run(self) -> anyhow::Result<SimResult> {
+        self.run_with(&mut ())
+    }
+
+    /// Run any remaining steps, feeding each one to `objective`, and summarize. The
+    /// objective's headline number is `objective.finalize()` afterwards.
+    pub fn run_with<O: Objective + ?Sized>(
+        mut self,
+        objective: &mut O,
+    ) -> anyhow::Result<SimResult> {
+        self.check_config()?;
+        while self.step_with(objective) {}
+        Ok(self.summarize())
+    }
+
+    /// Run until the step with the first disqualifying event, or until `abort` is set, and
+    /// summarize; `steps_run` tells how far it got. Fail-fast batches share one `abort`
+    /// across their workers.
+    pub fn run_until_disqualified(mut self, abort: &AtomicBool) -> anyhow::Result<SimResult> {
+        self.check_config()?;
+        while self.disqualifications.is_empty() && !abort.load(Ordering::Relaxed) && self.step() {}
+        Ok(self.summarize())
+    }
+
+    fn check_config(&self) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.config.seeded_reserves.is_none(),
+            "seeded_reserves must be resolved with SimulationConfig::with_seeded_reserves before the pools are built"
+        );
+        anyhow::ensure!(
+            self.config.norm_subsidy_bps == 0.0 || self.config.norm_subsidy_budget.is_some(),
+            "norm_subsidy_bps {} needs a norm_subsidy_budget",
+            self.config.norm_subsidy_bps
+        );
+        Ok(())
+    }
+
+    fn summarize(self) -> SimResult {
+        let k_final = self.amm_sub.reserve_x * self.amm_sub.reserve_y;
+        let k_growth = if self.k_initial > 0.0 {
+            k_final / self.k_initial - 1.0
+        } else {
+            0.0
+        };
+
+        let recorded = self.k_series.as_ref().map_or(0, Vec::len);
+        let recording_capped = self.k_series.is_some() && recorded < self.step as usize;
+
+        let (measured_x, measured_y, _) = self.measured_from;
+        let over_holding = impermanent_loss(
+            (measured_x, measured_y),
+            (self.amm_sub.reserve_x, self.amm_sub.reserve_y),
+            self.fair_price,
+        );
+        let lp_return = self.lp_return(over_holding);
+        let (x0, y0) = self.initial_reserves;
+
+        SimResult {
+            seed: self.config.seed,
+            steps_run: self.step,
+            submission_edge: self.submission_edge.value(),
+            phases: self.phases(),
+            k_growth,
+            impermanent_loss: over_holding,
+            k_series: self.k_series,
+            waterfall: self.waterfall,
+            disqualifications: self.disqualifications,
+            total_quote_slippage: self.total_quote_slippage,
+            max_quote_slippage: self.max_quote_slippage,
+            inventory_rejected: self.amm_sub.inventory_rejected(),
+            storage_never_written: self.amm_sub.storage_never_written(),
+            recording_capped,
+            recording_bytes: recorded * std::mem::size_of::<f64>(),
+            initial_value: x0 * self.config.initial_price + y0,
+            lp_return,
+            retail_volume: self.retail_volume.map(|volume| volume.value()),
+        }
+    }
+
+    /// Split the pool's gain over holding the reserves it entered the measured steps with
+    /// into fee revenue and divergence, and annualize it over the measured steps.
+    fn lp_return(&self, over_holding: f64) -> LpReturn {
+        let (x0, y0, start_price) = self.measured_from;
+        let held = x0 * self.fair_price + y0;
+        let fee_revenue = self.waterfall.retail_spread + self.waterfall.arb_spread;
+        let growth = (held + over_holding) / held;
+        let steps = self.phases().measured.steps;
+        let annualized_rate = if steps > 0 && growth > 0.0 && growth.is_finite() {
+            growth.ln() * self.config.steps_per_year / steps as f64
+        } else {
+            0.0
+        };
+        LpReturn {
+            fee_revenue,
+            divergence: over_holding - fee_revenue,
+            price_move: x0 * (self.fair_price - start_price),
+            annualized_rate,
+        }
+    }
+}
+
+/// The change in a submission fill's edge from the transfer fee and maker rebate its pool
+/// settled on the input leg (see `BpfAmm::set_input_settlement`), less the fill's
+/// `per_trade_cost`.
+fn settlement_edge(
+    config: &SimulationConfig,
+    fair_price: f64,
+    amm_buys_x: bool,
+    amount_x: f64,
+    amount_y: f64,
+) -> f64 {
+    let net_bps = config.maker_rebate_bps - config.transfer_fee_bps;
+    let input = if net_bps == 0.0 {
+        0.0
+    } else if amm_buys_x {
+        amount_x * net_bps / 10_000.0 * fair_price
+    } else {
+        amount_y * net_bps / 10_000.0
+    };
+    input - config.per_trade_cost
 }
 
 /// Run simulation with BPF programs (slow, for validation)
@@ -83,14 +1277,8 @@ pub fn run_simulation(
     );
     let norm_x = config.initial_x * config.norm_liquidity_mult;
     let norm_y = config.initial_y * config.norm_liquidity_mult;
-    let mut amm_norm = BpfAmm::new(
-        normalizer_program,
-        norm_x,
-        norm_y,
-        "normalizer".to_string(),
-    );
-    amm_norm.set_initial_storage(&config.norm_fee_bps.to_le_bytes());
-    run_sim_inner(amm_sub, amm_norm, config)
+    let amm_norm = BpfAmm::new(normalizer_program, norm_x, norm_y, "normalizer".to_string());
+    Simulation::<Pcg64>::with_normalizer_fee(amm_sub, amm_norm, config).run()
 }
 
 /// Run simulation with native swap functions (fast, for production)
@@ -100,6 +1288,25 @@ pub fn run_simulation_native(
     normalizer_fn: SwapFn,
     normalizer_after_swap: Option<AfterSwapFn>,
     config: &SimulationConfig,
+) -> anyhow::Result<SimResult> {
+    Simulation::native(
+        submission_fn,
+        submission_after_swap,
+        normalizer_fn,
+        normalizer_after_swap,
+        config,
+    )
+    .run()
+}
+
+/// Native submission competing with `opponent` for the flow: arbitrageurs trade both pools
+/// toward the fair price and the router sends each retail order to the better quote, so
+/// `SimResult::retail_share` is the share of retail volume the submission won.
+pub fn run_competitive_simulation(
+    submission_fn: SwapFn,
+    submission_after_swap: Option<AfterSwapFn>,
+    opponent: &Opponent,
+    config: &SimulationConfig,
 ) -> anyhow::Result<SimResult> {
     let amm_sub = BpfAmm::new_native(
         submission_fn,
@@ -108,17 +1315,28 @@ pub fn run_simulation_native(
         config.initial_y,
         "submission".to_string(),
     );
-    let norm_x = config.initial_x * config.norm_liquidity_mult;
-    let norm_y = config.initial_y * config.norm_liquidity_mult;
-    let mut amm_norm = BpfAmm::new_native(
+    Simulation::new(amm_sub, opponent.pool(config), config).run()
+}
+
+/// Replay a recorded trade tape against native swap functions; see `Simulation::with_tape`.
+/// `config` supplies everything but the flow and the price path (seed, pools, arbitrageurs).
+pub fn run_simulation_with_tape(
+    submission_fn: SwapFn,
+    submission_after_swap: Option<AfterSwapFn>,
+    normalizer_fn: SwapFn,
+    normalizer_after_swap: Option<AfterSwapFn>,
+    config: &SimulationConfig,
+    tape: &TradeTape,
+) -> anyhow::Result<SimResult> {
+    Simulation::native(
+        submission_fn,
+        submission_after_swap,
         normalizer_fn,
         normalizer_after_swap,
-        norm_x,
-        norm_y,
-        "normalizer".to_string(),
-    );
-    amm_norm.set_initial_storage(&config.norm_fee_bps.to_le_bytes());
-    run_sim_inner(amm_sub, amm_norm, config)
+        config,
+    )
+    .with_tape(tape)
+    .run()
 }
 
 /// Run simulation with BPF submission + native normalizer (mixed mode)
@@ -128,21 +1346,11 @@ pub fn run_simulation_mixed(
     normalizer_after_swap: Option<AfterSwapFn>,
     config: &SimulationConfig,
 ) -> anyhow::Result<SimResult> {
-    let amm_sub = BpfAmm::new(
+    Simulation::mixed(
         submission_program,
-        config.initial_x,
-        config.initial_y,
-        "submission".to_string(),
-    );
-    let norm_x = config.initial_x * config.norm_liquidity_mult;
-    let norm_y = config.initial_y * config.norm_liquidity_mult;
-    let mut amm_norm = BpfAmm::new_native(
         normalizer_fn,
         normalizer_after_swap,
-        norm_x,
-        norm_y,
-        "normalizer".to_string(),
-    );
-    amm_norm.set_initial_storage(&config.norm_fee_bps.to_le_bytes());
-    run_sim_inner(amm_sub, amm_norm, config)
+        config,
+    )
+    .run()
 }
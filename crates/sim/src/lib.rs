@@ -1,10 +1,18 @@
 pub mod amm;
 pub mod arbitrageur;
+pub mod attribution;
+pub mod audit;
 pub mod bench;
+pub mod corpus;
 mod curve_checks;
 pub mod engine;
+pub mod objective;
+pub mod opponents;
 pub mod price_process;
 pub mod retail;
+pub mod rng;
 pub mod router;
 pub mod runner; // profiling utilities
 pub mod search_stats;
+pub mod stress;
+pub mod tape;
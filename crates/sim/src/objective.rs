@@ -0,0 +1,171 @@
+//! Pluggable scoring of a simulation run.
+//!
+//! The engine reports every trade against the submission pool to an [`Objective`] once per
+//! measured step (warmup steps are not scored); the objective folds them into a single
+//! headline number. [`EdgeObjective`] is the
+//! challenge's standard score and matches `SimResult::submission_edge`.
+
+use prop_amm_shared::sum::CompensatedSum;
+
+/// Who traded against the submission pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TradeSource {
+    Arbitrage,
+    Retail,
+}
+
+/// One fill against the submission pool.
+#[derive(Debug, Clone, Copy)]
+pub struct SubmissionTrade {
+    pub source: TradeSource,
+    /// True when the pool received X (the trader sold X).
+    pub amm_buys_x: bool,
+    pub amount_x: f64,
+    pub amount_y: f64,
+    /// Pool's profit on the fill, valued at the step's fair price.
+    pub edge: f64,
+}
+
+/// Everything the submission pool did during one step, in execution order.
+#[derive(Debug, Clone, Copy)]
+pub struct StepRecord<'a> {
+    pub step: u32,
+    pub trades: &'a [SubmissionTrade],
+}
+
+/// Market state at the end of a step.
+#[derive(Debug, Clone, Copy)]
+pub struct MarketCtx {
+    pub fair_price: f64,
+    pub submission_reserve_x: f64,
+    pub submission_reserve_y: f64,
+    pub normalizer_reserve_x: f64,
+    pub normalizer_reserve_y: f64,
+}
+
+pub trait Objective {
+    fn accumulate(&mut self, step: &StepRecord, ctx: &MarketCtx);
+    fn finalize(&self) -> f64;
+}
+
+/// Ignores every step; used when only the built-in result fields are wanted.
+impl Objective for () {
+    fn accumulate(&mut self, _step: &StepRecord, _ctx: &MarketCtx) {}
+
+    fn finalize(&self) -> f64 {
+        0.0
+    }
+}
+
+/// Total edge captured by the submission: the sum of per-fill edges, compensated like the
+/// engine's own so the two agree to the bit.
+#[derive(Debug, Clone, Default)]
+pub struct EdgeObjective {
+    total: CompensatedSum,
+}
+
+impl Objective for EdgeObjective {
+    fn accumulate(&mut self, step: &StepRecord, _ctx: &MarketCtx) {
+        for trade in step.trades {
+            self.total += trade.edge;
+        }
+    }
+
+    fn finalize(&self) -> f64 {
+        self.total.value()
+    }
+}
+
+/// One step that traded against the submission, kept for narration.
+#[derive(Debug, Clone)]
+pub struct NotableStep {
+    pub step: u32,
+    /// Fair price at the end of the previous step.
+    pub price_before: f64,
+    /// Fair price at the end of this step.
+    pub price_after: f64,
+    pub trades: Vec<SubmissionTrade>,
+}
+
+impl NotableStep {
+    /// Net edge the submission captured during the step.
+    pub fn edge(&self) -> f64 {
+        self.trades.iter().map(|t| t.edge).sum()
+    }
+
+    /// Relative fair-price change over the step.
+    pub fn price_move(&self) -> f64 {
+        self.price_after / self.price_before - 1.0
+    }
+}
+
+/// Keeps the `top_k` steps with the largest absolute edge and, separately, the single worst
+/// step. Its headline number is the total edge, like [`EdgeObjective`].
+#[derive(Debug, Clone)]
+pub struct NotableSteps {
+    top_k: usize,
+    last_fair_price: f64,
+    /// In step order: evicting the smallest entry never reorders the rest.
+    top: Vec<NotableStep>,
+    worst: Option<NotableStep>,
+    total: f64,
+}
+
+impl NotableSteps {
+    /// `initial_price` is the fair price before the first step.
+    pub fn new(top_k: usize, initial_price: f64) -> Self {
+        Self {
+            top_k,
+            last_fair_price: initial_price,
+            top: Vec::with_capacity(top_k + 1),
+            worst: None,
+            total: 0.0,
+        }
+    }
+
+    /// The most impactful steps, in step order.
+    pub fn top(&self) -> &[NotableStep] {
+        &self.top
+    }
+
+    /// The step with the lowest net edge, if anything traded.
+    pub fn worst(&self) -> Option<&NotableStep> {
+        self.worst.as_ref()
+    }
+}
+
+impl Objective for NotableSteps {
+    fn accumulate(&mut self, step: &StepRecord, ctx: &MarketCtx) {
+        let price_before = std::mem::replace(&mut self.last_fair_price, ctx.fair_price);
+        if step.trades.is_empty() {
+            return;
+        }
+        let record = NotableStep {
+            step: step.step,
+            price_before,
+            price_after: ctx.fair_price,
+            trades: step.trades.to_vec(),
+        };
+        let edge = record.edge();
+        self.total += edge;
+        if self.worst.as_ref().is_none_or(|w| edge < w.edge()) {
+            self.worst = Some(record.clone());
+        }
+        self.top.push(record);
+        if self.top.len() > self.top_k {
+            let smallest = self
+                .top
+                .iter()
+                .enumerate()
+                .min_by(|(_, a), (_, b)| a.edge().abs().total_cmp(&b.edge().abs()))
+                .map(|(i, _)| i);
+            if let Some(i) = smallest {
+                self.top.remove(i);
+            }
+        }
+    }
+
+    fn finalize(&self) -> f64 {
+        self.total
+    }
+}
@@ -0,0 +1,394 @@
+//! Built-in reference strategies a submission can be matched against.
+//!
+//! Each opponent takes the normalizer's seat in the simulation: same liquidity multiple,
+//! same arbitrageurs, same router. Only the real normalizer keeps the `"normalizer"` pool
+//! name, since the arbitrageur solves that curve in closed form; every other opponent is
+//! searched numerically like a submission.
+
+use prop_amm_executor::{AfterSwapFn, SwapFn};
+use prop_amm_shared::config::SimulationConfig;
+use prop_amm_shared::normalizer;
+use prop_amm_shared::strategies::{dynamic_fee, starter};
+
+use crate::amm::BpfAmm;
+use crate::runner;
+
+const BASE_FEE_BPS: u128 = 30;
+/// Largest share of the output reserve the linear curves give up in one trade.
+const MAX_LINEAR_FILL_DIVISOR: u128 = 10;
+
+/// A reference strategy as a pair of native callbacks.
+#[derive(Debug, Clone, Copy)]
+pub struct Opponent {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub swap: SwapFn,
+    pub after_swap: Option<AfterSwapFn>,
+}
+
+impl Opponent {
+    /// The opponent's pool for `config`, sized and seeded like the normalizer.
+    pub fn pool(&self, config: &SimulationConfig) -> BpfAmm {
+        let mut amm = BpfAmm::new_native(
+            self.swap,
+            self.after_swap,
+            config.initial_x * config.norm_liquidity_mult,
+            config.initial_y * config.norm_liquidity_mult,
+            self.name.to_string(),
+        );
+        amm.expect_pure_quotes();
+        if self.name == "normalizer" {
+            amm.set_initial_storage(&config.normalizer_fee_word().to_le_bytes());
+        }
+        amm
+    }
+}
+
+/// Every built-in opponent, the challenge's normalizer first.
+pub const OPPONENTS: &[Opponent] = &[
+    Opponent {
+        name: "normalizer",
+        description: "constant product, fee sampled per seed",
+        swap: normalizer::compute_swap,
+        after_swap: Some(normalizer::after_swap),
+    },
+    Opponent {
+        name: "starter",
+        description: "constant product, 500 bps fee",
+        swap: starter::compute_swap,
+        after_swap: Some(starter::after_swap),
+    },
+    Opponent {
+        name: "constant-sum",
+        description: "fills at spot less 30 bps, up to 10% of reserves",
+        swap: constant_sum_swap,
+        after_swap: Some(no_op_after_swap),
+    },
+    Opponent {
+        name: "stableswap",
+        description: "average of constant-sum and constant product, 30 bps fee",
+        swap: stableswap_swap,
+        after_swap: Some(no_op_after_swap),
+    },
+    Opponent {
+        name: "self-lagged",
+        description: "constant product, fee widened by its own last price move",
+        swap: self_lagged_swap,
+        after_swap: Some(self_lagged_after_swap),
+    },
+    Opponent {
+        name: "dynamic-fee",
+        description: "constant product, fee widened by an EMA of squared returns",
+        swap: dynamic_fee::compute_swap,
+        after_swap: Some(dynamic_fee::after_swap),
+    },
+];
+
+/// Look up a built-in opponent by name.
+pub fn find(name: &str) -> Option<&'static Opponent> {
+    OPPONENTS.iter().find(|opponent| opponent.name == name)
+}
+
+fn decode(data: &[u8]) -> Option<(u8, u128, u128, u128)> {
+    if data.len() < 25 {
+        return None;
+    }
+    let side = data[0];
+    let input = u64::from_le_bytes(data[1..9].try_into().ok()?) as u128;
+    let reserve_x = u64::from_le_bytes(data[9..17].try_into().ok()?) as u128;
+    let reserve_y = u64::from_le_bytes(data[17..25].try_into().ok()?) as u128;
+    if reserve_x == 0 || reserve_y == 0 || side > 1 {
+        return None;
+    }
+    Some((side, input, reserve_x, reserve_y))
+}
+
+fn constant_product_out(side: u8, input: u128, rx: u128, ry: u128, fee_bps: u128) -> u128 {
+    let net = input.saturating_mul(10_000 - fee_bps.min(10_000)) / 10_000;
+    let k = rx.saturating_mul(ry);
+    if side == 0 {
+        rx.saturating_sub(k.div_ceil(ry + net))
+    } else {
+        ry.saturating_sub(k.div_ceil(rx + net))
+    }
+}
+
+fn constant_sum_out(side: u8, input: u128, rx: u128, ry: u128, fee_bps: u128) -> u128 {
+    let net = input.saturating_mul(10_000 - fee_bps.min(10_000)) / 10_000;
+    if side == 0 {
+        (net.saturating_mul(rx) / ry).min(rx / MAX_LINEAR_FILL_DIVISOR)
+    } else {
+        (net.saturating_mul(ry) / rx).min(ry / MAX_LINEAR_FILL_DIVISOR)
+    }
+}
+
+fn constant_sum_swap(data: &[u8]) -> u64 {
+    decode(data).map_or(0, |(side, input, rx, ry)| {
+        constant_sum_out(side, input, rx, ry, BASE_FEE_BPS) as u64
+    })
+}
+
+fn stableswap_swap(data: &[u8]) -> u64 {
+    decode(data).map_or(0, |(side, input, rx, ry)| {
+        let sum = constant_sum_out(side, input, rx, ry, BASE_FEE_BPS);
+        let product = constant_product_out(side, input, rx, ry, BASE_FEE_BPS);
+        ((sum + product) / 2) as u64
+    })
+}
+
+/// Quote at 30 bps plus the size of the pool's own last price move (capped at 500 bps
+/// total). The pre-trade spot of the last trade is kept in `storage[0..8]`.
+fn self_lagged_swap(data: &[u8]) -> u64 {
+    decode(data).map_or(0, |(side, input, rx, ry)| {
+        let lagged = data
+            .get(25..33)
+            .map_or(0, |b| u64::from_le_bytes(b.try_into().unwrap()) as u128);
+        let spot = ry.saturating_mul(1_000_000_000) / rx;
+        let move_bps = spot
+            .abs_diff(lagged)
+            .saturating_mul(10_000)
+            .checked_div(lagged)
+            .unwrap_or(0);
+        let fee_bps = (BASE_FEE_BPS + move_bps).min(500);
+        constant_product_out(side, input, rx, ry, fee_bps) as u64
+    })
+}
+
+fn self_lagged_after_swap(data: &[u8], storage: &mut [u8]) {
+    if data.len() < 34 || storage.len() < 8 {
+        return;
+    }
+    let read = |at: usize| u64::from_le_bytes(data[at..at + 8].try_into().unwrap()) as u128;
+    let (input, output, rx, ry) = (read(2), read(10), read(18), read(26));
+    // Undo the trade to recover the reserves it was quoted against.
+    let (pre_x, pre_y) = if data[1] == 0 {
+        (rx + output, ry.saturating_sub(input))
+    } else {
+        (rx.saturating_sub(input), ry + output)
+    };
+    if pre_x == 0 {
+        return;
+    }
+    let pre_spot = (pre_y.saturating_mul(1_000_000_000) / pre_x).min(u64::MAX as u128) as u64;
+    storage[0..8].copy_from_slice(&pre_spot.to_le_bytes());
+}
+
+fn no_op_after_swap(_data: &[u8], _storage: &mut [u8]) {}
+
+/// Average result of one opponent in a tournament.
+#[derive(Debug, Clone)]
+pub struct TournamentRow {
+    pub opponent: &'static str,
+    pub avg_edge: f64,
+    /// Worst single-seed edge against this opponent.
+    pub min_edge: f64,
+}
+
+/// Rows sorted from the opponent the submission beats by the most to the least.
+#[derive(Debug, Clone)]
+pub struct Tournament {
+    pub rows: Vec<TournamentRow>,
+}
+
+impl Tournament {
+    /// Worst average edge across opponents: how the strategy fares against its hardest match.
+    pub fn robustness(&self) -> f64 {
+        self.rows
+            .iter()
+            .map(|row| row.avg_edge)
+            .fold(f64::INFINITY, f64::min)
+    }
+}
+
+/// Run the submission against every opponent in `opponents` over the same `configs`.
+pub fn run_tournament(
+    submission_fn: SwapFn,
+    submission_after_swap: Option<AfterSwapFn>,
+    opponents: &[Opponent],
+    configs: &[SimulationConfig],
+    n_workers: Option<usize>,
+) -> anyhow::Result<Tournament> {
+    let mut rows = Vec::with_capacity(opponents.len());
+    for opponent in opponents {
+        let batch = runner::run_batch_native_against(
+            submission_fn,
+            submission_after_swap,
+            opponent,
+            configs.to_vec(),
+            n_workers,
+        )?;
+        let Some(avg_edge) = batch.avg_edge() else {
+            anyhow::bail!("a tournament needs at least one simulation per opponent");
+        };
+        rows.push(TournamentRow {
+            opponent: opponent.name,
+            avg_edge,
+            min_edge: batch
+                .results
+                .iter()
+                .map(|r| r.submission_edge)
+                .fold(f64::INFINITY, f64::min),
+        });
+    }
+    rows.sort_by(|a, b| b.avg_edge.total_cmp(&a.avg_edge));
+    Ok(Tournament { rows })
+}
+
+/// Head-to-head results of a round robin: every entrant played every entrant, itself
+/// included, and the normalizer.
+#[derive(Debug, Clone)]
+pub struct RoundRobin {
+    /// Entrants in the order they were given.
+    pub names: Vec<&'static str>,
+    /// `edges[i][j]`: entrant `i`'s average edge with entrant `j`'s pool as its competitor
+    /// (self-play on the diagonal); `edges[i][names.len()]` is against the normalizer.
+    pub edges: Vec<Vec<f64>>,
+}
+
+impl RoundRobin {
+    /// Entrant `i`'s average edge against every other entrant and the normalizer.
+    pub fn score(&self, i: usize) -> f64 {
+        let others: Vec<f64> = self.edges[i]
+            .iter()
+            .enumerate()
+            .filter(|&(j, _)| j != i)
+            .map(|(_, &edge)| edge)
+            .collect();
+        others.iter().sum::<f64>() / others.len() as f64
+    }
+
+    /// Entrant indices from the highest score to the lowest.
+    pub fn ranking(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.names.len()).collect();
+        order.sort_by(|&a, &b| self.score(b).total_cmp(&self.score(a)));
+        order
+    }
+}
+
+/// Play every entrant against every entrant and the normalizer, in the competitive
+/// two-venue setup, over the same `configs`.
+pub fn run_round_robin(
+    entrants: &[Opponent],
+    configs: &[SimulationConfig],
+    n_workers: Option<usize>,
+) -> anyhow::Result<RoundRobin> {
+    anyhow::ensure!(
+        !entrants.is_empty(),
+        "a round robin needs at least one entrant"
+    );
+    anyhow::ensure!(
+        !configs.is_empty(),
+        "a round robin needs at least one simulation per pairing"
+    );
+    let normalizer = find("normalizer").expect("the normalizer is always registered");
+    let field: Vec<&Opponent> = entrants.iter().chain([normalizer]).collect();
+    let edges = entrants
+        .iter()
+        .map(|entrant| {
+            field
+                .iter()
+                .map(|opponent| {
+                    let batch = runner::run_batch_native_against(
+                        entrant.swap,
+                        entrant.after_swap,
+                        opponent,
+                        configs.to_vec(),
+                        n_workers,
+                    )?;
+                    Ok(batch.avg_edge().unwrap_or_default())
+                })
+                .collect::<anyhow::Result<Vec<f64>>>()
+        })
+        .collect::<anyhow::Result<_>>()?;
+    Ok(RoundRobin {
+        names: entrants.iter().map(|entrant| entrant.name).collect(),
+        edges,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use prop_amm_shared::instruction::encode_instruction;
+    use prop_amm_shared::normalizer;
+
+    use super::{find, run_round_robin, run_tournament, OPPONENTS};
+    use crate::runner::seeded_configs;
+
+    #[test]
+    fn opponent_curves_are_monotonic() {
+        let (rx, ry) = (100_000_000_000u64, 10_000_000_000_000u64);
+        for opponent in OPPONENTS {
+            for side in 0..2u8 {
+                let mut last = 0;
+                for step in 1..=50u64 {
+                    let input = step * 2_000_000_000;
+                    let out = (opponent.swap)(&encode_instruction(side, input, rx, ry));
+                    assert!(out >= last, "{} side {side} not monotonic", opponent.name);
+                    last = out;
+                }
+                assert!(last > 0, "{} side {side} never fills", opponent.name);
+            }
+        }
+    }
+
+    #[test]
+    fn tournament_runs_every_registered_opponent() {
+        let configs = seeded_configs(2, 200, 0, 1);
+        let tournament = run_tournament(
+            normalizer::compute_swap,
+            Some(normalizer::after_swap),
+            OPPONENTS,
+            &configs,
+            Some(1),
+        )
+        .unwrap();
+
+        assert_eq!(tournament.rows.len(), OPPONENTS.len());
+        for opponent in OPPONENTS {
+            assert!(tournament
+                .rows
+                .iter()
+                .any(|row| row.opponent == opponent.name));
+        }
+        assert!(tournament
+            .rows
+            .windows(2)
+            .all(|pair| pair[0].avg_edge >= pair[1].avg_edge));
+        assert_eq!(
+            tournament.robustness(),
+            tournament.rows.last().unwrap().avg_edge
+        );
+        assert!(find("stableswap").is_some());
+    }
+
+    #[test]
+    fn round_robin_fills_the_matrix_and_ranks_by_score() {
+        let entrants: Vec<_> = ["normalizer", "starter", "dynamic-fee"]
+            .iter()
+            .map(|name| *find(name).unwrap())
+            .collect();
+        let configs = seeded_configs(2, 300, 0, 1);
+        let round_robin = run_round_robin(&entrants, &configs, Some(2)).unwrap();
+
+        assert_eq!(round_robin.names, ["normalizer", "starter", "dynamic-fee"]);
+        assert_eq!(round_robin.edges.len(), 3);
+        assert!(round_robin
+            .edges
+            .iter()
+            .all(|row| row.len() == 4 && row.iter().all(|edge| edge.is_finite())));
+        // Self-play and playing the registered normalizer are the same pairing.
+        assert_eq!(round_robin.edges[0][0], round_robin.edges[0][3]);
+
+        let ranking = round_robin.ranking();
+        let mut seen = ranking.clone();
+        seen.sort();
+        assert_eq!(seen, [0, 1, 2]);
+        assert!(ranking
+            .windows(2)
+            .all(|pair| round_robin.score(pair[0]) >= round_robin.score(pair[1])));
+        let starter_score = round_robin.score(1);
+        let expected =
+            (round_robin.edges[1][0] + round_robin.edges[1][2] + round_robin.edges[1][3]) / 3.0;
+        assert_eq!(starter_score, expected);
+    }
+}
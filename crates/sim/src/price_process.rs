@@ -1,33 +1,260 @@
-use rand::SeedableRng;
 use rand_distr::{Distribution, StandardNormal};
 use rand_pcg::Pcg64;
+use serde::{Deserialize, Serialize};
 
-pub struct GBMPriceProcess {
+use crate::rng::{Draws, EngineRng};
+
+pub struct GBMPriceProcess<R = Pcg64> {
     current_price: f64,
     drift_term: f64,
     vol_term: f64,
-    rng: Pcg64,
+    /// `(rate * dt, ln(anchor))` when the log price reverts toward `anchor`.
+    reversion: Option<(f64, f64)>,
+    rng: R,
+    secondary: Option<SecondaryPath<R>>,
+    tick_size: Option<f64>,
+}
+
+/// Second reference asset driven by `rho * z1 + sqrt(1 - rho^2) * z2` (2x2 Cholesky factor).
+/// It draws from its own RNG so enabling it never perturbs the primary path.
+struct SecondaryPath<R> {
+    current_price: f64,
+    rho: f64,
+    rho_complement: f64,
+    rng: R,
+}
+
+/// Mutable state of a price process (prices and RNG positions), for checkpointing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct PriceState<R = Pcg64> {
+    current_price: f64,
+    rng: R,
+    secondary: Option<(f64, R)>,
 }
 
-impl GBMPriceProcess {
-    pub fn new(initial_price: f64, mu: f64, sigma: f64, dt: f64, seed: u64) -> Self {
+impl<R: EngineRng> GBMPriceProcess<R> {
+    pub fn new(initial_price: f64, mu: f64, sigma: f64, dt: f64, rng: R) -> Self {
         Self {
             current_price: initial_price,
             drift_term: (mu - 0.5 * sigma * sigma) * dt,
             vol_term: sigma * dt.sqrt(),
-            rng: Pcg64::seed_from_u64(seed),
+            reversion: None,
+            rng,
+            secondary: None,
+            tick_size: None,
         }
     }
 
+    /// Report prices rounded to the nearest multiple of `tick_size`. The path itself stays
+    /// continuous, so a price can drift within a tick before the reported value moves.
+    pub fn with_tick_size(mut self, tick_size: Option<f64>) -> Self {
+        self.tick_size = tick_size.filter(|tick| tick.is_finite() && *tick > 0.0);
+        self
+    }
+
+    /// Pull the primary log price toward `ln(anchor)` at `rate` per unit time (an
+    /// Ornstein-Uhlenbeck drift on top of the GBM one, sampled at the start of each advance).
+    /// A zero rate leaves the process plain GBM.
+    pub fn with_mean_reversion(mut self, rate: f64, anchor: f64, dt: f64) -> Self {
+        self.reversion = (rate != 0.0 && anchor > 0.0).then(|| (rate * dt, anchor.ln()));
+        self
+    }
+
+    /// Also emit a second price path with the same drift/volatility whose log-returns have
+    /// correlation `rho` (clamped to [-1, 1]) with the primary path.
+    pub fn with_correlated_pair(mut self, initial_price: f64, rho: f64, rng: R) -> Self {
+        let rho = if rho.is_finite() {
+            rho.clamp(-1.0, 1.0)
+        } else {
+            0.0
+        };
+        self.secondary = Some(SecondaryPath {
+            current_price: initial_price,
+            rho,
+            rho_complement: (1.0 - rho * rho).max(0.0).sqrt(),
+            rng,
+        });
+        self
+    }
+
     #[inline]
     pub fn current_price(&self) -> f64 {
-        self.current_price
+        match self.tick_size {
+            Some(tick) => round_to_tick(self.current_price, tick),
+            None => self.current_price,
+        }
+    }
+
+    #[inline]
+    pub fn secondary_price(&self) -> Option<f64> {
+        self.secondary.as_ref().map(|s| s.current_price)
     }
 
     #[inline]
     pub fn step(&mut self) -> f64 {
-        let z: f64 = StandardNormal.sample(&mut self.rng);
-        self.current_price *= (self.drift_term + self.vol_term * z).exp();
-        self.current_price
+        self.advance(1.0)
+    }
+
+    /// Advance by `fraction` of a step (`fraction * dt` of simulated time). `advance(1.0)`
+    /// is exactly `step`.
+    #[inline]
+    pub fn advance(&mut self, fraction: f64) -> f64 {
+        let drift = self.drift_term * fraction;
+        let vol = self.vol_term * fraction.sqrt();
+        let z: f64 = StandardNormal.sample(&mut Draws(&mut self.rng));
+        let pull = self.reversion.map_or(0.0, |(rate, ln_anchor)| {
+            -rate * fraction * (self.current_price.ln() - ln_anchor)
+        });
+        self.current_price *= (drift + pull + vol * z).exp();
+        if let Some(secondary) = self.secondary.as_mut() {
+            let w: f64 = StandardNormal.sample(&mut Draws(&mut secondary.rng));
+            let z2 = secondary.rho * z + secondary.rho_complement * w;
+            secondary.current_price *= (drift + vol * z2).exp();
+        }
+        self.current_price()
+    }
+
+    pub(crate) fn state(&self) -> PriceState<R> {
+        PriceState {
+            current_price: self.current_price,
+            rng: self.rng.clone(),
+            secondary: self
+                .secondary
+                .as_ref()
+                .map(|s| (s.current_price, s.rng.clone())),
+        }
+    }
+
+    /// Restore a checkpoint taken from a process built with the same parameters.
+    pub(crate) fn restore_state(&mut self, state: &PriceState<R>) {
+        self.current_price = state.current_price;
+        self.rng = state.rng.clone();
+        if let (Some(secondary), Some((price, rng))) =
+            (self.secondary.as_mut(), state.secondary.as_ref())
+        {
+            secondary.current_price = *price;
+            secondary.rng = rng.clone();
+        }
+    }
+
+    /// Advance both paths; the secondary price is `NAN` unless a correlated pair was requested.
+    #[inline]
+    pub fn step_pair(&mut self) -> (f64, f64) {
+        let primary = self.step();
+        (primary, self.secondary_price().unwrap_or(f64::NAN))
+    }
+}
+
+/// `price` rounded to the nearest multiple of `tick`.
+#[inline]
+pub fn round_to_tick(price: f64, tick: f64) -> f64 {
+    (price / tick).round() * tick
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64;
+
+    use super::{round_to_tick, GBMPriceProcess};
+
+    fn seeded(seed: u64) -> Pcg64 {
+        Pcg64::seed_from_u64(seed)
+    }
+
+    fn empirical_return_correlation(rho: f64, n: usize) -> f64 {
+        let mut process = GBMPriceProcess::new(100.0, 0.0, 0.01, 1.0, seeded(11))
+            .with_correlated_pair(50.0, rho, seeded(12));
+        let (mut prev_a, mut prev_b) = (100.0_f64, 50.0_f64);
+        let (mut sum_a, mut sum_b, mut sum_aa, mut sum_bb, mut sum_ab) = (0.0, 0.0, 0.0, 0.0, 0.0);
+        for _ in 0..n {
+            let (a, b) = process.step_pair();
+            let (ra, rb) = ((a / prev_a).ln(), (b / prev_b).ln());
+            prev_a = a;
+            prev_b = b;
+            sum_a += ra;
+            sum_b += rb;
+            sum_aa += ra * ra;
+            sum_bb += rb * rb;
+            sum_ab += ra * rb;
+        }
+        let n = n as f64;
+        let cov = sum_ab / n - (sum_a / n) * (sum_b / n);
+        let var_a = sum_aa / n - (sum_a / n).powi(2);
+        let var_b = sum_bb / n - (sum_b / n).powi(2);
+        cov / (var_a * var_b).sqrt()
+    }
+
+    #[test]
+    fn correlated_pair_matches_requested_rho() {
+        for rho in [-0.8, 0.0, 0.5, 0.95] {
+            let measured = empirical_return_correlation(rho, 200_000);
+            assert!(
+                (measured - rho).abs() < 0.01,
+                "rho={rho}: empirical correlation {measured}"
+            );
+        }
+    }
+
+    #[test]
+    fn split_advances_keep_per_step_variance() {
+        let sigma = 0.01;
+        let n = 100_000;
+        let mut process = GBMPriceProcess::new(100.0, 0.0, sigma, 1.0, seeded(9));
+        let mut prev = 100.0_f64;
+        let mut sum_sq = 0.0;
+        for _ in 0..n {
+            process.advance(0.3);
+            let price = process.advance(0.7);
+            sum_sq += (price / prev).ln().powi(2);
+            prev = price;
+        }
+        let measured = (sum_sq / n as f64).sqrt();
+        assert!(
+            (measured - sigma).abs() < 0.02 * sigma,
+            "per-step vol {measured}"
+        );
+    }
+
+    #[test]
+    fn tick_size_rounds_reported_price_without_changing_the_path() {
+        let mut continuous = GBMPriceProcess::new(100.0, 0.0, 0.003, 1.0, seeded(4));
+        let mut ticked =
+            GBMPriceProcess::new(100.0, 0.0, 0.003, 1.0, seeded(4)).with_tick_size(Some(0.25));
+        for _ in 0..1_000 {
+            let (exact, rounded) = (continuous.step(), ticked.step());
+            assert_eq!(rounded, round_to_tick(exact, 0.25));
+            assert!((rounded - exact).abs() <= 0.125);
+        }
+    }
+
+    #[test]
+    fn secondary_path_does_not_perturb_primary() {
+        let mut single = GBMPriceProcess::new(100.0, 0.0, 0.003, 1.0, seeded(5));
+        let mut paired = GBMPriceProcess::new(100.0, 0.0, 0.003, 1.0, seeded(5))
+            .with_correlated_pair(100.0, 0.7, seeded(6));
+        assert_eq!(single.secondary_price(), None);
+        for _ in 0..1_000 {
+            assert_eq!(single.step(), paired.step_pair().0);
+        }
+    }
+
+    #[test]
+    fn mean_reversion_holds_the_log_price_near_its_anchor() {
+        let (sigma, rate) = (0.01, 0.05);
+        let mut process = GBMPriceProcess::new(100.0, 0.0, sigma, 1.0, seeded(8))
+            .with_mean_reversion(rate, 100.0, 1.0);
+        let n = 100_000;
+        let mut sum_sq = 0.0;
+        for _ in 0..n {
+            sum_sq += (process.step() / 100.0).ln().powi(2);
+        }
+        // Stationary spread of the discretized OU process: sigma / sqrt(rate * (2 - rate)).
+        let measured = (sum_sq / n as f64).sqrt();
+        let expected = sigma / (rate * (2.0 - rate)).sqrt();
+        assert!(
+            (measured - expected).abs() < 0.1 * expected,
+            "measured {measured}, expected {expected}"
+        );
     }
 }
@@ -1,49 +1,68 @@
-use rand::SeedableRng;
 use rand_distr::{Distribution, LogNormal, Poisson};
 use rand_pcg::Pcg64;
 
+use crate::rng::{Draws, EngineRng};
+
 pub struct RetailOrder {
     pub is_buy: bool,
     pub size: f64,
 }
 
-pub struct RetailTrader {
+pub struct RetailTrader<R = Pcg64> {
+    base_buy_prob: f64,
+    /// `base_buy_prob` under the current direction bias.
     buy_prob: f64,
-    rng: Pcg64,
+    rng: R,
     poisson: Poisson<f64>,
     lognormal: LogNormal<f64>,
 }
 
-impl RetailTrader {
-    pub fn new(
-        arrival_rate: f64,
-        mean_size: f64,
-        size_sigma: f64,
-        buy_prob: f64,
-        seed: u64,
-    ) -> Self {
+impl<R: EngineRng> RetailTrader<R> {
+    pub fn new(arrival_rate: f64, mean_size: f64, size_sigma: f64, buy_prob: f64, rng: R) -> Self {
         let sigma = size_sigma.max(0.01);
         let mu_ln = mean_size.max(0.01).ln() - 0.5 * sigma * sigma;
         Self {
+            base_buy_prob: buy_prob,
             buy_prob,
-            rng: Pcg64::seed_from_u64(seed),
+            rng,
             poisson: Poisson::new(arrival_rate.max(0.01)).unwrap(),
             lognormal: LogNormal::new(mu_ln, sigma).unwrap(),
         }
     }
 
+    pub(crate) fn rng(&self) -> &R {
+        &self.rng
+    }
+
+    pub(crate) fn set_rng(&mut self, rng: R) {
+        self.rng = rng;
+    }
+
+    /// Lean order direction by `bias` in [-1, 1] (clamped): the buy probability moves that
+    /// fraction of the way to 1 (buy) or 0 (sell). A bias of 0 restores the base probability.
+    pub fn set_direction_bias(&mut self, bias: f64) {
+        let bias = bias.clamp(-1.0, 1.0);
+        self.buy_prob = if bias >= 0.0 {
+            self.base_buy_prob + bias * (1.0 - self.base_buy_prob)
+        } else {
+            self.base_buy_prob * (1.0 + bias)
+        };
+    }
+
     #[inline]
     pub fn generate_orders(&mut self) -> Vec<RetailOrder> {
-        let n = self.poisson.sample(&mut self.rng) as usize;
+        let n = self.poisson.sample(&mut Draws(&mut self.rng)) as usize;
         if n == 0 {
             return Vec::new();
         }
-        (0..n)
-            .map(|_| {
-                let size = self.lognormal.sample(&mut self.rng);
-                let is_buy = rand::Rng::gen::<f64>(&mut self.rng) < self.buy_prob;
-                RetailOrder { is_buy, size }
-            })
-            .collect()
+        (0..n).map(|_| self.sample_order()).collect()
+    }
+
+    /// A single order, without the per-step Poisson count.
+    #[inline]
+    pub fn sample_order(&mut self) -> RetailOrder {
+        let size = self.lognormal.sample(&mut Draws(&mut self.rng));
+        let is_buy = self.rng.next_f64() < self.buy_prob;
+        RetailOrder { is_buy, size }
     }
 }
@@ -0,0 +1,84 @@
+use rand::{RngCore, SeedableRng};
+use rand_pcg::Pcg64;
+
+/// Source of every random stream in a simulation (price path, retail flow, arbitrageurs,
+/// arrivals). The engine seeds one generator per stream with `seed_from_u64`, using the
+/// same seeds whatever the implementation, so swapping generators never reshuffles which
+/// stream a seed feeds. `Pcg64` is the default.
+pub trait EngineRng: Clone {
+    fn seed_from_u64(seed: u64) -> Self;
+
+    fn next_u64(&mut self) -> u64;
+
+    /// Uniform in `[0, 1)`, from the top 53 bits of `next_u64` (as `rand` does).
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+
+    /// An independent generator for a substream, advancing this one.
+    fn split(&mut self) -> Self {
+        let seed = self.next_u64();
+        Self::seed_from_u64(seed)
+    }
+}
+
+impl EngineRng for Pcg64 {
+    fn seed_from_u64(seed: u64) -> Self {
+        <Pcg64 as SeedableRng>::seed_from_u64(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        RngCore::next_u64(self)
+    }
+}
+
+/// Lets `rand_distr` samplers draw from an `EngineRng`. `next_u32` keeps the low half of
+/// `next_u64`, as `Pcg64` does, so the default generator's streams are unchanged.
+pub(crate) struct Draws<'a, R>(pub(crate) &'a mut R);
+
+impl<R: EngineRng> RngCore for Draws<'_, R> {
+    fn next_u32(&mut self) -> u32 {
+        self.0.next_u64() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.0.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+    use rand_distr::{Distribution, StandardNormal};
+    use rand_pcg::Pcg64;
+
+    use super::{Draws, EngineRng};
+
+    #[test]
+    fn pcg_draws_match_direct_sampling() {
+        let mut direct = <Pcg64 as EngineRng>::seed_from_u64(5);
+        let mut wrapped = direct.clone();
+        for _ in 0..100 {
+            let a: f64 = StandardNormal.sample(&mut direct);
+            let b: f64 = StandardNormal.sample(&mut Draws(&mut wrapped));
+            assert_eq!(a, b);
+            assert_eq!(direct.gen::<f64>(), wrapped.next_f64());
+            assert_eq!(
+                direct.gen_range(0..7usize),
+                Draws(&mut wrapped).gen_range(0..7usize)
+            );
+        }
+    }
+}
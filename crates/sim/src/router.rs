@@ -10,6 +10,19 @@ pub struct RoutedTrade {
     pub amount_y: f64,
 }
 
+/// The fills for one retail order, plus how far the submission's fill fell short of the
+/// output it quoted for its share of the split.
+pub struct RoutedOrder {
+    pub trades: Vec<RoutedTrade>,
+    /// Quoted minus realized submission output, in Y at the fair price. Zero unless
+    /// partial-fill mode cut the leg or the execution paid out less than the quote.
+    pub submission_quote_slippage: f64,
+    /// Input of a submission leg its pool refused outright (inventory cap, locked reserve,
+    /// reserve overflow), in Y at the fair price. Not slippage: the leg was re-routed to
+    /// the normalizer instead.
+    pub submission_refused: f64,
+}
+
 const MIN_TRADE_SIZE: f64 = 0.001;
 const GOLDEN_RATIO_CONJUGATE: f64 = 0.618_033_988_749_894_8;
 const GOLDEN_MAX_ITERS: usize = 14;
@@ -18,8 +31,15 @@ const GOLDEN_ALPHA_TOL: f64 = 1e-3;
 const GOLDEN_SUBMISSION_AMOUNT_REL_TOL: f64 = 1e-2;
 // Stop once the two evaluated total outputs are within 1% of each other.
 const GOLDEN_SCORE_REL_GAP_TOL: f64 = 1e-2;
-
-pub struct OrderRouter;
+// Input size at which a pool's small-trade price is sampled for the partial-fill limit.
+const IMPACT_PROBE_SIZE: f64 = 10.0 * MIN_TRADE_SIZE;
+const IMPACT_BISECT_ITERS: usize = 30;
+
+pub struct OrderRouter {
+    // Partial-fill mode: cap each leg where its average price is this far below the
+    // pool's small-trade price, dropping the rest of the order.
+    max_impact_bps: Option<f64>,
+}
 
 #[derive(Clone, Copy)]
 struct QuotePoint {
@@ -34,9 +54,58 @@ struct SplitSearchResult {
     sampled: Vec<QuotePoint>,
 }
 
+impl Default for OrderRouter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl OrderRouter {
     pub fn new() -> Self {
-        Self
+        Self {
+            max_impact_bps: None,
+        }
+    }
+
+    /// Fill each pool's share of an order only up to `max_impact_bps` of price impact
+    /// (measured as average price against the pool's small-trade price); the remainder
+    /// of the order goes unfilled.
+    pub fn with_partial_fill(mut self, max_impact_bps: f64) -> Self {
+        self.max_impact_bps =
+            (max_impact_bps.is_finite() && max_impact_bps >= 0.0).then_some(max_impact_bps);
+        self
+    }
+
+    /// Largest input up to `input` whose average price stays within the impact limit.
+    /// `output` is the pool's quote for the full `input`.
+    fn clamp_to_impact<Q>(&self, input: f64, output: f64, mut quote: Q) -> f64
+    where
+        Q: FnMut(f64) -> f64,
+    {
+        let Some(max_impact_bps) = self.max_impact_bps else {
+            return input;
+        };
+        if input <= IMPACT_PROBE_SIZE {
+            return input;
+        }
+        let reference = quote(IMPACT_PROBE_SIZE) / IMPACT_PROBE_SIZE;
+        if !reference.is_finite() || reference <= 0.0 {
+            return input;
+        }
+        let floor = reference * (1.0 - max_impact_bps / 10_000.0);
+        if output / input >= floor {
+            return input;
+        }
+        let (mut lo, mut hi) = (IMPACT_PROBE_SIZE, input);
+        for _ in 0..IMPACT_BISECT_ITERS {
+            let mid = 0.5 * (lo + hi);
+            if quote(mid) / mid >= floor {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        lo
     }
 
     pub fn route_order(
@@ -45,12 +114,12 @@ impl OrderRouter {
         amm_sub: &mut BpfAmm,
         amm_norm: &mut BpfAmm,
         fair_price: f64,
-    ) -> Vec<RoutedTrade> {
+    ) -> RoutedOrder {
         if order.is_buy {
-            self.route_buy(order.size, amm_sub, amm_norm)
+            self.route_buy(order.size, amm_sub, amm_norm, fair_price)
         } else {
             let total_x = order.size / fair_price;
-            self.route_sell(total_x, amm_sub, amm_norm)
+            self.route_sell(total_x, amm_sub, amm_norm, fair_price)
         }
     }
 
@@ -59,7 +128,8 @@ impl OrderRouter {
         total_y: f64,
         amm_sub: &mut BpfAmm,
         amm_norm: &mut BpfAmm,
-    ) -> Vec<RoutedTrade> {
+        fair_price: f64,
+    ) -> RoutedOrder {
         let search = Self::maximize_split(total_y, |alpha| {
             Self::quote_buy_split(total_y, alpha, amm_sub, amm_norm)
         });
@@ -76,21 +146,32 @@ impl OrderRouter {
         let best = search.best;
 
         let mut trades = Vec::new();
-        let y_sub = best.in_sub;
-        let y_norm = best.in_norm;
+        let y_sub = self.clamp_to_impact(best.in_sub, best.out_sub, |y| amm_sub.quote_buy_x(y));
 
+        let mut x_sub_out = 0.0;
+        let mut refused_y = 0.0;
         if y_sub > MIN_TRADE_SIZE && best.out_sub > 0.0 {
             let x_out = amm_sub.execute_buy_x(y_sub);
             if x_out > 0.0 {
+                x_sub_out = x_out;
                 trades.push(RoutedTrade {
                     is_submission: true,
                     amm_buys_x: false,
                     amount_x: x_out,
                     amount_y: y_sub,
                 });
+            } else {
+                refused_y = y_sub;
             }
         }
-        if y_norm > MIN_TRADE_SIZE && best.out_norm > 0.0 {
+        let (in_norm, out_norm) = if refused_y > 0.0 {
+            let in_norm = best.in_norm + refused_y;
+            (in_norm, amm_norm.quote_buy_x(in_norm))
+        } else {
+            (best.in_norm, best.out_norm)
+        };
+        let y_norm = self.clamp_to_impact(in_norm, out_norm, |y| amm_norm.quote_buy_x(y));
+        if y_norm > MIN_TRADE_SIZE && out_norm > 0.0 {
             let x_out = amm_norm.execute_buy_x(y_norm);
             if x_out > 0.0 {
                 trades.push(RoutedTrade {
@@ -101,7 +182,12 @@ impl OrderRouter {
                 });
             }
         }
-        trades
+        RoutedOrder {
+            trades,
+            submission_quote_slippage: Self::shortfall(best.out_sub, x_sub_out, refused_y)
+                * fair_price,
+            submission_refused: refused_y,
+        }
     }
 
     fn route_sell(
@@ -109,7 +195,8 @@ impl OrderRouter {
         total_x: f64,
         amm_sub: &mut BpfAmm,
         amm_norm: &mut BpfAmm,
-    ) -> Vec<RoutedTrade> {
+        fair_price: f64,
+    ) -> RoutedOrder {
         let search = Self::maximize_split(total_x, |alpha| {
             Self::quote_sell_split(total_x, alpha, amm_sub, amm_norm)
         });
@@ -126,21 +213,32 @@ impl OrderRouter {
         let best = search.best;
 
         let mut trades = Vec::new();
-        let x_sub = best.in_sub;
-        let x_norm = best.in_norm;
+        let x_sub = self.clamp_to_impact(best.in_sub, best.out_sub, |x| amm_sub.quote_sell_x(x));
 
+        let mut y_sub_out = 0.0;
+        let mut refused_x = 0.0;
         if x_sub > MIN_TRADE_SIZE && best.out_sub > 0.0 {
             let y_out = amm_sub.execute_sell_x(x_sub);
             if y_out > 0.0 {
+                y_sub_out = y_out;
                 trades.push(RoutedTrade {
                     is_submission: true,
                     amm_buys_x: true,
                     amount_x: x_sub,
                     amount_y: y_out,
                 });
+            } else {
+                refused_x = x_sub;
             }
         }
-        if x_norm > MIN_TRADE_SIZE && best.out_norm > 0.0 {
+        let (in_norm, out_norm) = if refused_x > 0.0 {
+            let in_norm = best.in_norm + refused_x;
+            (in_norm, amm_norm.quote_sell_x(in_norm))
+        } else {
+            (best.in_norm, best.out_norm)
+        };
+        let x_norm = self.clamp_to_impact(in_norm, out_norm, |x| amm_norm.quote_sell_x(x));
+        if x_norm > MIN_TRADE_SIZE && out_norm > 0.0 {
             let y_out = amm_norm.execute_sell_x(x_norm);
             if y_out > 0.0 {
                 trades.push(RoutedTrade {
@@ -151,7 +249,21 @@ impl OrderRouter {
                 });
             }
         }
-        trades
+        RoutedOrder {
+            trades,
+            submission_quote_slippage: Self::shortfall(best.out_sub, y_sub_out, refused_x),
+            submission_refused: refused_x * fair_price,
+        }
+    }
+
+    /// Output the submission quoted for its share but did not pay out. A refused leg
+    /// (`refused` input) is re-routed rather than slipped, so it has none.
+    fn shortfall(quoted: f64, realized: f64, refused: f64) -> f64 {
+        if quoted > 0.0 && refused == 0.0 {
+            (quoted - realized).max(0.0)
+        } else {
+            0.0
+        }
     }
 
     fn quote_buy_split(
@@ -360,12 +472,12 @@ mod tests {
             0 => {
                 let net = input.saturating_mul(fee_numerator) / fee_denominator;
                 let new_ry = ry + net;
-                rx.saturating_sub((k + new_ry - 1) / new_ry) as u64
+                rx.saturating_sub(k.div_ceil(new_ry)) as u64
             }
             1 => {
                 let net = input.saturating_mul(fee_numerator) / fee_denominator;
                 let new_rx = rx + net;
-                ry.saturating_sub((k + new_rx - 1) / new_rx) as u64
+                ry.saturating_sub(k.div_ceil(new_rx)) as u64
             }
             _ => 0,
         }
@@ -492,8 +604,8 @@ mod tests {
             norm_reserves.1,
             "norm".to_string(),
         );
-        let trades = router.route_order(order, &mut amm_sub, &mut amm_norm, fair_price);
-        total_output_from_trades(order, &trades)
+        let routed = router.route_order(order, &mut amm_sub, &mut amm_norm, fair_price);
+        total_output_from_trades(order, &routed.trades)
     }
 
     fn assert_close_to_optimal(
@@ -616,6 +728,94 @@ mod tests {
         }
     }
 
+    #[test]
+    fn partial_fill_caps_whale_order_price_impact() {
+        let whale = RetailOrder {
+            is_buy: true,
+            size: 5_000.0,
+        };
+        let spot_after = |router: OrderRouter| {
+            let mut amm_sub =
+                BpfAmm::new_native(normalizer_swap, None, 100.0, 10_000.0, "sub".to_string());
+            let mut amm_norm =
+                BpfAmm::new_native(normalizer_swap, None, 100.0, 10_000.0, "norm".to_string());
+            let trades = router
+                .route_order(&whale, &mut amm_sub, &mut amm_norm, 100.0)
+                .trades;
+            let filled_y: f64 = trades.iter().map(|t| t.amount_y).sum();
+            (amm_sub.reserve_y / amm_sub.reserve_x, filled_y)
+        };
+
+        let (full_spot, full_y) = spot_after(OrderRouter::new());
+        let (partial_spot, partial_y) = spot_after(OrderRouter::new().with_partial_fill(100.0));
+        assert!((full_y - whale.size).abs() < 1e-6);
+        assert!(
+            partial_y < 0.5 * whale.size,
+            "filled {partial_y} of {}",
+            whale.size
+        );
+        assert!(
+            partial_spot - 100.0 < 0.25 * (full_spot - 100.0),
+            "partial fill moved spot to {partial_spot}, full fill to {full_spot}"
+        );
+        // Within the limit, partial-fill mode fills everything.
+        let small = RetailOrder {
+            is_buy: true,
+            size: 20.0,
+        };
+        let mut a = BpfAmm::new_native(normalizer_swap, None, 100.0, 10_000.0, "a".to_string());
+        let mut b = BpfAmm::new_native(normalizer_swap, None, 100.0, 10_000.0, "b".to_string());
+        let trades = OrderRouter::new()
+            .with_partial_fill(100.0)
+            .route_order(&small, &mut a, &mut b, 100.0)
+            .trades;
+        let filled: f64 = trades.iter().map(|t| t.amount_y).sum();
+        assert!((filled - small.size).abs() < 1e-9);
+    }
+
+    #[test]
+    fn refused_submission_leg_goes_to_the_normalizer_without_slippage() {
+        let route = |order: &RetailOrder| {
+            let mut amm_sub =
+                BpfAmm::new_native(zero_fee_swap, None, 100.0, 10_000.0, "sub".to_string());
+            // Any fill at all breaks the cap on its input side.
+            amm_sub.set_inventory_limit(Some(100.0), Some(10_000.0));
+            let mut amm_norm =
+                BpfAmm::new_native(normalizer_swap, None, 100.0, 10_000.0, "norm".to_string());
+            let routed = OrderRouter::new().route_order(order, &mut amm_sub, &mut amm_norm, 100.0);
+            assert_eq!((amm_sub.reserve_x, amm_sub.reserve_y), (100.0, 10_000.0));
+            routed
+        };
+
+        for order in [
+            RetailOrder {
+                is_buy: true,
+                size: 200.0,
+            },
+            RetailOrder {
+                is_buy: false,
+                size: 200.0,
+            },
+        ] {
+            let routed = route(&order);
+            assert!(routed.trades.iter().all(|t| !t.is_submission));
+            let filled_y: f64 = routed
+                .trades
+                .iter()
+                .map(|t| {
+                    if order.is_buy {
+                        t.amount_y
+                    } else {
+                        t.amount_x * 100.0
+                    }
+                })
+                .sum();
+            assert!((filled_y - order.size).abs() < 1e-9, "filled {filled_y}");
+            assert_eq!(routed.submission_quote_slippage, 0.0);
+            assert!(routed.submission_refused > 0.0);
+        }
+    }
+
     #[test]
     fn router_finds_near_optimal_split_on_endpoint_dominance_regimes() {
         let mut rng = Pcg64::seed_from_u64(99);
@@ -1,53 +1,299 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+
 use rayon::prelude::*;
 
 use prop_amm_executor::{AfterSwapFn, BpfProgram, SwapFn};
 use prop_amm_shared::config::{HyperparameterVariance, SimulationConfig};
-use prop_amm_shared::result::{BatchResult, SimResult};
+use prop_amm_shared::result::{BatchResult, Disqualification, DisqualificationKind, SimResult};
 
 use crate::engine;
+use crate::opponents::Opponent;
 
-fn default_configs(
+/// Default-variance configs with seeds `seed_start + i * seed_stride`.
+pub fn seeded_configs(
     n_sims: u32,
     n_steps: u32,
     seed_start: u64,
     seed_stride: u64,
+) -> Vec<SimulationConfig> {
+    configs_for_seeds(
+        (0..n_sims as u64).map(|i| seed_start.wrapping_add(i.wrapping_mul(seed_stride))),
+        n_steps,
+    )
+}
+
+/// Default-variance configs for exactly `seeds`, in order.
+pub fn configs_for_seeds(
+    seeds: impl IntoIterator<Item = u64>,
+    n_steps: u32,
 ) -> Vec<SimulationConfig> {
     let variance = HyperparameterVariance::default();
-    let mut base = SimulationConfig::default();
-    base.n_steps = n_steps;
+    let base = SimulationConfig {
+        n_steps,
+        ..SimulationConfig::default()
+    };
+
+    seeds
+        .into_iter()
+        .map(|seed| variance.apply(&base, seed))
+        .collect()
+}
+
+/// Increment of the SplitMix64 generator (the 64-bit golden ratio).
+const SPLITMIX64_GAMMA: u64 = 0x9E37_79B9_7F4A_7C15;
+
+/// Finalizer of SplitMix64 (Steele, Lea & Flood 2014).
+fn splitmix64_mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Seed of simulation `sim_index` in a sweep rooted at `root_seed`.
+///
+/// This is the `sim_index`-th output of a SplitMix64 generator seeded with `root_seed`,
+/// computed directly as `mix(root_seed + (sim_index + 1) * GAMMA)`. It depends only on the
+/// root and the index, so a sweep's first N simulations are identical however many more are
+/// appended, and the seeds are scattered across the u64 range so the engine's per-component
+/// streams (`seed`, `seed + 1`, ...) never collide between neighbouring simulations the way
+/// consecutive seeds do.
+pub fn substream_seed(root_seed: u64, sim_index: u64) -> u64 {
+    splitmix64_mix(root_seed.wrapping_add(sim_index.wrapping_add(1).wrapping_mul(SPLITMIX64_GAMMA)))
+}
+
+/// Default-variance configs whose seeds are the SplitMix64 substreams of `root_seed`.
+pub fn substream_configs(n_sims: u32, n_steps: u32, root_seed: u64) -> Vec<SimulationConfig> {
+    let variance = HyperparameterVariance::default();
+    let base = SimulationConfig {
+        n_steps,
+        ..SimulationConfig::default()
+    };
 
     (0..n_sims)
-        .map(|i| {
-            variance.apply(
-                &base,
-                seed_start.wrapping_add((i as u64).wrapping_mul(seed_stride)),
-            )
-        })
+        .map(|i| variance.apply(&base, substream_seed(root_seed, i as u64)))
         .collect()
 }
 
-pub fn run_batch(
-    submission_program: BpfProgram,
-    normalizer_program: BpfProgram,
-    configs: Vec<SimulationConfig>,
+/// A simulation in a batch that failed (returned an error, panicked, e.g. on a curve-shape
+/// violation, or scored a non-finite edge), with the config that reproduces it.
+#[derive(Debug, thiserror::Error)]
+#[error("simulation with seed {} ({} steps) failed: {reason}", config.seed, config.n_steps)]
+pub struct SimulationError {
+    pub config: Box<SimulationConfig>,
+    /// Step of the disqualifying event that failed a fail-fast batch; `None` when the
+    /// whole simulation failed.
+    pub step: Option<u32>,
+    pub kind: DisqualificationKind,
+    pub reason: String,
+}
+
+impl SimulationError {
+    /// The failure as a disqualifying event, of the whole simulation unless `step` is set.
+    pub fn disqualification(&self) -> Disqualification {
+        Disqualification {
+            seed: self.config.seed,
+            step: self.step,
+            kind: self.kind,
+            detail: self.reason.clone(),
+        }
+    }
+}
+
+/// Run one simulation, turning errors, panics and non-finite edges into a `SimulationError`.
+fn simulate_checked<F>(
+    config: &SimulationConfig,
+    simulate: &F,
+) -> Result<SimResult, SimulationError>
+where
+    F: Fn(&SimulationConfig) -> anyhow::Result<SimResult>,
+{
+    let (kind, reason) = match panic::catch_unwind(AssertUnwindSafe(|| simulate(config))) {
+        Ok(Ok(result)) if result.submission_edge.is_finite() => return Ok(result),
+        Ok(Ok(result)) => (
+            DisqualificationKind::NonFinite,
+            format!("non-finite edge {}", result.submission_edge),
+        ),
+        Ok(Err(err)) => (DisqualificationKind::Failed, format!("{:#}", err)),
+        Err(payload) => (
+            DisqualificationKind::Panic,
+            match payload
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            {
+                Some(message) => format!("panicked: {}", message),
+                None => "panicked".to_string(),
+            },
+        ),
+    };
+    Err(SimulationError {
+        config: Box::new(config.clone()),
+        step: None,
+        kind,
+        reason,
+    })
+}
+
+/// Cap on the default worker count; an explicit `--workers` may go past it.
+pub const DEFAULT_MAX_WORKERS: usize = 8;
+
+/// Whether batch workers are pinned to CPUs; see `set_pin_workers`.
+static PIN_WORKERS: AtomicBool = AtomicBool::new(false);
+
+/// Pin worker `i` of every batch started from now on to the `i`-th CPU this process may run
+/// on, so benchmark numbers don't move with the OS scheduler. Process-wide, like the worker
+/// count a command picks once for all its batches; only effective on Linux.
+pub fn set_pin_workers(pin: bool) {
+    PIN_WORKERS.store(pin, Ordering::Relaxed);
+}
+
+pub fn pin_workers() -> bool {
+    PIN_WORKERS.load(Ordering::Relaxed)
+}
+
+/// Threads a batch run with `n_workers` uses: exactly `n`, or with `None` ("0" on the command
+/// line) one per core rayon sees (all cores unless `RAYON_NUM_THREADS` says otherwise), at
+/// most `DEFAULT_MAX_WORKERS`.
+pub fn worker_count(n_workers: Option<usize>) -> usize {
+    n_workers
+        .unwrap_or_else(|| rayon::current_num_threads().min(DEFAULT_MAX_WORKERS))
+        .max(1)
+}
+
+/// CPUs the process's affinity mask allows, in ascending order; `None` off Linux.
+pub fn allowed_cpus() -> Option<Vec<usize>> {
+    #[cfg(target_os = "linux")]
+    {
+        // SAFETY: `set` is a plain bitmask the kernel fills in; pid 0 is this thread.
+        unsafe {
+            let mut set: libc::cpu_set_t = std::mem::zeroed();
+            if libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set) != 0 {
+                return None;
+            }
+            Some(
+                (0..libc::CPU_SETSIZE as usize)
+                    .filter(|&cpu| libc::CPU_ISSET(cpu, &set))
+                    .collect(),
+            )
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    None
+}
+
+/// Restrict the calling thread to `cpu`.
+fn pin_current_thread(cpu: usize) {
+    #[cfg(target_os = "linux")]
+    // SAFETY: as in `allowed_cpus`; a failed call leaves the thread unpinned.
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(cpu, &mut set);
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set);
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = cpu;
+}
+
+pub(crate) fn worker_pool(n_workers: Option<usize>) -> anyhow::Result<rayon::ThreadPool> {
+    let mut builder = rayon::ThreadPoolBuilder::new().num_threads(worker_count(n_workers));
+    if let Some(cpus) = allowed_cpus().filter(|cpus| pin_workers() && !cpus.is_empty()) {
+        builder = builder.start_handler(move |i| pin_current_thread(cpus[i % cpus.len()]));
+    }
+    Ok(builder.build()?)
+}
+
+/// Run `simulate` over `configs` in parallel. Once `cancel` is set, simulations that have not
+/// started yet are skipped; in-flight ones finish and are included in the result. A failing
+/// simulation fails the batch with a `SimulationError` carrying its config.
+fn run_configs<F>(
+    configs: &[SimulationConfig],
     n_workers: Option<usize>,
-) -> anyhow::Result<BatchResult> {
-    let pool = rayon::ThreadPoolBuilder::new()
-        .num_threads(n_workers.unwrap_or_else(|| rayon::current_num_threads().min(8)))
-        .build()?;
+    cancel: Option<&AtomicBool>,
+    simulate: F,
+) -> anyhow::Result<BatchResult>
+where
+    F: Fn(&SimulationConfig) -> anyhow::Result<SimResult> + Sync,
+{
+    let pool = worker_pool(n_workers)?;
+
+    let results: Result<Vec<Option<SimResult>>, SimulationError> = pool.install(|| {
+        configs
+            .par_iter()
+            .map(|config| {
+                if cancel.is_some_and(|flag| flag.load(Ordering::Relaxed)) {
+                    return Ok(None);
+                }
+                simulate_checked(config, &simulate).map(Some)
+            })
+            .collect()
+    });
 
-    let results: Result<Vec<SimResult>, _> = pool.install(|| {
+    Ok(BatchResult::from_results(
+        results?.into_iter().flatten().collect(),
+    ))
+}
+
+/// Like `run_configs`, but the first disqualifying event anywhere fails the batch with a
+/// `SimulationError` naming its seed and step: the simulation stops at that step, no new
+/// ones start, and in-flight ones stop at the end of their current step.
+fn run_configs_fail_fast<F>(
+    configs: &[SimulationConfig],
+    n_workers: Option<usize>,
+    cancel: &AtomicBool,
+    build: F,
+) -> anyhow::Result<BatchResult>
+where
+    F: Fn(&SimulationConfig) -> engine::Simulation + Sync,
+{
+    let pool = worker_pool(n_workers)?;
+    let abort = AtomicBool::new(false);
+
+    let results: Result<Vec<Option<SimResult>>, SimulationError> = pool.install(|| {
         configs
             .par_iter()
             .map(|config| {
-                let sub = submission_program.clone();
-                let norm = normalizer_program.clone();
-                engine::run_simulation(sub, norm, config)
+                if abort.load(Ordering::Relaxed) || cancel.load(Ordering::Relaxed) {
+                    return Ok(None);
+                }
+                let result = simulate_checked(config, &|config| {
+                    build(config).run_until_disqualified(&abort)
+                })
+                .inspect_err(|_| abort.store(true, Ordering::Relaxed))?;
+                if let Some(event) = result.disqualifications.first() {
+                    abort.store(true, Ordering::Relaxed);
+                    return Err(SimulationError {
+                        config: Box::new(config.clone()),
+                        step: event.step,
+                        kind: event.kind,
+                        reason: event.detail.clone(),
+                    });
+                }
+                // Cut short by another simulation's disqualification.
+                if abort.load(Ordering::Relaxed) {
+                    return Ok(None);
+                }
+                Ok(Some(result))
             })
             .collect()
     });
 
-    Ok(BatchResult::from_results(results?))
+    Ok(BatchResult::from_results(
+        results?.into_iter().flatten().collect(),
+    ))
+}
+
+pub fn run_batch(
+    submission_program: BpfProgram,
+    normalizer_program: BpfProgram,
+    configs: Vec<SimulationConfig>,
+    n_workers: Option<usize>,
+) -> anyhow::Result<BatchResult> {
+    run_configs(&configs, n_workers, None, |config| {
+        let sub = submission_program.clone();
+        let norm = normalizer_program.clone();
+        engine::run_simulation(sub, norm, config)
+    })
 }
 
 pub fn run_batch_native(
@@ -58,26 +304,106 @@ pub fn run_batch_native(
     configs: Vec<SimulationConfig>,
     n_workers: Option<usize>,
 ) -> anyhow::Result<BatchResult> {
-    let pool = rayon::ThreadPoolBuilder::new()
-        .num_threads(n_workers.unwrap_or_else(|| rayon::current_num_threads().min(8)))
-        .build()?;
+    run_batch_native_cancellable(
+        submission_fn,
+        submission_after_swap,
+        normalizer_fn,
+        normalizer_after_swap,
+        configs,
+        n_workers,
+        &AtomicBool::new(false),
+    )
+}
 
-    let results: Result<Vec<SimResult>, _> = pool.install(|| {
-        configs
-            .par_iter()
-            .map(|config| {
-                engine::run_simulation_native(
-                    submission_fn,
-                    submission_after_swap,
-                    normalizer_fn,
-                    normalizer_after_swap,
-                    config,
-                )
-            })
-            .collect()
-    });
+/// Like `run_batch_native`, but stops starting new simulations once `cancel` is set and
+/// returns the ones that completed.
+pub fn run_batch_native_cancellable(
+    submission_fn: SwapFn,
+    submission_after_swap: Option<AfterSwapFn>,
+    normalizer_fn: SwapFn,
+    normalizer_after_swap: Option<AfterSwapFn>,
+    configs: Vec<SimulationConfig>,
+    n_workers: Option<usize>,
+    cancel: &AtomicBool,
+) -> anyhow::Result<BatchResult> {
+    run_configs(&configs, n_workers, Some(cancel), |config| {
+        engine::run_simulation_native(
+            submission_fn,
+            submission_after_swap,
+            normalizer_fn,
+            normalizer_after_swap,
+            config,
+        )
+    })
+}
 
-    Ok(BatchResult::from_results(results?))
+/// `run_batch_native_cancellable`, failing the batch on the first disqualifying event
+/// instead of tallying them (see `run_configs_fail_fast`).
+pub fn run_batch_native_fail_fast(
+    submission_fn: SwapFn,
+    submission_after_swap: Option<AfterSwapFn>,
+    normalizer_fn: SwapFn,
+    normalizer_after_swap: Option<AfterSwapFn>,
+    configs: Vec<SimulationConfig>,
+    n_workers: Option<usize>,
+    cancel: &AtomicBool,
+) -> anyhow::Result<BatchResult> {
+    run_configs_fail_fast(&configs, n_workers, cancel, |config| {
+        engine::Simulation::native(
+            submission_fn,
+            submission_after_swap,
+            normalizer_fn,
+            normalizer_after_swap,
+            config,
+        )
+    })
+}
+
+/// Native submission against one of the built-in reference strategies.
+pub fn run_batch_native_against(
+    submission_fn: SwapFn,
+    submission_after_swap: Option<AfterSwapFn>,
+    opponent: &Opponent,
+    configs: Vec<SimulationConfig>,
+    n_workers: Option<usize>,
+) -> anyhow::Result<BatchResult> {
+    run_configs(&configs, n_workers, None, |config| {
+        engine::run_competitive_simulation(submission_fn, submission_after_swap, opponent, config)
+    })
+}
+
+/// BPF submission against the native normalizer, stopping early once `cancel` is set.
+pub fn run_batch_mixed_cancellable(
+    submission_program: BpfProgram,
+    normalizer_fn: SwapFn,
+    normalizer_after_swap: Option<AfterSwapFn>,
+    configs: Vec<SimulationConfig>,
+    n_workers: Option<usize>,
+    cancel: &AtomicBool,
+) -> anyhow::Result<BatchResult> {
+    run_configs(&configs, n_workers, Some(cancel), |config| {
+        let sub = submission_program.clone();
+        engine::run_simulation_mixed(sub, normalizer_fn, normalizer_after_swap, config)
+    })
+}
+
+/// `run_batch_mixed_cancellable`, failing the batch on the first disqualifying event.
+pub fn run_batch_mixed_fail_fast(
+    submission_program: BpfProgram,
+    normalizer_fn: SwapFn,
+    normalizer_after_swap: Option<AfterSwapFn>,
+    configs: Vec<SimulationConfig>,
+    n_workers: Option<usize>,
+    cancel: &AtomicBool,
+) -> anyhow::Result<BatchResult> {
+    run_configs_fail_fast(&configs, n_workers, cancel, |config| {
+        engine::Simulation::mixed(
+            submission_program.clone(),
+            normalizer_fn,
+            normalizer_after_swap,
+            config,
+        )
+    })
 }
 
 pub fn run_default_batch(
@@ -87,7 +413,7 @@ pub fn run_default_batch(
     n_steps: u32,
     n_workers: Option<usize>,
 ) -> anyhow::Result<BatchResult> {
-    let configs = default_configs(n_sims, n_steps, 0, 1);
+    let configs = seeded_configs(n_sims, n_steps, 0, 1);
     run_batch(submission_program, normalizer_program, configs, n_workers)
 }
 
@@ -100,7 +426,7 @@ pub fn run_default_batch_seeded(
     seed_start: u64,
     seed_stride: u64,
 ) -> anyhow::Result<BatchResult> {
-    let configs = default_configs(n_sims, n_steps, seed_start, seed_stride);
+    let configs = seeded_configs(n_sims, n_steps, seed_start, seed_stride);
     run_batch(submission_program, normalizer_program, configs, n_workers)
 }
 
@@ -112,25 +438,14 @@ pub fn run_default_batch_mixed(
     n_steps: u32,
     n_workers: Option<usize>,
 ) -> anyhow::Result<BatchResult> {
-    let configs = default_configs(n_sims, n_steps, 0, 1);
-
-    let pool = rayon::ThreadPoolBuilder::new()
-        .num_threads(n_workers.unwrap_or_else(|| rayon::current_num_threads().min(8)))
-        .build()?;
-
-    let results: Result<Vec<SimResult>, _> = pool.install(|| {
-        configs
-            .par_iter()
-            .map(|config| {
-                let sub = submission_program.clone();
-                engine::run_simulation_mixed(sub, normalizer_fn, normalizer_after_swap, config)
-            })
-            .collect()
-    });
-
-    Ok(BatchResult::from_results(results?))
+    let configs = seeded_configs(n_sims, n_steps, 0, 1);
+    run_configs(&configs, n_workers, None, |config| {
+        let sub = submission_program.clone();
+        engine::run_simulation_mixed(sub, normalizer_fn, normalizer_after_swap, config)
+    })
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn run_default_batch_mixed_seeded(
     submission_program: BpfProgram,
     normalizer_fn: SwapFn,
@@ -141,23 +456,11 @@ pub fn run_default_batch_mixed_seeded(
     seed_start: u64,
     seed_stride: u64,
 ) -> anyhow::Result<BatchResult> {
-    let configs = default_configs(n_sims, n_steps, seed_start, seed_stride);
-
-    let pool = rayon::ThreadPoolBuilder::new()
-        .num_threads(n_workers.unwrap_or_else(|| rayon::current_num_threads().min(8)))
-        .build()?;
-
-    let results: Result<Vec<SimResult>, _> = pool.install(|| {
-        configs
-            .par_iter()
-            .map(|config| {
-                let sub = submission_program.clone();
-                engine::run_simulation_mixed(sub, normalizer_fn, normalizer_after_swap, config)
-            })
-            .collect()
-    });
-
-    Ok(BatchResult::from_results(results?))
+    let configs = seeded_configs(n_sims, n_steps, seed_start, seed_stride);
+    run_configs(&configs, n_workers, None, |config| {
+        let sub = submission_program.clone();
+        engine::run_simulation_mixed(sub, normalizer_fn, normalizer_after_swap, config)
+    })
 }
 
 pub fn run_default_batch_native(
@@ -169,7 +472,7 @@ pub fn run_default_batch_native(
     n_steps: u32,
     n_workers: Option<usize>,
 ) -> anyhow::Result<BatchResult> {
-    let configs = default_configs(n_sims, n_steps, 0, 1);
+    let configs = seeded_configs(n_sims, n_steps, 0, 1);
     run_batch_native(
         submission_fn,
         submission_after_swap,
@@ -180,6 +483,7 @@ pub fn run_default_batch_native(
     )
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn run_default_batch_native_seeded(
     submission_fn: SwapFn,
     submission_after_swap: Option<AfterSwapFn>,
@@ -191,7 +495,7 @@ pub fn run_default_batch_native_seeded(
     seed_start: u64,
     seed_stride: u64,
 ) -> anyhow::Result<BatchResult> {
-    let configs = default_configs(n_sims, n_steps, seed_start, seed_stride);
+    let configs = seeded_configs(n_sims, n_steps, seed_start, seed_stride);
     run_batch_native(
         submission_fn,
         submission_after_swap,
@@ -201,3 +505,204 @@ pub fn run_default_batch_native_seeded(
         n_workers,
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Barrier;
+
+    use prop_amm_shared::result::{BatchResult, DisqualificationKind, SimResult};
+
+    use prop_amm_shared::normalizer::{after_swap, compute_swap};
+
+    use super::{
+        allowed_cpus, run_batch_native, run_configs, run_configs_fail_fast, seeded_configs,
+        substream_configs, substream_seed, worker_count, SimulationError,
+    };
+    use crate::engine::Simulation;
+
+    #[test]
+    fn workers_bound_the_batch_concurrency_exactly() {
+        let peak_concurrency = |workers| {
+            let (active, peak) = (AtomicUsize::new(0), AtomicUsize::new(0));
+            // The first `workers` simulations wait for each other, so they can only all get
+            // past here if that many really run at once.
+            let (started, rendezvous) = (AtomicUsize::new(0), Barrier::new(workers));
+            let configs = seeded_configs(8, 10, 0, 1);
+            run_configs(&configs, Some(workers), None, |config| {
+                let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                if started.fetch_add(1, Ordering::SeqCst) < workers {
+                    rendezvous.wait();
+                }
+                let result = Simulation::native(
+                    compute_swap,
+                    Some(after_swap),
+                    compute_swap,
+                    Some(after_swap),
+                    config,
+                )
+                .run();
+                active.fetch_sub(1, Ordering::SeqCst);
+                result
+            })
+            .unwrap();
+            peak.load(Ordering::SeqCst)
+        };
+        assert_eq!(peak_concurrency(1), 1);
+        assert_eq!(peak_concurrency(4), 4);
+
+        assert_eq!(worker_count(Some(3)), 3);
+        assert_eq!(worker_count(None), rayon::current_num_threads().min(8));
+        if cfg!(target_os = "linux") {
+            assert!(!allowed_cpus().unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn cancelled_batch_returns_completed_simulations() {
+        let configs = seeded_configs(10, 10, 0, 1);
+        let cancel = AtomicBool::new(false);
+        let started = std::sync::atomic::AtomicUsize::new(0);
+        let result = run_configs(&configs, Some(1), Some(&cancel), |config| {
+            if started.fetch_add(1, Ordering::SeqCst) + 1 == 3 {
+                // Interrupt arrives while the third simulation is running.
+                cancel.store(true, Ordering::SeqCst);
+            }
+            Ok(SimResult {
+                seed: config.seed,
+                steps_run: config.n_steps,
+                submission_edge: 1.0,
+                phases: Default::default(),
+                k_growth: 0.0,
+                impermanent_loss: 0.0,
+                k_series: None,
+                waterfall: Default::default(),
+                disqualifications: Vec::new(),
+                total_quote_slippage: 0.0,
+                max_quote_slippage: 0.0,
+                inventory_rejected: 0.0,
+                storage_never_written: false,
+                recording_capped: false,
+                recording_bytes: 0,
+                initial_value: 20_000.0,
+                lp_return: Default::default(),
+                retail_volume: [0.0; 2],
+            })
+        })
+        .unwrap();
+        assert_eq!(result.n_sims(), 3);
+        assert_eq!(result.total_edge, 3.0);
+
+        let none = run_configs(&configs, Some(1), Some(&cancel), |_| unreachable!()).unwrap();
+        assert_eq!(none.n_sims(), 0);
+    }
+
+    #[test]
+    fn failing_simulation_reports_its_config() {
+        let configs = seeded_configs(1, 250, 42, 1);
+        let err = run_configs(&configs, Some(1), None, |_| -> anyhow::Result<SimResult> {
+            panic!("submission shape violation")
+        })
+        .unwrap_err();
+        let failure = err.downcast_ref::<SimulationError>().unwrap();
+        assert_eq!((failure.config.seed, failure.config.n_steps), (42, 250));
+        assert_eq!(failure.reason, "panicked: submission shape violation");
+        assert_eq!(failure.disqualification().kind, DisqualificationKind::Panic);
+    }
+
+    #[test]
+    fn fail_fast_stops_after_the_first_disqualified_simulation() {
+        fn over_quote(_data: &[u8]) -> u64 {
+            u64::MAX
+        }
+
+        let configs = seeded_configs(20, 500, 7, 1);
+        let built = std::sync::atomic::AtomicUsize::new(0);
+        let err = run_configs_fail_fast(&configs, Some(1), &AtomicBool::new(false), |config| {
+            built.fetch_add(1, Ordering::SeqCst);
+            Simulation::native(over_quote, None, compute_swap, Some(after_swap), config)
+        })
+        .unwrap_err();
+        assert_eq!(built.load(Ordering::SeqCst), 1);
+        let failure = err.downcast_ref::<SimulationError>().unwrap();
+        assert_eq!(failure.config.seed, 7);
+        assert_eq!(failure.disqualification().seed, 7);
+
+        // A clean batch runs to completion, exactly like the tallying runner.
+        let clean = run_configs_fail_fast(&configs[..3], Some(1), &AtomicBool::new(false), |c| {
+            Simulation::native(
+                compute_swap,
+                Some(after_swap),
+                compute_swap,
+                Some(after_swap),
+                c,
+            )
+        })
+        .unwrap();
+        let tallied = run_batch_native(
+            compute_swap,
+            Some(after_swap),
+            compute_swap,
+            Some(after_swap),
+            configs[..3].to_vec(),
+            Some(1),
+        )
+        .unwrap();
+        assert_eq!(clean.n_sims(), 3);
+        assert_eq!(clean.total_edge, tallied.total_edge);
+    }
+
+    #[test]
+    fn substream_seeds_follow_splitmix64_reference_sequence() {
+        // Reference outputs of SplitMix64 seeded with 1234567.
+        assert_eq!(substream_seed(1234567, 0), 6457827717110365317);
+        assert_eq!(substream_seed(1234567, 1), 3203168211198807973);
+        assert_eq!(substream_seed(1234567, 2), 9817491932198370423);
+    }
+
+    #[test]
+    fn substream_configs_are_prefix_stable() {
+        let small = substream_configs(10, 100, 42);
+        let large = substream_configs(50, 100, 42);
+        for (a, b) in small.iter().zip(&large) {
+            assert_eq!(a.seed, b.seed);
+            assert_eq!(a.gbm_sigma, b.gbm_sigma);
+        }
+        assert_ne!(substream_configs(1, 100, 43)[0].seed, small[0].seed);
+    }
+
+    #[test]
+    fn merged_disjoint_batches_match_one_combined_run() {
+        let run = |n_sims, seed_start| {
+            let configs = seeded_configs(n_sims, 200, seed_start, 1);
+            run_batch_native(
+                compute_swap,
+                Some(after_swap),
+                compute_swap,
+                Some(after_swap),
+                configs,
+                Some(2),
+            )
+            .unwrap()
+        };
+        let combined = run(8, 0);
+        // Later seeds first, plus an overlapping seed that must not be counted twice.
+        let merged = run(5, 3).merge(run(4, 0));
+
+        let seeds = |batch: &BatchResult| batch.results.iter().map(|r| r.seed).collect::<Vec<_>>();
+        assert_eq!(seeds(&merged), (0..8).collect::<Vec<_>>());
+        assert_eq!(seeds(&merged), seeds(&combined));
+        let edges = |batch: &BatchResult| {
+            batch
+                .results
+                .iter()
+                .map(|r| r.submission_edge)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(edges(&merged), edges(&combined));
+        assert_eq!(merged.n_sims(), combined.n_sims());
+        assert_eq!(merged.total_edge, combined.total_edge);
+        assert_eq!(merged.avg_waterfall(), combined.avg_waterfall());
+    }
+}
@@ -0,0 +1,216 @@
+//! Time to ruin: how many steps a pool lasts in a high-volatility market before its value
+//! falls below a fraction of what its starting reserves would be worth if simply held. Mean
+//! edge says how much a strategy earns on average; this says how fast it can blow up.
+
+use std::panic;
+
+use rayon::prelude::*;
+
+use prop_amm_shared::config::SimulationConfig;
+use prop_amm_shared::normalizer::{after_swap as normalizer_after_swap, compute_swap};
+
+use crate::engine::Simulation;
+use crate::objective::{MarketCtx, Objective, StepRecord};
+use crate::opponents::Opponent;
+use crate::runner;
+
+/// Per-step volatility of the adverse regime, above the top of the sampled range.
+pub const STRESS_SIGMA: f64 = 0.01;
+
+/// `config` moved into the adverse regime: `sigma` volatility per step and no pull back
+/// toward the starting price.
+pub fn adverse(config: &SimulationConfig, sigma: f64) -> SimulationConfig {
+    SimulationConfig {
+        gbm_sigma: sigma,
+        mean_reversion: 0.0,
+        ..config.clone()
+    }
+}
+
+/// Notes the first step at whose end the submission's reserves, at the fair price, are worth
+/// less than `threshold` of its starting reserves held. Marking against holding keeps the
+/// price path alone from ruining a pool. Its headline number is the ruin step, or infinity
+/// while the pool survives.
+#[derive(Debug, Clone)]
+pub struct RuinClock {
+    initial_x: f64,
+    initial_y: f64,
+    threshold: f64,
+    ruined_at: Option<u32>,
+}
+
+impl RuinClock {
+    /// Ruin is a value below `threshold` (e.g. 0.5) of the starting reserves held.
+    pub fn new(initial_x: f64, initial_y: f64, threshold: f64) -> Self {
+        Self {
+            initial_x,
+            initial_y,
+            threshold,
+            ruined_at: None,
+        }
+    }
+
+    /// Step at whose end the pool was first ruined.
+    pub fn ruined_at(&self) -> Option<u32> {
+        self.ruined_at
+    }
+}
+
+impl Objective for RuinClock {
+    fn accumulate(&mut self, step: &StepRecord, ctx: &MarketCtx) {
+        let value = ctx.submission_reserve_x * ctx.fair_price + ctx.submission_reserve_y;
+        let held = self.initial_x * ctx.fair_price + self.initial_y;
+        if self.ruined_at.is_none() && value < self.threshold * held {
+            self.ruined_at = Some(step.step);
+        }
+    }
+
+    fn finalize(&self) -> f64 {
+        self.ruined_at.map_or(f64::INFINITY, |step| step as f64)
+    }
+}
+
+/// Steps `candidate` lasted against the normalizer under `config` before it was ruined (see
+/// `RuinClock`; ruin on the first step counts as 1), or `None` when it survived all
+/// `n_steps`. The simulation stops at ruin.
+pub fn steps_to_ruin(
+    candidate: &Opponent,
+    config: &SimulationConfig,
+    threshold: f64,
+) -> Option<u32> {
+    let mut sim = Simulation::native(
+        candidate.swap,
+        candidate.after_swap,
+        compute_swap,
+        Some(normalizer_after_swap),
+        config,
+    );
+    let pool = sim.submission();
+    let mut clock = RuinClock::new(pool.reserve_x, pool.reserve_y, threshold);
+    while clock.ruined_at.is_none() && sim.step_with(&mut clock) {}
+    clock.ruined_at.map(|step| step + 1)
+}
+
+/// How one candidate fared across seeds.
+#[derive(Debug, Clone)]
+pub struct Survival {
+    pub name: &'static str,
+    /// Steps to ruin per config, `None` for seeds it survived.
+    pub ruin_steps: Vec<Option<u32>>,
+    /// Mean steps to ruin, survivors counted at their full `n_steps`: a lower bound whenever
+    /// any seed survived.
+    pub mean_steps: f64,
+}
+
+impl Survival {
+    pub fn ruined(&self) -> usize {
+        self.ruin_steps.iter().flatten().count()
+    }
+}
+
+/// Race every candidate over the same `configs`, longest-lived first.
+pub fn race(
+    candidates: &[Opponent],
+    configs: &[SimulationConfig],
+    threshold: f64,
+    n_workers: Option<usize>,
+) -> anyhow::Result<Vec<Survival>> {
+    anyhow::ensure!(!configs.is_empty(), "a stress race needs at least one seed");
+    anyhow::ensure!(
+        threshold > 0.0 && threshold < 1.0,
+        "ruin threshold must be between 0 and 1, got {}",
+        threshold
+    );
+    let pool = runner::worker_pool(n_workers)?;
+    let mut rows = candidates
+        .iter()
+        .map(|candidate| {
+            let ruin_steps: Vec<Option<u32>> = pool.install(|| {
+                configs
+                    .par_iter()
+                    .map(|config| {
+                        panic::catch_unwind(|| steps_to_ruin(candidate, config, threshold)).map_err(
+                            |_| {
+                                anyhow::anyhow!(
+                                    "{} panicked on seed {} (see the message above)",
+                                    candidate.name,
+                                    config.seed
+                                )
+                            },
+                        )
+                    })
+                    .collect::<anyhow::Result<_>>()
+            })?;
+            let total: f64 = ruin_steps
+                .iter()
+                .zip(configs)
+                .map(|(ruin, config)| ruin.unwrap_or(config.n_steps) as f64)
+                .sum();
+            Ok(Survival {
+                name: candidate.name,
+                mean_steps: total / configs.len() as f64,
+                ruin_steps,
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    rows.sort_by(|a, b| b.mean_steps.total_cmp(&a.mean_steps));
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{adverse, race, steps_to_ruin, STRESS_SIGMA};
+    use crate::opponents::{self, Opponent};
+    use crate::runner::seeded_configs;
+
+    /// How many times deeper than its reserves the zero-fee curve quotes.
+    const VIRTUAL_DEPTH: u128 = 10;
+
+    /// Zero-fee constant product quoted as if the reserves were `VIRTUAL_DEPTH` times larger:
+    /// every arbitrage overshoots the fair price and the next one trades back against it.
+    fn zero_fee_deep_swap(data: &[u8]) -> u64 {
+        let input = u64::from_le_bytes(data[1..9].try_into().unwrap()) as u128;
+        let rx = u64::from_le_bytes(data[9..17].try_into().unwrap()) as u128;
+        let ry = u64::from_le_bytes(data[17..25].try_into().unwrap()) as u128;
+        let (vx, vy) = (rx * VIRTUAL_DEPTH, ry * VIRTUAL_DEPTH);
+        let out = if data[0] == 0 {
+            (vx - (vx * vy).div_ceil(vy + input)).min(rx.saturating_sub(1))
+        } else {
+            (vy - (vx * vy).div_ceil(vx + input)).min(ry.saturating_sub(1))
+        };
+        out as u64
+    }
+
+    #[test]
+    fn zero_fee_strategy_is_ruined_far_sooner_than_a_fee_charging_one() {
+        let fragile = Opponent {
+            name: "zero-fee-deep",
+            description: "constant product quoted 10x too deep, no fee",
+            swap: zero_fee_deep_swap,
+            after_swap: None,
+        };
+        let starter = *opponents::find("starter").unwrap();
+        let configs: Vec<_> = seeded_configs(6, 3_000, 0, 1)
+            .iter()
+            .map(|config| adverse(config, STRESS_SIGMA))
+            .collect();
+
+        let rows = race(&[fragile, starter], &configs, 0.5, Some(2)).unwrap();
+        assert_eq!(rows[0].name, "starter");
+        assert_eq!(rows[1].name, "zero-fee-deep");
+        assert_eq!(rows[1].ruined(), configs.len());
+        assert_eq!(rows[0].ruined(), 0);
+        assert!(
+            rows[1].mean_steps * 100.0 < rows[0].mean_steps,
+            "{:?} vs {:?}",
+            rows[1],
+            rows[0]
+        );
+
+        // Same adverse path, same answer: the race is deterministic per seed.
+        assert_eq!(
+            steps_to_ruin(&fragile, &configs[0], 0.5),
+            rows[1].ruin_steps[0]
+        );
+    }
+}
@@ -0,0 +1,153 @@
+//! Historical trade tapes: a recorded sequence of trades replayed against the submission pool
+//! in place of synthetic retail flow, with the recorded reference price standing in for the
+//! simulated fair price (see `engine::run_simulation_with_tape`).
+
+use std::path::Path;
+
+use anyhow::Context;
+
+/// One recorded trade. `size` is in X: a buy takes `size` X worth `size * price` Y from the
+/// pool, a sell puts `size` X into it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TapeTrade {
+    pub timestamp: f64,
+    pub is_buy: bool,
+    pub size: f64,
+    /// Reference (fair) price at the time of the trade; arbitrageurs close the gap to it
+    /// before the trade lands.
+    pub price: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TradeTape {
+    trades: Vec<TapeTrade>,
+}
+
+const COLUMNS: [&str; 4] = ["timestamp", "side", "size", "price"];
+
+impl TradeTape {
+    pub fn new(trades: Vec<TapeTrade>) -> anyhow::Result<Self> {
+        for (i, pair) in trades.windows(2).enumerate() {
+            anyhow::ensure!(
+                pair[1].timestamp >= pair[0].timestamp,
+                "tape trade {} at {} is earlier than the one before it at {}",
+                i + 1,
+                pair[1].timestamp,
+                pair[0].timestamp
+            );
+        }
+        Ok(Self { trades })
+    }
+
+    /// Parse a CSV with a `timestamp,side,size,price` header (columns in any order, extra
+    /// columns ignored); `side` is `buy` or `sell`. Timestamps must not go backwards.
+    pub fn from_csv(text: &str) -> anyhow::Result<Self> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty());
+        let (_, header) = lines.next().context("trade tape is empty")?;
+        let names: Vec<&str> = header.split(',').map(str::trim).collect();
+        let mut index = [0; COLUMNS.len()];
+        for (slot, column) in index.iter_mut().zip(COLUMNS) {
+            *slot = names
+                .iter()
+                .position(|name| name.eq_ignore_ascii_case(column))
+                .with_context(|| format!("trade tape header has no `{}` column", column))?;
+        }
+        let [timestamp, side, size, price] = index;
+
+        let mut trades = Vec::new();
+        for (i, line) in lines {
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            let field = |at: usize| {
+                fields
+                    .get(at)
+                    .copied()
+                    .with_context(|| format!("line {}: missing `{}`", i + 1, names[at]))
+            };
+            let number = |at: usize| -> anyhow::Result<f64> {
+                let text = field(at)?;
+                let value: f64 = text
+                    .parse()
+                    .with_context(|| format!("line {}: bad {} `{}`", i + 1, names[at], text))?;
+                anyhow::ensure!(
+                    value.is_finite(),
+                    "line {}: {} is not finite",
+                    i + 1,
+                    names[at]
+                );
+                Ok(value)
+            };
+            let is_buy = match field(side)?.to_ascii_lowercase().as_str() {
+                "buy" | "b" => true,
+                "sell" | "s" => false,
+                other => anyhow::bail!("line {}: side must be buy or sell, got `{}`", i + 1, other),
+            };
+            let trade = TapeTrade {
+                timestamp: number(timestamp)?,
+                is_buy,
+                size: number(size)?,
+                price: number(price)?,
+            };
+            anyhow::ensure!(trade.size >= 0.0, "line {}: negative size", i + 1);
+            anyhow::ensure!(trade.price > 0.0, "line {}: price must be positive", i + 1);
+            trades.push(trade);
+        }
+        Self::new(trades)
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("reading trade tape {}", path.display()))?;
+        Self::from_csv(&text).with_context(|| format!("parsing trade tape {}", path.display()))
+    }
+
+    pub fn trades(&self) -> &[TapeTrade] {
+        &self.trades
+    }
+
+    pub fn len(&self) -> usize {
+        self.trades.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.trades.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TapeTrade, TradeTape};
+
+    #[test]
+    fn csv_columns_are_found_by_name_and_bad_rows_are_named() {
+        let tape = TradeTape::from_csv(
+            "price,size,venue,side,timestamp\n100.5,2,x,buy,1\n\n99,0.5,y,SELL,1.5\n",
+        )
+        .unwrap();
+        assert_eq!(
+            tape.trades(),
+            [
+                TapeTrade {
+                    timestamp: 1.0,
+                    is_buy: true,
+                    size: 2.0,
+                    price: 100.5
+                },
+                TapeTrade {
+                    timestamp: 1.5,
+                    is_buy: false,
+                    size: 0.5,
+                    price: 99.0
+                },
+            ]
+        );
+
+        let err = |csv: &str| TradeTape::from_csv(csv).unwrap_err().to_string();
+        assert!(err("timestamp,side,size\n").contains("`price`"));
+        assert!(err("timestamp,side,size,price\n1,hold,1,100\n").contains("line 2"));
+        assert!(err("timestamp,side,size,price\n1,buy,1,abc\n").contains("bad price"));
+        assert!(err("timestamp,side,size,price\n2,buy,1,100\n1,buy,1,100\n").contains("earlier"));
+    }
+}
@@ -1,10 +1,15 @@
-use prop_amm_executor::NativeExecutor;
-use prop_amm_shared::config::{HyperparameterVariance, SimulationConfig};
+use prop_amm_executor::{AfterSwapFn, NativeExecutor, SwapFn};
+use prop_amm_shared::config::{
+    ArbAgent, HyperparameterVariance, InventoryRebalance, ReserveRange, RetailMomentum,
+    RetailNetting, SimulationConfig, StepOrder,
+};
 use prop_amm_shared::instruction::STORAGE_SIZE;
 use prop_amm_shared::nano::{f64_to_nano, nano_to_f64};
 use prop_amm_shared::normalizer::{
     after_swap as normalizer_after_swap, compute_swap as normalizer_swap,
 };
+use prop_amm_shared::result::{BatchResult, DisqualificationKind, SimResult};
+use prop_amm_sim::engine::Simulation;
 
 const EMPTY_STORAGE: [u8; STORAGE_SIZE] = [0u8; STORAGE_SIZE];
 
@@ -28,12 +33,12 @@ fn starter_swap(data: &[u8]) -> u64 {
         0 => {
             let net_y = input_amount.saturating_mul(950) / 1000;
             let new_ry = reserve_y + net_y;
-            reserve_x.saturating_sub((k + new_ry - 1) / new_ry) as u64
+            reserve_x.saturating_sub(k.div_ceil(new_ry)) as u64
         }
         1 => {
             let net_x = input_amount.saturating_mul(950) / 1000;
             let new_rx = reserve_x + net_x;
-            reserve_y.saturating_sub((k + new_rx - 1) / new_rx) as u64
+            reserve_y.saturating_sub(k.div_ceil(new_rx)) as u64
         }
         _ => 0,
     }
@@ -48,8 +53,7 @@ fn linear_swap(data: &[u8]) -> u64 {
         return 0;
     }
 
-    let input_amount = u64::from_le_bytes(data[1..9].try_into().expect("linear input amount"));
-    input_amount
+    u64::from_le_bytes(data[1..9].try_into().expect("linear input amount"))
 }
 
 fn normalizer_exec() -> NativeExecutor {
@@ -60,6 +64,46 @@ fn starter_exec() -> NativeExecutor {
     NativeExecutor::new(starter_swap, Some(starter_after_swap))
 }
 
+/// `n_steps` steps of `seed`, everything else at its default.
+fn config(n_steps: u32, seed: u64) -> SimulationConfig {
+    SimulationConfig {
+        n_steps,
+        seed,
+        ..SimulationConfig::default()
+    }
+}
+
+/// Run `swap` / `after_swap` against the normalizer to completion.
+fn run_vs_normalizer(
+    swap: SwapFn,
+    after_swap: Option<AfterSwapFn>,
+    config: &SimulationConfig,
+) -> SimResult {
+    prop_amm_sim::engine::run_simulation_native(
+        swap,
+        after_swap,
+        normalizer_swap,
+        Some(normalizer_after_swap),
+        config,
+    )
+    .unwrap()
+}
+
+/// `swap` / `after_swap` against the normalizer, to step by hand.
+fn sim_vs_normalizer(
+    swap: SwapFn,
+    after_swap: Option<AfterSwapFn>,
+    config: &SimulationConfig,
+) -> Simulation {
+    Simulation::native(
+        swap,
+        after_swap,
+        normalizer_swap,
+        Some(normalizer_after_swap),
+        config,
+    )
+}
+
 #[test]
 fn test_normalizer_basic_execution() {
     let exec = normalizer_exec();
@@ -265,19 +309,8 @@ fn test_integer_concavity_check_accepts_linear_compute_swap() {
 
 #[test]
 fn test_normalizer_vs_normalizer_zero_edge() {
-    let config = SimulationConfig {
-        n_steps: 500,
-        seed: 42,
-        ..SimulationConfig::default()
-    };
-    let result = prop_amm_sim::engine::run_simulation_native(
-        normalizer_swap,
-        Some(normalizer_after_swap),
-        normalizer_swap,
-        Some(normalizer_after_swap),
-        &config,
-    )
-    .unwrap();
+    let config = config(500, 42);
+    let result = run_vs_normalizer(normalizer_swap, Some(normalizer_after_swap), &config);
     assert!(
         result.submission_edge.abs() < 50.0,
         "edge should be ~0, got {}",
@@ -286,21 +319,20 @@ fn test_normalizer_vs_normalizer_zero_edge() {
 }
 
 #[test]
-fn test_simulation_produces_positive_edge() {
-    // Any reasonable CFMM should produce positive edge (retail spread > arb loss)
+fn test_normalizer_stays_in_sync_with_its_closed_form_curve() {
+    // Panics as soon as the pool drifts past `NORMALIZER_SYNC_NANO_PER_FILL` per fill.
     let config = SimulationConfig {
-        n_steps: 2000,
-        seed: 42,
-        ..SimulationConfig::default()
+        check_normalizer_sync: true,
+        ..config(100_000, 42)
     };
-    let result = prop_amm_sim::engine::run_simulation_native(
-        starter_swap,
-        Some(starter_after_swap),
-        normalizer_swap,
-        Some(normalizer_after_swap),
-        &config,
-    )
-    .unwrap();
+    run_vs_normalizer(normalizer_swap, Some(normalizer_after_swap), &config);
+}
+
+#[test]
+fn test_simulation_produces_positive_edge() {
+    // Any reasonable CFMM should produce positive edge (retail spread > arb loss)
+    let config = config(2000, 42);
+    let result = run_vs_normalizer(starter_swap, Some(starter_after_swap), &config);
     assert!(
         result.submission_edge > 0.0,
         "submission edge should be positive, got {}",
@@ -310,13 +342,7 @@ fn test_simulation_produces_positive_edge() {
 
 #[test]
 fn test_batch_runner() {
-    let configs: Vec<SimulationConfig> = (0..4)
-        .map(|i| SimulationConfig {
-            n_steps: 500,
-            seed: i,
-            ..SimulationConfig::default()
-        })
-        .collect();
+    let configs: Vec<SimulationConfig> = (0..4).map(|i| config(500, i)).collect();
 
     let result = prop_amm_sim::runner::run_batch_native(
         starter_swap,
@@ -345,57 +371,329 @@ fn test_storage_persists_across_swaps() {
     // Use the simulation engine to verify storage flows through correctly.
     // Since starter/normalizer don't use storage, just verify it doesn't crash
     // and that the engine runs with the new storage-enabled paths.
+    let config = config(100, 99);
+    let result = run_vs_normalizer(starter_swap, Some(starter_after_swap), &config);
+    assert!(result.submission_edge.is_finite(), "edge should be finite");
+}
+
+#[test]
+fn test_storage_never_written_is_flagged() {
     let config = SimulationConfig {
-        n_steps: 100,
-        seed: 99,
-        ..SimulationConfig::default()
+        check_storage_writes: true,
+        ..config(200, 99)
     };
-    let result = prop_amm_sim::engine::run_simulation_native(
-        starter_swap,
-        Some(starter_after_swap),
-        normalizer_swap,
-        Some(normalizer_after_swap),
-        &config,
-    )
-    .unwrap();
-    assert!(result.submission_edge.is_finite(), "edge should be finite");
+    let run = |swap: SwapFn, after_swap: Option<AfterSwapFn>| {
+        run_vs_normalizer(swap, after_swap, &config)
+    };
+
+    // Reads its fee from storage, but the no-op after_swap never persists anything.
+    assert!(run(normalizer_swap, Some(starter_after_swap)).storage_never_written);
+    let writer = prop_amm_sim::opponents::find("self-lagged").unwrap();
+    assert!(!run(writer.swap, writer.after_swap).storage_never_written);
+    // Without an after_swap there is nothing to wire up.
+    assert!(!run(normalizer_swap, None).storage_never_written);
+    // Off by default.
+    let unchecked = SimulationConfig {
+        check_storage_writes: false,
+        ..config.clone()
+    };
+    let result = run_vs_normalizer(normalizer_swap, Some(starter_after_swap), &unchecked);
+    assert!(!result.storage_never_written);
 }
 
 #[test]
 fn test_storage_reset_between_simulations() {
     // Run two simulations with the same config — they should produce identical results
     // since storage resets between sims
+    let config = config(500, 42);
+    let result1 = run_vs_normalizer(starter_swap, Some(starter_after_swap), &config);
+    let result2 = run_vs_normalizer(starter_swap, Some(starter_after_swap), &config);
+    assert_eq!(
+        result1.submission_edge, result2.submission_edge,
+        "same config should produce identical results when storage resets"
+    );
+}
+
+#[test]
+fn test_zero_fee_reference_isolates_the_fee_share_of_edge() {
+    let run = |zero_fee_reference: bool| {
+        let config = SimulationConfig {
+            n_steps: 2000,
+            seed: 11,
+            norm_fee_bps: 30,
+            zero_fee_reference,
+            ..SimulationConfig::default()
+        };
+        run_vs_normalizer(normalizer_swap, Some(normalizer_after_swap), &config)
+    };
+    let twin = run(false);
+    // The same curve at cost wins every order too small to move its price past 30 bps.
+    let mirror = run(true);
+    let gap = twin.submission_edge - mirror.submission_edge;
+    assert!(
+        gap > 0.25 * twin.submission_edge,
+        "twin {} vs mirror {}",
+        twin.submission_edge,
+        mirror.submission_edge
+    );
+    // The gap is what the fee earns: it shows up as retail flow lost to the mirror.
+    let retail_gap = twin.waterfall.retail_spread - mirror.waterfall.retail_spread;
+    assert!(
+        retail_gap > gap,
+        "retail gap {retail_gap} vs edge gap {gap}"
+    );
+}
+
+#[test]
+fn test_opponent_spread_floor_raises_submission_edge() {
+    let run = |opponent_min_spread_bps: u16| {
+        let config = SimulationConfig {
+            n_steps: 2000,
+            seed: 11,
+            norm_fee_bps: 30,
+            opponent_min_spread_bps,
+            ..SimulationConfig::default()
+        };
+        run_vs_normalizer(normalizer_swap, Some(normalizer_after_swap), &config)
+    };
+    // A floor under the sampled fee changes nothing.
+    assert_eq!(run(0).submission_edge, run(30).submission_edge);
+    let open = run(0);
+    let floored = run(80);
+    assert!(
+        floored.submission_edge > open.submission_edge,
+        "floored {} vs open {}",
+        floored.submission_edge,
+        open.submission_edge
+    );
+}
+
+#[test]
+fn test_quote_slippage_is_zero_for_full_fills_and_positive_for_clamped_orders() {
+    let run = |partial_fill: bool| {
+        let config = SimulationConfig {
+            n_steps: 500,
+            seed: 5,
+            retail_mean_size: 2_000.0,
+            partial_fill,
+            max_impact_bps: 20.0,
+            ..SimulationConfig::default()
+        };
+        run_vs_normalizer(normalizer_swap, Some(normalizer_after_swap), &config)
+    };
+    // A full fill executes exactly the amount that was quoted.
+    let full = run(false);
+    assert_eq!(full.total_quote_slippage, 0.0);
+    assert_eq!(full.max_quote_slippage, 0.0);
+
+    let partial = run(true);
+    assert!(partial.max_quote_slippage > 0.0);
+    assert!(partial.total_quote_slippage >= partial.max_quote_slippage);
+}
+
+#[test]
+fn test_oracle_lag_lets_arbs_trade_on_stale_prices_during_a_trend() {
+    let run = |oracle_lag_steps: usize| {
+        let config = SimulationConfig {
+            n_steps: 2000,
+            seed: 21,
+            gbm_mu: 0.0005,
+            gbm_sigma: 0.0002,
+            oracle_lag_steps,
+            ..SimulationConfig::default()
+        };
+        run_vs_normalizer(normalizer_swap, Some(normalizer_after_swap), &config)
+    };
+    let fresh = run(0);
+    let stale = run(5);
+    // On a rising path the stale price is too low: arbs keep the pool underpriced and buy X
+    // from it below the price their fills are marked at, so the submission loses more.
+    assert!(
+        stale.waterfall.arb_spread < fresh.waterfall.arb_spread,
+        "arb spread {} with lag vs {} without",
+        stale.waterfall.arb_spread,
+        fresh.waterfall.arb_spread
+    );
+    assert!(stale.submission_edge < fresh.submission_edge);
+}
+
+#[test]
+fn test_step_order_changes_results_deterministically() {
+    let run = |step_order: StepOrder| {
+        let config = SimulationConfig {
+            n_steps: 500,
+            seed: 13,
+            step_order,
+            ..SimulationConfig::default()
+        };
+        run_vs_normalizer(normalizer_swap, Some(normalizer_after_swap), &config).submission_edge
+    };
+    let arb_first = run(StepOrder::ArbFirst);
+    assert_eq!(arb_first.to_bits(), run(StepOrder::default()).to_bits());
+    assert_eq!(arb_first.to_bits(), run(StepOrder::ArbFirst).to_bits());
+
+    let retail_first = run(StepOrder::RetailFirst);
+    assert_ne!(retail_first.to_bits(), arb_first.to_bits());
+    assert_eq!(
+        retail_first.to_bits(),
+        run(StepOrder::RetailFirst).to_bits()
+    );
+
+    let shuffled = run(StepOrder::Shuffled);
+    assert_eq!(shuffled.to_bits(), run(StepOrder::Shuffled).to_bits());
+}
+
+/// Total X the submission pool exchanged with retail, fill by fill.
+#[derive(Default)]
+struct RetailVolumeX(f64);
+
+impl prop_amm_sim::objective::Objective for RetailVolumeX {
+    fn accumulate(
+        &mut self,
+        step: &prop_amm_sim::objective::StepRecord,
+        _ctx: &prop_amm_sim::objective::MarketCtx,
+    ) {
+        self.0 += step.trades.iter().map(|t| t.amount_x).sum::<f64>();
+    }
+
+    fn finalize(&self) -> f64 {
+        self.0
+    }
+}
+
+#[test]
+fn test_net_retail_flow_moves_reserves_less_than_sequential_deterministically() {
+    // A flat price, no arbitrage and balanced flow of several orders a step: whatever the
+    // reserves do is down to how a step's buys and sells meet the pool.
+    let run = |retail_netting: RetailNetting| {
+        let config = SimulationConfig {
+            n_steps: 400,
+            seed: 21,
+            gbm_sigma: 0.0,
+            retail_arrival_rate: 4.0,
+            retail_buy_prob: 0.5,
+            min_arb_profit: 1e12,
+            retail_netting,
+            ..SimulationConfig::default()
+        };
+        let mut sim = sim_vs_normalizer(normalizer_swap, Some(normalizer_after_swap), &config);
+        let mut volume = RetailVolumeX::default();
+        let mut displaced = 0.0;
+        let mut before = sim.submission().reserve_x;
+        while sim.step_with(&mut volume) {
+            let after = sim.submission().reserve_x;
+            displaced += (after - before).abs();
+            before = after;
+        }
+        let edge = sim.run().unwrap().submission_edge;
+        (volume.0, displaced, edge)
+    };
+    let sequential = run(RetailNetting::Sequential);
+    let net = run(RetailNetting::Net);
+    assert_eq!(
+        sequential.2.to_bits(),
+        run(RetailNetting::default()).2.to_bits()
+    );
+    assert_eq!(
+        sequential.2.to_bits(),
+        run(RetailNetting::Sequential).2.to_bits()
+    );
+    assert_eq!(net.2.to_bits(), run(RetailNetting::Net).2.to_bits());
+
+    // Within a step the netted pool only ever travels the residual...
+    assert!(
+        net.0 < 0.75 * sequential.0,
+        "retail moved {} X through the pool netted vs {} sequential",
+        net.0,
+        sequential.0
+    );
+    // ...and it ends each step no further from where it started.
+    assert!(
+        net.1 <= sequential.1,
+        "step-end reserve X moved {} netted vs {} sequential",
+        net.1,
+        sequential.1
+    );
+}
+
+/// Returns the same word forever. `1 << 63` makes every standard normal draw exactly 0 and
+/// every uniform exactly 0.5.
+#[derive(Debug, Clone)]
+struct FixedRng;
+
+impl prop_amm_sim::rng::EngineRng for FixedRng {
+    fn seed_from_u64(_seed: u64) -> Self {
+        FixedRng
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        1 << 63
+    }
+}
+
+#[test]
+fn test_fixed_rng_drives_a_hand_computable_simulation() {
+    use prop_amm_sim::objective::{MarketCtx, Objective, StepRecord, TradeSource};
+
+    #[derive(Default)]
+    struct RetailSides {
+        buys: u32,
+        sells: u32,
+    }
+
+    impl Objective for RetailSides {
+        fn accumulate(&mut self, step: &StepRecord, _ctx: &MarketCtx) {
+            for trade in step.trades {
+                if matches!(trade.source, TradeSource::Retail) {
+                    if trade.amm_buys_x {
+                        self.sells += 1;
+                    } else {
+                        self.buys += 1;
+                    }
+                }
+            }
+        }
+
+        fn finalize(&self) -> f64 {
+            0.0
+        }
+    }
+
     let config = SimulationConfig {
-        n_steps: 500,
-        seed: 42,
+        n_steps: 50,
+        gbm_mu: 0.0,
+        gbm_sigma: 0.01,
+        gbm_dt: 1.0,
+        retail_arrival_rate: 0.8,
+        retail_buy_prob: 0.5,
         ..SimulationConfig::default()
     };
-    let result1 = prop_amm_sim::engine::run_simulation_native(
-        starter_swap,
-        Some(starter_after_swap),
+    let mut sim = Simulation::<FixedRng>::native_with_rng(
         normalizer_swap,
         Some(normalizer_after_swap),
-        &config,
-    )
-    .unwrap();
-    let result2 = prop_amm_sim::engine::run_simulation_native(
-        starter_swap,
-        Some(starter_after_swap),
         normalizer_swap,
         Some(normalizer_after_swap),
         &config,
-    )
-    .unwrap();
-    assert_eq!(
-        result1.submission_edge, result2.submission_edge,
-        "same config should produce identical results when storage resets"
     );
+    let mut sides = RetailSides::default();
+    // z = 0 leaves only the Ito drift: each step scales the price by exp(-sigma^2 / 2).
+    let factor = (-0.5 * 0.01f64 * 0.01).exp();
+    let mut expected_price = config.initial_price;
+    while sim.step_with(&mut sides) {
+        expected_price *= factor;
+        assert_eq!(sim.fair_price(), expected_price);
+    }
+    // Knuth's Poisson sampler multiplies uniforms of 0.5 until the product drops below
+    // exp(-0.8) = 0.449: one order per step. A uniform of 0.5 is not below buy_prob 0.5, so
+    // each one sells X, and the submission fills part of every order.
+    assert_eq!(sides.buys, 0);
+    assert_eq!(sides.sells, config.n_steps);
 }
 
 #[test]
 fn test_native_normalizer_fee_from_storage() {
-    use prop_amm_shared::normalizer::compute_swap;
     use prop_amm_shared::instruction::encode_swap_instruction;
+    use prop_amm_shared::normalizer::compute_swap;
 
     let rx = f64_to_nano(100.0);
     let ry = f64_to_nano(10000.0);
@@ -411,54 +709,74 @@ fn test_native_normalizer_fee_from_storage() {
     storage_30[0..2].copy_from_slice(&30u16.to_le_bytes());
     let data_30 = encode_swap_instruction(0, input, rx, ry, &storage_30);
     let out_30 = compute_swap(&data_30);
-    assert_eq!(out_default, out_30, "zero storage should equal explicit 30bps");
+    assert_eq!(
+        out_default, out_30,
+        "zero storage should equal explicit 30bps"
+    );
 
     // 100bps (1%) → less output than 30bps
     let mut storage_100 = [0u8; STORAGE_SIZE];
     storage_100[0..2].copy_from_slice(&100u16.to_le_bytes());
     let data_100 = encode_swap_instruction(0, input, rx, ry, &storage_100);
     let out_100 = compute_swap(&data_100);
-    assert!(out_100 < out_30, "100bps ({}) should give less output than 30bps ({})", out_100, out_30);
+    assert!(
+        out_100 < out_30,
+        "100bps ({}) should give less output than 30bps ({})",
+        out_100,
+        out_30
+    );
 
     // 10bps → more output than 30bps
     let mut storage_10 = [0u8; STORAGE_SIZE];
     storage_10[0..2].copy_from_slice(&10u16.to_le_bytes());
     let data_10 = encode_swap_instruction(0, input, rx, ry, &storage_10);
     let out_10 = compute_swap(&data_10);
-    assert!(out_10 > out_30, "10bps ({}) should give more output than 30bps ({})", out_10, out_30);
+    assert!(
+        out_10 > out_30,
+        "10bps ({}) should give more output than 30bps ({})",
+        out_10,
+        out_30
+    );
 }
 
 #[test]
 fn test_norm_liquidity_mult_affects_edge() {
-    use prop_amm_shared::normalizer::{compute_swap as norm_swap, after_swap as norm_after};
+    use prop_amm_shared::normalizer::{after_swap as norm_after, compute_swap as norm_swap};
 
     // Low liquidity normalizer (0.5x) — easier to beat
     let config_low = SimulationConfig {
-        n_steps: 1000,
-        seed: 42,
         norm_liquidity_mult: 0.5,
-        ..SimulationConfig::default()
+        ..config(1000, 42)
     };
     let result_low = prop_amm_sim::engine::run_simulation_native(
-        norm_swap, Some(norm_after), norm_swap, Some(norm_after), &config_low,
-    ).unwrap();
+        norm_swap,
+        Some(norm_after),
+        norm_swap,
+        Some(norm_after),
+        &config_low,
+    )
+    .unwrap();
 
     // High liquidity normalizer (2.0x) — harder to beat
     let config_high = SimulationConfig {
-        n_steps: 1000,
-        seed: 42,
         norm_liquidity_mult: 2.0,
-        ..SimulationConfig::default()
+        ..config(1000, 42)
     };
     let result_high = prop_amm_sim::engine::run_simulation_native(
-        norm_swap, Some(norm_after), norm_swap, Some(norm_after), &config_high,
-    ).unwrap();
+        norm_swap,
+        Some(norm_after),
+        norm_swap,
+        Some(norm_after),
+        &config_high,
+    )
+    .unwrap();
 
     // Different liquidity should produce different edges
     assert!(
         (result_low.submission_edge - result_high.submission_edge).abs() > 0.01,
         "different liquidity mults should produce different edges: low={}, high={}",
-        result_low.submission_edge, result_high.submission_edge
+        result_low.submission_edge,
+        result_high.submission_edge
     );
 }
 
@@ -469,8 +787,14 @@ fn test_hyperparameter_variance_generates_varied_configs() {
 
     assert_eq!(configs.len(), 100);
 
-    let sigma_min = configs.iter().map(|c| c.gbm_sigma).fold(f64::INFINITY, f64::min);
-    let sigma_max = configs.iter().map(|c| c.gbm_sigma).fold(f64::NEG_INFINITY, f64::max);
+    let sigma_min = configs
+        .iter()
+        .map(|c| c.gbm_sigma)
+        .fold(f64::INFINITY, f64::min);
+    let sigma_max = configs
+        .iter()
+        .map(|c| c.gbm_sigma)
+        .fold(f64::NEG_INFINITY, f64::max);
     assert!(sigma_min >= 0.0001, "sigma_min {} below range", sigma_min);
     assert!(sigma_max <= 0.007, "sigma_max {} above range", sigma_max);
     assert!(
@@ -484,11 +808,1502 @@ fn test_hyperparameter_variance_generates_varied_configs() {
     let fee_max = configs.iter().map(|c| c.norm_fee_bps).max().unwrap();
     assert!(fee_min >= 30, "fee_min {} below range", fee_min);
     assert!(fee_max <= 80, "fee_max {} above range", fee_max);
-    assert!(fee_max - fee_min > 30, "fee range too narrow: [{}, {}]", fee_min, fee_max);
+    assert!(
+        fee_max - fee_min > 30,
+        "fee range too narrow: [{}, {}]",
+        fee_min,
+        fee_max
+    );
 
-    let liq_min = configs.iter().map(|c| c.norm_liquidity_mult).fold(f64::INFINITY, f64::min);
-    let liq_max = configs.iter().map(|c| c.norm_liquidity_mult).fold(f64::NEG_INFINITY, f64::max);
+    let liq_min = configs
+        .iter()
+        .map(|c| c.norm_liquidity_mult)
+        .fold(f64::INFINITY, f64::min);
+    let liq_max = configs
+        .iter()
+        .map(|c| c.norm_liquidity_mult)
+        .fold(f64::NEG_INFINITY, f64::max);
     assert!(liq_min >= 0.4, "liq_min {} below range", liq_min);
     assert!(liq_max <= 2.0, "liq_max {} above range", liq_max);
-    assert!(liq_max - liq_min > 0.5, "liq range too narrow: [{}, {}]", liq_min, liq_max);
+    assert!(
+        liq_max - liq_min > 0.5,
+        "liq range too narrow: [{}, {}]",
+        liq_min,
+        liq_max
+    );
+}
+
+#[test]
+fn test_tighter_arb_solver_tolerance_converges_for_smooth_curve() {
+    // The starter CFMM is smooth, so the default ~1% arb search should already sit close to
+    // the converged edge. Bound: tightening the solver moves edge by < 2% (plus 1 Y slack).
+    const SOLVER_EDGE_REL_BOUND: f64 = 0.02;
+    const SOLVER_EDGE_ABS_SLACK: f64 = 1.0;
+
+    let default_config = config(2000, 7);
+    let tight_config = SimulationConfig {
+        arb_solver_iters: 200,
+        arb_solver_tolerance: 1e-6,
+        ..default_config.clone()
+    };
+
+    let run = |config: &SimulationConfig| {
+        run_vs_normalizer(starter_swap, Some(starter_after_swap), config).submission_edge
+    };
+    let default_edge = run(&default_config);
+    let tight_edge = run(&tight_config);
+
+    let bound = SOLVER_EDGE_REL_BOUND * default_edge.abs() + SOLVER_EDGE_ABS_SLACK;
+    assert!(
+        (tight_edge - default_edge).abs() < bound,
+        "tightening the arb solver moved edge too far: default={}, tight={}, bound={}",
+        default_edge,
+        tight_edge,
+        bound
+    );
+}
+
+#[test]
+fn test_k_growth_tracks_fee_accrual_across_volatility_regimes() {
+    let run = |gbm_sigma: f64| {
+        let config = SimulationConfig {
+            n_steps: 2000,
+            seed: 3,
+            gbm_sigma,
+            record_k_series: true,
+            ..SimulationConfig::default()
+        };
+        run_vs_normalizer(starter_swap, Some(starter_after_swap), &config)
+    };
+
+    let calm = run(0.0002);
+    let volatile = run(0.007);
+    assert!(calm.k_growth > 0.0, "calm k_growth {}", calm.k_growth);
+    // Fees stay in the pool, so more arbitrage volume grows k faster even as arbs extract edge.
+    assert!(
+        volatile.k_growth > calm.k_growth,
+        "volatile k_growth {} should exceed calm {}",
+        volatile.k_growth,
+        calm.k_growth
+    );
+
+    let series = calm.k_series.expect("k series requested");
+    assert_eq!(series.len(), 2000);
+    assert!(!calm.recording_capped);
+    assert_eq!(calm.recording_bytes, 2000 * 8);
+    let k_initial = 100.0 * 10_000.0;
+    let last = *series.last().unwrap();
+    assert!((last / k_initial - 1.0 - calm.k_growth).abs() < 1e-12);
+}
+
+#[test]
+fn test_substream_sweep_prefix_matches_smaller_sweep() {
+    let run = |n_sims: u32| {
+        prop_amm_sim::runner::run_batch_native(
+            starter_swap,
+            Some(starter_after_swap),
+            normalizer_swap,
+            Some(normalizer_after_swap),
+            prop_amm_sim::runner::substream_configs(n_sims, 300, 2024),
+            Some(4),
+        )
+        .unwrap()
+    };
+
+    let small = run(8);
+    let large = run(40);
+    assert_eq!(small.results.len(), 8);
+    for (a, b) in small.results.iter().zip(&large.results) {
+        assert_eq!(a.seed, b.seed);
+        assert_eq!(a.submission_edge.to_bits(), b.submission_edge.to_bits());
+        assert_eq!(a.k_growth.to_bits(), b.k_growth.to_bits());
+    }
+}
+
+#[test]
+fn test_low_threshold_arbs_reduce_submission_edge() {
+    let avg_edge = |arb_agents: Vec<ArbAgent>| {
+        let total: f64 = (0..6u64)
+            .map(|seed| {
+                let config = SimulationConfig {
+                    n_steps: 2000,
+                    seed,
+                    arb_agents: arb_agents.clone(),
+                    ..SimulationConfig::default()
+                };
+                run_vs_normalizer(starter_swap, Some(starter_after_swap), &config).submission_edge
+            })
+            .sum();
+        total / 6.0
+    };
+
+    // Wide-threshold arbs let mispricing build up; adding tight ones picks it off earlier.
+    let patient = avg_edge(vec![ArbAgent::new(500.0)]);
+    let layered = avg_edge(ArbAgent::uniform_thresholds(4, 150.0, 500.0, 9));
+    let aggressive = avg_edge(vec![ArbAgent::new(500.0), ArbAgent::new(0.0)]);
+    assert!(
+        layered < patient && aggressive < layered,
+        "patient {patient}, layered {layered}, aggressive {aggressive}"
+    );
+}
+
+/// Starter curve whose after_swap counts trades in storage[0..8] and records the last step.
+fn counting_after_swap(data: &[u8], storage: &mut [u8]) {
+    let count = u64::from_le_bytes(storage[0..8].try_into().unwrap()) + 1;
+    storage[0..8].copy_from_slice(&count.to_le_bytes());
+    storage[8..16].copy_from_slice(&data[34..42]);
+}
+
+/// Normalizer after_swap that sums its Y-side volume (nano) into storage[8..16], leaving the
+/// fee word in storage[0..2] alone.
+fn y_volume_after_swap(data: &[u8], storage: &mut [u8]) {
+    let y_amount = if data[1] == 0 {
+        &data[2..10]
+    } else {
+        &data[10..18]
+    };
+    let volume = u64::from_le_bytes(storage[8..16].try_into().unwrap())
+        + u64::from_le_bytes(y_amount.try_into().unwrap());
+    storage[8..16].copy_from_slice(&volume.to_le_bytes());
+}
+
+#[test]
+fn test_negative_normalizer_fee_draws_volume_until_the_subsidy_budget_runs_out() {
+    let run = |norm_subsidy_bps: f64, norm_subsidy_budget: Option<f64>| {
+        let config = SimulationConfig {
+            n_steps: 2000,
+            seed: 17,
+            norm_fee_bps: 30,
+            norm_subsidy_bps,
+            norm_subsidy_budget,
+            ..SimulationConfig::default()
+        };
+        let mut sim = Simulation::native(
+            normalizer_swap,
+            Some(normalizer_after_swap),
+            normalizer_swap,
+            Some(y_volume_after_swap),
+            &config,
+        );
+        while sim.step() {}
+        let storage = sim.normalizer().storage();
+        let volume = nano_to_f64(u64::from_le_bytes(storage[8..16].try_into().unwrap()));
+        let paid = sim.normalizer().subsidy_paid();
+        (volume, paid, sim.run().unwrap())
+    };
+
+    let (plain_volume, plain_paid, plain) = run(0.0, None);
+    assert_eq!(plain_paid, 0.0);
+    // Effective fee -20 bps: the reference undercuts a fee-free curve and takes the flow.
+    let (subsidized_volume, subsidized_paid, subsidized) = run(50.0, Some(1e9));
+    assert!(
+        subsidized_volume > 1.5 * plain_volume,
+        "volume {subsidized_volume} subsidized vs {plain_volume}"
+    );
+    assert!(subsidized_paid > 0.0);
+    assert!(subsidized.waterfall.retail_spread < plain.waterfall.retail_spread);
+
+    let (_, capped_paid, _) = run(50.0, Some(5.0));
+    assert!(subsidized_paid > 5.0);
+    assert!((capped_paid - 5.0).abs() < 1e-9, "paid {capped_paid} of 5");
+
+    let config = SimulationConfig {
+        n_steps: 10,
+        norm_subsidy_bps: 50.0,
+        ..SimulationConfig::default()
+    };
+    let err = prop_amm_sim::engine::run_simulation_native(
+        normalizer_swap,
+        Some(normalizer_after_swap),
+        normalizer_swap,
+        Some(normalizer_after_swap),
+        &config,
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("needs a norm_subsidy_budget"));
+}
+
+#[test]
+fn test_inventory_cap_cuts_volume_and_inventory_drawdown() {
+    let run = |max_inventory: Option<f64>| {
+        let config = SimulationConfig {
+            gbm_sigma: 0.003,
+            max_inventory_x: max_inventory.map(|cap| 100.0 * cap),
+            max_inventory_y: max_inventory.map(|cap| 10_000.0 * cap),
+            ..config(2000, 29)
+        };
+        let mut sim = sim_vs_normalizer(normalizer_swap, Some(y_volume_after_swap), &config);
+        // Largest excess of either reserve over its starting level, as a fraction of it.
+        let mut drawdown = 0.0f64;
+        while sim.step() {
+            let pool = sim.submission();
+            drawdown = drawdown
+                .max(pool.reserve_x / config.initial_x - 1.0)
+                .max(pool.reserve_y / config.initial_y - 1.0);
+        }
+        let storage = sim.submission().storage();
+        let volume = nano_to_f64(u64::from_le_bytes(storage[8..16].try_into().unwrap()));
+        (volume, drawdown, sim.run().unwrap())
+    };
+
+    let (free_volume, free_drawdown, free) = run(None);
+    assert_eq!(free.inventory_rejected, 0.0);
+    let (capped_volume, capped_drawdown, capped) = run(Some(1.02));
+    assert!(
+        capped_volume < free_volume,
+        "volume {capped_volume} capped vs {free_volume}"
+    );
+    assert!(free_drawdown > 0.02, "unlimited drawdown {free_drawdown}");
+    assert!(capped_drawdown <= 0.02, "capped drawdown {capped_drawdown}");
+    assert!(capped.inventory_rejected > 0.0);
+}
+
+#[test]
+fn test_stepped_simulation_exposes_live_storage() {
+    let config = SimulationConfig {
+        gbm_sigma: 0.01,
+        ..config(1000, 5)
+    };
+    let mut sim = sim_vs_normalizer(starter_swap, Some(counting_after_swap), &config);
+
+    let mut last_count = 0u64;
+    while sim.step() {
+        let storage = sim.submission().storage();
+        assert_eq!(storage.len(), STORAGE_SIZE);
+        let count = u64::from_le_bytes(storage[0..8].try_into().unwrap());
+        assert!(count >= last_count);
+        if count > last_count {
+            let last_step = u64::from_le_bytes(storage[8..16].try_into().unwrap());
+            assert_eq!(last_step, sim.current_step() as u64 - 1);
+        }
+        last_count = count;
+    }
+    assert!(last_count > 0, "starter should trade in a volatile market");
+    assert!(!sim.step());
+
+    let stepped = sim.run().unwrap();
+    let direct = run_vs_normalizer(starter_swap, Some(counting_after_swap), &config);
+    assert_eq!(
+        stepped.submission_edge.to_bits(),
+        direct.submission_edge.to_bits()
+    );
+}
+
+#[test]
+fn test_initial_mispricing_causes_early_loss_then_stabilizes() {
+    let edge_path = |initial_mispricing_bps: f64| {
+        let config = SimulationConfig {
+            n_steps: 500,
+            seed: 11,
+            initial_mispricing_bps,
+            ..SimulationConfig::default()
+        };
+        let mut sim = sim_vs_normalizer(starter_swap, Some(starter_after_swap), &config);
+        let mut path = Vec::new();
+        while sim.step() {
+            path.push(sim.submission_edge());
+        }
+        path
+    };
+
+    let fair = edge_path(0.0);
+    let stale = edge_path(2_000.0);
+    let gap = |step: usize| stale[step] - fair[step];
+    // The first arb snaps the stale pool back to within its fee band at once...
+    assert!(gap(0) < -20.0, "initial snap-back loss {}", gap(0));
+    // ...after which the two runs accumulate edge at a similar rate.
+    let drift = (gap(499) - gap(0)).abs();
+    assert!(
+        drift < 0.25 * gap(0).abs(),
+        "gap drifted by {drift} after snap-back"
+    );
+}
+
+#[test]
+fn test_edge_objective_reproduces_submission_edge() {
+    use prop_amm_sim::objective::{EdgeObjective, Objective};
+
+    let config = SimulationConfig {
+        gbm_sigma: 0.004,
+        ..config(1500, 21)
+    };
+    let sim = sim_vs_normalizer(starter_swap, Some(starter_after_swap), &config);
+    let mut objective = EdgeObjective::default();
+    let result = sim.run_with(&mut objective).unwrap();
+    assert_ne!(result.submission_edge, 0.0);
+    assert_eq!(
+        objective.finalize().to_bits(),
+        result.submission_edge.to_bits()
+    );
+}
+
+#[test]
+fn test_warmup_steps_trade_but_are_left_out_of_the_edge() {
+    use prop_amm_sim::objective::{EdgeObjective, Objective};
+
+    let plain_config = config(1_000, 17);
+    let config = SimulationConfig {
+        warmup_steps: 200,
+        ..plain_config.clone()
+    };
+    let build = |config: &SimulationConfig| {
+        sim_vs_normalizer(normalizer_swap, Some(normalizer_after_swap), config)
+    };
+
+    let plain = build(&plain_config).run().unwrap();
+    let mut objective = EdgeObjective::default();
+    let warm = build(&config).run_with(&mut objective).unwrap();
+    assert_eq!(warm.phases.warmup.steps, 200);
+    assert_eq!(warm.phases.measured.steps, 800);
+    assert_eq!(plain.phases.warmup.steps, 0);
+    assert!(warm.phases.warmup.edge > 0.0);
+    // The warmup trades the same market; it is only scored apart.
+    let whole = warm.submission_edge + warm.phases.warmup.edge;
+    assert!((whole - plain.submission_edge).abs() < 1e-9 * plain.submission_edge.abs().max(1.0));
+    assert_eq!(
+        warm.phases.measured.edge.to_bits(),
+        warm.submission_edge.to_bits()
+    );
+    assert_eq!(
+        objective.finalize().to_bits(),
+        warm.submission_edge.to_bits()
+    );
+    assert!((warm.waterfall.total() - warm.submission_edge).abs() < 1e-6);
+    // Volume and the LP return cover the measured steps too, fees and divergence alike.
+    assert!(warm.retail_volume[0] > 0.0 && warm.retail_volume[0] < plain.retail_volume[0]);
+    assert!(warm.retail_volume[1] < plain.retail_volume[1]);
+    assert_eq!(
+        warm.lp_return.fee_revenue,
+        warm.waterfall.retail_spread + warm.waterfall.arb_spread
+    );
+    assert!((warm.lp_return.total() - warm.impermanent_loss).abs() < 1e-9);
+    assert_ne!(warm.impermanent_loss, plain.impermanent_loss);
+
+    for resume_at in [120, 300] {
+        let mut first = build(&config);
+        for _ in 0..resume_at {
+            assert!(first.step());
+        }
+        let json = serde_json::to_string(&first.snapshot()).unwrap();
+        let mut resumed = build(&config);
+        resumed
+            .restore(serde_json::from_str(&json).unwrap())
+            .unwrap();
+        let resumed = resumed.run().unwrap();
+        assert_eq!(resumed.phases, warm.phases);
+        assert_eq!(
+            resumed.submission_edge.to_bits(),
+            warm.submission_edge.to_bits()
+        );
+        assert_eq!(resumed.retail_volume, warm.retail_volume);
+        assert_eq!(resumed.lp_return, warm.lp_return);
+    }
+
+    // Fills the inventory cap refuses while warming up are not counted either.
+    let capped = |warmup_steps| SimulationConfig {
+        warmup_steps,
+        max_inventory_x: Some(100.5),
+        ..plain_config.clone()
+    };
+    let all = build(&capped(0)).run().unwrap().inventory_rejected;
+    let measured = build(&capped(200)).run().unwrap().inventory_rejected;
+    assert!(measured > 0.0 && measured < all, "{measured} of {all}");
+}
+
+#[test]
+fn test_custom_objective_composes_with_edge() {
+    use prop_amm_sim::objective::{EdgeObjective, MarketCtx, Objective, StepRecord, TradeSource};
+
+    /// Edge minus a penalty on the submission's X inventory drift, plus an arb-fill count.
+    struct InventoryPenalized {
+        edge: EdgeObjective,
+        initial_x: f64,
+        penalty: f64,
+        drift: f64,
+        arb_fills: usize,
+    }
+
+    impl Objective for InventoryPenalized {
+        fn accumulate(&mut self, step: &StepRecord, ctx: &MarketCtx) {
+            self.edge.accumulate(step, ctx);
+            self.drift += (ctx.submission_reserve_x - self.initial_x).abs() * ctx.fair_price;
+            self.arb_fills += step
+                .trades
+                .iter()
+                .filter(|t| t.source == TradeSource::Arbitrage)
+                .count();
+        }
+
+        fn finalize(&self) -> f64 {
+            self.edge.finalize() - self.penalty * self.drift
+        }
+    }
+
+    let config = SimulationConfig {
+        gbm_sigma: 0.004,
+        ..config(1500, 21)
+    };
+    let mut objective = InventoryPenalized {
+        edge: EdgeObjective::default(),
+        initial_x: config.initial_x,
+        penalty: 1e-4,
+        drift: 0.0,
+        arb_fills: 0,
+    };
+    let result = sim_vs_normalizer(starter_swap, Some(starter_after_swap), &config)
+        .run_with(&mut objective)
+        .unwrap();
+
+    assert!(objective.arb_fills > 0);
+    assert!(objective.drift > 0.0);
+    assert_eq!(objective.edge.finalize(), result.submission_edge);
+    assert!(objective.finalize() < result.submission_edge);
+}
+
+#[test]
+fn test_snapshot_restore_continues_like_straight_run() {
+    let config = SimulationConfig {
+        gbm_sigma: 0.004,
+        correlation: 0.5,
+        arb_agents: vec![ArbAgent::new(0.0), ArbAgent::new(20.0)],
+        record_k_series: true,
+        ..config(1200, 17)
+    };
+    let build = || sim_vs_normalizer(starter_swap, Some(counting_after_swap), &config);
+
+    let straight = build().run().unwrap();
+
+    let mut first = build();
+    for _ in 0..500 {
+        assert!(first.step());
+    }
+    let json = serde_json::to_string(&first.snapshot()).unwrap();
+    drop(first);
+
+    let mut resumed = build();
+    resumed
+        .restore(serde_json::from_str(&json).unwrap())
+        .unwrap();
+    assert_eq!(resumed.current_step(), 500);
+    let resumed = resumed.run().unwrap();
+
+    assert_eq!(
+        resumed.submission_edge.to_bits(),
+        straight.submission_edge.to_bits()
+    );
+    assert_eq!(resumed.k_growth.to_bits(), straight.k_growth.to_bits());
+    assert_eq!(resumed.k_series, straight.k_series);
+}
+
+#[test]
+fn test_pnl_waterfall_reconciles_to_submission_edge() {
+    for seed in 0..6u64 {
+        let config = SimulationConfig {
+            n_steps: 2000,
+            seed,
+            gbm_sigma: 0.005,
+            arb_agents: vec![ArbAgent::new(0.0), ArbAgent::new(15.0)],
+            ..SimulationConfig::default()
+        };
+        let result = run_vs_normalizer(starter_swap, Some(starter_after_swap), &config);
+        let waterfall = result.waterfall;
+        let tolerance = 1e-6 * (1.0 + result.submission_edge.abs());
+        assert!(
+            (waterfall.total() - result.submission_edge).abs() < tolerance,
+            "seed {seed}: waterfall {waterfall:?} vs edge {}",
+            result.submission_edge
+        );
+        assert!(waterfall.retail_spread > 0.0, "seed {seed}: {waterfall:?}");
+        assert!(
+            waterfall.adverse_selection < 0.0,
+            "seed {seed}: {waterfall:?}"
+        );
+    }
+}
+
+#[test]
+fn test_dust_filter_drops_small_retail_trades() {
+    use prop_amm_sim::objective::{MarketCtx, Objective, StepRecord, TradeSource};
+
+    #[derive(Default)]
+    struct RetailFlow {
+        trades: usize,
+        volume_y: f64,
+        edge: f64,
+    }
+
+    impl Objective for RetailFlow {
+        fn accumulate(&mut self, step: &StepRecord, _ctx: &MarketCtx) {
+            for trade in step.trades {
+                if trade.source == TradeSource::Retail {
+                    self.trades += 1;
+                    self.volume_y += trade.amount_y;
+                    self.edge += trade.edge;
+                }
+            }
+        }
+
+        fn finalize(&self) -> f64 {
+            self.edge
+        }
+    }
+
+    let retail_flow = |min_trade_size: f64| {
+        let mut flow = RetailFlow::default();
+        for seed in 0..6u64 {
+            let config = SimulationConfig {
+                n_steps: 2000,
+                seed,
+                min_trade_size,
+                ..SimulationConfig::default()
+            };
+            sim_vs_normalizer(normalizer_swap, Some(normalizer_after_swap), &config)
+                .run_with(&mut flow)
+                .unwrap();
+        }
+        flow
+    };
+
+    let all = retail_flow(0.0);
+    let filtered = retail_flow(10.0);
+    assert!(
+        filtered.trades < all.trades * 3 / 4,
+        "dust filter kept {} of {} retail fills",
+        filtered.trades,
+        all.trades
+    );
+    // Fills are priced the same way, so the fee earned per unit of volume barely moves.
+    let fee_rate = |flow: &RetailFlow| flow.edge / flow.volume_y;
+    let ratio = fee_rate(&filtered) / fee_rate(&all);
+    assert!(
+        (0.7..1.5).contains(&ratio),
+        "fee per unit volume moved by {ratio}"
+    );
+}
+
+#[test]
+fn test_faster_poisson_arbs_keep_pool_closer_to_fair() {
+    use prop_amm_shared::config::TradeArrival;
+    use prop_amm_sim::objective::{MarketCtx, Objective, StepRecord};
+
+    /// Mean absolute gap between the submission's spot price and fair, in bps.
+    #[derive(Default)]
+    struct Mispricing {
+        total_bps: f64,
+        steps: usize,
+    }
+
+    impl Objective for Mispricing {
+        fn accumulate(&mut self, _step: &StepRecord, ctx: &MarketCtx) {
+            let spot = ctx.submission_reserve_y / ctx.submission_reserve_x;
+            self.total_bps += (spot / ctx.fair_price - 1.0).abs() * 10_000.0;
+            self.steps += 1;
+        }
+
+        fn finalize(&self) -> f64 {
+            self.total_bps / self.steps as f64
+        }
+    }
+
+    let mispricing = |arb_rate: f64| {
+        let mut gap = Mispricing::default();
+        for seed in 0..3u64 {
+            let config = SimulationConfig {
+                n_steps: 2000,
+                seed,
+                gbm_sigma: 0.003,
+                trade_arrival: TradeArrival::Poisson {
+                    retail_rate: 0.8,
+                    arb_rate,
+                },
+                ..SimulationConfig::default()
+            };
+            sim_vs_normalizer(normalizer_swap, Some(normalizer_after_swap), &config)
+                .run_with(&mut gap)
+                .unwrap();
+        }
+        gap.finalize()
+    };
+
+    let slow = mispricing(0.2);
+    let fast = mispricing(5.0);
+    assert!(
+        fast < 0.8 * slow,
+        "mean mispricing {fast:.2} bps with fast arbs vs {slow:.2} bps with slow ones"
+    );
+}
+
+/// Quotes 50 bps either side of a fixed price of 100, ignoring reserves, so the fills it
+/// gets do not depend on how the engine credits its reserves.
+fn fixed_price_swap(data: &[u8]) -> u64 {
+    if data.len() < 25 {
+        return 0;
+    }
+    let input = u64::from_le_bytes(data[1..9].try_into().expect("fixed input amount")) as u128;
+    match data[0] {
+        0 => (input * 995 / 100_000) as u64,
+        1 => (input * 995 / 10) as u64,
+        _ => 0,
+    }
+}
+
+#[test]
+fn test_maker_rebate_adds_its_share_of_each_fill_to_edge() {
+    use prop_amm_sim::objective::{MarketCtx, Objective, StepRecord};
+
+    /// Total edge, plus the Y notional of every input the submission received.
+    #[derive(Default)]
+    struct InputNotional {
+        edge: f64,
+        notional_y: f64,
+    }
+
+    impl Objective for InputNotional {
+        fn accumulate(&mut self, step: &StepRecord, ctx: &MarketCtx) {
+            for trade in step.trades {
+                self.edge += trade.edge;
+                self.notional_y += if trade.amm_buys_x {
+                    trade.amount_x * ctx.fair_price
+                } else {
+                    trade.amount_y
+                };
+            }
+        }
+
+        fn finalize(&self) -> f64 {
+            self.edge
+        }
+    }
+
+    let run = |maker_rebate_bps: f64| {
+        let config = SimulationConfig {
+            n_steps: 500,
+            seed: 8,
+            gbm_sigma: 0.0,
+            maker_rebate_bps,
+            ..SimulationConfig::default()
+        };
+        let mut flow = InputNotional::default();
+        let result = sim_vs_normalizer(fixed_price_swap, None, &config)
+            .run_with(&mut flow)
+            .unwrap();
+        (result, flow)
+    };
+
+    let (plain, plain_flow) = run(0.0);
+    let (rebated, rebated_flow) = run(2.0);
+    assert!(plain_flow.notional_y > 0.0);
+    assert_eq!(plain_flow.notional_y, rebated_flow.notional_y);
+
+    let expected = plain_flow.notional_y * 2.0 / 10_000.0;
+    let gained = rebated.submission_edge - plain.submission_edge;
+    assert!(
+        (gained - expected).abs() < 1e-9 * plain_flow.notional_y,
+        "rebate added {gained}, expected {expected}"
+    );
+    assert!((rebated_flow.finalize() - rebated.submission_edge).abs() < 1e-9);
+}
+
+#[test]
+fn test_coarse_tick_size_reduces_arbitrage_frequency() {
+    use prop_amm_sim::objective::{MarketCtx, Objective, StepRecord, TradeSource};
+
+    #[derive(Default)]
+    struct ArbFills(usize);
+
+    impl Objective for ArbFills {
+        fn accumulate(&mut self, step: &StepRecord, _ctx: &MarketCtx) {
+            self.0 += step
+                .trades
+                .iter()
+                .filter(|t| t.source == TradeSource::Arbitrage)
+                .count();
+        }
+
+        fn finalize(&self) -> f64 {
+            self.0 as f64
+        }
+    }
+
+    let arb_fills = |tick_size: Option<f64>| {
+        let mut fills = ArbFills::default();
+        for seed in 0..3u64 {
+            let config = SimulationConfig {
+                n_steps: 2000,
+                seed,
+                gbm_sigma: 0.002,
+                tick_size,
+                ..SimulationConfig::default()
+            };
+            sim_vs_normalizer(normalizer_swap, Some(normalizer_after_swap), &config)
+                .run_with(&mut fills)
+                .unwrap();
+        }
+        fills.0
+    };
+
+    let continuous = arb_fills(None);
+    // A 1.0 tick at a price of 100 hides any mispricing under 100 bps.
+    let coarse = arb_fills(Some(1.0));
+    assert!(continuous > 0);
+    assert!(
+        coarse < continuous / 2,
+        "coarse tick: {coarse} arb fills vs {continuous} continuous"
+    );
+}
+
+#[test]
+fn test_twap_oracle_matches_independent_average_of_step_end_spots() {
+    use std::collections::VecDeque;
+
+    use prop_amm_shared::instruction::{decode_oracle, Oracle};
+
+    fn oracle_echo_swap(data: &[u8]) -> u64 {
+        decode_oracle(data).unwrap_or(0)
+    }
+    let echo = NativeExecutor::new(oracle_echo_swap, None);
+    assert_eq!(echo.execute(0, 1, 1, 1, &EMPTY_STORAGE), 0);
+    assert_eq!(
+        echo.execute_with_oracle(0, 1, 1, 1, &EMPTY_STORAGE, Oracle::twap(123)),
+        123
+    );
+
+    let window = 20;
+    let config = SimulationConfig {
+        gbm_sigma: 0.004,
+        twap_window: Some(window),
+        ..config(300, 6)
+    };
+    let mut sim = sim_vs_normalizer(starter_swap, Some(starter_after_swap), &config);
+    let mut spots = VecDeque::from([sim.submission().spot_price()]);
+    while sim.step() {
+        let expected = spots.iter().sum::<f64>() / spots.len() as f64;
+        let provided = sim.submission().twap().expect("oracle enabled");
+        assert!(
+            (provided - expected).abs() <= 1e-9 * expected,
+            "step {}: oracle {provided} vs independent {expected}",
+            sim.current_step()
+        );
+        spots.push_back(sim.submission().spot_price());
+        if spots.len() > window as usize {
+            spots.pop_front();
+        }
+    }
+
+    let plain = sim_vs_normalizer(
+        starter_swap,
+        Some(starter_after_swap),
+        &SimulationConfig::default(),
+    );
+    assert_eq!(plain.submission().twap(), None);
+}
+
+#[test]
+fn test_edge_converged_stops_early_for_calm_market_and_caps_volatile_one() {
+    use prop_amm_shared::config::StopCondition;
+
+    let run = |gbm_sigma: f64, seed: u64, stop_condition: StopCondition, n_steps: u32| {
+        let config = SimulationConfig {
+            n_steps,
+            seed,
+            gbm_sigma,
+            stop_condition,
+            ..SimulationConfig::default()
+        };
+        run_vs_normalizer(starter_swap, Some(starter_after_swap), &config)
+    };
+    let window = 500;
+    let converged = StopCondition::EdgeConverged {
+        window,
+        tolerance: 0.002,
+    };
+    let cap = 6_000;
+
+    // Nearly still price: the edge is steady retail spread and settles within a few blocks.
+    let calm = run(0.0001, 2, converged, cap);
+    assert!(calm.steps_run < cap, "calm run used all {cap} steps");
+    assert_eq!(calm.steps_run % window, 0);
+    // Stopping early is just a shorter fixed-step run.
+    let fixed = run(0.0001, 2, StopCondition::Steps, calm.steps_run);
+    assert_eq!(fixed.steps_run, calm.steps_run);
+    assert_eq!(fixed.submission_edge, calm.submission_edge);
+
+    // Large price moves make each block's edge swing too much to ever settle.
+    let volatile = run(0.03, 3, converged, cap);
+    assert_eq!(volatile.steps_run, cap);
+}
+
+#[test]
+fn test_seeded_reserves_vary_per_seed_and_keep_capital_normalized_edge() {
+    // Edge per unit of starting capital, for each seed's fixed- and seeded-reserve config.
+    let per_capital = |config: &SimulationConfig| {
+        let result = run_vs_normalizer(starter_swap, Some(starter_after_swap), config);
+        result.submission_edge / (config.initial_x * config.initial_price + config.initial_y)
+    };
+    let (mut fixed_total, mut seeded_total) = (0.0, 0.0);
+    let mut sizes = Vec::new();
+    for seed in 0..12 {
+        let fixed = SimulationConfig {
+            n_steps: 2000,
+            seed,
+            ..SimulationConfig::default()
+        };
+        let seeded = SimulationConfig {
+            seeded_reserves: Some(ReserveRange::default()),
+            ..fixed.clone()
+        }
+        .with_seeded_reserves();
+        sizes.push(seeded.initial_x);
+        fixed_total += per_capital(&fixed);
+        seeded_total += per_capital(&seeded);
+    }
+    sizes.sort_by(f64::total_cmp);
+    sizes.dedup();
+    assert_eq!(sizes.len(), 12, "every seed should draw its own pool size");
+    assert!(sizes[11] / sizes[0] > 5.0, "pool sizes {sizes:?}");
+    assert!(
+        (seeded_total - fixed_total).abs() < 0.1 * fixed_total,
+        "seeded {seeded_total} vs fixed {fixed_total}"
+    );
+}
+
+#[test]
+fn test_zero_steps_scores_exactly_zero() {
+    // Even a mispriced pool has nothing to arbitrage before the first step.
+    let config = SimulationConfig {
+        initial_mispricing_bps: 40.0,
+        ..config(0, 3)
+    };
+    let result = run_vs_normalizer(starter_swap, Some(starter_after_swap), &config);
+    assert_eq!(result.steps_run, 0);
+    assert_eq!(result.submission_edge, 0.0);
+    assert_eq!(result.waterfall.total(), 0.0);
+    assert!(result.k_growth.is_finite());
+    assert_eq!(result.impermanent_loss, 0.0);
+    assert!(result.disqualifications.is_empty());
+
+    // The capital is what the mispriced pool actually started with, not the configured one.
+    let sim = sim_vs_normalizer(starter_swap, Some(starter_after_swap), &config);
+    let pool = sim.submission();
+    assert_ne!(pool.reserve_x, config.initial_x);
+    assert_eq!(
+        result.initial_value,
+        pool.reserve_x * config.initial_price + pool.reserve_y
+    );
+    assert_eq!(result.lp_return.price_move, 0.0);
+
+    let batch = prop_amm_sim::runner::run_batch_native(
+        starter_swap,
+        Some(starter_after_swap),
+        normalizer_swap,
+        Some(normalizer_after_swap),
+        Vec::new(),
+        None,
+    )
+    .unwrap();
+    assert_eq!(batch.n_sims(), 0);
+    assert_eq!(batch.avg_edge(), None);
+}
+
+#[test]
+fn test_lp_return_is_fee_driven_when_calm_and_divergence_driven_when_volatile() {
+    // Mean LP return components over a few seeds of a 30 bps pool at `sigma`.
+    let run = |sigma: f64| {
+        let (mut fees, mut divergence, mut rate) = (0.0, 0.0, 0.0);
+        for seed in 0..8 {
+            let config = SimulationConfig {
+                n_steps: 2000,
+                seed,
+                gbm_sigma: sigma,
+                norm_fee_bps: 30,
+                ..SimulationConfig::default()
+            };
+            let result = run_vs_normalizer(normalizer_swap, Some(normalizer_after_swap), &config);
+            let lp = result.lp_return;
+            assert!((lp.total() - result.impermanent_loss).abs() < 1e-9 * result.initial_value);
+            fees += lp.fee_revenue / 8.0;
+            divergence += lp.divergence / 8.0;
+            rate += lp.annualized_rate / 8.0;
+        }
+        (fees, divergence, rate)
+    };
+    let default_sigma = SimulationConfig::default().gbm_sigma;
+
+    let (fees, divergence, rate) = run(default_sigma / 3.0);
+    assert!(
+        fees > 10.0 * divergence.abs(),
+        "calm: fees {fees}, divergence {divergence}"
+    );
+    assert!(rate > 0.0, "calm LP return {rate}");
+
+    let (fees, divergence, rate) = run(default_sigma * 30.0);
+    assert!(
+        -divergence > fees,
+        "volatile: fees {fees}, divergence {divergence}"
+    );
+    assert!(rate < 0.0, "volatile LP return {rate}");
+}
+
+#[test]
+fn test_tape_replay_books_hand_computed_fee_revenue() {
+    use prop_amm_sim::tape::TradeTape;
+
+    let tape = TradeTape::from_csv(
+        "timestamp,side,size,price\n\
+         0,buy,2,100\n\
+         1,sell,3,100\n\
+         2,buy,0.5,100\n\
+         3,sell,1,100\n",
+    )
+    .unwrap();
+    let config = SimulationConfig {
+        initial_x: 100.0,
+        initial_y: 10_000.0,
+        initial_price: 100.0,
+        // Arbitrageurs never trade, so every fill is a tape trade.
+        min_arb_profit: 1e12,
+        ..SimulationConfig::default()
+    };
+    let result = prop_amm_sim::engine::run_simulation_with_tape(
+        starter_swap,
+        Some(starter_after_swap),
+        normalizer_swap,
+        Some(normalizer_after_swap),
+        &config,
+        &tape,
+    )
+    .unwrap();
+    assert_eq!(result.steps_run, 4);
+
+    // The starter is constant product keeping 5% of the input; at a fixed price of 100 each
+    // fill earns the value it takes in minus the value it pays out.
+    let (mut x, mut y) = (100.0f64, 10_000.0f64);
+    let mut expected = 0.0;
+    for trade in tape.trades() {
+        if trade.is_buy {
+            let input_y = trade.size * trade.price;
+            let out_x = x - x * y / (y + 0.95 * input_y);
+            expected += input_y - out_x * trade.price;
+            (x, y) = (x - out_x, y + input_y);
+        } else {
+            let out_y = y - x * y / (x + 0.95 * trade.size);
+            expected += trade.size * trade.price - out_y;
+            (x, y) = (x + trade.size, y - out_y);
+        }
+    }
+    let fees = result.lp_return.fee_revenue;
+    assert!(
+        (fees - expected).abs() < 1e-6 * expected,
+        "booked {fees}, hand-computed {expected}"
+    );
+    assert_eq!(result.waterfall.arb_spread, 0.0);
+    assert!((result.submission_edge - expected).abs() < 1e-6 * expected);
+}
+
+#[test]
+fn test_tape_reference_price_moves_are_arbitraged() {
+    let tape = prop_amm_sim::tape::TradeTape::from_csv(
+        "timestamp,side,size,price\n0,buy,0.1,100\n1,sell,0.1,120\n",
+    )
+    .unwrap();
+    let config = SimulationConfig {
+        initial_x: 100.0,
+        initial_y: 10_000.0,
+        initial_price: 100.0,
+        ..SimulationConfig::default()
+    };
+    let mut sim =
+        sim_vs_normalizer(starter_swap, Some(starter_after_swap), &config).with_tape(&tape);
+    sim.step();
+    assert_eq!(sim.fair_price(), 100.0);
+    sim.step();
+    assert_eq!(sim.fair_price(), 120.0);
+    assert!(!sim.step(), "the run ends with the tape");
+    // Arbitrageurs pulled the pool up toward 120, stopping inside its 5% fee band.
+    let spot = sim.submission().spot_price();
+    assert!(spot > 120.0 * 0.94 && spot < 120.0, "spot {spot}");
+    assert!(sim.waterfall().arb_spread != 0.0);
+}
+
+#[test]
+fn test_twap_referenced_edge_is_less_volatile_on_a_mean_reverting_path() {
+    use prop_amm_shared::config::ReferenceMode;
+
+    let spread = |reference_mode: ReferenceMode| {
+        let edges: Vec<f64> = (0..24)
+            .map(|seed| {
+                let config = SimulationConfig {
+                    n_steps: 1_000,
+                    seed,
+                    gbm_sigma: 0.005,
+                    mean_reversion: 0.2,
+                    reference_mode,
+                    ..SimulationConfig::default()
+                };
+                run_vs_normalizer(normalizer_swap, Some(normalizer_after_swap), &config)
+                    .submission_edge
+            })
+            .collect();
+        let mean = edges.iter().sum::<f64>() / edges.len() as f64;
+        let var = edges.iter().map(|e| (e - mean).powi(2)).sum::<f64>() / edges.len() as f64;
+        (mean, var.sqrt())
+    };
+    let spot = spread(ReferenceMode::Spot);
+    assert_eq!(spot, spread(ReferenceMode::default()));
+    let twap = spread(ReferenceMode::Twap(20));
+    assert!(
+        twap.1 < spot.1,
+        "edge std dev {} under TWAP vs {} spot",
+        twap.1,
+        spot.1
+    );
+}
+
+fn u64_max_swap(_data: &[u8]) -> u64 {
+    u64::MAX
+}
+
+#[test]
+fn test_u64_max_native_quote_is_disqualified_without_touching_the_pool() {
+    let config = config(50, 7);
+    let result = run_vs_normalizer(u64_max_swap, None, &config);
+
+    // Every quote is refused, so the pool never trades and the edge stays exactly zero.
+    assert_eq!(result.submission_edge, 0.0);
+    assert_eq!(result.k_growth, 0.0);
+    let first = result
+        .disqualifications
+        .first()
+        .expect("over-quote recorded");
+    assert_eq!(first.kind, DisqualificationKind::OverQuote);
+    assert_eq!(first.step, Some(0));
+    assert!(result
+        .disqualifications
+        .iter()
+        .all(|event| event.kind == DisqualificationKind::OverQuote && event.step.is_some()));
+}
+
+#[test]
+fn test_adaptive_undercutter_takes_most_retail_flow_from_a_wide_strategy() {
+    // The starter charges 500 bps. Against a fixed 1000 bps normalizer it is the better
+    // quote and gets the flow; an adaptive one re-prices just under it every step.
+    let run = |opponent_undercut_bps: Option<u16>| {
+        let config = SimulationConfig {
+            n_steps: 1_000,
+            seed: 8,
+            norm_fee_bps: 1_000,
+            opponent_undercut_bps,
+            ..SimulationConfig::default()
+        };
+        let mut sim = sim_vs_normalizer(starter_swap, Some(starter_after_swap), &config);
+        let mut volume = RetailVolumeX::default();
+        while sim.step_with(&mut volume) {}
+        let fee_word = sim.normalizer().storage()[..2].try_into().unwrap();
+        (volume.0, u16::from_le_bytes(fee_word))
+    };
+    let (fixed, fixed_fee) = run(None);
+    let (undercut, undercut_fee) = run(Some(50));
+    assert_eq!(fixed_fee, 1_000);
+    // Half the starter's ~1026 bps round-trip spread, less 50.
+    assert!((460..=466).contains(&undercut_fee), "{undercut_fee}");
+    assert!(
+        undercut < 0.4 * fixed,
+        "starter filled {} X of retail against the undercutter vs {} against a fixed fee",
+        undercut,
+        fixed
+    );
+}
+
+#[test]
+fn test_quote_cache_serves_repeat_probes_without_changing_the_run() {
+    let config = config(300, 12);
+    let run = |cached: bool| {
+        let mut sim = sim_vs_normalizer(starter_swap, Some(starter_after_swap), &config);
+        if cached {
+            sim.submission_mut().cache_quotes();
+        }
+        while sim.step() {}
+        let pool = sim.submission();
+        (
+            (pool.reserve_x, pool.reserve_y, sim.submission_edge()),
+            pool.quote_cache_hits(),
+        )
+    };
+    let (plain, no_hits) = run(false);
+    let (cached, hits) = run(true);
+    assert_eq!(no_hits, 0);
+    assert!(hits > 0);
+    assert_eq!(plain, cached);
+
+    // The undercutting opponent turns it on for the pool it probes every step.
+    let undercut = SimulationConfig {
+        opponent_undercut_bps: Some(5),
+        ..config.clone()
+    };
+    let mut sim = sim_vs_normalizer(starter_swap, Some(starter_after_swap), &undercut);
+    while sim.step() {}
+    assert!(sim.submission().quote_cache_hits() > 0);
+}
+
+#[test]
+fn test_tighter_quote_wins_most_of_the_retail_flow_head_to_head() {
+    let config = config(1_000, 3);
+    let starter = prop_amm_sim::opponents::find("starter").unwrap();
+    let normalizer = prop_amm_sim::opponents::find("normalizer").unwrap();
+    // 30 bps against 500 bps: the router sends nearly every order to the tighter pool.
+    let tight = prop_amm_sim::engine::run_competitive_simulation(
+        normalizer_swap,
+        Some(normalizer_after_swap),
+        starter,
+        &config,
+    )
+    .unwrap();
+    let wide = prop_amm_sim::engine::run_competitive_simulation(
+        starter_swap,
+        Some(starter_after_swap),
+        normalizer,
+        &config,
+    )
+    .unwrap();
+    let tight_share = tight.retail_share().unwrap();
+    let wide_share = wide.retail_share().unwrap();
+    assert!(tight_share > 0.5, "{tight_share}");
+    assert!(wide_share < 0.5, "{wide_share}");
+}
+
+/// Submission fills of every kind, one per trade.
+#[derive(Default)]
+struct SubmissionFills(usize);
+
+impl prop_amm_sim::objective::Objective for SubmissionFills {
+    fn accumulate(
+        &mut self,
+        step: &prop_amm_sim::objective::StepRecord,
+        _ctx: &prop_amm_sim::objective::MarketCtx,
+    ) {
+        self.0 += step.trades.len();
+    }
+
+    fn finalize(&self) -> f64 {
+        self.0 as f64
+    }
+}
+
+#[test]
+fn test_per_trade_cost_charges_each_fill_and_spares_wide_quotes() {
+    // Edge and fill count for a submission against the 30 bps normalizer.
+    let run = |swap: SwapFn, after_swap: Option<AfterSwapFn>, per_trade_cost: f64| {
+        let config = SimulationConfig {
+            n_steps: 1_000,
+            seed: 4,
+            per_trade_cost,
+            ..SimulationConfig::default()
+        };
+        let mut sim = sim_vs_normalizer(swap, after_swap, &config);
+        let mut fills = SubmissionFills::default();
+        while sim.step_with(&mut fills) {}
+        (sim.submission_edge(), fills.0)
+    };
+    let cost = 0.05;
+    let (tight_free, tight_fills) = run(normalizer_swap, Some(normalizer_after_swap), 0.0);
+    let (tight_paid, tight_fills_paid) = run(normalizer_swap, Some(normalizer_after_swap), cost);
+    let (wide_free, wide_fills) = run(starter_swap, Some(starter_after_swap), 0.0);
+    let (wide_paid, _) = run(starter_swap, Some(starter_after_swap), cost);
+
+    // The cost only moves the edge, so the same fills happen and each one pays it.
+    assert_eq!(tight_fills, tight_fills_paid);
+    let tight_drop = tight_free - tight_paid;
+    assert!(
+        (tight_drop - cost * tight_fills as f64).abs() < 1e-6,
+        "{tight_drop} for {tight_fills} fills"
+    );
+    // The 500 bps starter trades far less often, so the same cost takes far less of its edge.
+    assert!(
+        wide_fills * 2 < tight_fills,
+        "{wide_fills} vs {tight_fills}"
+    );
+    assert!(
+        wide_free - wide_paid < 0.5 * tight_drop,
+        "{} vs {}",
+        wide_free - wide_paid,
+        tight_drop
+    );
+}
+
+#[test]
+fn test_batch_total_edge_is_bit_identical_across_worker_counts_and_result_order() {
+    let configs = prop_amm_sim::runner::seeded_configs(96, 200, 0, 1);
+    let totals: Vec<u64> = [1, 2, 8]
+        .into_iter()
+        .map(|workers| {
+            let batch = prop_amm_sim::runner::run_batch_native(
+                starter_swap,
+                Some(starter_after_swap),
+                normalizer_swap,
+                Some(normalizer_after_swap),
+                configs.clone(),
+                Some(workers),
+            )
+            .unwrap();
+            // Summed smallest first, these edges round differently; in any order the batch
+            // total must come out the same.
+            let mut reordered = batch.results.clone();
+            reordered.sort_by(|a, b| a.submission_edge.abs().total_cmp(&b.submission_edge.abs()));
+            let reordered = BatchResult::from_results(reordered);
+            assert_eq!(reordered.total_edge.to_bits(), batch.total_edge.to_bits());
+            batch.total_edge.to_bits()
+        })
+        .collect();
+    assert!(totals.windows(2).all(|w| w[0] == w[1]), "{totals:?}");
+}
+
+#[test]
+fn test_ignoring_after_swap_changes_only_stateful_strategies() {
+    use prop_amm_shared::strategies::dynamic_fee;
+
+    let run = |swap: SwapFn, after_swap: AfterSwapFn, ignore_after_swap: bool| {
+        let config = SimulationConfig {
+            n_steps: 1_000,
+            seed: 6,
+            ignore_after_swap,
+            ..SimulationConfig::default()
+        };
+        run_vs_normalizer(swap, Some(after_swap), &config).submission_edge
+    };
+    // The dynamic fee widens with an EMA of squared returns kept in storage; without its
+    // after_swap that average stays zero.
+    let stateful = run(dynamic_fee::compute_swap, dynamic_fee::after_swap, false);
+    let ablated = run(dynamic_fee::compute_swap, dynamic_fee::after_swap, true);
+    assert_ne!(stateful, ablated);
+    let stateless = run(starter_swap, starter_after_swap, false);
+    assert_eq!(
+        stateless.to_bits(),
+        run(starter_swap, starter_after_swap, true).to_bits()
+    );
+}
+
+#[test]
+fn test_rebalancing_costs_hit_drifting_inventory_and_spare_a_balanced_pool() {
+    let run = |swap: SwapFn, rebalance: Option<InventoryRebalance>| {
+        // A calm market with one-sided retail flow and a reference pool too wide to win it.
+        let config = SimulationConfig {
+            gbm_sigma: 0.0,
+            retail_buy_prob: 0.6,
+            norm_fee_bps: 80,
+            inventory_rebalance: rebalance,
+            ..config(3_000, 12)
+        };
+        let mut sim = sim_vs_normalizer(swap, None, &config);
+        let mut max_drift = 0.0f64;
+        while sim.step() {
+            max_drift = max_drift.max((sim.submission().reserve_x / config.initial_x - 1.0).abs());
+        }
+        (sim.run().unwrap(), max_drift)
+    };
+    let rebalance = Some(InventoryRebalance {
+        band: 0.05,
+        cost_bps: 10.0,
+    });
+
+    // The fixed-price pool sells X to every buyer whatever its reserves, so its inventory
+    // walks off; its quotes ignore reserves, so rebalancing changes nothing but the cost.
+    let (drifting, free_drift) = run(fixed_price_swap, None);
+    let (rebalanced, kept_drift) = run(fixed_price_swap, rebalance);
+    assert!(free_drift > 0.2, "unmanaged drift {free_drift}");
+    assert!(kept_drift < 0.1, "managed drift {kept_drift}");
+    let cost = rebalanced.waterfall.inventory_cost;
+    assert!(cost < 0.0);
+    assert!(rebalanced.submission_edge < drifting.submission_edge);
+    assert!(
+        (rebalanced.submission_edge - drifting.submission_edge - cost).abs() < 1e-6,
+        "edge fell by {}, rebalancing cost {cost}",
+        drifting.submission_edge - rebalanced.submission_edge
+    );
+    assert!((rebalanced.waterfall.total() - rebalanced.submission_edge).abs() < 1e-6);
+
+    // Arbitrageurs hold a constant-product pool at the fixed fair price, so its X reserve
+    // never leaves the band.
+    let (balanced, _) = run(normalizer_swap, None);
+    let (balanced_rebalanced, _) = run(normalizer_swap, rebalance);
+    assert_eq!(balanced_rebalanced.waterfall.inventory_cost, 0.0);
+    assert_eq!(
+        balanced_rebalanced.submission_edge.to_bits(),
+        balanced.submission_edge.to_bits()
+    );
+}
+
+/// Constant product with 30 bps fee, recentred on the price oracle: the X reserve sets the
+/// depth, the oracle the price, so its quotes follow the oracle rather than its inventory.
+fn oracle_centred_swap(data: &[u8]) -> u64 {
+    use prop_amm_shared::instruction::decode_price_oracle;
+
+    let Some(price) = decode_price_oracle(data) else {
+        return 0;
+    };
+    let input = u64::from_le_bytes(data[1..9].try_into().unwrap()) as u128 * 9_970 / 10_000;
+    let rx = u64::from_le_bytes(data[9..17].try_into().unwrap()) as u128;
+    let ry = u64::from_le_bytes(data[17..25].try_into().unwrap()) as u128;
+    let virtual_y = rx * price as u128 / 1_000_000_000;
+    let output = match data[0] {
+        0 => rx * input / (virtual_y + input),
+        _ => (virtual_y * input / (rx + input)).min(ry / 2),
+    };
+    output as u64
+}
+
+#[test]
+fn test_noisier_price_oracle_degrades_an_oracle_quoting_strategy() {
+    let run = |oracle_noise_stddev: f64| {
+        let config = SimulationConfig {
+            n_steps: 2_000,
+            seed: 17,
+            price_oracle: true,
+            oracle_noise_stddev,
+            ..SimulationConfig::default()
+        };
+        let mut sim = sim_vs_normalizer(oracle_centred_swap, None, &config);
+        let mut max_error = 0.0f64;
+        while sim.step() {
+            let observed = sim.submission().price_oracle().expect("oracle enabled");
+            max_error = max_error.max((observed / sim.fair_price() - 1.0).abs());
+        }
+        (sim.run().unwrap().submission_edge, max_error)
+    };
+
+    let (perfect, perfect_error) = run(0.0);
+    assert!(
+        perfect_error < 1e-9,
+        "perfect oracle off by {perfect_error}"
+    );
+    assert!(perfect > 0.0, "perfect-oracle edge {perfect}");
+    let (noisy, noisy_error) = run(0.002);
+    let (noisier, _) = run(0.01);
+    assert!(
+        noisy_error > 0.002,
+        "noisy oracle off by at most {noisy_error}"
+    );
+    assert!(noisy < perfect, "{noisy} vs {perfect}");
+    assert!(noisier < noisy, "{noisier} vs {noisy}");
+
+    let plain = sim_vs_normalizer(oracle_centred_swap, None, &SimulationConfig::default());
+    assert_eq!(plain.submission().price_oracle(), None);
+}
+
+/// `fixed_price_swap`, but never selling more than half the X it is shown: the pool keeps
+/// selling X at the same price however little it has left.
+fn draining_swap(data: &[u8]) -> u64 {
+    let output = fixed_price_swap(data);
+    match data[0] {
+        0 => output.min(u64::from_le_bytes(data[9..17].try_into().expect("reserve_x")) / 2),
+        _ => output,
+    }
+}
+
+#[test]
+fn test_locked_reserves_stop_a_draining_strategy_at_the_floor() {
+    let run = |locked_reserve_x: f64| {
+        // Every retail trader buys X, and the pool sells it whatever it holds.
+        let config = SimulationConfig {
+            n_steps: 3_000,
+            seed: 12,
+            gbm_sigma: 0.0,
+            retail_buy_prob: 1.0,
+            norm_fee_bps: 80,
+            locked_reserve_x,
+            ..SimulationConfig::default()
+        };
+        let mut sim = sim_vs_normalizer(draining_swap, None, &config);
+        let mut low = f64::INFINITY;
+        while sim.step() {
+            low = low.min(sim.submission().reserve_x);
+        }
+        (sim.submission().tradeable_reserves(), low, config.initial_x)
+    };
+
+    let ((free_x, _), free_low, initial_x) = run(0.0);
+    assert!(
+        free_low < 0.05 * initial_x,
+        "unlocked pool kept {free_low} X"
+    );
+    assert_eq!(free_x, free_low);
+
+    let lock = 0.5 * initial_x;
+    let ((tradeable_x, _), locked_low, _) = run(lock);
+    assert!(locked_low > lock, "locked pool fell to {locked_low} X");
+    assert!(
+        locked_low < lock + 0.05 * initial_x,
+        "locked pool kept {locked_low} X"
+    );
+    assert!(
+        tradeable_x < 0.05 * initial_x,
+        "{tradeable_x} X above the lock"
+    );
+}
+
+#[test]
+fn test_retail_buy_bias_makes_the_pool_shed_x() {
+    let final_x = |retail_direction_bias: f64| {
+        // A calm market and a reference pool too wide to win retail, so the submission fills
+        // every order; its price ignores reserves, so only the flow moves its inventory.
+        let config = SimulationConfig {
+            n_steps: 1_000,
+            seed: 9,
+            gbm_sigma: 0.0,
+            norm_fee_bps: 80,
+            retail_direction_bias,
+            ..SimulationConfig::default()
+        };
+        let mut sim = sim_vs_normalizer(draining_swap, None, &config);
+        while sim.step() {}
+        sim.submission().reserve_x / config.initial_x - 1.0
+    };
+
+    let (buying, neutral, selling) = (final_x(0.3), final_x(0.0), final_x(-0.3));
+    assert!(buying < -0.2, "buy-biased drift {buying}");
+    assert!(selling > 0.2, "sell-biased drift {selling}");
+    assert!(neutral.abs() < 0.1, "neutral drift {neutral}");
+    assert_eq!(final_x(0.3).to_bits(), buying.to_bits());
+}
+
+#[test]
+fn test_momentum_retail_is_seed_stable_and_survives_a_snapshot() {
+    let config = SimulationConfig {
+        n_steps: 1_200,
+        seed: 21,
+        gbm_sigma: 0.004,
+        retail_momentum: Some(RetailMomentum {
+            window: 20,
+            strength: 0.8,
+        }),
+        ..SimulationConfig::default()
+    };
+    let build = |config: &SimulationConfig| sim_vs_normalizer(starter_swap, None, config);
+
+    let straight = build(&config).run().unwrap();
+    assert_eq!(
+        build(&config).run().unwrap().submission_edge.to_bits(),
+        straight.submission_edge.to_bits()
+    );
+    let unbiased = SimulationConfig {
+        retail_momentum: None,
+        ..config.clone()
+    };
+    assert_ne!(
+        build(&unbiased).run().unwrap().submission_edge,
+        straight.submission_edge
+    );
+
+    let mut first = build(&config);
+    for _ in 0..500 {
+        assert!(first.step());
+    }
+    let json = serde_json::to_string(&first.snapshot()).unwrap();
+    let mut resumed = build(&config);
+    resumed
+        .restore(serde_json::from_str(&json).unwrap())
+        .unwrap();
+    assert_eq!(
+        resumed.run().unwrap().submission_edge.to_bits(),
+        straight.submission_edge.to_bits()
+    );
 }
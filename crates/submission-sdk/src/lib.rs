@@ -7,11 +7,77 @@ pub enum StorageError {
     TooLarge,
 }
 
+/// Direction of a compute_swap instruction, its first byte. Mirrors
+/// `prop_amm_shared::instruction::Side`: the simulator only sends `BuyX` and `SellX`, and
+/// 6 and 7 are reserved for exact-out quotes. Bytes 2 to 5 are the other instruction tags.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum Side {
+    BuyX = 0,
+    SellX = 1,
+    ExactOutBuy = 6,
+    ExactOutSell = 7,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DecodeError {
+    /// A side byte outside `Side`.
+    InvalidSide(u8),
+    /// Fewer bytes than the layout needs.
+    Truncated { expected: usize, actual: usize },
+}
+
+/// The side of a compute_swap instruction, or why `data` does not start with one. Use it
+/// instead of treating an unknown side as a zero quote.
+#[inline]
+pub fn decode_side(data: &[u8]) -> Result<Side, DecodeError> {
+    match data.first() {
+        Some(0) => Ok(Side::BuyX),
+        Some(1) => Ok(Side::SellX),
+        Some(6) => Ok(Side::ExactOutBuy),
+        Some(7) => Ok(Side::ExactOutSell),
+        Some(&other) => Err(DecodeError::InvalidSide(other)),
+        None => Err(DecodeError::Truncated {
+            expected: 1,
+            actual: 0,
+        }),
+    }
+}
+
+/// `1.0` in the 1e9 fixed-point scale `rebalance_skew` takes its ratios in.
+pub const RATIO_ONE: u64 = 1_000_000_000;
+
+/// Signed fee adjustment in bps that pulls an inventory ratio back toward `target_ratio`.
+///
+/// Both ratios are 1e9 fixed-point (see `RATIO_ONE`) and can be any inventory measure, e.g.
+/// `reserve_x * price / reserve_y`. The skew is `aggressiveness` times the relative deviation
+/// `(current - target) / target`, rounded toward zero, and saturates at `±aggressiveness`
+/// once the ratio is off by 100%. Positive means too much of the numerator token: add the
+/// skew to the fee on trades that bring more of it in and subtract it on trades that take
+/// it out.
+#[inline]
+pub fn rebalance_skew(current_ratio: u64, target_ratio: u64, aggressiveness: u32) -> i32 {
+    let bound = aggressiveness.min(i32::MAX as u32) as i128;
+    if target_ratio == 0 {
+        return if current_ratio == 0 { 0 } else { bound as i32 };
+    }
+    let deviation = current_ratio as i128 - target_ratio as i128;
+    (deviation * bound / target_ratio as i128).clamp(-bound, bound) as i32
+}
+
 #[inline]
 pub fn set_return_data_u64(value: u64) {
     set_return_data_bytes(&value.to_le_bytes());
 }
 
+/// Return a signed value as its two's-complement little-endian bytes, the convention
+/// `BpfExecutor::execute_signed` decodes. Only for calls that ask for a signed result;
+/// compute_swap outputs stay `set_return_data_u64`.
+#[inline]
+pub fn set_return_data_i64(value: i64) {
+    set_return_data_bytes(&value.to_le_bytes());
+}
+
 #[inline]
 pub fn set_return_data_bytes(bytes: &[u8]) {
     pinocchio::program::set_return_data(bytes);
@@ -79,3 +145,66 @@ pub fn ffi_after_swap(
 
     after_swap(data_slice, storage_slice);
 }
+
+/// Safe wrapper for native metadata glue: hands out a `&'static str` as pointer + length.
+///
+/// A null `out_len` is ignored; the pointer is still returned.
+#[cfg(not(target_os = "solana"))]
+#[inline]
+pub fn ffi_str(value: &'static str, out_len: *mut usize) -> *const u8 {
+    if !out_len.is_null() {
+        unsafe { *out_len = value.len() };
+    }
+    value.as_ptr()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_side, rebalance_skew, DecodeError, Side, RATIO_ONE};
+
+    #[test]
+    fn decode_side_accepts_sides_and_rejects_other_tags() {
+        assert_eq!(decode_side(&[0, 9, 9]), Ok(Side::BuyX));
+        assert_eq!(decode_side(&[1]), Ok(Side::SellX));
+        assert_eq!(decode_side(&[6]), Ok(Side::ExactOutBuy));
+        assert_eq!(decode_side(&[7]), Ok(Side::ExactOutSell));
+        for tag in 2..=5 {
+            assert_eq!(decode_side(&[tag]), Err(DecodeError::InvalidSide(tag)));
+        }
+        assert_eq!(
+            decode_side(&[]),
+            Err(DecodeError::Truncated {
+                expected: 1,
+                actual: 0
+            })
+        );
+    }
+
+    #[test]
+    fn rebalance_skew_is_zero_at_target() {
+        assert_eq!(rebalance_skew(RATIO_ONE, RATIO_ONE, 50), 0);
+        assert_eq!(rebalance_skew(3 * RATIO_ONE / 7, 3 * RATIO_ONE / 7, 50), 0);
+        assert_eq!(rebalance_skew(0, 0, 50), 0);
+    }
+
+    #[test]
+    fn rebalance_skew_sign_follows_the_excess_side() {
+        assert_eq!(rebalance_skew(3 * RATIO_ONE / 2, RATIO_ONE, 50), 25);
+        assert_eq!(rebalance_skew(RATIO_ONE / 2, RATIO_ONE, 50), -25);
+        // 2% over at 50 bps is exactly 1 bps; 1% over rounds toward zero.
+        assert_eq!(rebalance_skew(RATIO_ONE + RATIO_ONE / 50, RATIO_ONE, 50), 1);
+        assert_eq!(
+            rebalance_skew(RATIO_ONE + RATIO_ONE / 100, RATIO_ONE, 50),
+            0
+        );
+    }
+
+    #[test]
+    fn rebalance_skew_saturates_at_aggressiveness() {
+        assert_eq!(rebalance_skew(2 * RATIO_ONE, RATIO_ONE, 50), 50);
+        assert_eq!(rebalance_skew(u64::MAX, RATIO_ONE, 50), 50);
+        assert_eq!(rebalance_skew(0, RATIO_ONE, 50), -50);
+        assert_eq!(rebalance_skew(RATIO_ONE, 0, 50), 50);
+        assert_eq!(rebalance_skew(u64::MAX, 1, u32::MAX), i32::MAX);
+    }
+}
@@ -0,0 +1,52 @@
+use pinocchio::{account_info::AccountInfo, entrypoint, pubkey::Pubkey, ProgramResult};
+use prop_amm_submission_sdk::{set_return_data_bytes, set_return_data_u64, set_storage};
+
+// The strategy itself lives in the shared crate so the simulator can run it natively as the
+// `dynamic-fee` opponent; it only uses `core`.
+#[path = "../../../crates/shared/src/strategies/dynamic_fee.rs"]
+mod dynamic_fee;
+
+const NAME: &str = "Dynamic Fee";
+const MODEL_USED: &str = "None";
+const STORAGE_SIZE: usize = 1024;
+const AFTER_SWAP_STORAGE_OFFSET: usize = 42;
+
+#[cfg(not(feature = "no-entrypoint"))]
+entrypoint!(process_instruction);
+
+pub fn process_instruction(
+    _program_id: &Pubkey,
+    _accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    if instruction_data.is_empty() {
+        return Ok(());
+    }
+
+    match instruction_data[0] {
+        // tag 0 or 1 = compute_swap (side)
+        0 | 1 => set_return_data_u64(dynamic_fee::compute_swap(instruction_data)),
+        // tag 2 = after_swap: fold the trade into the variance average
+        2 => {
+            let mut storage = [0u8; STORAGE_SIZE];
+            if let Some(current) = instruction_data
+                .get(AFTER_SWAP_STORAGE_OFFSET..AFTER_SWAP_STORAGE_OFFSET + STORAGE_SIZE)
+            {
+                storage.copy_from_slice(current);
+            }
+            dynamic_fee::after_swap(instruction_data, &mut storage);
+            let _ = set_storage(&storage);
+        }
+        // tag 3 = get_name (for leaderboard display)
+        3 => set_return_data_bytes(NAME.as_bytes()),
+        // tag 4 = get_model_used (for metadata display)
+        4 => set_return_data_bytes(get_model_used().as_bytes()),
+        _ => {}
+    }
+
+    Ok(())
+}
+
+pub fn get_model_used() -> &'static str {
+    MODEL_USED
+}